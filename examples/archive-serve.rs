@@ -0,0 +1,50 @@
+//! Serves the concatenation of files to every client connecting over TCP.
+//!
+//! Every connection gets the files read from the start. The server logs the file it is sending
+//! and the number of bytes sent per connection.
+//!
+//! ```text
+//! cargo run --example archive-serve -- 127.0.0.1:7878 part-1.bin part-2.bin
+//! nc 127.0.0.1 7878 > archive.bin
+//! ```
+use concat_reader::{FileConcatRead, FileConcatReader};
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
+fn serve(mut stream: TcpStream, files: &[PathBuf]) -> io::Result<u64> {
+    let mut reader = FileConcatReader::new(files);
+    let mut buf = [0; 64 * 1024];
+    let mut last = None;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(reader.position());
+        }
+        if reader.file_path() != last.as_deref() {
+            last = reader.file_path().map(PathBuf::from);
+            eprintln!("archive-serve: sending {:?}", last);
+        }
+        stream.write_all(&buf[..n])?;
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let files: Vec<PathBuf> = args.map(PathBuf::from).collect();
+
+    let listener = TcpListener::bind(&addr)?;
+    eprintln!("archive-serve: listening on {}", addr);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let files = files.clone();
+        thread::spawn(move || match serve(stream, &files) {
+            Ok(n) => eprintln!("archive-serve: sent {} bytes", n),
+            Err(e) => eprintln!("archive-serve: {}", e),
+        });
+    }
+    Ok(())
+}
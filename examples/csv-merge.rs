@@ -0,0 +1,41 @@
+//! Merges CSV files with the same header into one CSV file and reports the progress.
+//!
+//! The merged file is written to `stdout`, a copy of the raw input is archived when an archive
+//! path is given.
+//!
+//! ```text
+//! cargo run --example csv-merge -- [--archive raw.csv] 2019.csv 2020.csv 2021.csv > all.csv
+//! ```
+use concat_reader::progress::{Progress, ProgressReader};
+use concat_reader::text::DropHeaders;
+use concat_reader::FileConcatReader;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+
+fn report(p: &Progress) {
+    match p.fraction() {
+        Some(fraction) => eprintln!("csv-merge: {:.0}%", fraction * 100.0),
+        None => eprintln!("csv-merge: {} bytes", p.bytes_read),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let archive = match args.iter().position(|a| a == "--archive") {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap()),
+        _ => None,
+    };
+
+    let reader = FileConcatReader::new(&args);
+    let total = reader.remaining_len();
+    let mut merged: Box<dyn Read> = match archive {
+        Some(path) => {
+            let raw = reader.tee(File::create(path)?);
+            Box::new(DropHeaders::new(ProgressReader::new(raw, total, report)))
+        }
+        None => Box::new(DropHeaders::new(ProgressReader::new(reader, total, report))),
+    };
+    io::copy(&mut merged, &mut io::stdout())?;
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! Prints the lines of multiple log files prefixed with their file name and line number.
+//!
+//! ```text
+//! cargo run --example log-merge -- /var/log/app.log.2 /var/log/app.log.1 /var/log/app.log
+//! ```
+use concat_reader::FileConcatReader;
+use std::env;
+use std::io::{self, Write};
+
+fn main() -> io::Result<()> {
+    let files: Vec<String> = env::args().skip(1).collect();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    let mut lines = FileConcatReader::new(&files).lines_with_source();
+    for line in &mut lines {
+        match line {
+            Ok((path, line_no, line)) => writeln!(out, "{}:{}: {}", path.display(), line_no, line)?,
+            Err(e) => eprintln!("log-merge: {}", e),
+        }
+    }
+
+    let reader = lines.into_inner();
+    eprintln!("log-merge: read {} bytes", reader.position());
+    Ok(())
+}
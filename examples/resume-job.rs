@@ -0,0 +1,68 @@
+//! Copies the concatenation of files to `stdout` and resumes after an interruption.
+//!
+//! The job writes a checkpoint after every chunk. When it is started again with the same files,
+//! it continues from the last checkpoint instead of starting over.
+//!
+//! ```text
+//! cargo run --example resume-job -- job.checkpoint a.bin b.bin c.bin >> out.bin
+//! ```
+use concat_reader::checkpoint::Checkpoint;
+use concat_reader::FileConcatReader;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+fn load(path: &str, files: &[PathBuf]) -> io::Result<Checkpoint> {
+    let checkpoint: Checkpoint = match fs::read_to_string(path) {
+        Ok(text) => text.parse()?,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Checkpoint::default()),
+        Err(e) => return Err(e),
+    };
+    if checkpoint.path.is_some()
+        && checkpoint.path.as_ref() != files.get(checkpoint.source as usize)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "checkpoint was written for other files",
+        ));
+    }
+    Ok(checkpoint)
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    let checkpoint_path = args
+        .next()
+        .expect("usage: resume-job <checkpoint> <files>...");
+    let files: Vec<PathBuf> = args.map(PathBuf::from).collect();
+
+    let start = load(&checkpoint_path, &files)?;
+    let rest = files.get(start.source as usize..).unwrap_or(&[]);
+    let mut reader = FileConcatReader::new(rest);
+    io::copy(&mut (&mut reader).take(start.offset), &mut io::sink())?;
+    eprintln!(
+        "resume-job: starting at file {} offset {}",
+        start.source, start.offset
+    );
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        out.write_all(&buf[..n])?;
+        out.flush()?;
+
+        let local = reader.checkpoint();
+        let checkpoint = Checkpoint {
+            source: start.source + local.source,
+            position: start.position + local.position - start.offset,
+            ..local
+        };
+        fs::write(&checkpoint_path, checkpoint.to_string())?;
+        if n == 0 {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! A buffered reader which tunes its buffer size to the data it reads.
+//!
+//! A concat reader never returns bytes of two sources in one `read` call, so the size of each
+//! read tells a lot about the sources: many tiny files produce short reads, few huge files fill
+//! every buffer. [`AdaptiveReader`] observes the first reads and grows or shrinks its buffer
+//! until the buffer fits the data, instead of relying on a manually tuned capacity.
+//!
+//! ```
+//! use concat_reader::adaptive::AdaptiveReader;
+//! use concat_reader::concat;
+//! use std::io::prelude::*;
+//!
+//! let big = vec![0u8; 1024 * 1024];
+//! let mut reader = AdaptiveReader::new(concat(vec![&big[..], &big[..]]));
+//! let mut buffer = Vec::new();
+//! reader.read_to_end(&mut buffer).unwrap();
+//! assert!(reader.capacity() > 8 * 1024);
+//! ```
+//!
+//! [`AdaptiveReader`]:         struct.AdaptiveReader.html
+use std::cmp;
+use std::io::{self, BufRead, Read};
+
+const DEFAULT_MIN: usize = 512;
+const DEFAULT_MAX: usize = 1024 * 1024;
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+const WINDOW: usize = 8;
+const WARMUP: usize = 256;
+
+/// Decides the buffer capacity from the sizes of observed reads.
+///
+/// The first reads are grouped into windows. After each window the capacity is doubled if most
+/// reads filled the buffer and halved if the reads used less than a quarter of it on average.
+/// After the warmup the capacity is fixed.
+#[derive(Clone, Debug)]
+pub struct Tuner {
+    min: usize,
+    max: usize,
+    capacity: usize,
+    reads: usize,
+    full_reads: usize,
+    window_bytes: usize,
+    window_reads: usize,
+}
+
+impl Tuner {
+    /// Creates a new `Tuner` keeping the capacity between `min` and `max`.
+    pub fn new(min: usize, max: usize) -> Tuner {
+        let min = cmp::max(min, 1);
+        let max = cmp::max(max, min);
+        Tuner {
+            min,
+            max,
+            capacity: cmp::min(cmp::max(DEFAULT_CAPACITY, min), max),
+            reads: 0,
+            full_reads: 0,
+            window_bytes: 0,
+            window_reads: 0,
+        }
+    }
+
+    /// Returns the current capacity.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns `true` if the tuner stopped adjusting the capacity.
+    pub fn is_settled(&self) -> bool {
+        self.reads >= WARMUP
+    }
+
+    /// Records a read of `n` bytes into a buffer of the current capacity.
+    pub fn observe(&mut self, n: usize) {
+        if self.is_settled() || n == 0 {
+            return;
+        }
+
+        self.reads += 1;
+        self.window_reads += 1;
+        self.window_bytes += n;
+        if n >= self.capacity {
+            self.full_reads += 1;
+        }
+
+        if self.window_reads == WINDOW {
+            let average = self.window_bytes / self.window_reads;
+            if self.full_reads * 4 >= self.window_reads * 3 {
+                self.capacity = cmp::min(self.capacity * 2, self.max);
+            } else if average * 4 < self.capacity {
+                self.capacity = cmp::max(self.capacity / 2, self.min);
+            }
+            self.full_reads = 0;
+            self.window_bytes = 0;
+            self.window_reads = 0;
+        }
+    }
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Tuner::new(DEFAULT_MIN, DEFAULT_MAX)
+    }
+}
+
+/// A [`BufRead`] adapter with a buffer size chosen by a [`Tuner`].
+///
+/// [`BufRead`]:                https://doc.rust-lang.org/std/io/trait.BufRead.html
+/// [`Tuner`]:                  struct.Tuner.html
+#[derive(Debug)]
+pub struct AdaptiveReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+    tuner: Tuner,
+}
+
+impl<R: Read> AdaptiveReader<R> {
+    /// Creates a new `AdaptiveReader` with the default limits.
+    pub fn new(inner: R) -> AdaptiveReader<R> {
+        Self::with_tuner(inner, Tuner::default())
+    }
+
+    /// Creates a new `AdaptiveReader` which uses `tuner` to size its buffer.
+    pub fn with_tuner(inner: R, tuner: Tuner) -> AdaptiveReader<R> {
+        AdaptiveReader {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            filled: 0,
+            tuner,
+        }
+    }
+
+    /// Returns the current buffer capacity.
+    pub fn capacity(&self) -> usize {
+        self.tuner.capacity()
+    }
+
+    /// Returns a reference to the tuner.
+    pub fn tuner(&self) -> &Tuner {
+        &self.tuner
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// Reading directly from the underlying reader skips the buffered data.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `AdaptiveReader`, returning the underlying reader.
+    ///
+    /// Buffered data is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for AdaptiveReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.filled && buf.len() >= self.capacity() {
+            let n = self.inner.read(buf)?;
+            self.tuner.observe(cmp::min(n, self.capacity()));
+            return Ok(n);
+        }
+
+        let n = {
+            let mut available = self.fill_buf()?;
+            available.read(buf)?
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for AdaptiveReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            let capacity = self.capacity();
+            if self.buf.len() != capacity {
+                self.buf.resize(capacity, 0);
+            }
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+            self.tuner.observe(self.filled);
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.filled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveReader, Tuner};
+    use crate::ConcatReader;
+    use std::io::prelude::*;
+
+    #[test]
+    fn shrinks_buffer_for_tiny_sources() {
+        let sources: Vec<&[u8]> = vec![b"0123456789"; 1000];
+        let mut reader = AdaptiveReader::new(ConcatReader::new(sources));
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.len(), 10_000);
+        assert_eq!(reader.capacity(), 512);
+        assert!(reader.tuner().is_settled());
+    }
+
+    #[test]
+    fn grows_buffer_for_large_sources() {
+        let data = vec![7u8; 512 * 1024];
+        let mut reader = AdaptiveReader::with_tuner(&data[..], Tuner::new(1024, 64 * 1024));
+
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line).unwrap();
+        assert_eq!(line, data);
+        assert_eq!(reader.capacity(), 64 * 1024);
+    }
+}
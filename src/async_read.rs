@@ -0,0 +1,586 @@
+//! Asynchronous concatenation of readers.
+//!
+//! This module is only available with the `futures` feature. [`AsyncConcatReader`] implements
+//! `futures::io::AsyncRead`, which is supported by smol and async-std directly and by tokio
+//! through its compatibility layer.
+//!
+//! ```
+//! use concat_reader::async_read::AsyncConcatReader;
+//! use concat_reader::async_read::futures_io::AsyncRead;
+//! use std::pin::Pin;
+//! use std::task::{Context, Poll, Waker};
+//!
+//! let sources: Vec<&[u8]> = vec![b"some ", b"bytes"];
+//! let mut reader = AsyncConcatReader::new(sources);
+//! let mut cx = Context::from_waker(Waker::noop());
+//!
+//! let mut buf = [0; 16];
+//! let mut out = Vec::new();
+//! while let Poll::Ready(Ok(n)) = Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+//!     if n == 0 {
+//!         break;
+//!     }
+//!     out.extend_from_slice(&buf[..n]);
+//! }
+//! assert_eq!(out, b"some bytes");
+//! ```
+//!
+//! [`into_stream`] turns the reader into a `Stream` of `Bytes` chunks, which can be used as a
+//! streaming body by hyper or axum.
+//!
+//! Sources which are discovered asynchronously, e.g. by listing the pages of a bucket, can be
+//! read from a `Stream` with [`AsyncStreamConcatReader`], which awaits the next source when the
+//! current one is exhausted.
+//!
+//! [`AsyncConcatReader`]:      struct.AsyncConcatReader.html
+//! [`into_stream`]:            struct.AsyncConcatReader.html#method.into_stream
+//! [`AsyncStreamConcatReader`]: struct.AsyncStreamConcatReader.html
+use crate::throttle::AsyncThrottle;
+use crate::timeout::AsyncTimeout;
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use futures_io::AsyncRead;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+pub use bytes;
+pub use futures_core;
+pub use futures_io;
+
+/// The state machine of an asynchronous concat reader.
+///
+/// It is independent of the async `Read` trait being implemented: `poll` drives any poll
+/// function of the current item, so the implementations for different runtimes share it.
+struct Sources<I: IntoIterator> {
+    iter: I::IntoIter,
+    curr: Option<I::Item>,
+    done: bool,
+}
+
+impl<I: IntoIterator> Sources<I> {
+    fn new(iter: I) -> Self {
+        Sources {
+            iter: iter.into_iter(),
+            curr: None,
+            done: false,
+        }
+    }
+
+    fn skip(&mut self) -> bool {
+        self.curr = if self.done { None } else { self.iter.next() };
+        self.done = self.curr.is_none();
+        !self.done
+    }
+
+    /// Polls the current item with `poll_item` until it returns data, an error or is pending.
+    /// An item which reached its end is replaced by the next one.
+    fn poll<F>(
+        &mut self,
+        cx: &mut Context,
+        empty: bool,
+        mut poll_item: F,
+    ) -> Poll<io::Result<usize>>
+    where
+        I::Item: Unpin,
+        F: FnMut(Pin<&mut I::Item>, &mut Context) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            if self.curr.is_none() && !self.skip() {
+                return Poll::Ready(Ok(0));
+            }
+            let curr = self.curr.as_mut().expect("checked above");
+            match poll_item(Pin::new(curr), cx) {
+                Poll::Ready(Ok(0)) if !empty => self.curr = None,
+                res => return res,
+            }
+        }
+    }
+}
+
+/// The asynchronous counterpart of [`ConcatReader`].
+///
+/// `AsyncConcatReader` reads all items of an iterator of `AsyncRead` objects one after another.
+/// The items must be `Unpin`; pin boxed items with `Box::pin` otherwise.
+///
+/// [`ConcatReader`]:           ../read/struct.ConcatReader.html
+pub struct AsyncConcatReader<I: IntoIterator> {
+    sources: Sources<I>,
+}
+
+impl<I: IntoIterator> AsyncConcatReader<I> {
+    /// Creates a new `AsyncConcatReader` from a value which can be converted into an iterator.
+    pub fn new(iter: I) -> Self {
+        AsyncConcatReader {
+            sources: Sources::new(iter),
+        }
+    }
+
+    /// Skips to the next item. Returns `false` if there is none.
+    pub fn skip(&mut self) -> bool {
+        self.sources.skip()
+    }
+
+    /// Returns the item currently read from.
+    pub fn current(&self) -> Option<&I::Item> {
+        self.sources.curr.as_ref()
+    }
+
+    /// Wraps the reader in an [`AsyncThrottle`], which reads at most `bytes_per_sec` bytes per
+    /// second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sec` is `0`.
+    ///
+    /// [`AsyncThrottle`]:          ../throttle/struct.AsyncThrottle.html
+    pub fn throttle(self, bytes_per_sec: u64) -> AsyncThrottle<Self>
+    where
+        I::Item: AsyncRead + Unpin,
+        I::IntoIter: Unpin,
+    {
+        AsyncThrottle::new(self, bytes_per_sec)
+    }
+
+    /// Wraps the reader in an [`AsyncTimeout`], which fails reads pending longer than `timeout`
+    /// with an error of kind `TimedOut`.
+    ///
+    /// [`AsyncTimeout`]:           ../timeout/struct.AsyncTimeout.html
+    pub fn timeout(self, timeout: Duration) -> AsyncTimeout<Self>
+    where
+        I::Item: AsyncRead + Unpin,
+        I::IntoIter: Unpin,
+    {
+        AsyncTimeout::new(self, timeout)
+    }
+
+    /// Converts the reader into a stream of chunks of at most `chunk_size` bytes.
+    ///
+    /// A chunk never spans two items. The stream ends after the first error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn into_stream(self, chunk_size: usize) -> ByteStream<I> {
+        ByteStream {
+            inner: self.into_tagged_stream(chunk_size, |_| ()),
+        }
+    }
+
+    /// Converts the reader into a stream of chunks of at most `chunk_size` bytes, each tagged
+    /// with the result of `tag` for the item it was read from.
+    ///
+    /// Items which know their origin can be tagged with it, e.g. the path of a file:
+    ///
+    /// ```
+    /// use concat_reader::async_read::futures_core::Stream;
+    /// use concat_reader::async_read::AsyncConcatReader;
+    /// use std::path::PathBuf;
+    /// use std::pin::Pin;
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// struct Named<'a>(PathBuf, &'a [u8]);
+    /// # impl concat_reader::async_read::futures_io::AsyncRead for Named<'_> {
+    /// #     fn poll_read(
+    /// #         mut self: Pin<&mut Self>,
+    /// #         cx: &mut Context,
+    /// #         buf: &mut [u8],
+    /// #     ) -> Poll<std::io::Result<usize>> {
+    /// #         Pin::new(&mut self.1).poll_read(cx, buf)
+    /// #     }
+    /// # }
+    ///
+    /// let items = vec![Named("a".into(), b"123"), Named("b".into(), b"45")];
+    /// let mut stream = AsyncConcatReader::new(items).into_tagged_stream(2, |n| n.0.clone());
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// let mut chunks = Vec::new();
+    /// while let Poll::Ready(Some(chunk)) = Pin::new(&mut stream).poll_next(&mut cx) {
+    ///     let (path, bytes) = chunk.unwrap();
+    ///     chunks.push((path.display().to_string(), bytes.to_vec()));
+    /// }
+    /// assert_eq!(chunks, [
+    ///     ("a".into(), b"12".to_vec()),
+    ///     ("a".into(), b"3".to_vec()),
+    ///     ("b".into(), b"45".to_vec()),
+    /// ]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn into_tagged_stream<F, T>(self, chunk_size: usize, tag: F) -> TaggedByteStream<I, F>
+    where
+        F: FnMut(&I::Item) -> T,
+    {
+        assert!(chunk_size > 0, "chunk size must not be 0");
+        TaggedByteStream {
+            sources: self.sources,
+            chunk_size,
+            tag,
+            buf: BytesMut::new(),
+            failed: false,
+        }
+    }
+}
+
+impl<I> AsyncRead for AsyncConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsyncRead + Unpin,
+    I::IntoIter: Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let empty = buf.is_empty();
+        self.get_mut()
+            .sources
+            .poll(cx, empty, |item, cx| item.poll_read(cx, buf))
+    }
+}
+
+/// A stream of the chunks read from an [`AsyncConcatReader`].
+///
+/// This struct is created by [`AsyncConcatReader::into_stream`].
+///
+/// [`AsyncConcatReader`]:      struct.AsyncConcatReader.html
+/// [`AsyncConcatReader::into_stream`]: struct.AsyncConcatReader.html#method.into_stream
+pub struct ByteStream<I: IntoIterator> {
+    inner: TaggedByteStream<I, fn(&I::Item)>,
+}
+
+impl<I> Stream for ByteStream<I>
+where
+    I: IntoIterator,
+    I::Item: AsyncRead + Unpin,
+    I::IntoIter: Unpin,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|res| res.map(|((), bytes)| bytes)))
+    }
+}
+
+/// A stream of the chunks read from an [`AsyncConcatReader`], tagged with their origin.
+///
+/// This struct is created by [`AsyncConcatReader::into_tagged_stream`].
+///
+/// [`AsyncConcatReader`]:      struct.AsyncConcatReader.html
+/// [`AsyncConcatReader::into_tagged_stream`]: struct.AsyncConcatReader.html#method.into_tagged_stream
+pub struct TaggedByteStream<I: IntoIterator, F> {
+    sources: Sources<I>,
+    chunk_size: usize,
+    tag: F,
+    buf: BytesMut,
+    failed: bool,
+}
+
+impl<I, F, T> Stream for TaggedByteStream<I, F>
+where
+    I: IntoIterator,
+    I::Item: AsyncRead + Unpin,
+    I::IntoIter: Unpin,
+    F: FnMut(&I::Item) -> T + Unpin,
+{
+    type Item = io::Result<(T, Bytes)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None);
+        }
+
+        this.buf.resize(this.chunk_size, 0);
+        let buf = &mut this.buf;
+        let res = match this
+            .sources
+            .poll(cx, false, |item, cx| item.poll_read(cx, buf))
+        {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(res) => res,
+        };
+        Poll::Ready(match res {
+            Ok(0) => None,
+            Ok(n) => {
+                let curr = this.sources.curr.as_ref().expect("read from an item");
+                let bytes = this.buf.split_to(n).freeze();
+                Some(Ok(((this.tag)(curr), bytes)))
+            }
+            Err(e) => {
+                this.failed = true;
+                Some(Err(e))
+            }
+        })
+    }
+}
+
+impl<I> fmt::Debug for AsyncConcatReader<I>
+where
+    I: IntoIterator,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncConcatReader")
+            .field("done", &self.sources.done)
+            .finish()
+    }
+}
+
+/// An [`AsyncConcatReader`] which takes its items from a `Stream` instead of an iterator.
+///
+/// The next item is awaited when the current one reached its end, so sources can be read while
+/// later ones are still being discovered. The stream and its items must be `Unpin`.
+///
+/// ```
+/// use concat_reader::async_read::futures_core::Stream;
+/// use concat_reader::async_read::futures_io::AsyncRead;
+/// use concat_reader::async_read::AsyncStreamConcatReader;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll, Waker};
+///
+/// /// A stream of pages of sources, like the results of a paginated listing.
+/// struct Pages(Vec<Vec<&'static [u8]>>);
+///
+/// impl Stream for Pages {
+///     type Item = &'static [u8];
+///
+///     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Option<Self::Item>> {
+///         while let Some(page) = self.0.first_mut() {
+///             if !page.is_empty() {
+///                 return Poll::Ready(Some(page.remove(0)));
+///             }
+///             self.0.remove(0);
+///         }
+///         Poll::Ready(None)
+///     }
+/// }
+///
+/// let pages = Pages(vec![vec![b"some "], vec![b"paged ", b"sources"]]);
+/// let mut reader = AsyncStreamConcatReader::new(pages);
+/// let mut cx = Context::from_waker(Waker::noop());
+///
+/// let mut buf = [0; 16];
+/// let mut out = Vec::new();
+/// while let Poll::Ready(Ok(n)) = Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+///     if n == 0 {
+///         break;
+///     }
+///     out.extend_from_slice(&buf[..n]);
+/// }
+/// assert_eq!(out, b"some paged sources");
+/// ```
+///
+/// [`AsyncConcatReader`]:      struct.AsyncConcatReader.html
+pub struct AsyncStreamConcatReader<S: Stream> {
+    stream: S,
+    curr: Option<S::Item>,
+    done: bool,
+}
+
+impl<S: Stream> AsyncStreamConcatReader<S> {
+    /// Creates a new `AsyncStreamConcatReader` reading the items of `stream`.
+    pub fn new(stream: S) -> Self {
+        AsyncStreamConcatReader {
+            stream,
+            curr: None,
+            done: false,
+        }
+    }
+
+    /// Returns the item currently read from.
+    pub fn current(&self) -> Option<&S::Item> {
+        self.curr.as_ref()
+    }
+
+    /// Drops the current item, the next read continues with the next item of the stream.
+    ///
+    /// Returns `false` if there was no current item.
+    pub fn skip(&mut self) -> bool {
+        self.curr.take().is_some()
+    }
+
+    /// Unwraps this `AsyncStreamConcatReader`, returning the underlying stream.
+    ///
+    /// The current item is dropped.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> AsyncRead for AsyncStreamConcatReader<S>
+where
+    S: Stream + Unpin,
+    S::Item: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.curr.is_none() {
+                if this.done {
+                    return Poll::Ready(Ok(0));
+                }
+                match Pin::new(&mut this.stream).poll_next(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(item) => {
+                        this.done = item.is_none();
+                        this.curr = item;
+                        continue;
+                    }
+                }
+            }
+            let curr = this.curr.as_mut().expect("checked above");
+            match Pin::new(curr).poll_read(cx, buf) {
+                Poll::Ready(Ok(0)) if !buf.is_empty() => this.curr = None,
+                res => return res,
+            }
+        }
+    }
+}
+
+impl<S: Stream> fmt::Debug for AsyncStreamConcatReader<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncStreamConcatReader")
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsyncConcatReader, AsyncStreamConcatReader};
+    use futures_core::Stream;
+    use futures_io::AsyncRead;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Returns `Pending` once before every read.
+    struct Flaky<'a> {
+        data: &'a [u8],
+        ready: bool,
+    }
+
+    impl AsyncRead for Flaky<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Pin::new(&mut self.data).poll_read(cx, buf)
+        }
+    }
+
+    #[test]
+    fn reads_pending_items_in_order() {
+        let items = vec![
+            Flaky {
+                data: b"ab",
+                ready: false,
+            },
+            Flaky {
+                data: b"",
+                ready: false,
+            },
+            Flaky {
+                data: b"cde",
+                ready: false,
+            },
+        ];
+        let mut reader = AsyncConcatReader::new(items);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut out = Vec::new();
+        let mut pending = 0;
+        let mut buf = [0; 2];
+        loop {
+            match Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+                Poll::Pending => pending += 1,
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(n)) => out.extend_from_slice(&buf[..n]),
+                Poll::Ready(Err(e)) => panic!("{}", e),
+            }
+        }
+        assert_eq!(out, b"abcde");
+        assert!(pending >= 3);
+        assert!(reader.current().is_none());
+        assert!(!reader.skip());
+    }
+
+    #[test]
+    fn streams_chunks_within_items() {
+        let sources: Vec<&[u8]> = vec![b"abc", b"", b"de"];
+        let mut stream = AsyncConcatReader::new(sources).into_stream(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut chunks = Vec::new();
+        while let Poll::Ready(Some(chunk)) = Pin::new(&mut stream).poll_next(&mut cx) {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(chunks, ["ab", "c", "de"]);
+    }
+
+    /// Returns `Pending` between its items, like a paginated listing.
+    struct Listing<T> {
+        items: Vec<T>,
+        ready: bool,
+    }
+
+    impl<T: Unpin> Stream for Listing<T> {
+        type Item = T;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            if self.items.is_empty() {
+                return Poll::Ready(None);
+            }
+            Poll::Ready(Some(self.items.remove(0)))
+        }
+    }
+
+    #[test]
+    fn awaits_sources_from_stream() {
+        let items: Vec<&[u8]> = vec![b"ab", b"", b"cde"];
+        let listing = Listing {
+            items,
+            ready: false,
+        };
+        let mut reader = AsyncStreamConcatReader::new(listing);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut out = Vec::new();
+        let mut pending = 0;
+        let mut buf = [0; 2];
+        loop {
+            match Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+                Poll::Pending => pending += 1,
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(n)) => out.extend_from_slice(&buf[..n]),
+                Poll::Ready(Err(e)) => panic!("{}", e),
+            }
+        }
+        assert_eq!(out, b"abcde");
+        assert_eq!(pending, 3);
+        assert!(reader.current().is_none());
+        assert!(!reader.skip());
+    }
+}
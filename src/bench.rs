@@ -0,0 +1,205 @@
+//! A harness to measure the throughput of reader configurations.
+//!
+//! This module is only available with the `bench-util` feature. It reads a [`Corpus`] of files
+//! with different [`Config`]urations and returns a [`Report`] for each, so the best settings
+//! can be chosen on the hardware and data they will be used with.
+//!
+//! ```
+//! use concat_reader::bench::{compare, Config, Corpus};
+//!
+//! let corpus = Corpus::generate(16, 64 * 1024).unwrap();
+//! let configs = [
+//!     Config::default(),
+//!     Config {
+//!         buffered: Some(64 * 1024),
+//!         ..Config::default()
+//!     },
+//! ];
+//!
+//! for report in compare(&corpus, &configs, 3).unwrap() {
+//!     println!("{:?}: {:.0} bytes/s", report.config, report.throughput());
+//! }
+//! ```
+//!
+//! [`Corpus`]:                 struct.Corpus.html
+//! [`Config`]:                 struct.Config.html
+//! [`Report`]:                 struct.Report.html
+use crate::adaptive::AdaptiveReader;
+use crate::file::known_len;
+use crate::test_util::TempTree;
+use crate::FileConcatReader;
+use std::io::{self, BufReader, Read};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// A list of files to run a benchmark against.
+#[derive(Debug)]
+pub struct Corpus {
+    paths: Vec<PathBuf>,
+    _tree: Option<TempTree>,
+}
+
+impl Corpus {
+    /// Generates `files` temporary files of `file_len` bytes each.
+    ///
+    /// The files are removed when the `Corpus` is dropped.
+    pub fn generate(files: usize, file_len: usize) -> io::Result<Corpus> {
+        let tree = TempTree::new()?;
+        let paths = (0..files)
+            .map(|i| tree.sized_file(format!("{:06}.bin", i), file_len))
+            .collect::<io::Result<_>>()?;
+        Ok(Corpus {
+            paths,
+            _tree: Some(tree),
+        })
+    }
+
+    /// Creates a `Corpus` of existing files.
+    pub fn from_paths<I: IntoIterator>(paths: I) -> Corpus
+    where
+        I::Item: Into<PathBuf>,
+    {
+        Corpus {
+            paths: paths.into_iter().map(Into::into).collect(),
+            _tree: None,
+        }
+    }
+
+    /// Returns the paths of all files in the corpus.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Returns the summed size of all files in the corpus, `None` if the length of a file is
+    /// unknown.
+    pub fn total_len(&self) -> Option<u64> {
+        self.paths.iter().map(known_len).sum()
+    }
+}
+
+/// A reader configuration to measure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Size of the buffer passed to each `read` call.
+    pub read_size: usize,
+    /// Capacity of a `BufReader` wrapping the reader, if any.
+    pub buffered: Option<usize>,
+    /// Value for `FileConcatReader::limit_per_source`, if any.
+    pub limit_per_source: Option<u64>,
+    /// Wraps the reader in an `AdaptiveReader`. Takes precedence over `buffered`.
+    pub adaptive: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            read_size: 8 * 1024,
+            buffered: None,
+            limit_per_source: None,
+            adaptive: false,
+        }
+    }
+}
+
+/// The result of reading a `Corpus` with a `Config`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// The measured configuration.
+    pub config: Config,
+    /// Number of bytes read.
+    pub bytes: u64,
+    /// Number of files in the corpus.
+    pub sources: usize,
+    /// Time it took to read all bytes.
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// Returns the throughput in bytes per second.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.bytes as f64 / secs
+    }
+}
+
+/// Reads `corpus` once with `config` and reports the result.
+pub fn run(corpus: &Corpus, config: &Config) -> io::Result<Report> {
+    let mut reader = FileConcatReader::new(corpus.paths());
+    if let Some(limit) = config.limit_per_source {
+        reader = reader.limit_per_source(limit);
+    }
+
+    let start = Instant::now();
+    let bytes = match config.buffered {
+        _ if config.adaptive => drain(AdaptiveReader::new(reader), config.read_size)?,
+        Some(capacity) => drain(BufReader::with_capacity(capacity, reader), config.read_size)?,
+        None => drain(reader, config.read_size)?,
+    };
+
+    Ok(Report {
+        config: config.clone(),
+        bytes,
+        sources: corpus.paths().len(),
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Runs every config `iterations` times and reports the fastest run of each.
+pub fn compare(corpus: &Corpus, configs: &[Config], iterations: usize) -> io::Result<Vec<Report>> {
+    let mut reports = Vec::with_capacity(configs.len());
+    for config in configs {
+        let mut best: Option<Report> = None;
+        for _ in 0..iterations.max(1) {
+            let report = run(corpus, config)?;
+            if best.as_ref().is_none_or(|b| report.elapsed < b.elapsed) {
+                best = Some(report);
+            }
+        }
+        reports.extend(best);
+    }
+    Ok(reports)
+}
+
+fn drain<R: Read>(mut reader: R, read_size: usize) -> io::Result<u64> {
+    let mut buf = vec![0; read_size.max(1)];
+    let mut bytes = 0;
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(bytes),
+            n => bytes += n as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, Config, Corpus};
+
+    #[test]
+    fn reports_all_bytes_of_corpus() {
+        let corpus = Corpus::generate(4, 1000).unwrap();
+        let configs = [
+            Config::default(),
+            Config {
+                buffered: Some(16),
+                read_size: 7,
+                limit_per_source: Some(100),
+                adaptive: false,
+            },
+            Config {
+                adaptive: true,
+                ..Config::default()
+            },
+        ];
+
+        let reports = compare(&corpus, &configs, 2).unwrap();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].bytes, corpus.total_len().unwrap());
+        assert_eq!(reports[1].bytes, 400);
+        assert_eq!(reports[1].sources, 4);
+        assert_eq!(reports[2].bytes, 4000);
+    }
+}
@@ -0,0 +1,201 @@
+//! Concatenating HTTP response bodies.
+//!
+//! This module is only available with the `futures` feature. HTTP clients expose response bodies
+//! as streams of byte chunks, like `reqwest::Response::bytes_stream` or the `BodyStream` of
+//! `http-body-util` for hyper. A [`BodyReader`] turns such a stream into an `AsyncRead`, and
+//! [`concat_bodies`] concatenates the bodies of several responses, e.g. of a file published as
+//! numbered parts:
+//!
+//! ```ignore
+//! use concat_reader::body::concat_bodies;
+//!
+//! let mut parts = Vec::new();
+//! for i in 0..3 {
+//!     let url = format!("https://example.com/dump.part{}", i);
+//!     parts.push(client.get(&url).send().await?.error_for_status()?.bytes_stream());
+//! }
+//! futures::io::copy(concat_bodies(parts), &mut out).await?;
+//! ```
+//!
+//! An error of a body is returned as an `io::Error` of kind `Other` wrapping the error of the
+//! client, or unchanged if it already is an `io::Error`, annotated with the offset in the body,
+//! see [`provenance`]. The reader can continue with the next body after calling
+//! [`AsyncConcatReader::skip`].
+//!
+//! [`BodyReader`]:             struct.BodyReader.html
+//! [`concat_bodies`]:          fn.concat_bodies.html
+//! [`provenance`]:             ../provenance/index.html
+//! [`AsyncConcatReader::skip`]: ../async_read/struct.AsyncConcatReader.html#method.skip
+use crate::async_read::AsyncConcatReader;
+use crate::provenance;
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_io::AsyncRead;
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::iter::Map;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The reader returned by [`concat_bodies`].
+///
+/// [`concat_bodies`]:          fn.concat_bodies.html
+pub type ConcatBodies<I> = AsyncConcatReader<
+    Map<
+        <I as IntoIterator>::IntoIter,
+        fn(<I as IntoIterator>::Item) -> BodyReader<<I as IntoIterator>::Item>,
+    >,
+>;
+
+/// Concats the bodies of several responses into a single `AsyncRead`.
+///
+/// Every item of `bodies` is a stream of byte chunks, see [`BodyReader`].
+///
+/// [`BodyReader`]:             struct.BodyReader.html
+pub fn concat_bodies<I: IntoIterator>(bodies: I) -> ConcatBodies<I> {
+    AsyncConcatReader::new(bodies.into_iter().map(BodyReader::new as fn(_) -> _))
+}
+
+/// An `AsyncRead` over a stream of byte chunks, like an HTTP response body.
+///
+/// The chunks can be of any type which converts into `Bytes`, the errors of any type which
+/// converts into a boxed error.
+pub struct BodyReader<S> {
+    body: S,
+    chunk: Bytes,
+    /// Bytes of the body which were read so far.
+    pos: u64,
+    done: bool,
+}
+
+impl<S> BodyReader<S> {
+    /// Creates a new `BodyReader` reading the chunks of `body`.
+    pub fn new(body: S) -> Self {
+        BodyReader {
+            body,
+            chunk: Bytes::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the number of bytes of the body which were read so far.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Unwraps this `BodyReader`, returning the underlying stream.
+    ///
+    /// Bytes which were received but not yet read are lost.
+    pub fn into_inner(self) -> S {
+        self.body
+    }
+}
+
+impl<S, B, E> AsyncRead for BodyReader<S>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: Into<Bytes>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            if !this.chunk.is_empty() {
+                let n = cmp::min(buf.len(), this.chunk.len());
+                buf[..n].copy_from_slice(&this.chunk.split_to(n));
+                this.pos += n as u64;
+                return Poll::Ready(Ok(n));
+            }
+            if this.done {
+                return Poll::Ready(Ok(0));
+            }
+            match Pin::new(&mut this.body).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => this.done = true,
+                Poll::Ready(Some(Ok(chunk))) => this.chunk = chunk.into(),
+                Poll::Ready(Some(Err(e))) => {
+                    let err = match e.into().downcast::<io::Error>() {
+                        Ok(err) => *err,
+                        Err(e) => io::Error::other(e),
+                    };
+                    return Poll::Ready(Err(provenance::annotate(err, "body", None, this.pos)));
+                }
+            }
+        }
+    }
+}
+
+impl<S> fmt::Debug for BodyReader<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BodyReader")
+            .field("pos", &self.pos)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::concat_bodies;
+    use crate::provenance::Provenance;
+    use futures_core::Stream;
+    use futures_io::AsyncRead;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    type Chunk = Result<&'static [u8], io::Error>;
+
+    struct Body(VecDeque<Chunk>);
+
+    impl Stream for Body {
+        type Item = Chunk;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Option<Chunk>> {
+            Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    #[test]
+    fn reads_bodies_and_their_errors() {
+        let broken = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        let bodies = vec![
+            Body(vec![Ok(&b"12"[..]), Ok(b""), Ok(b"345")].into()),
+            Body(vec![Ok(&b"6"[..]), Err(broken)].into()),
+            Body(vec![Ok(&b"78"[..])].into()),
+        ];
+        let mut reader = concat_bodies(bodies);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut buf = [0; 4];
+        let mut out = Vec::new();
+        let err = loop {
+            match Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+                Poll::Ready(Ok(n)) => out.extend_from_slice(&buf[..n]),
+                Poll::Ready(Err(e)) => break e,
+                Poll::Pending => unreachable!("bodies are always ready"),
+            }
+        };
+        assert_eq!(out, b"123456");
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(Provenance::of(&err).unwrap().crumbs()[0].offset, 1);
+
+        assert!(reader.skip());
+        let n = match Pin::new(&mut reader).poll_read(&mut cx, &mut buf) {
+            Poll::Ready(res) => res.unwrap(),
+            Poll::Pending => unreachable!("bodies are always ready"),
+        };
+        assert_eq!(&buf[..n], b"78");
+    }
+}
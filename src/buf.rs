@@ -0,0 +1,170 @@
+//! Concatenating buffered readers without copying.
+//!
+//! A [`ConcatBufReader`] concatenates readers which implement `BufRead` themselves, like
+//! `BufReader`s, byte slices or `Cursor`s. Its `fill_buf` returns the buffer of the current
+//! source as is, instead of copying it into a buffer of its own like a `BufReader` over a
+//! [`ConcatReader`] does. Parsers which work on `BufRead`, like `serde_json::StreamDeserializer`
+//! or incremental `nom` parsers, read the data of every source straight from its buffer.
+//!
+//! `fill_buf` only returns the data of one source at a time. A buffer is never empty unless all
+//! sources are at their end, so the provided methods of `BufRead`, like `read_line` or
+//! `read_until`, continue in the next source when a line crosses a boundary.
+//!
+//! ```
+//! use concat_reader::buf::ConcatBufReader;
+//! use std::io::prelude::*;
+//!
+//! let sources: Vec<&[u8]> = vec![b"first li", b"ne\nsecond", b" line\n"];
+//! let mut reader = ConcatBufReader::new(sources);
+//!
+//! assert_eq!(reader.fill_buf().unwrap(), b"first li");
+//! let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+//! assert_eq!(lines, ["first line", "second line"]);
+//! ```
+//!
+//! [`ConcatBufReader`]:        struct.ConcatBufReader.html
+//! [`ConcatReader`]:           ../read/struct.ConcatReader.html
+use crate::ConcatRead;
+use std::cmp;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+/// Reads from a list of `BufRead` sources one after another, exposing their buffers directly.
+///
+/// [`current`] returns the source `fill_buf` returns the buffer of and [`skip`] drops it
+/// together with the data left in its buffer.
+///
+/// [`current`]:                ../trait.ConcatRead.html#tymethod.current
+/// [`skip`]:                   ../trait.ConcatRead.html#tymethod.skip
+pub struct ConcatBufReader<I: IntoIterator> {
+    curr: Option<I::Item>,
+    iter: I::IntoIter,
+}
+
+impl<I> ConcatBufReader<I>
+where
+    I: IntoIterator,
+    I::Item: BufRead,
+{
+    /// Creates a new `ConcatBufReader` from the buffered readers of `iter`.
+    pub fn new(iter: I) -> Self {
+        let mut iter = iter.into_iter();
+        ConcatBufReader {
+            curr: iter.next(),
+            iter,
+        }
+    }
+}
+
+impl<I> ConcatRead for ConcatBufReader<I>
+where
+    I: IntoIterator,
+    I::Item: BufRead,
+{
+    type Item = I::Item;
+
+    fn skip(&mut self) -> bool {
+        self.curr = self.iter.next();
+        self.curr.is_some()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.curr.as_ref()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.curr.as_mut()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let curr = self.curr.take()?;
+        self.curr = self.iter.next();
+        Some(curr)
+    }
+}
+
+impl<I> BufRead for ConcatBufReader<I>
+where
+    I: IntoIterator,
+    I::Item: BufRead,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        loop {
+            match self.curr.as_mut() {
+                None => return Ok(&[]),
+                Some(curr) => {
+                    if !curr.fill_buf()?.is_empty() {
+                        break;
+                    }
+                }
+            }
+            self.curr = self.iter.next();
+        }
+        // the buffer is filled, so this returns it again without reading
+        self.curr.as_mut().expect("checked above").fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(curr) = self.curr.as_mut() {
+            curr.consume(amt);
+        }
+    }
+}
+
+impl<I> Read for ConcatBufReader<I>
+where
+    I: IntoIterator,
+    I::Item: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let available = self.fill_buf()?;
+        let n = cmp::min(buf.len(), available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<I> fmt::Debug for ConcatBufReader<I>
+where
+    I: IntoIterator,
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConcatBufReader")
+            .field("curr", &self.curr)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConcatBufReader;
+    use crate::ConcatRead;
+    use std::io::{BufRead, Read};
+
+    #[test]
+    fn exposes_buffers_of_sources() {
+        let first: &'static [u8] = b"ab";
+        let second: &'static [u8] = b"c\nd";
+        let mut reader = ConcatBufReader::new(vec![first, b"", second, b"ef"]);
+
+        assert_eq!(reader.fill_buf().unwrap().as_ptr(), first.as_ptr());
+        reader.consume(2);
+        assert_eq!(reader.fill_buf().unwrap().as_ptr(), second.as_ptr());
+
+        reader.consume(1);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "\n");
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "\ndef");
+
+        assert!(reader.fill_buf().unwrap().is_empty());
+        assert!(reader.current().is_none());
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,231 @@
+//! Stopping and pausing readers from other threads.
+//!
+//! A [`CancelToken`] is shared between a reader and the code controlling it. Readers created with
+//! `cancel_on` check the token before every read: after [`cancel`] they fail with a [`Cancelled`]
+//! error, while [`pause`] blocks them until [`resume`]. A [`FileConcatReader`] in follow mode
+//! also wakes up from waiting for new data when it is cancelled.
+//!
+//! ```no_run
+//! use concat_reader::cancel::{CancelToken, Cancelled};
+//! use concat_reader::*;
+//! use std::io::{self, BufRead, BufReader};
+//! use std::time::Duration;
+//!
+//! let token = CancelToken::new();
+//! let shutdown = token.clone();
+//! std::thread::spawn(move || {
+//!     std::thread::sleep(Duration::from_secs(60));
+//!     shutdown.cancel();
+//! });
+//!
+//! let files = ["app.log"];
+//! let reader = FileConcatReader::new(&files)
+//!     .follow(Duration::from_millis(250))
+//!     .cancel_on(token);
+//! for line in BufReader::new(reader).lines() {
+//!     match line {
+//!         Ok(line) => println!("{}", line),
+//!         Err(e) if Cancelled::of(&e).is_some() => break,
+//!         Err(e) => panic!("{}", e),
+//!     }
+//! }
+//! ```
+//!
+//! [`CancelToken`]:            struct.CancelToken.html
+//! [`cancel`]:                 struct.CancelToken.html#method.cancel
+//! [`pause`]:                  struct.CancelToken.html#method.pause
+//! [`resume`]:                 struct.CancelToken.html#method.resume
+//! [`Cancelled`]:              struct.Cancelled.html
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+use crate::provenance::Provenance;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A handle to cancel or pause the readers it was passed to.
+///
+/// Clones share their state, so any clone controls all readers.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    state: Arc<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    flags: Mutex<Flags>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Flags {
+    cancelled: bool,
+    paused: bool,
+}
+
+impl CancelToken {
+    /// Creates a new `CancelToken` which is neither cancelled nor paused.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels all readers of this token. A cancelled token can't be reset.
+    pub fn cancel(&self) {
+        self.update(|flags| flags.cancelled = true);
+    }
+
+    /// Returns `true` if the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.flags().cancelled
+    }
+
+    /// Blocks all readers of this token on their next read until [`resume`] or [`cancel`].
+    ///
+    /// [`resume`]:                 #method.resume
+    /// [`cancel`]:                 #method.cancel
+    pub fn pause(&self) {
+        self.update(|flags| flags.paused = true);
+    }
+
+    /// Lets paused readers continue.
+    pub fn resume(&self) {
+        self.update(|flags| flags.paused = false);
+    }
+
+    /// Returns `true` if the token is paused.
+    pub fn is_paused(&self) -> bool {
+        self.flags().paused
+    }
+
+    /// Waits while the token is paused. Fails if it is cancelled.
+    pub(crate) fn check(&self) -> io::Result<()> {
+        let mut flags = self.flags();
+        while flags.paused && !flags.cancelled {
+            flags = self
+                .state
+                .changed
+                .wait(flags)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        if flags.cancelled {
+            return Err(io::Error::other(Cancelled));
+        }
+        Ok(())
+    }
+
+    /// Sleeps for `duration`, waking up early if the token is cancelled.
+    pub(crate) fn sleep(&self, duration: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + duration;
+        let mut flags = self.flags();
+        while !flags.cancelled {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            flags = self
+                .state
+                .changed
+                .wait_timeout(flags, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        drop(flags);
+        self.check()
+    }
+
+    fn flags(&self) -> MutexGuard<'_, Flags> {
+        self.state.flags.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Flags)) {
+        f(&mut self.flags());
+        self.state.changed.notify_all();
+    }
+}
+
+/// The error of a reader whose [`CancelToken`] was cancelled.
+///
+/// The error is returned as an `io::Error` of kind `Other`, not `Interrupted`, because
+/// `read_to_end` and other helpers retry reads which were interrupted. Use [`Cancelled::of`] to
+/// find it in an error.
+///
+/// [`CancelToken`]:            struct.CancelToken.html
+/// [`Cancelled::of`]:          #method.of
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Cancelled {
+    /// Returns the `Cancelled` error wrapped in `err`, if any.
+    pub fn of(err: &io::Error) -> Option<&Cancelled> {
+        let err = match Provenance::of(err) {
+            Some(provenance) => provenance.error(),
+            None => err,
+        };
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("reading was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+#[cfg(test)]
+mod tests {
+    use super::{CancelToken, Cancelled};
+    use crate::test_util::TempTree;
+    use crate::{ConcatReader, FileConcatReader};
+    use std::io::Read;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn cancels_and_pauses_readers() {
+        let token = CancelToken::new();
+        let bytes: Vec<&[u8]> = vec![b"12", b"34"];
+        let mut reader = ConcatReader::new(bytes).cancel_on(token.clone());
+
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+
+        token.pause();
+        let control = token.clone();
+        let resumer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            control.resume();
+        });
+        let start = Instant::now();
+        reader.read_exact(&mut buf[..1]).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        resumer.join().unwrap();
+
+        token.cancel();
+        let err = reader.read(&mut buf).unwrap_err();
+        assert!(Cancelled::of(&err).is_some());
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn wakes_up_following_reader() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![tree.file("a", b"1").unwrap()];
+        let token = CancelToken::new();
+        let mut reader = FileConcatReader::new(paths)
+            .follow(Duration::from_secs(60))
+            .cancel_on(token.clone());
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(buf, b"1");
+        assert!(Cancelled::of(&err).is_some());
+        canceller.join().unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+//! Checkpoints of the read progress of a [`FileConcatReader`].
+//!
+//! A [`Checkpoint`] records which file a reader is at and how far it read. It can be persisted
+//! in a small, versioned text format, which stays readable across crate versions:
+//!
+//! ```text
+//! concat-reader checkpoint
+//! version: 1
+//! source: 2
+//! offset: 1024
+//! position: 9216
+//! path: /var/log/app.log.2
+//! ```
+//!
+//! The first line identifies the format, every other line is a `key: value` pair.
+//!
+//! * `version` — format version, currently `1`.
+//! * `source` — index of the current file in the list of paths, starting at `0`.
+//! * `offset` — number of bytes read from the current file.
+//! * `position` — number of bytes read from all files.
+//! * `path` — path of the current file, missing if the reader is at the end. Non UTF-8 paths are
+//!   stored lossily, `\` and line breaks are escaped as `\\`, `\n` and `\r`.
+//!
+//! # Compatibility
+//!
+//! Keys which are added within a version are optional and unknown keys are ignored when parsing,
+//! so older crate versions can read checkpoints of newer ones within the same format version.
+//! Incompatible changes increase the version. Parsing always accepts all older versions and
+//! migrates them to the current [`Checkpoint`], checkpoints of a newer version are rejected with
+//! an `InvalidData` error.
+//!
+//! ```
+//! use concat_reader::checkpoint::Checkpoint;
+//!
+//! let checkpoint: Checkpoint = "concat-reader checkpoint\nversion: 1\nsource: 1\noffset: 3\nposition: 10\n"
+//!     .parse()
+//!     .unwrap();
+//! assert_eq!(checkpoint.source, 1);
+//! assert_eq!(checkpoint.to_string().parse::<Checkpoint>().unwrap(), checkpoint);
+//! ```
+//!
+//! A [`Snapshot`] additionally records the paths left to read, so a reader can be recreated from
+//! it alone with [`FileConcatReader::resume`]. With the `serde` feature both types implement
+//! `Serialize` and `Deserialize`.
+//!
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+//! [`Checkpoint`]:             struct.Checkpoint.html
+//! [`Snapshot`]:               struct.Snapshot.html
+//! [`FileConcatReader::resume`]: ../file/struct.FileConcatReader.html#method.resume
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The current version of the checkpoint format.
+pub const VERSION: u32 = 1;
+
+const MAGIC: &str = "concat-reader checkpoint";
+
+/// The read progress of a [`FileConcatReader`].
+///
+/// Created by [`FileConcatReader::checkpoint`].
+///
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`FileConcatReader::checkpoint`]: ../file/struct.FileConcatReader.html#method.checkpoint
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Checkpoint {
+    /// Index of the current file in the list of paths. Equals the number of files if all files
+    /// have been read.
+    pub source: u64,
+    /// Path of the current file, `None` if all files have been read.
+    pub path: Option<PathBuf>,
+    /// Number of bytes read from the current file.
+    pub offset: u64,
+    /// Number of bytes read from all files.
+    pub position: u64,
+}
+
+/// The full read state of a [`FileConcatReader`], including the paths left to read.
+///
+/// Created by [`FileConcatReader::snapshot`] and turned back into a reader by
+/// [`FileConcatReader::resume`], which continues at the same byte of the same file. With the
+/// `serde` feature it can be stored in any serde format, paths which aren't valid UTF-8 fail to
+/// serialize in most of them.
+///
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`FileConcatReader::snapshot`]: ../file/struct.FileConcatReader.html#method.snapshot
+/// [`FileConcatReader::resume`]: ../file/struct.FileConcatReader.html#method.resume
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// Index of the current file in the list of paths. Equals the number of files if all files
+    /// have been read.
+    pub source: u64,
+    /// Path of the current file, `None` if the reader is between files or at the end.
+    pub path: Option<PathBuf>,
+    /// Number of bytes read from the current file.
+    pub offset: u64,
+    /// Number of bytes read from all files.
+    pub position: u64,
+    /// Paths after the current file which are left to read.
+    pub remaining: Vec<PathBuf>,
+}
+
+impl Snapshot {
+    /// Returns the [`Checkpoint`] part of the snapshot.
+    ///
+    /// [`Checkpoint`]:             struct.Checkpoint.html
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            source: self.source,
+            path: self.path.clone(),
+            offset: self.offset,
+            position: self.position,
+        }
+    }
+}
+
+impl From<Checkpoint> for Snapshot {
+    /// Creates a `Snapshot` without remaining paths.
+    fn from(checkpoint: Checkpoint) -> Snapshot {
+        Snapshot {
+            source: checkpoint.source,
+            path: checkpoint.path,
+            offset: checkpoint.offset,
+            position: checkpoint.position,
+            remaining: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "version: {}", VERSION)?;
+        writeln!(f, "source: {}", self.source)?;
+        writeln!(f, "offset: {}", self.offset)?;
+        writeln!(f, "position: {}", self.position)?;
+        if let Some(path) = &self.path {
+            writeln!(f, "path: {}", escape(&path.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Checkpoint> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim_end) != Some(MAGIC) {
+            return Err(invalid("not a concat-reader checkpoint"));
+        }
+
+        let mut fields = HashMap::new();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let (key, value) = split_field(line)?;
+            fields.insert(key, value);
+        }
+
+        match parse_field::<u32>(&fields, "version")? {
+            1 => from_v1(&fields),
+            version => Err(invalid(format!(
+                "checkpoint version {} is newer than the supported version {}",
+                version, VERSION
+            ))),
+        }
+    }
+}
+
+/// Reads a checkpoint in format version 1.
+///
+/// Future versions add their own `from_v*` function, which migrates the old fields.
+fn from_v1(fields: &HashMap<&str, &str>) -> io::Result<Checkpoint> {
+    Ok(Checkpoint {
+        source: parse_field(fields, "source")?,
+        path: fields.get("path").map(|p| PathBuf::from(unescape(p))),
+        offset: parse_field(fields, "offset")?,
+        position: parse_field(fields, "position")?,
+    })
+}
+
+fn split_field(line: &str) -> io::Result<(&str, &str)> {
+    let mut parts = line.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) => Ok((key.trim(), value.strip_prefix(' ').unwrap_or(value))),
+        _ => Err(invalid(format!("malformed checkpoint line {:?}", line))),
+    }
+}
+
+fn parse_field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> io::Result<T> {
+    let value = fields
+        .get(key)
+        .ok_or_else(|| invalid(format!("checkpoint misses {:?}", key)))?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid(format!("invalid checkpoint {} {:?}", key, value)))
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+pub(crate) fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('r')) => {
+                out.push('\r');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            (c, _) => out.push(c),
+        }
+    }
+    out
+}
+
+pub(crate) fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checkpoint;
+    use std::io;
+    use std::path::PathBuf;
+
+    #[test]
+    fn round_trips_escaped_paths() {
+        let checkpoint = Checkpoint {
+            source: 4,
+            path: Some(PathBuf::from("dir\\with\nnewline: and colon")),
+            offset: 12,
+            position: 1 << 40,
+        };
+        let encoded = checkpoint.to_string();
+        assert_eq!(encoded.lines().count(), 6);
+        assert_eq!(encoded.parse::<Checkpoint>().unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn ignores_unknown_keys_and_rejects_newer_versions() {
+        let text = "concat-reader checkpoint\r\nversion: 1\r\nsource: 0\r\noffset: 0\r\nposition: 0\r\nhash: abc\r\n";
+        let checkpoint: Checkpoint = text.parse().unwrap();
+        assert_eq!(checkpoint, Checkpoint::default());
+
+        let err = "concat-reader checkpoint\nversion: 2\n"
+            .parse::<Checkpoint>()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version 2"));
+
+        let err = "version: 1\n".parse::<Checkpoint>().unwrap_err();
+        assert_eq!(err.to_string(), "not a concat-reader checkpoint");
+    }
+}
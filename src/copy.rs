@@ -0,0 +1,175 @@
+//! Copying the concatenation of files in one call.
+//!
+//! [`concat_path_to`] and [`concat_path_to_file`] do what `cat a b c > out` does: they read all
+//! files into a writer or a file and return the number of bytes copied. Every file is copied
+//! with an `io::copy` of its own, see [`ConcatReader::copy_to`], so a copy from file to file
+//! uses fast paths like `copy_file_range` on Linux. Errors opening a file carry its path, see
+//! [`provenance`], and errors while copying the offset in the output.
+//!
+//! [`concat_path_to_with_progress`] also reports the progress of the copy. It reads through an
+//! [`AdaptiveReader`], so the buffer size fits the files.
+//!
+//! ```no_run
+//! use concat_reader::concat_path_to_file;
+//!
+//! let copied = concat_path_to_file("joined.bin", vec!["part-0", "part-1", "part-2"]).unwrap();
+//! println!("{} bytes", copied);
+//! ```
+//!
+//! [`concat_path_to`]:         fn.concat_path_to.html
+//! [`concat_path_to_file`]:    fn.concat_path_to_file.html
+//! [`ConcatReader::copy_to`]:  ../read/struct.ConcatReader.html#method.copy_to
+//! [`AdaptiveReader`]:         ../adaptive/struct.AdaptiveReader.html
+//! [`provenance`]:             ../provenance/index.html
+//! [`concat_path_to_with_progress`]: fn.concat_path_to_with_progress.html
+use crate::adaptive::AdaptiveReader;
+use crate::progress::ProgressObserver;
+use crate::provenance;
+use crate::{ConcatReader, FileConcatReader};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+/// Copies the files of `paths` one after another into `dest`. Returns the number of bytes
+/// copied.
+///
+/// `dest` is flushed after the last file.
+pub fn concat_path_to<W, I>(dest: &mut W, paths: I) -> io::Result<u64>
+where
+    W: Write + ?Sized,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    copy_files(paths, dest, None)
+}
+
+/// Like [`concat_path_to`], but reports the progress of the copy to `observer`, see
+/// [`FileConcatReader::progress`].
+///
+/// [`concat_path_to`]:         fn.concat_path_to.html
+/// [`FileConcatReader::progress`]: ../file/struct.FileConcatReader.html#method.progress
+pub fn concat_path_to_with_progress<W, I, O>(dest: &mut W, paths: I, observer: O) -> io::Result<u64>
+where
+    W: Write + ?Sized,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+    I::IntoIter: Clone,
+    O: ProgressObserver,
+{
+    copy_buffered(FileConcatReader::new(paths).progress(observer), dest)
+}
+
+/// Copies the files of `paths` one after another into a new file at `dest`, replacing a file
+/// which exists. Returns the number of bytes copied.
+pub fn concat_path_to_file<P, I>(dest: P, paths: I) -> io::Result<u64>
+where
+    P: AsRef<Path>,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let dest = dest.as_ref();
+    let mut file =
+        File::create(dest).map_err(|e| provenance::annotate(e, "copy", Some(dest), 0))?;
+    copy_files(paths, &mut file, Some(dest))
+}
+
+/// Copies the files with `ConcatReader::copy_to`, opening each when the copy reaches it.
+fn copy_files<I, W>(paths: I, dest: &mut W, dest_path: Option<&Path>) -> io::Result<u64>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+    W: Write + ?Sized,
+{
+    let mut failed = None;
+    let files = paths.into_iter().map_while(|p| {
+        let path = p.as_ref();
+        File::open(path)
+            .map_err(|e| failed = Some(provenance::annotate(e, "file", Some(path), 0)))
+            .ok()
+    });
+    let mut reader = ConcatReader::new(files);
+    let res = reader.copy_to(dest);
+    let written = reader.position();
+    drop(reader);
+
+    let annotate = |e| provenance::annotate(e, "copy", dest_path, written);
+    res.map_err(annotate)?;
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    dest.flush().map_err(annotate)?;
+    Ok(written)
+}
+
+/// Copies `reader` through an `AdaptiveReader`, for readers which can't be copied file by file.
+fn copy_buffered<R, W>(reader: R, dest: &mut W) -> io::Result<u64>
+where
+    R: Read,
+    W: Write + ?Sized,
+{
+    let mut reader = AdaptiveReader::new(reader);
+    let mut written = 0;
+    let annotate = |e, offset| provenance::annotate(e, "copy", None, offset);
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(buf) => buf,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let len = buf.len();
+        dest.write_all(buf).map_err(|e| annotate(e, written))?;
+        reader.consume(len);
+        written += len as u64;
+    }
+    dest.flush().map_err(|e| annotate(e, written))?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{concat_path_to, concat_path_to_file, concat_path_to_with_progress};
+    use crate::progress::Progress;
+    use crate::provenance::Provenance;
+    use crate::test_util::TempTree;
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn copies_files_like_cat() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"").unwrap(),
+            tree.file("c", b"345").unwrap(),
+        ];
+
+        let mut out = Vec::new();
+        assert_eq!(concat_path_to(&mut out, &paths).unwrap(), 5);
+        assert_eq!(out, b"12345");
+
+        let dest = tree.path().join("out");
+        assert_eq!(concat_path_to_file(&dest, &paths).unwrap(), 5);
+        assert_eq!(fs::read(&dest).unwrap(), b"12345");
+
+        let mut last = None;
+        let mut out = Vec::new();
+        let observer = |p: &Progress| last = Some((p.bytes_read, p.total_bytes));
+        concat_path_to_with_progress(&mut out, &paths, observer).unwrap();
+        assert_eq!(last, Some((5, Some(5))));
+
+        let missing = tree.path().join("missing");
+        let mut out = Vec::new();
+        let err = concat_path_to(&mut out, vec![&paths[0], &missing, &paths[2]]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(out, b"12");
+        assert_eq!(
+            Provenance::of(&err).unwrap().path(),
+            Some(missing.as_path())
+        );
+
+        let err = concat_path_to(&mut &mut [0u8; 3][..], &paths).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(Provenance::of(&err).unwrap().crumbs()[0].layer, "copy");
+    }
+}
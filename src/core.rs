@@ -0,0 +1,29 @@
+//! The abstractions shared by all subsystems of the crate.
+//!
+//! Optional subsystems behind features, like [`encoding`] or [`hash`], build on the types
+//! re-exported here and on nothing else from the crate. Nothing in this module is gated by a
+//! feature, so every subset of features compiles and the subsystems of different features stack
+//! on each other:
+//!
+//! * the reader traits [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`],
+//! * their object safe companions [`AnyConcatRead`] and [`AnyFileConcatRead`],
+//! * the layer markers [`UnframedRead`] and [`RawRead`],
+//! * error annotation with [`provenance`],
+//! * mixed sources with [`Source`].
+//!
+//! [`encoding`]:               ../encoding/index.html
+//! [`hash`]:                   ../hash/index.html
+//! [`ConcatRead`]:             trait.ConcatRead.html
+//! [`SourceRead`]:             trait.SourceRead.html
+//! [`FileConcatRead`]:         trait.FileConcatRead.html
+//! [`AnyConcatRead`]:          trait.AnyConcatRead.html
+//! [`AnyFileConcatRead`]:      trait.AnyFileConcatRead.html
+//! [`UnframedRead`]:           trait.UnframedRead.html
+//! [`RawRead`]:                trait.RawRead.html
+//! [`provenance`]:             ../provenance/index.html
+//! [`Source`]:                 ../source/enum.Source.html
+pub use crate::erased::{AnyConcatRead, AnyFileConcatRead};
+pub use crate::layer::{BoxedFileRead, BoxedRead, RawRead, UnframedRead};
+pub use crate::provenance;
+pub use crate::source::Source;
+pub use crate::{ConcatRead, FileConcatRead, SourceRead};
@@ -0,0 +1,134 @@
+//! Concatenating [`embedded-io`] readers.
+//!
+//! An [`EmbeddedConcatReader`] concatenates readers implementing the `Read` trait of
+//! `embedded-io` instead of `std::io::Read`, e.g. to stitch together flash regions or DMA buffers
+//! on embedded targets. It only builds on `core` and the `embedded-io` traits, without their
+//! `std` feature, and reports the errors of its sources unchanged.
+//!
+//! The rest of the crate still requires `std`, so the crate as a whole can't be built for
+//! `no_std` targets yet. This module is kept free of `std` and `alloc` for the time the crate
+//! gets a `std` feature.
+//!
+//! ```
+//! use concat_reader::embedded::EmbeddedConcatReader;
+//! use embedded_io::Read;
+//!
+//! let regions: [&[u8]; 3] = [b"boot", b"", b"loader"];
+//! let mut reader = EmbeddedConcatReader::new(regions);
+//!
+//! let mut buf = [0; 10];
+//! reader.read_exact(&mut buf).unwrap();
+//! assert_eq!(&buf, b"bootloader");
+//! ```
+//!
+//! [`embedded-io`]:            https://docs.rs/embedded-io
+//! [`EmbeddedConcatReader`]:   struct.EmbeddedConcatReader.html
+use core::fmt;
+use embedded_io::{ErrorType, Read};
+
+/// Reads from a list of `embedded-io` readers one after another.
+///
+/// Unlike [`ConcatReader`], this reader implements `embedded_io::Read`. Its error type is the
+/// error type of the sources.
+///
+/// [`ConcatReader`]:           ../read/struct.ConcatReader.html
+pub struct EmbeddedConcatReader<I: IntoIterator> {
+    curr: Option<I::Item>,
+    iter: I::IntoIter,
+}
+
+impl<I: IntoIterator> EmbeddedConcatReader<I> {
+    /// Creates a new `EmbeddedConcatReader` from the readers of `readers`.
+    pub fn new(readers: I) -> Self {
+        let mut iter = readers.into_iter();
+        EmbeddedConcatReader {
+            curr: iter.next(),
+            iter,
+        }
+    }
+
+    /// Returns the reader currently read from.
+    pub fn current(&self) -> Option<&I::Item> {
+        self.curr.as_ref()
+    }
+
+    /// Skips to the next reader. Returns `false` if there is none.
+    pub fn skip(&mut self) -> bool {
+        self.curr = self.iter.next();
+        self.curr.is_some()
+    }
+}
+
+impl<I> ErrorType for EmbeddedConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: ErrorType,
+{
+    type Error = <I::Item as ErrorType>::Error;
+}
+
+impl<I> Read for EmbeddedConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some(reader) = self.curr.as_mut() {
+            match reader.read(buf)? {
+                0 => self.curr = self.iter.next(),
+                n => return Ok(n),
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl<I> fmt::Debug for EmbeddedConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EmbeddedConcatReader")
+            .field("curr", &self.curr)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmbeddedConcatReader;
+    use embedded_io::{ErrorKind, ErrorType, Read};
+
+    struct Failing;
+
+    impl ErrorType for Failing {
+        type Error = ErrorKind;
+    }
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> Result<usize, ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+    }
+
+    #[test]
+    fn reads_across_sources() {
+        let mut reader = EmbeddedConcatReader::new([&b"12"[..], b"", b"345"]);
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"345");
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(reader.current().is_none());
+
+        let mut failing = EmbeddedConcatReader::new([Failing, Failing]);
+        assert_eq!(failing.read(&mut buf), Err(ErrorKind::Other));
+        assert!(failing.skip());
+        assert!(!failing.skip());
+        assert_eq!(failing.read(&mut buf), Ok(0));
+    }
+}
@@ -0,0 +1,287 @@
+//! Transcoding sources of different character encodings to UTF-8.
+//!
+//! This module is only available with the `encoding` feature. [`Transcode`] decodes every source
+//! with its own [`Encoding`] and produces one UTF-8 stream. The encoding of a source is taken
+//! from its byte order mark, from a declaration for the source, or from a default in this order.
+//!
+//! ```
+//! use concat_reader::encoding::{encoding_rs, Transcode};
+//! use concat_reader::concat;
+//! use std::io::prelude::*;
+//!
+//! let sources: Vec<&[u8]> = vec![b"caf\xE9 ", b"\xFF\xFEu\0t\0f\0"];
+//! let mut reader = Transcode::new(concat(sources), encoding_rs::WINDOWS_1252);
+//!
+//! let mut buffer = String::new();
+//! reader.read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "café utf");
+//! ```
+//!
+//! [`Transcode`]:              struct.Transcode.html
+//! [`Encoding`]:               https://docs.rs/encoding_rs/0.8/encoding_rs/struct.Encoding.html
+use crate::core::{provenance, ConcatRead, FileConcatRead, RawRead, SourceRead, UnframedRead};
+use encoding_rs::{Decoder, Encoding};
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+
+pub use encoding_rs;
+
+const CHUNK: usize = 8 * 1024;
+
+type Declare<R> = Box<dyn FnMut(&R) -> Option<&'static Encoding> + Send>;
+
+/// A reader adapter which transcodes every source to UTF-8.
+///
+/// Malformed sequences are replaced with `U+FFFD`. A byte order mark at the start of a source
+/// selects UTF-8, UTF-16LE or UTF-16BE and is removed.
+///
+/// `Transcode` requires a [`RawRead`] stream, as the bytes must be decoded before anything else
+/// changes them. It implements [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`] when the
+/// wrapped reader does.
+///
+/// [`RawRead`]:                ../layer/trait.RawRead.html
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct Transcode<R> {
+    inner: R,
+    default: &'static Encoding,
+    declare: Option<Declare<R>>,
+    decoder: Option<Decoder>,
+    input: Vec<u8>,
+    in_pos: usize,
+    out: Vec<u8>,
+    out_pos: usize,
+    source_done: bool,
+    position: u64,
+}
+
+impl<R: RawRead> Transcode<R> {
+    /// Creates a new `Transcode` reader decoding sources without a byte order mark with
+    /// `default`.
+    pub fn new(inner: R, default: &'static Encoding) -> Self {
+        Transcode {
+            inner,
+            default,
+            declare: None,
+            decoder: None,
+            input: Vec::new(),
+            in_pos: 0,
+            out: Vec::new(),
+            out_pos: 0,
+            source_done: false,
+            position: 0,
+        }
+    }
+
+    /// Declares the encoding of each source.
+    ///
+    /// `declare` is called with the wrapped reader at the start of every source. Sources for
+    /// which it returns `None` use the default encoding.
+    ///
+    /// ```no_run
+    /// use concat_reader::encoding::encoding_rs::{UTF_8, WINDOWS_1252};
+    /// use concat_reader::*;
+    ///
+    /// let files = ["legacy.log", "current.log"];
+    /// let reader = FileConcatReader::new(&files)
+    ///     .transcode(UTF_8)
+    ///     .declare(|r: &FileConcatReader<_>| match r.file_path() {
+    ///         Some(p) if p.ends_with("legacy.log") => Some(WINDOWS_1252),
+    ///         _ => None,
+    ///     });
+    /// ```
+    pub fn declare<F>(mut self, declare: F) -> Self
+    where
+        F: FnMut(&R) -> Option<&'static Encoding> + Send + 'static,
+    {
+        self.declare = Some(Box::new(declare));
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `Transcode`, returning the underlying reader.
+    ///
+    /// Bytes which were read but not yet returned are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn new_decoder(&mut self) -> Decoder {
+        let inner = &self.inner;
+        let encoding = self
+            .declare
+            .as_mut()
+            .and_then(|declare| declare(inner))
+            .unwrap_or(self.default);
+        encoding.new_decoder()
+    }
+
+    /// Drops the decoder and buffered bytes of the current source.
+    fn reset_source(&mut self) {
+        self.decoder = None;
+        self.input.clear();
+        self.in_pos = 0;
+        self.out.clear();
+        self.out_pos = 0;
+        self.source_done = false;
+    }
+
+    /// Decodes the next chunk of the current source into `out`.
+    fn fill(&mut self) -> io::Result<()> {
+        let mut last = false;
+        if self.in_pos == self.input.len() {
+            self.input.resize(CHUNK, 0);
+            let n = match self.inner.read_source(&mut self.input) {
+                Ok(n) => n,
+                Err(e) => {
+                    self.input.clear();
+                    return Err(e);
+                }
+            };
+            self.input.truncate(n);
+            self.in_pos = 0;
+            last = n == 0;
+        }
+
+        let mut decoder = match self.decoder.take() {
+            Some(decoder) => decoder,
+            None => self.new_decoder(),
+        };
+        let input = &self.input[self.in_pos..];
+        let len = decoder
+            .max_utf8_buffer_length(input.len())
+            .unwrap_or(CHUNK * 3);
+        self.out.resize(len, 0);
+        let (_, read, written, _) = decoder.decode_to_utf8(input, &mut self.out, last);
+        self.in_pos += read;
+        self.out.truncate(written);
+        self.out_pos = 0;
+        self.decoder = Some(decoder);
+        self.source_done = last;
+        Ok(())
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.out_pos < self.out.len() {
+                let n = (&self.out[self.out_pos..]).read(buf)?;
+                self.out_pos += n;
+                return Ok(n);
+            }
+            if self.source_done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+    }
+}
+
+impl<R: RawRead> Read for Transcode<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || self.inner.current().is_none() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<R: RawRead> ConcatRead for Transcode<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.reset_source();
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.take_current()?;
+        self.reset_source();
+        Some(item)
+    }
+}
+
+impl<R: RawRead> SourceRead for Transcode<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.read_inner(buf);
+        provenance::track(res, "transcode", &mut self.position)
+    }
+}
+
+impl<R: RawRead> UnframedRead for Transcode<R> {}
+
+impl<R: RawRead + FileConcatRead> FileConcatRead for Transcode<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encoding_rs::{UTF_8, WINDOWS_1252};
+    use crate::test_util::TempTree;
+    use crate::{FileConcatRead, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn transcodes_declared_and_sniffed_encodings() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("utf8.txt", "grüße\n".as_bytes()).unwrap(),
+            tree.file("latin1.txt", b"gr\xFC\xDFe\n").unwrap(),
+            tree.utf16le_file("utf16.txt", "grüße\n").unwrap(),
+        ];
+        let latin1 = paths[1].clone();
+        let mut reader = FileConcatReader::new(&paths).transcode(UTF_8).declare(
+            move |r: &FileConcatReader<_>| {
+                if r.file_path() == Some(latin1.as_path()) {
+                    Some(WINDOWS_1252)
+                } else {
+                    None
+                }
+            },
+        );
+
+        let mut out = Vec::new();
+        let mut buf = [0; 1];
+        while reader.read(&mut buf).unwrap() == 1 {
+            out.push(buf[0]);
+        }
+        assert_eq!(String::from_utf8(out).unwrap(), "grüße\n".repeat(3));
+    }
+}
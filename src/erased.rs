@@ -0,0 +1,147 @@
+//! Trait objects for readers of any item type.
+//!
+//! [`ConcatRead`] has an associated `Item` type, so a trait object has to name it, like
+//! `Box<dyn FileConcatRead<Item = File>>`. Code which only drives a reader and doesn't touch its
+//! items, like a plugin accepting any concat reader, can use the companion traits of this module
+//! instead. They have no associated types and are implemented for every reader:
+//!
+//! * [`AnyConcatRead`] for every [`ConcatRead`],
+//! * [`AnyFileConcatRead`] for every [`FileConcatRead`].
+//!
+//! Their methods have names of their own, so they can be imported next to the typed traits
+//! without making calls ambiguous.
+//!
+//! ```
+//! use concat_reader::erased::{AnyConcatRead, AnyFileConcatRead};
+//! use concat_reader::{concat, concat_path};
+//!
+//! fn count_sources(reader: &mut dyn AnyConcatRead) -> usize {
+//!     let mut count = reader.has_current() as usize;
+//!     while reader.next_source() {
+//!         count += 1;
+//!     }
+//!     count
+//! }
+//!
+//! fn plugin(files: &mut dyn AnyFileConcatRead) {
+//!     while files.next_source() {
+//!         println!("{:?}", files.current_path());
+//!     }
+//! }
+//!
+//! let bytes: Vec<&[u8]> = vec![b"1", b"2", b"3"];
+//! assert_eq!(count_sources(&mut concat(bytes)), 3);
+//!
+//! let mut files: Box<dyn AnyFileConcatRead> = Box::new(concat_path(Vec::<&str>::new()));
+//! plugin(&mut *files);
+//! ```
+//!
+//! [`ConcatRead`]:             ../trait.ConcatRead.html
+//! [`FileConcatRead`]:         ../trait.FileConcatRead.html
+//! [`AnyConcatRead`]:          trait.AnyConcatRead.html
+//! [`AnyFileConcatRead`]:      trait.AnyFileConcatRead.html
+use crate::{ConcatRead, FileConcatRead};
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The object safe part of [`ConcatRead`], implemented for every `ConcatRead`.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+pub trait AnyConcatRead: Read {
+    /// Skips to the next item, see [`ConcatRead::skip`].
+    ///
+    /// [`ConcatRead::skip`]:       ../trait.ConcatRead.html#tymethod.skip
+    fn next_source(&mut self) -> bool;
+
+    /// Returns `true` if there is a current item, see [`ConcatRead::current`].
+    ///
+    /// [`ConcatRead::current`]:    ../trait.ConcatRead.html#tymethod.current
+    fn has_current(&self) -> bool;
+}
+
+impl<R: ConcatRead + ?Sized> AnyConcatRead for R {
+    fn next_source(&mut self) -> bool {
+        self.skip()
+    }
+
+    fn has_current(&self) -> bool {
+        self.current().is_some()
+    }
+}
+
+/// The object safe part of [`FileConcatRead`], implemented for every `FileConcatRead`.
+///
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub trait AnyFileConcatRead: AnyConcatRead {
+    /// Returns the path of the current file, see [`FileConcatRead::file_path`].
+    ///
+    /// [`FileConcatRead::file_path`]: ../trait.FileConcatRead.html#tymethod.file_path
+    fn current_path(&self) -> Option<&Path>;
+
+    /// Returns the path of the next file, see [`FileConcatRead::peek_next_path`].
+    ///
+    /// [`FileConcatRead::peek_next_path`]: ../trait.FileConcatRead.html#method.peek_next_path
+    fn next_path(&mut self) -> Option<&Path>;
+
+    /// Returns the metadata of the current file, see [`FileConcatRead::current_metadata`].
+    ///
+    /// [`FileConcatRead::current_metadata`]: ../trait.FileConcatRead.html#method.current_metadata
+    fn source_metadata(&self) -> io::Result<Metadata>;
+}
+
+impl<R: FileConcatRead + ?Sized> AnyFileConcatRead for R {
+    fn current_path(&self) -> Option<&Path> {
+        self.file_path()
+    }
+
+    fn next_path(&mut self) -> Option<&Path> {
+        self.peek_next_path()
+    }
+
+    fn source_metadata(&self) -> io::Result<Metadata> {
+        self.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnyConcatRead, AnyFileConcatRead};
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, ConcatReader, FileConcatRead, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn drives_readers_of_any_item() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"3").unwrap(),
+        ];
+        let bytes: Vec<&[u8]> = vec![b"4", b"5"];
+        let mut file = FileConcatReader::new(paths.clone());
+        let mut readers: Vec<&mut dyn AnyConcatRead> = vec![&mut file];
+        let mut bytes = ConcatReader::new(bytes);
+        readers.push(&mut bytes);
+
+        let mut buf = [0; 1];
+        for reader in readers {
+            reader.read_exact(&mut buf).unwrap();
+            assert!(reader.has_current());
+            assert!(reader.next_source());
+            reader.read_exact(&mut buf).unwrap();
+            assert!(!reader.next_source());
+            assert!(!reader.has_current());
+        }
+
+        let mut file: Box<dyn AnyFileConcatRead> = Box::new(FileConcatReader::new(paths.clone()));
+        assert_eq!(file.next_path(), Some(paths[1].as_path()));
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(file.current_path(), Some(paths[0].as_path()));
+        assert_eq!(file.source_metadata().unwrap().len(), 2);
+
+        let mut typed = FileConcatReader::new(paths);
+        typed.skip();
+        assert!(typed.file_path().is_some());
+    }
+}
@@ -1,13 +1,79 @@
+use crate::cancel::{CancelToken, Cancelled};
+use crate::checkpoint::{Checkpoint, Snapshot};
+#[cfg(feature = "encoding")]
+use crate::encoding::Transcode;
+use crate::layer::{RawRead, UnframedRead};
+use crate::map::{CurrentSource, MapSources};
+use crate::preamble::SkipPreamble;
+use crate::progress::{ProgressObserver, ProgressReader, WeightedProgressReader};
+use crate::provenance::{self, Provenance};
+use crate::queue::SourceQueue;
+use crate::read::{AfterError, Poisoned, Remaining};
+use crate::records::{DelimitedRecords, FixedRecords, LinesWithSource, Remainder};
+use crate::restart::Restart;
+use crate::tee::TeeReader;
+use crate::text::{DropHeaders, Normalize};
+use crate::throttle::Throttle;
+use crate::timeout::Timeout;
 use crate::ConcatRead;
 use crate::FileConcatRead;
+use crate::SourceRead;
+use std::cmp;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
-use std::io::{self, Read, Result};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Result, Seek, SeekFrom, Write};
+use std::iter::Chain;
+use std::mem;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use std::vec;
 
 trait FileLike: fmt::Debug + Read + Sized {
     fn open<P: AsRef<Path>>(p: P) -> Result<Self>;
+
+    /// Opens `p` with `options` instead of read only, and hints the OS that the file is read
+    /// sequentially if `sequential` is set.
+    fn open_tuned<P: AsRef<Path>>(
+        p: P,
+        _options: Option<&fs::OpenOptions>,
+        _sequential: bool,
+    ) -> Result<Self> {
+        Self::open(p)
+    }
+
+    /// Opens `p` without waiting for a writer if it is a FIFO.
+    fn open_no_wait<P: AsRef<Path>>(p: P) -> Result<Self> {
+        Self::open(p)
+    }
+
+    /// Opens `p` so that reads return `WouldBlock` instead of waiting for data.
+    fn open_non_blocking<P: AsRef<Path>>(p: P) -> Result<Self> {
+        Self::open(p)
+    }
+
+    fn stamp(&self) -> Result<Stamp>;
+
+    /// Hints the OS that the file at `p` will be read soon.
+    fn advise_will_need(_p: &Path) {}
+
+    /// Hints the OS that the data of the file won't be read again.
+    fn advise_dont_need(&self) {}
+
+    fn is_fifo(&self) -> bool {
+        false
+    }
+
+    /// Seeks to the first data at or after `pos` and returns the range of that data. The range
+    /// starts at the end of the file if only a hole follows `pos`.
+    fn seek_data(&mut self, pos: u64) -> Result<Range<u64>> {
+        Ok(pos..u64::MAX)
+    }
 }
 
 impl FileLike for File {
@@ -15,8 +81,573 @@ impl FileLike for File {
     fn open<P: AsRef<Path>>(p: P) -> Result<Self> {
         File::open(p)
     }
+
+    fn open_tuned<P: AsRef<Path>>(
+        p: P,
+        options: Option<&fs::OpenOptions>,
+        sequential: bool,
+    ) -> Result<Self> {
+        let options = options.cloned().unwrap_or_else(|| {
+            let mut options = fs::OpenOptions::new();
+            options.read(true);
+            options
+        });
+        #[cfg(windows)]
+        let options = {
+            use std::os::windows::fs::OpenOptionsExt;
+
+            const FILE_FLAG_SEQUENTIAL_SCAN: u32 = 0x0800_0000;
+            let mut options = options;
+            if sequential {
+                options.custom_flags(FILE_FLAG_SEQUENTIAL_SCAN);
+            }
+            options
+        };
+        let file = options.open(p)?;
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        if sequential {
+            use std::os::unix::io::AsRawFd;
+
+            // SAFETY: the descriptor is valid and owned by `file`. The advice is only a hint, so
+            // errors like `ESPIPE` for a FIFO are ignored.
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+        }
+        #[cfg(not(any(
+            windows,
+            target_os = "linux",
+            target_os = "android",
+            target_os = "freebsd"
+        )))]
+        let _ = sequential;
+        Ok(file)
+    }
+
+    #[cfg(unix)]
+    fn open_no_wait<P: AsRef<Path>>(p: P) -> Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = Self::open_non_blocking(p)?;
+        // reads wait for data again, a FIFO without a writer still reads as `EOF`
+        let fd = file.as_raw_fd();
+        // SAFETY: `fd` is a valid descriptor owned by `file`
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    #[cfg(unix)]
+    fn open_non_blocking<P: AsRef<Path>>(p: P) -> Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(p)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    fn advise_will_need(p: &Path) {
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        // a FIFO must not wait for a writer
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(p);
+        if let Ok(file) = file {
+            // SAFETY: the descriptor is valid and owned by `file`, the hint starts reading the
+            // file into the page cache, which keeps it after the file is closed
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_WILLNEED) };
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    fn advise_dont_need(&self) {
+        use std::os::unix::io::AsRawFd;
+
+        // SAFETY: the descriptor is valid and owned by `self`
+        unsafe { libc::posix_fadvise(self.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    }
+
+    #[cfg(unix)]
+    fn is_fifo(&self) -> bool {
+        use std::os::unix::fs::FileTypeExt;
+
+        self.metadata()
+            .map(|meta| meta.file_type().is_fifo())
+            .unwrap_or(false)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    fn seek_data(&mut self, pos: u64) -> Result<Range<u64>> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = self.as_raw_fd();
+        let lseek = |offset: u64, whence| {
+            // SAFETY: `fd` is a valid descriptor owned by `self`
+            match unsafe { libc::lseek(fd, offset as libc::off_t, whence) } {
+                -1 => Err(io::Error::last_os_error()),
+                offset => Ok(offset as u64),
+            }
+        };
+        let start = match lseek(pos, libc::SEEK_DATA) {
+            Ok(start) => start,
+            // only a hole is left
+            Err(ref e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                let len = cmp::max(self.metadata()?.len(), pos);
+                return self.seek(SeekFrom::Start(len)).map(|len| len..len);
+            }
+            // not seekable, like a FIFO
+            Err(ref e) if e.raw_os_error() == Some(libc::ESPIPE) => return Ok(pos..u64::MAX),
+            Err(e) => return Err(e),
+        };
+        let end = lseek(start, libc::SEEK_HOLE)?;
+        self.seek(SeekFrom::Start(start))?;
+        Ok(start..end)
+    }
+
+    fn stamp(&self) -> Result<Stamp> {
+        let meta = self.metadata()?;
+        Ok(Stamp {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+/// How a [`FileConcatReader`] treats paths which are symbolic links.
+///
+/// Only the last component of a path is checked, symbolic links to parent directories are
+/// always followed. The check happens right before the file is opened.
+///
+/// [`FileConcatReader`]:       struct.FileConcatReader.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Symlinks {
+    /// Reads the file the link points to.
+    #[default]
+    Follow,
+    /// Fails with an `InvalidInput` error when the reader reaches a link.
+    Error,
+    /// Continues with the next path without reading the link.
+    Skip,
+}
+
+/// How a [`FileConcatReader`] treats paths which are named pipes (FIFOs).
+///
+/// Opening a FIFO for reading normally waits until a process opens it for writing, and a FIFO
+/// reaches `EOF` once all its writers closed it. Only Unix has FIFOs, on other platforms all
+/// policies behave like `Block`.
+///
+/// [`FileConcatReader`]:       struct.FileConcatReader.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Fifos {
+    /// Waits for a writer when the reader reaches a FIFO. In [`follow`] mode a FIFO which is
+    /// the last path is followed like a file, so the data of later writers is read as well.
+    ///
+    /// [`follow`]:                 struct.FileConcatReader.html#method.follow
+    #[default]
+    Block,
+    /// Opens FIFOs without waiting, a FIFO without a writer reads as empty. When the writers
+    /// disconnect, the reader continues with the next path, also in follow mode.
+    NoWait,
+    /// Waits for a writer like `Block`, but when the writers disconnect, the reader continues
+    /// with the next path, also in follow mode.
+    SkipOnDisconnect,
+}
+
+/// A hole in a sparse file which a [`FileConcatReader`] skipped, see [`skip_holes`].
+///
+/// [`FileConcatReader`]:       struct.FileConcatReader.html
+/// [`skip_holes`]:             struct.FileConcatReader.html#method.skip_holes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hole<'a> {
+    /// Path of the file.
+    pub path: &'a Path,
+    /// Offset of the hole in the file.
+    pub offset: u64,
+    /// Length of the hole in bytes.
+    pub len: u64,
+}
+
+/// Statistics of one file of a [`FileConcatReader`], see [`collect_stats`].
+///
+/// [`FileConcatReader`]:       struct.FileConcatReader.html
+/// [`collect_stats`]:          struct.FileConcatReader.html#method.collect_stats
+#[derive(Debug)]
+pub struct SourceStats {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Number of bytes read from the file.
+    pub bytes_read: u64,
+    /// Time it took to open the file.
+    pub open_latency: Duration,
+    /// Time from opening the file until the reader moved on to the next file, or until now for
+    /// the current file. Includes the time the caller spent between reads.
+    pub wall_time: Duration,
+    /// The error opening or reading the file failed with.
+    pub error: Option<io::Error>,
+}
+
+impl Clone for SourceStats {
+    fn clone(&self) -> Self {
+        SourceStats {
+            path: self.path.clone(),
+            bytes_read: self.bytes_read,
+            open_latency: self.open_latency,
+            wall_time: self.wall_time,
+            error: self.error.as_ref().map(copy_error),
+        }
+    }
+}
+
+/// Statistics collected by `collect_stats`.
+#[derive(Debug, Default)]
+struct Stats {
+    done: Vec<SourceStats>,
+    /// Statistics of the current file with the time it was opened.
+    curr: Option<(SourceStats, Instant)>,
+}
+
+impl Stats {
+    fn opened(&mut self, path: &Path, start: Instant, error: Option<&io::Error>) {
+        self.finish();
+        let open_latency = start.elapsed();
+        let stats = SourceStats {
+            path: path.to_path_buf(),
+            bytes_read: 0,
+            open_latency,
+            wall_time: open_latency,
+            error: error.map(copy_error),
+        };
+        self.curr = Some((stats, start));
+    }
+
+    fn read(&mut self, n: usize) {
+        if let Some((stats, _)) = &mut self.curr {
+            stats.bytes_read += n as u64;
+        }
+    }
+
+    fn failed(&mut self, err: &io::Error) {
+        if let Some((stats, _)) = &mut self.curr {
+            stats.error = Some(copy_error(err));
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some((mut stats, start)) = self.curr.take() {
+            stats.wall_time = start.elapsed();
+            self.done.push(stats);
+        }
+    }
+
+    fn report(&self) -> Vec<SourceStats> {
+        let mut report = self.done.clone();
+        if let Some((stats, start)) = &self.curr {
+            let mut stats = stats.clone();
+            stats.wall_time = start.elapsed();
+            report.push(stats);
+        }
+        report
+    }
+}
+
+/// Span of the current file, nothing without the `tracing` feature.
+#[cfg(feature = "tracing")]
+type SourceSpan = tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug)]
+struct SourceSpan;
+
+#[cfg(not(feature = "tracing"))]
+impl SourceSpan {
+    fn none() -> Self {
+        SourceSpan
+    }
+}
+
+/// Time the current file was opened, nothing without the `metrics` feature.
+#[cfg(feature = "metrics")]
+type SourceClock = Option<Instant>;
+
+#[cfg(not(feature = "metrics"))]
+type SourceClock = ();
+
+/// How the holes of sparse files are read, set by `zero_holes` or `skip_holes`.
+enum Holes {
+    Zeros,
+    Skip(Box<dyn FnMut(&Hole) + Send>),
+}
+
+/// The files a [`FileConcatReader`] would read, returned by [`plan`].
+///
+/// [`FileConcatReader`]:       struct.FileConcatReader.html
+/// [`plan`]:                   struct.FileConcatReader.html#method.plan
+#[derive(Debug)]
+pub struct Plan {
+    /// The files in the order they would be read, including the ones with a problem.
+    pub files: Vec<PlannedFile>,
+    /// Summed length of the regular files without a problem.
+    pub total_len: u64,
+}
+
+impl Plan {
+    /// Returns the files which would fail to be read.
+    pub fn problems(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|file| file.problem.is_some())
+    }
+
+    /// Returns `true` if no file has a problem.
+    pub fn is_ok(&self) -> bool {
+        self.problems().next().is_none()
+    }
+}
+
+/// A file of a [`Plan`].
+///
+/// [`Plan`]:                   struct.Plan.html
+#[derive(Debug)]
+pub struct PlannedFile {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Length of the file, `None` if it isn't a regular file or has a problem.
+    pub len: Option<u64>,
+    /// The error reading the file would fail with, like a missing file or a permission error.
+    pub problem: Option<io::Error>,
+}
+
+impl PlannedFile {
+    /// Inspects the file at `path` without reading it.
+    fn inspect(path: PathBuf) -> PlannedFile {
+        let (len, problem) = match fs::metadata(&path) {
+            Err(e) => (None, Some(e)),
+            Ok(meta) if meta.is_dir() => {
+                let err = io::Error::new(io::ErrorKind::InvalidInput, "is a directory");
+                (None, Some(err))
+            }
+            // opening a FIFO would wait for a writer
+            Ok(meta) if !meta.is_file() => (None, None),
+            Ok(meta) => match File::open(&path) {
+                Ok(_) => (Some(meta.len()), None),
+                Err(e) => (None, Some(e)),
+            },
+        };
+        PlannedFile { path, len, problem }
+    }
+}
+
+/// Identity of a file, compared by `skip_duplicates`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
+}
+
+impl FileId {
+    #[cfg(unix)]
+    fn of(path: &Path) -> Option<FileId> {
+        use std::os::unix::fs::MetadataExt;
+        let meta = fs::metadata(path).ok()?;
+        Some(FileId::Inode(meta.dev(), meta.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn of(path: &Path) -> Option<FileId> {
+        fs::canonicalize(path).ok().map(FileId::Path)
+    }
+}
+
+/// Length and modification time of a file, compared by `verify_unchanged`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// The error of a [`FileConcatReader`] in [`verify_unchanged`] mode for a file which changed
+/// while it was read.
+///
+/// The error is returned as an `io::Error` of kind `InvalidData`. Use [`FileChanged::of`] to
+/// find it in an error.
+///
+/// [`FileConcatReader`]:       struct.FileConcatReader.html
+/// [`verify_unchanged`]:       struct.FileConcatReader.html#method.verify_unchanged
+/// [`FileChanged::of`]:        #method.of
+#[derive(Debug)]
+pub struct FileChanged {
+    path: PathBuf,
+    len_at_open: u64,
+    len_at_eof: u64,
+}
+
+impl FileChanged {
+    /// Returns the `FileChanged` error wrapped in `err`, if any.
+    pub fn of(err: &io::Error) -> Option<&FileChanged> {
+        let err = match Provenance::of(err) {
+            Some(provenance) => provenance.error(),
+            None => err,
+        };
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+
+    /// Returns the path of the file which changed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the length of the file when it was opened.
+    pub fn len_at_open(&self) -> u64 {
+        self.len_at_open
+    }
+
+    /// Returns the length of the file when the reader reached its end.
+    pub fn len_at_eof(&self) -> u64 {
+        self.len_at_eof
+    }
+}
+
+impl fmt::Display for FileChanged {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} changed while it was read, length {} when opened and {} at its end",
+            self.path.display(),
+            self.len_at_open,
+            self.len_at_eof
+        )
+    }
+}
+
+impl Error for FileChanged {}
+
+/// The first difference [`verify_against`] found between a concatenation and its reference.
+///
+/// The error is returned as an `io::Error` of kind `InvalidData`. Use [`Mismatch::of`] to find
+/// it in an error.
+///
+/// [`verify_against`]:         struct.FileConcatReader.html#method.verify_against
+/// [`Mismatch::of`]:           #method.of
+#[derive(Debug)]
+pub struct Mismatch {
+    offset: u64,
+    source: Option<(PathBuf, u64)>,
+    reference_ended: bool,
+}
+
+impl Mismatch {
+    /// Returns the `Mismatch` error wrapped in `err`, if any.
+    pub fn of(err: &io::Error) -> Option<&Mismatch> {
+        let err = match Provenance::of(err) {
+            Some(provenance) => provenance.error(),
+            None => err,
+        };
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+
+    /// Returns the offset of the first differing byte, counted from where the comparison
+    /// started.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the path of the file the differing byte is in, `None` if the concatenation ended
+    /// before the reference.
+    pub fn path(&self) -> Option<&Path> {
+        self.source.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Returns the offset of the differing byte in its file, `None` if the concatenation ended
+    /// before the reference.
+    pub fn source_offset(&self) -> Option<u64> {
+        self.source.as_ref().map(|(_, offset)| *offset)
+    }
+
+    /// Returns `true` if the reference ended before the concatenation.
+    pub fn reference_ended(&self) -> bool {
+        self.reference_ended
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.source {
+            None => write!(
+                f,
+                "concatenation ends at offset {}, before the reference",
+                self.offset
+            ),
+            Some((path, offset)) if self.reference_ended => write!(
+                f,
+                "reference ends at offset {}, the concatenation continues with {} at offset {}",
+                self.offset,
+                path.display(),
+                offset
+            ),
+            Some((path, offset)) => write!(
+                f,
+                "concatenation differs from the reference at offset {}, in {} at offset {}",
+                self.offset,
+                path.display(),
+                offset
+            ),
+        }
+    }
+}
+
+impl Error for Mismatch {}
+
+/// The error of [`FileConcatReader::validate`] listing all files which can't be read.
+///
+/// The error is returned as an `io::Error` of the kind of the first problem. Use
+/// [`PreflightFailed::of`] to find it in an error.
+///
+/// [`FileConcatReader::validate`]: struct.FileConcatReader.html#method.validate
+/// [`PreflightFailed::of`]:    #method.of
+#[derive(Debug)]
+pub struct PreflightFailed {
+    problems: Vec<PlannedFile>,
+    files: usize,
+}
+
+impl PreflightFailed {
+    /// Returns the `PreflightFailed` error wrapped in `err`, if any.
+    pub fn of(err: &io::Error) -> Option<&PreflightFailed> {
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+
+    /// Returns the files which can't be read, with their problem.
+    pub fn problems(&self) -> &[PlannedFile] {
+        &self.problems
+    }
 }
 
+impl fmt::Display for PreflightFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} files can't be read",
+            self.problems.len(),
+            self.files
+        )?;
+        for (i, file) in self.problems.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{}", sep, file.path.display())?;
+            if let Some(problem) = &file.problem {
+                write!(f, " ({})", problem)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for PreflightFailed {}
+
 /// The `FileConcatReader` struct is a reader over multiple [`File`]'s created from an [`Iterator`] with
 /// [`AsRef<Path>`] items.
 ///
@@ -24,6 +655,15 @@ impl FileLike for File {
 /// If the current reader reaches its `EOF` the `FileConcatReader` will start reading from the next
 /// path in the iterator. If all readers reached `EOF` the `FileConcatReader` will also be `EOF`.
 ///
+/// With the `tracing` feature every file gets a `source` span with its path and index, in which
+/// the reader emits `DEBUG` events when the file is opened, ends or is skipped, and `WARN` events
+/// for errors. The end of the concatenation is an `INFO` event with the total number of bytes.
+///
+/// With the `metrics` feature the reader counts `concat_reader_bytes_read_total`,
+/// `concat_reader_files_opened_total` and `concat_reader_open_errors_total`, and records the time
+/// from opening a file until the reader moves on to the next one in the
+/// `concat_reader_file_duration_seconds` histogram.
+///
 /// # Examples
 /// ```no_run
 /// use concat_reader::*;
@@ -54,7 +694,6 @@ impl FileLike for File {
 /// [`File`]:                   https://doc.rust-lang.org/std/fs/struct.File.html
 /// [`Iterator`]:               https://doc.rust-lang.org/std/iter/trait.Iterator.html
 /// [`AsRef<Path>`]:            https://doc.rust-lang.org/std/convert/trait.AsRef.html
-
 pub struct FileConcatReader<I: IntoIterator> {
     inner: InnerReader<File, I>,
 }
@@ -76,47 +715,1606 @@ where
     /// }
     /// ```
     pub fn new(iter: I) -> Self {
-        Self {
-            inner: InnerReader::new(iter),
+        let mut inner = InnerReader::new(iter);
+        inner.opts.fuse = true;
+        inner.exhausted = !inner.curr.is_init();
+        Self { inner }
+    }
+
+    /// Collects the remaining paths of the internal iterator into a `Vec`.
+    ///
+    /// Features like [`remaining_len`] and [`snapshot`] require a `Clone` iterator and aren't
+    /// available for one-shot iterators like channels or queues. `materialize` converts the reader
+    /// into one backed by a `Vec`, keeping the current file and its read position. At most `limit`
+    /// paths will be collected. If the iterator yields more paths, the reader is returned as `Err`
+    /// without losing any paths.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    ///
+    /// let mut c = FileConcatReader::with_queue();
+    /// c.push_path("foo.txt");
+    /// c.push_path("bar.txt");
+    ///
+    /// let c = match c.materialize(10) {
+    ///     Ok(c) => c,
+    ///     Err(_) => panic!("more than 10 paths left"),
+    /// };
+    /// assert_eq!(format!("{:?}", c), "CatReader { curr: ReaderState::Init(\"foo.txt\"), peeked: None, remaining: 1 }");
+    /// ```
+    ///
+    /// [`remaining_len`]:          #method.remaining_len
+    /// [`snapshot`]:               #method.snapshot
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    pub fn materialize(
+        self,
+        limit: usize,
+    ) -> std::result::Result<
+        FileConcatReader<Vec<I::Item>>,
+        FileConcatReader<Chain<vec::IntoIter<I::Item>, I::IntoIter>>,
+    > {
+        match self.inner.materialize(limit) {
+            Ok(inner) => Ok(FileConcatReader { inner }),
+            Err(inner) => Err(FileConcatReader { inner }),
         }
     }
-}
 
-impl<I> ConcatRead for FileConcatReader<I>
-where
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    type Item = File;
+    /// Turns on follow mode, similar to `tail -f` over a sequence of rotated files.
+    ///
+    /// When the current file reaches `EOF` and there is a next path, the `FileConcatReader` moves
+    /// on to it as usual. When the current file is the last one, the reader keeps the file open
+    /// and checks every `interval` for new data in it or for new paths in the source list instead
+    /// of reporting `EOF`. Together with a [`SourceQueue`] or a channel this follows a log file
+    /// across rotations.
+    ///
+    /// A reader in follow mode only returns `Ok(0)` for an empty buffer.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let mut c = FileConcatReader::with_queue().follow(Duration::from_millis(250));
+    /// c.push_path("app.log.1");
+    /// c.push_path("app.log");
+    ///
+    /// let mut line = String::new();
+    /// let mut lines = std::io::BufReader::new(c);
+    /// while lines.read_line(&mut line).unwrap() > 0 {
+    ///     print!("{}", line);
+    ///     line.clear();
+    /// }
+    /// ```
+    ///
+    /// [`SourceQueue`]:            ../queue/struct.SourceQueue.html
+    pub fn follow(mut self, interval: Duration) -> Self {
+        self.inner.opts.follow = Some(interval);
+        self.inner.exhausted = false;
+        self
+    }
 
-    fn current(&self) -> Option<&Self::Item> {
-        self.inner.current()
+    /// Limits the number of bytes read from each file.
+    ///
+    /// After `limit` bytes have been read from the current file, the `FileConcatReader` continues
+    /// with the next file, as if [`Read::take`] was applied to each file. [`current`] and
+    /// [`file_path`] still refer to the file which was read from.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// // read the first 512 bytes of each file
+    /// let files = ["foo.bin", "bar.bin", "baz.bin"];
+    /// let mut c = FileConcatReader::new(&files).limit_per_source(512);
+    /// let mut headers = Vec::new();
+    /// c.read_to_end(&mut headers).unwrap();
+    /// ```
+    ///
+    /// [`Read::take`]:             https://doc.rust-lang.org/std/io/trait.Read.html#method.take
+    /// [`current`]:                ../trait.ConcatRead.html#tymethod.current
+    /// [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+    pub fn limit_per_source(mut self, limit: u64) -> Self {
+        self.inner.opts.limit_per_source = Some(limit);
+        self
     }
 
-    fn skip(&mut self) -> bool {
-        self.inner.skip()
+    /// Limits the number of bytes read from all files together.
+    ///
+    /// After `limit` bytes have been read the `FileConcatReader` reports `EOF`, even if there are
+    /// files left. The file it stopped at is still available through [`file_path`] and
+    /// [`current_position`].
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["foo.txt", "bar.txt", "baz.txt"];
+    /// let mut c = FileConcatReader::new(&files).limit_total(1024);
+    /// let mut preview = String::new();
+    /// c.read_to_string(&mut preview).unwrap();
+    /// println!("stopped in {:?} at {}", c.file_path(), c.current_position());
+    /// ```
+    ///
+    /// [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+    /// [`current_position`]:       #method.current_position
+    pub fn limit_total(mut self, limit: u64) -> Self {
+        self.inner.opts.limit_total = Some(limit);
+        self
     }
-}
 
-impl<I> FileConcatRead for FileConcatReader<I>
-where
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    fn file_path(&self) -> Option<&Path> {
-        self.inner.file_path()
+    /// Fails instead of returning torn output when a file changes while it is read.
+    ///
+    /// The length and modification time of every file are recorded when it is opened and
+    /// compared when the reader reaches its end. If they differ, or the number of bytes read
+    /// doesn't match the length at open, the read fails with a [`FileChanged`] error. The reader
+    /// stays at the file until it is skipped. Files which are [`follow`]ed grow by design and are
+    /// not verified.
+    ///
+    /// ```no_run
+    /// use concat_reader::file::FileChanged;
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["app.log.2", "app.log.1"];
+    /// let mut c = FileConcatReader::new(&files).verify_unchanged();
+    /// let mut buffer = Vec::new();
+    /// if let Err(e) = c.read_to_end(&mut buffer) {
+    ///     if let Some(changed) = FileChanged::of(&e) {
+    ///         eprintln!("{} was written to, retrying later", changed.path().display());
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`FileChanged`]:            struct.FileChanged.html
+    /// [`follow`]:                 #method.follow
+    pub fn verify_unchanged(mut self) -> Self {
+        self.inner.opts.verify = true;
+        self
     }
-}
 
-impl<I> From<I> for FileConcatReader<I>
-where
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    fn from(iter: I) -> Self {
-        Self::new(iter)
+    /// Sets how paths which are symbolic links are treated, see [`Symlinks`].
+    ///
+    /// ```no_run
+    /// use concat_reader::file::Symlinks;
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["backup/a.db", "backup/b.db"];
+    /// let mut c = FileConcatReader::new(&files).symlinks(Symlinks::Error);
+    /// let mut buffer = Vec::new();
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    ///
+    /// [`Symlinks`]:               enum.Symlinks.html
+    pub fn symlinks(mut self, policy: Symlinks) -> Self {
+        self.inner.opts.symlinks = policy;
+        self
     }
-}
+
+    /// Skips files which were already read through another path.
+    ///
+    /// Files are identified by device and inode on Unix, so repeated paths, hard links and
+    /// symbolic links to a file read before are skipped. Other platforms compare canonical
+    /// paths, which catches repeated paths and symbolic links. Paths which can't be inspected
+    /// are never skipped, their error is reported when they are opened.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["logs/app.log", "logs/./app.log", "logs/current.log"];
+    /// let mut c = FileConcatReader::new(&files).skip_duplicates();
+    /// let mut buffer = Vec::new();
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    pub fn skip_duplicates(mut self) -> Self {
+        self.inner.opts.skip_duplicates = true;
+        self
+    }
+
+    /// Sets how paths which are named pipes are treated, see [`Fifos`].
+    ///
+    /// ```no_run
+    /// use concat_reader::file::Fifos;
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["header.csv", "/run/export.fifo", "footer.csv"];
+    /// let mut c = FileConcatReader::new(&files).fifos(Fifos::NoWait);
+    /// let mut buffer = Vec::new();
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    ///
+    /// [`Fifos`]:                  enum.Fifos.html
+    pub fn fifos(mut self, policy: Fifos) -> Self {
+        self.inner.opts.fifos = policy;
+        self
+    }
+
+    /// Opens the files with `options` instead of read only, e.g. with platform specific flags.
+    ///
+    /// On Linux, `O_DIRECT` reads large files past the page cache, so a scan doesn't evict the
+    /// cache of other processes. `O_DIRECT` requires reads into buffers aligned to the block size
+    /// of the file system, other reads and file systems without direct I/O fail with `EINVAL`.
+    /// FIFOs are opened without these options with [`Fifos::NoWait`].
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// use concat_reader::*;
+    /// use std::fs::OpenOptions;
+    /// use std::os::unix::fs::OpenOptionsExt;
+    ///
+    /// let mut options = OpenOptions::new();
+    /// options.read(true).custom_flags(libc::O_DIRECT);
+    /// let c = FileConcatReader::new(&["scan.0", "scan.1"]).open_options(options);
+    /// # }
+    /// ```
+    ///
+    /// [`Fifos::NoWait`]:          enum.Fifos.html#variant.NoWait
+    pub fn open_options(mut self, options: fs::OpenOptions) -> Self {
+        self.inner.opts.open_options = Some(options);
+        self
+    }
+
+    /// Tells the OS that the files are read from start to end, so it reads ahead more
+    /// aggressively.
+    ///
+    /// Uses `posix_fadvise` with `POSIX_FADV_SEQUENTIAL` on Linux, Android and FreeBSD, and
+    /// `FILE_FLAG_SEQUENTIAL_SCAN` on Windows. Other platforms ignore the hint.
+    pub fn advise_sequential(mut self) -> Self {
+        self.inner.opts.sequential = true;
+        self
+    }
+
+    /// Tells the OS to read the next file into the page cache while the current one is read, and
+    /// to drop completed files from the cache.
+    ///
+    /// Uses `posix_fadvise` with `POSIX_FADV_WILLNEED` and `POSIX_FADV_DONTNEED` on Linux,
+    /// Android and FreeBSD, other platforms ignore the hints. Unlike a prefetch thread, the OS
+    /// reads ahead in the background without a buffer in the reader. The next path is taken
+    /// from the source list when a file is opened, so with [`from_channel`] opening a file waits
+    /// for the next path to arrive.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::fs::File;
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let batches = ["batch-0.bin", "batch-1.bin", "batch-2.bin"];
+    ///     let mut c = FileConcatReader::new(&batches).advise_readahead();
+    ///     io::copy(&mut c, &mut File::create("batches.bin")?)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`from_channel`]:           #method.from_channel
+    pub fn advise_readahead(mut self) -> Self {
+        self.inner.opts.readahead = true;
+        self
+    }
+
+    /// Collects [`SourceStats`] of every file, which [`stats`] returns.
+    ///
+    /// Files are counted from the moment they are opened, files which are skipped before, like
+    /// by [`filter_sources`], are left out.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let paths: Vec<String> = (0..5000).map(|i| format!("input/{:04}.csv", i)).collect();
+    ///     let mut c = FileConcatReader::new(paths).collect_stats();
+    ///     io::copy(&mut c, &mut io::sink())?;
+    ///
+    ///     let mut stats = c.stats();
+    ///     stats.sort_by_key(|s| std::cmp::Reverse(s.wall_time));
+    ///     for s in &stats[..10] {
+    ///         println!("{:?} {} bytes in {:?}", s.path, s.bytes_read, s.wall_time);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`SourceStats`]:            struct.SourceStats.html
+    /// [`stats`]:                  #method.stats
+    /// [`filter_sources`]:         #method.filter_sources
+    pub fn collect_stats(mut self) -> Self {
+        self.inner.stats = Some(Stats::default());
+        self
+    }
+
+    /// Returns zeros for the holes of sparse files without reading them from disk.
+    ///
+    /// The output is the same as without this option, but a hole of gigabytes in a disk image
+    /// costs no I/O. Holes are found with `SEEK_DATA` and `SEEK_HOLE`, which only Linux, Android
+    /// and FreeBSD support, on other platforms holes are read like data. File systems without
+    /// sparse files report no holes.
+    pub fn zero_holes(mut self) -> Self {
+        self.inner.holes = Some(Holes::Zeros);
+        self
+    }
+
+    /// Leaves out the holes of sparse files and calls `callback` with every [`Hole`] instead.
+    ///
+    /// Holes are found like with [`zero_holes`], at the granularity of the file system blocks,
+    /// so the zeros in a block with data are still read. [`current_position`] and checkpoints
+    /// count the skipped bytes as part of the file, [`position`] only counts the bytes read.
+    ///
+    /// ```no_run
+    /// use concat_reader::file::Hole;
+    /// use concat_reader::*;
+    /// use std::fs::File;
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let parts = ["disk.img.0", "disk.img.1"];
+    ///     let mut disk = FileConcatReader::new(&parts).skip_holes(|hole: &Hole| {
+    ///         println!("{}: {} bytes hole at {}", hole.path.display(), hole.len, hole.offset)
+    ///     });
+    ///     io::copy(&mut disk, &mut File::create("disk.data")?)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`Hole`]:                   struct.Hole.html
+    /// [`zero_holes`]:             #method.zero_holes
+    /// [`current_position`]:       #method.current_position
+    /// [`position`]:               #method.position
+    pub fn skip_holes<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Hole) + Send + 'static,
+    {
+        self.inner.holes = Some(Holes::Skip(Box::new(callback)));
+        self
+    }
+
+    /// Sets what the reader does after a read failed, see [`AfterError`].
+    ///
+    /// By default the reader stays on a file which failed, and a file which failed to open
+    /// returns the same error until [`skip`] is called. With `AfterError::Skip` a batch job
+    /// carries on with the next file and sees every error once, with `AfterError::Poison` the
+    /// reader can't be read by accident after an error.
+    ///
+    /// ```no_run
+    /// use concat_reader::read::AfterError;
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    ///
+    /// let mut c = FileConcatReader::new(&["a.log", "b.log"]).after_error(AfterError::Skip);
+    /// let mut buffer = [0; 4096];
+    /// loop {
+    ///     match c.read(&mut buffer) {
+    ///         Ok(0) => break,
+    ///         Ok(n) => println!("{} bytes", n),
+    ///         Err(e) => eprintln!("skipped: {}", e),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`AfterError`]:             ../read/enum.AfterError.html
+    /// [`skip`]:                   ../trait.ConcatRead.html#tymethod.skip
+    pub fn after_error(mut self, policy: AfterError) -> Self {
+        self.inner.opts.after_error = policy;
+        self
+    }
+
+    /// Returns `Interrupted` errors of the current file instead of retrying the read.
+    ///
+    /// By default opening or reading a file interrupted by a signal is retried until it succeeds,
+    /// fails otherwise or the token set with [`cancel_on`] is cancelled, as slow reads from
+    /// FIFOs and network file systems are interrupted by every signal the process handles.
+    ///
+    /// [`cancel_on`]:              #method.cancel_on
+    pub fn return_interrupted(mut self) -> Self {
+        self.inner.opts.return_interrupted = true;
+        self
+    }
+
+    /// Turns on non-blocking mode, to read FIFOs and similar files inside a readiness loop.
+    ///
+    /// Files are opened with `O_NONBLOCK` on Unix, so opening a FIFO doesn't wait for a writer
+    /// and reading it returns `WouldBlock` while a writer is connected but sent no data. The
+    /// options of [`open_options`], [`advise_sequential`] and [`fifos`] don't apply. In
+    /// [`follow`] mode the reader returns `WouldBlock` instead of sleeping when the last file
+    /// has no new data, the next read checks again.
+    ///
+    /// `WouldBlock` errors are returned as they are, without the path of the file. The reader
+    /// stays on the file whatever [`after_error`] policy is set, and [`collect_stats`] doesn't
+    /// record them as failures. In blocking mode `WouldBlock` is handled like any other error.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    /// use std::time::Duration;
+    ///
+    /// let mut c = FileConcatReader::with_queue()
+    ///     .follow(Duration::from_millis(250))
+    ///     .non_blocking();
+    /// c.push_path("/run/events.fifo");
+    ///
+    /// let mut buffer = [0; 4096];
+    /// loop {
+    ///     match c.read(&mut buffer) {
+    ///         Ok(n) => println!("{} bytes", n),
+    ///         Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+    ///             // wait for readiness or do other work
+    ///         }
+    ///         Err(e) => return Err(e),
+    ///     }
+    /// }
+    /// # Ok::<(), io::Error>(())
+    /// ```
+    ///
+    /// [`open_options`]:           #method.open_options
+    /// [`advise_sequential`]:      #method.advise_sequential
+    /// [`fifos`]:                  #method.fifos
+    /// [`follow`]:                 #method.follow
+    /// [`after_error`]:            #method.after_error
+    /// [`collect_stats`]:          #method.collect_stats
+    pub fn non_blocking(mut self) -> Self {
+        self.inner.opts.non_blocking = true;
+        self
+    }
+
+    /// Checks `token` before every read and while waiting in [`follow`] mode, see
+    /// [`CancelToken`].
+    ///
+    /// [`follow`]:                 #method.follow
+    /// [`CancelToken`]:            ../cancel/struct.CancelToken.html
+    pub fn cancel_on(mut self, token: CancelToken) -> Self {
+        self.inner.cancel = Some(token);
+        self
+    }
+
+    /// Reads only the files whose path passes `filter`.
+    ///
+    /// The predicate is called once per path when the reader reaches it, so the path list is
+    /// never collected up front. Skipped paths still count for the source indices of
+    /// [`checkpoint`]. Setting a filter replaces a previous one.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::fs;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["logs/app.log", "logs/app.log.tmp", "logs/app.log.1"];
+    /// let mut c = FileConcatReader::new(&files).filter_sources(|path| {
+    ///     path.extension().is_none_or(|ext| ext != "tmp")
+    ///         && fs::metadata(path).is_ok_and(|meta| meta.len() > 0)
+    /// });
+    /// let mut buffer = Vec::new();
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    ///
+    /// [`checkpoint`]:             #method.checkpoint
+    pub fn filter_sources<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        let rejected = match &self.inner.curr {
+            ReaderState::Init(path) => self.inner.pos == 0 && !filter(path),
+            _ => false,
+        };
+        self.inner.filter = Some(Box::new(filter));
+        if rejected {
+            self.inner.skip();
+        }
+        self
+    }
+
+    /// Returns an iterator over records of `record_len` bytes.
+    ///
+    /// Records never span two files. Each [`Record`] carries the path of its file and its index
+    /// in the file. `remainder` defines what happens when a file ends with an incomplete record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `record_len` is `0`.
+    ///
+    /// ```no_run
+    /// use concat_reader::records::Remainder;
+    /// use concat_reader::*;
+    ///
+    /// let files = ["telemetry-1.bin", "telemetry-2.bin"];
+    /// for record in FileConcatReader::new(&files).fixed_records(64, Remainder::Error) {
+    ///     let record = record.unwrap();
+    ///     println!("{} #{}: {:?}", record.path.display(), record.index, record.data);
+    /// }
+    /// ```
+    ///
+    /// [`Record`]:                 ../records/struct.Record.html
+    pub fn fixed_records(self, record_len: usize, remainder: Remainder) -> FixedRecords<I> {
+        FixedRecords::new(self, record_len, remainder)
+    }
+
+    /// Returns an iterator over records separated by `delim`.
+    ///
+    /// Records never span two files. Each [`Record`] carries the path of its file and its index
+    /// in the file. Use [`records_by`] for delimiters of more than one byte.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    ///
+    /// // e.g. the output of `find -print0`
+    /// let files = ["found-1", "found-2"];
+    /// for record in FileConcatReader::new(&files).records(b'\0') {
+    ///     println!("{}", String::from_utf8_lossy(&record.unwrap().data));
+    /// }
+    /// ```
+    ///
+    /// [`Record`]:                 ../records/struct.Record.html
+    /// [`records_by`]:             #method.records_by
+    pub fn records(self, delim: u8) -> DelimitedRecords<I> {
+        DelimitedRecords::new(self, &[delim])
+    }
+
+    /// Returns an iterator over records separated by the byte sequence `delim`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delim` is empty.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    ///
+    /// // FASTA entries start with `>` at the beginning of a line
+    /// let files = ["a.fasta", "b.fasta"];
+    /// for entry in FileConcatReader::new(&files).records_by(b"\n>") {
+    ///     let entry = entry.unwrap();
+    ///     println!("{} #{}", entry.path.display(), entry.index);
+    /// }
+    /// ```
+    pub fn records_by(self, delim: &[u8]) -> DelimitedRecords<I> {
+        DelimitedRecords::new(self, delim)
+    }
+
+    /// Returns an iterator over the lines of all files, each with the path of its file and its
+    /// line number.
+    ///
+    /// Unlike asking a `BufReader` for the [`file_path`] of a line, the attribution is exact, as
+    /// lines never span two files.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    ///
+    /// let files = ["app-1.log", "app-2.log"];
+    /// for line in FileConcatReader::new(&files).lines_with_source() {
+    ///     let (path, line_no, line) = line.unwrap();
+    ///     println!("{}:{}: {}", path.display(), line_no, line);
+    /// }
+    /// ```
+    ///
+    /// [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+    pub fn lines_with_source(self) -> LinesWithSource<I> {
+        LinesWithSource::new(self)
+    }
+
+    /// Wraps the reader in a [`DropHeaders`] reader, which keeps the header line of the first
+    /// file only.
+    ///
+    /// This produces one valid CSV stream from multiple CSV files with the same header.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io;
+    ///
+    /// let files = ["2019.csv", "2020.csv", "2021.csv"];
+    /// let mut reader = FileConcatReader::new(&files).drop_repeated_headers();
+    /// io::copy(&mut reader, &mut io::stdout()).unwrap();
+    /// ```
+    ///
+    /// [`DropHeaders`]:            ../text/struct.DropHeaders.html
+    pub fn drop_repeated_headers(self) -> DropHeaders<Self> {
+        DropHeaders::new(self)
+    }
+
+    /// Wraps the reader in a [`SkipPreamble`] reader which skips `n` bytes at the start of every
+    /// file.
+    ///
+    /// Chain [`skip_lines_per_source`] to skip lines after the bytes and [`except_first`] to keep
+    /// the preamble of the first file.
+    ///
+    /// [`SkipPreamble`]:           ../preamble/struct.SkipPreamble.html
+    /// [`skip_lines_per_source`]:  ../preamble/struct.SkipPreamble.html#method.skip_lines_per_source
+    /// [`except_first`]:           ../preamble/struct.SkipPreamble.html#method.except_first
+    pub fn skip_bytes_per_source(self, n: u64) -> SkipPreamble<Self> {
+        SkipPreamble::new(self).skip_bytes_per_source(n)
+    }
+
+    /// Wraps the reader in a [`SkipPreamble`] reader which skips `n` lines at the start of every
+    /// file.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io;
+    ///
+    /// // every export starts with three lines of metadata
+    /// let files = ["export-1.txt", "export-2.txt"];
+    /// let mut reader = FileConcatReader::new(&files).skip_lines_per_source(3);
+    /// io::copy(&mut reader, &mut io::stdout()).unwrap();
+    /// ```
+    ///
+    /// [`SkipPreamble`]:           ../preamble/struct.SkipPreamble.html
+    pub fn skip_lines_per_source(self, n: u64) -> SkipPreamble<Self> {
+        SkipPreamble::new(self).skip_lines_per_source(n)
+    }
+
+    /// Wraps the reader in a [`Transcode`] reader, which converts every file to UTF-8.
+    ///
+    /// Files without a byte order mark are decoded with `default` unless an encoding is declared
+    /// for them with [`Transcode::declare`]. Only available with the `encoding` feature.
+    ///
+    /// [`Transcode`]:              ../encoding/struct.Transcode.html
+    /// [`Transcode::declare`]:     ../encoding/struct.Transcode.html#method.declare
+    #[cfg(feature = "encoding")]
+    pub fn transcode(self, default: &'static encoding_rs::Encoding) -> Transcode<Self> {
+        Transcode::new(self, default)
+    }
+
+    /// Wraps the reader in a [`Normalize`] reader, which strips byte order marks at the start of
+    /// every file and converts `\r\n` line endings to `\n`.
+    ///
+    /// [`Normalize`]:              ../text/struct.Normalize.html
+    pub fn normalize_text(self) -> Normalize<Self> {
+        Normalize::new(self)
+    }
+
+    /// Wraps the reader in a [`TeeReader`] which writes every byte read into `writer`.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::fs::File;
+    /// use std::io::{self, BufRead, BufReader};
+    ///
+    /// let archive = File::create("archive.log").unwrap();
+    /// let files = ["app-1.log", "app-2.log"];
+    /// let reader = BufReader::new(FileConcatReader::new(&files).tee(archive));
+    /// for line in reader.lines() {
+    ///     println!("{}", line.unwrap());
+    /// }
+    /// ```
+    ///
+    /// [`TeeReader`]:              ../tee/struct.TeeReader.html
+    pub fn tee<W: Write>(self, writer: W) -> TeeReader<Self, W> {
+        TeeReader::new(self, writer)
+    }
+
+    /// Wraps the reader in a [`Throttle`] reader, which reads at most `bytes_per_sec` bytes per
+    /// second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sec` is `0`.
+    ///
+    /// [`Throttle`]:               ../throttle/struct.Throttle.html
+    pub fn throttle(self, bytes_per_sec: u64) -> Throttle<Self> {
+        Throttle::new(self, bytes_per_sec)
+    }
+
+    /// Wraps the reader in a [`Timeout`] reader, which fails reads taking longer than `timeout`
+    /// with an error of kind `TimedOut` carrying the path of the file.
+    ///
+    /// Use this for files on pipes or network mounts which can hang. The reader is moved to a
+    /// thread of its own, so it has to be `Send`.
+    ///
+    /// [`Timeout`]:                ../timeout/struct.Timeout.html
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self>
+    where
+        Self: Send + 'static,
+    {
+        Timeout::with_paths(self, timeout)
+    }
+
+    /// Wraps the reader in a [`MapSources`] reader, which reads every file through the reader
+    /// returned by `map`.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["app-1.log", "app-2.log"];
+    /// let mut c = FileConcatReader::new(&files).map_sources(|source| source.take(1024));
+    /// let mut buffer = Vec::new();
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    ///
+    /// [`MapSources`]:             ../map/struct.MapSources.html
+    pub fn map_sources<F, W>(self, map: F) -> MapSources<Self, F, W>
+    where
+        F: FnMut(CurrentSource<Self>) -> W,
+        W: Read,
+    {
+        MapSources::new(self, map)
+    }
+
+    /// Returns a [`Checkpoint`] of the current read progress.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let files = ["Cargo.toml", "README.md"];
+    /// let mut c = FileConcatReader::new(&files);
+    /// c.skip();
+    /// let mut buffer = [0; 4];
+    /// c.read_exact(&mut buffer).unwrap();
+    ///
+    /// let checkpoint = c.checkpoint();
+    /// assert_eq!((checkpoint.source, checkpoint.offset), (1, 4));
+    /// ```
+    ///
+    /// [`Checkpoint`]:             ../checkpoint/struct.Checkpoint.html
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.inner.checkpoint()
+    }
+
+    /// Calls `callback` with a [`Checkpoint`] every `bytes` bytes.
+    ///
+    /// Reads are shortened to end exactly at multiples of `bytes`, counted by [`position`], so
+    /// the callback sees the positions `bytes`, `2 * bytes` and so on. The callback runs within
+    /// `read`, before the bytes are returned, so it can persist the checkpoint before the data is
+    /// processed. Setting a callback replaces a previous one.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    /// use std::sync::mpsc::channel;
+    ///
+    /// let (tx, rx) = channel();
+    /// let files = ["Cargo.toml", "README.md"];
+    /// let mut c = FileConcatReader::new(&files).checkpoint_every(64, move |checkpoint| {
+    ///     tx.send(checkpoint.clone()).unwrap();
+    /// });
+    /// let mut buffer = [0; 100];
+    /// c.read_exact(&mut buffer).unwrap();
+    ///
+    /// let checkpoint = rx.try_recv().unwrap();
+    /// assert_eq!((checkpoint.source, checkpoint.position), (0, 64));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is `0`.
+    ///
+    /// [`Checkpoint`]:             ../checkpoint/struct.Checkpoint.html
+    /// [`position`]:               #method.position
+    pub fn checkpoint_every<F>(mut self, bytes: u64, callback: F) -> Self
+    where
+        F: FnMut(&Checkpoint) + Send + 'static,
+    {
+        assert!(bytes > 0, "checkpoint interval must be greater than 0");
+        let mut checkpoints = Checkpoints {
+            every: bytes,
+            next: 0,
+            callback: Box::new(callback),
+        };
+        checkpoints.schedule(self.inner.total);
+        self.inner.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Reads the rest of the concatenation and `reference` side by side and checks that they are
+    /// equal. Returns the number of bytes compared.
+    ///
+    /// At the first difference the comparison stops with an `InvalidData` error carrying a
+    /// [`Mismatch`], which tells the offset of the difference and the file and offset in the file
+    /// it was found in. Read errors of the files and of `reference` are returned as they are.
+    ///
+    /// ```no_run
+    /// use concat_reader::file::Mismatch;
+    /// use concat_reader::*;
+    ///
+    /// let mut parts = FileConcatReader::new(vec!["upload.part0", "upload.part1"]);
+    /// if let Err(e) = parts.verify_against_file("original.bin") {
+    ///     match Mismatch::of(&e) {
+    ///         Some(mismatch) => eprintln!("{}", mismatch),
+    ///         None => eprintln!("failed to compare: {}", e),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`Mismatch`]:               struct.Mismatch.html
+    pub fn verify_against<R: Read>(&mut self, mut reference: R) -> Result<u64> {
+        let mut ours = vec![0; 64 * 1024];
+        let mut theirs = vec![0; 64 * 1024];
+        let mut compared = 0;
+        loop {
+            let n = retry_interrupted(|| self.read(&mut ours))?;
+            // a single byte tells if the reference continues after the concatenation ended
+            let want = cmp::max(n, 1);
+            let mut m = 0;
+            while m < want {
+                match retry_interrupted(|| reference.read(&mut theirs[m..want]))? {
+                    0 => break,
+                    read => m += read,
+                }
+            }
+            if n == 0 {
+                if m == 0 {
+                    return Ok(compared);
+                }
+                return Err(self.mismatch(compared, 0, false));
+            }
+
+            // a read never returns bytes of two files, so all `n` bytes are in the current one
+            if let Some(i) = (0..cmp::min(n, m)).find(|&i| ours[i] != theirs[i]) {
+                return Err(self.mismatch(compared + i as u64, (n - i) as u64, false));
+            }
+            if m < n {
+                return Err(self.mismatch(compared + m as u64, (n - m) as u64, true));
+            }
+            compared += n as u64;
+        }
+    }
+
+    /// Like [`verify_against`], with the file at `path` as reference.
+    ///
+    /// [`verify_against`]:         #method.verify_against
+    pub fn verify_against_file<P: AsRef<Path>>(&mut self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).map_err(|e| provenance::annotate(e, "verify", Some(path), 0))?;
+        self.verify_against(file)
+    }
+
+    /// Creates the `Mismatch` error for the byte at `offset`, which is `back` bytes before the
+    /// position in the current file.
+    fn mismatch(&self, offset: u64, back: u64, reference_ended: bool) -> io::Error {
+        let source = self
+            .file_path()
+            .filter(|_| back > 0)
+            .map(|path| (path.to_path_buf(), self.current_position() - back));
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            Mismatch {
+                offset,
+                source,
+                reference_ended,
+            },
+        )
+    }
+
+    /// Returns the statistics of the files opened so far, in the order they were read, see
+    /// [`collect_stats`]. Returns an empty list if the statistics aren't collected.
+    ///
+    /// [`collect_stats`]:          #method.collect_stats
+    pub fn stats(&self) -> Vec<SourceStats> {
+        self.inner
+            .stats
+            .as_ref()
+            .map_or_else(Vec::new, Stats::report)
+    }
+
+    /// Returns `true` if the reader reached its end, so every `read` returns `Ok(0)`.
+    ///
+    /// Tells a true end apart from an `Ok(0)` for an empty buffer. The reader is finished when
+    /// the last file reached `EOF` and the source list returned `None`, or when [`limit_total`]
+    /// was reached. Once the source list returned `None` it isn't polled again, so a finished
+    /// reader stays finished even if the iterator isn't fused. A reader created by
+    /// [`with_queue`] is finished while its queue is empty, a reader in [`follow`] mode is only
+    /// finished by `limit_total`.
+    ///
+    /// [`limit_total`]:            #method.limit_total
+    /// [`with_queue`]:             #method.with_queue
+    /// [`follow`]:                 #method.follow
+    pub fn is_finished(&self) -> bool {
+        let inner = &self.inner;
+        let ended = matches!(inner.curr, ReaderState::Eof)
+            && inner.peeked.is_none()
+            && inner.opts.follow.is_none()
+            && (inner.exhausted || !inner.opts.fuse);
+        ended || inner.total_left() == Some(0)
+    }
+
+    /// Returns the number of bytes read from all files.
+    pub fn position(&self) -> u64 {
+        self.inner.total
+    }
+
+    /// Returns the number of bytes read from the current file.
+    pub fn current_position(&self) -> u64 {
+        self.inner.pos
+    }
+
+    /// Returns the bounds on the number of files left, including the current one, like
+    /// `Iterator::size_hint`.
+    ///
+    /// The bounds are taken from the `size_hint` of the iterator. With a filter set by
+    /// [`filter_sources`] the lower bound only counts the paths which already passed it.
+    ///
+    /// [`filter_sources`]:         #method.filter_sources
+    pub fn sources_hint(&self) -> (usize, Option<usize>) {
+        let known = self.inner.known_sources();
+        let (lower, upper) = self.inner.rest.size_hint();
+        let lower = if self.inner.filter.is_some() {
+            0
+        } else {
+            lower
+        };
+        (
+            lower.saturating_add(known),
+            upper.and_then(|upper| upper.checked_add(known)),
+        )
+    }
+
+    /// Seeks the current file back to its start.
+    ///
+    /// The bytes of the file will be read again. [`position`] keeps counting all bytes read.
+    /// Does nothing if the current file wasn't opened yet, and returns the error of a file which
+    /// failed to open.
+    ///
+    /// [`position`]:               #method.position
+    pub fn rewind_current(&mut self) -> Result<()> {
+        match &mut self.inner.curr {
+            ReaderState::Open(file, _) => {
+                file.seek(SeekFrom::Start(0))?;
+                self.inner.pos = 0;
+                self.inner.data = 0..0;
+                Ok(())
+            }
+            ReaderState::Err(_, _) => Err(self.annotate(self.inner.curr.unwrap_err())),
+            ReaderState::Init(_) | ReaderState::Eof => Ok(()),
+        }
+    }
+
+    fn annotate(&self, err: io::Error) -> io::Error {
+        provenance::annotate(err, "file", self.file_path(), self.inner.pos)
+    }
+
+    /// Runs `read` unless the reader is poisoned, annotates its error and applies the
+    /// `AfterError` policy to it.
+    fn guarded<F>(&mut self, read: F) -> io::Result<usize>
+    where
+        F: FnOnce(&mut InnerReader<File, I>) -> io::Result<usize>,
+    {
+        if let Some(err) = &self.inner.poisoned {
+            return Err(Poisoned::repeat(err));
+        }
+        let err = match read(&mut self.inner) {
+            Err(e) => e,
+            ok => return ok,
+        };
+        let non_blocking = self.inner.opts.non_blocking;
+        #[cfg(feature = "tracing")]
+        if !non_blocking || err.kind() != io::ErrorKind::WouldBlock {
+            tracing::warn!(parent: &self.inner.span, offset = self.inner.pos, error = %err, "read failed");
+        }
+        let err = self.annotate(err);
+        let policy = self.inner.opts.after_error;
+        if !policy.applies_to(&err, non_blocking) {
+            return Err(err);
+        }
+        if policy == AfterError::Skip {
+            self.inner.skip();
+            return Err(err);
+        }
+        let (poisoned, err) = Poisoned::first(err);
+        self.inner.poisoned = Some(poisoned);
+        Err(err)
+    }
+
+    /// Moves past the file a read failed on with `err`, unless the `AfterError` policy already
+    /// did. Returns `false` if nothing can be read after the error.
+    pub(crate) fn skip_after_error(&mut self, err: &io::Error) -> bool {
+        let policy = self.inner.opts.after_error;
+        if !policy.applies_to(err, self.inner.opts.non_blocking) {
+            return self.skip();
+        }
+        policy == AfterError::Skip && !matches!(self.inner.curr, ReaderState::Eof)
+    }
+}
+
+impl<I> FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+    I::IntoIter: Clone,
+{
+    /// Returns the number of bytes left to read, `None` if the length of a file is unknown.
+    ///
+    /// The length is the sum of the unread length of the current file and the lengths of all
+    /// remaining files as reported by [`known_len`], or as cached by [`total_len`].
+    ///
+    /// [`known_len`]:              fn.known_len.html
+    /// [`total_len`]:              #method.total_len
+    pub fn remaining_len(&self) -> Option<u64> {
+        let mut total = 0;
+        for (i, (index, path)) in self.inner.remaining_paths().iter().enumerate() {
+            let len = match self.inner.lens.get(*index) {
+                Some(&Some(len)) => len,
+                _ => known_len(path)?,
+            };
+            total += if i == 0 && self.file_path().is_some() {
+                len.saturating_sub(self.current_position())
+            } else {
+                len
+            };
+        }
+        Some(total)
+    }
+
+    /// Resolves the files which are left to read without reading them, for a preview of what
+    /// would be concatenated.
+    ///
+    /// The [`Plan`] lists the current file and all remaining files in order, after
+    /// [`filter_sources`], the [`symlinks`] policy and [`skip_duplicates`] were applied, with
+    /// their lengths and the problems reading them would run into, like missing files,
+    /// permission errors or directories. The current file is listed as it is, even if it was
+    /// read partly. The reader is not changed.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    ///
+    /// let c = FileConcatReader::new(vec!["2024-01.log", "2024-02.log", "2024-03.log"]);
+    /// let plan = c.plan();
+    /// for file in &plan.files {
+    ///     match &file.problem {
+    ///         Some(e) => println!("{}: {}", file.path.display(), e),
+    ///         None => println!("{}: {:?} bytes", file.path.display(), file.len),
+    ///     }
+    /// }
+    /// println!("{} bytes in total", plan.total_len);
+    /// ```
+    ///
+    /// [`Plan`]:                   struct.Plan.html
+    /// [`filter_sources`]:         #method.filter_sources
+    /// [`symlinks`]:               #method.symlinks
+    /// [`skip_duplicates`]:        #method.skip_duplicates
+    pub fn plan(&self) -> Plan {
+        let opts = &self.inner.opts;
+        let mut seen = self.inner.seen.clone();
+        let mut files = Vec::new();
+        for (i, (_, path)) in self.inner.remaining_paths().into_iter().enumerate() {
+            // the policies were applied to an opened file already
+            let checked = i == 0 && !self.inner.curr.is_init();
+            if !checked {
+                let is_link = opts.symlinks != Symlinks::Follow
+                    && fs::symlink_metadata(&path)
+                        .map(|meta| meta.file_type().is_symlink())
+                        .unwrap_or(false);
+                if is_link && opts.symlinks == Symlinks::Skip {
+                    continue;
+                }
+                if is_link {
+                    let problem = io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is a symbolic link", path.display()),
+                    );
+                    files.push(PlannedFile {
+                        path,
+                        len: None,
+                        problem: Some(problem),
+                    });
+                    continue;
+                }
+                let duplicate =
+                    opts.skip_duplicates && FileId::of(&path).is_some_and(|id| !seen.insert(id));
+                if duplicate {
+                    continue;
+                }
+            }
+            files.push(PlannedFile::inspect(path));
+        }
+        let total_len = files
+            .iter()
+            .filter(|file| file.problem.is_none())
+            .filter_map(|file| file.len)
+            .sum();
+        Plan { files, total_len }
+    }
+
+    /// Checks that all files which are left to read can be read, before any bytes are read.
+    ///
+    /// Fails with a [`PreflightFailed`] error listing every problem of the [`plan`], so a long
+    /// job fails at its start instead of at the first missing file hours later. Every file is
+    /// inspected and opened, but no data is read and the reader is not changed.
+    ///
+    /// [`PreflightFailed`]:        struct.PreflightFailed.html
+    /// [`plan`]:                   #method.plan
+    pub fn validate(&self) -> Result<()> {
+        let plan = self.plan();
+        let files = plan.files.len();
+        let problems: Vec<_> = plan
+            .files
+            .into_iter()
+            .filter(|file| file.problem.is_some())
+            .collect();
+        match problems.first().and_then(|file| file.problem.as_ref()) {
+            None => Ok(()),
+            Some(problem) => Err(io::Error::new(
+                problem.kind(),
+                PreflightFailed { problems, files },
+            )),
+        }
+    }
+
+    /// Runs [`validate`] and returns the reader if all files can be read.
+    ///
+    /// Call it last in a chain of builder methods, the policies set after it are not validated.
+    ///
+    /// ```no_run
+    /// use concat_reader::file::PreflightFailed;
+    /// use concat_reader::*;
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let paths: Vec<String> = (0..5000).map(|i| format!("input/{:04}.csv", i)).collect();
+    ///     let mut c = match FileConcatReader::new(paths).skip_duplicates().preflight() {
+    ///         Ok(c) => c,
+    ///         Err(e) => {
+    ///             for file in PreflightFailed::of(&e).map_or(&[][..], |p| p.problems()) {
+    ///                 eprintln!("{}: {:?}", file.path.display(), file.problem);
+    ///             }
+    ///             return Err(e);
+    ///         }
+    ///     };
+    ///     io::copy(&mut c, &mut io::stdout())?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`validate`]:               #method.validate
+    pub fn preflight(self) -> Result<Self> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Returns the summed length of the current file and all remaining files.
+    ///
+    /// Unlike [`remaining_len`] this includes the bytes already read from the current file and
+    /// fails if a file can't be inspected or isn't a regular file. The length of every file is
+    /// read from the file system once and cached, so calling `total_len` again, e.g. after a
+    /// [`reset`], doesn't touch the file system for files it already inspected.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut c = FileConcatReader::new(vec!["foo.txt", "bar.txt"]);
+    ///     let mut buffer = Vec::with_capacity(c.total_len()? as usize);
+    ///     c.read_to_end(&mut buffer)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`remaining_len`]:          #method.remaining_len
+    /// [`reset`]:                  #method.reset
+    pub fn total_len(&mut self) -> Result<u64> {
+        let paths = self.inner.remaining_paths();
+        let lens = &mut self.inner.lens;
+        if let Some((last, _)) = paths.last() {
+            if lens.len() <= *last {
+                lens.resize(last + 1, None);
+            }
+        }
+
+        let mut total = 0;
+        for (index, path) in &paths {
+            let len = match lens[*index] {
+                Some(len) => len,
+                None => {
+                    let len = regular_len(path)
+                        .map_err(|e| provenance::annotate(e, "file", Some(path), 0))?;
+                    lens[*index] = Some(len);
+                    len
+                }
+            };
+            total += len;
+        }
+        Ok(total)
+    }
+
+    /// Wraps the reader in a [`ProgressReader`] reporting to `observer`.
+    ///
+    /// The total size is the [`remaining_len`] of the reader, which is `None` if the length of a
+    /// file is unknown.
+    ///
+    /// [`ProgressReader`]:         ../progress/struct.ProgressReader.html
+    /// [`remaining_len`]:          #method.remaining_len
+    pub fn progress<O: ProgressObserver>(self, observer: O) -> ProgressReader<Self, O> {
+        let total = self.remaining_len();
+        ProgressReader::new(self, total, observer)
+    }
+
+    /// Wraps the reader in a [`WeightedProgressReader`] reporting to `observer`.
+    ///
+    /// Every file is weighted with its size on disk, which makes the progress independent of
+    /// the number of bytes a file produces after decoding. Files with an unknown length are
+    /// weighted with the average size of the other files, or all files count the same if no
+    /// length is known.
+    ///
+    /// [`WeightedProgressReader`]: ../progress/struct.WeightedProgressReader.html
+    pub fn progress_weighted<O: ProgressObserver>(
+        self,
+        observer: O,
+    ) -> WeightedProgressReader<Self, O> {
+        let lens: Vec<Option<u64>> = self
+            .inner
+            .remaining_paths()
+            .iter()
+            .map(|(_, path)| known_len(path))
+            .collect();
+        let known: Vec<u64> = lens.iter().flatten().copied().collect();
+        let fallback = match known.len() {
+            0 => 1,
+            n => (known.iter().sum::<u64>() / n as u64).max(1),
+        };
+        let weights = lens.iter().map(|l| l.unwrap_or(fallback)).collect();
+        WeightedProgressReader::new(self, weights, observer)
+    }
+
+    /// Returns a [`Snapshot`] of the read progress and the paths left to read.
+    ///
+    /// Paths rejected by [`filter_sources`] aren't part of the snapshot.
+    ///
+    /// [`Snapshot`]:               ../checkpoint/struct.Snapshot.html
+    /// [`filter_sources`]:         #method.filter_sources
+    pub fn snapshot(&self) -> Snapshot {
+        let mut remaining = self.inner.remaining_paths().into_iter().map(|(_, p)| p);
+        if self.file_path().is_some() {
+            remaining.next();
+        }
+        Snapshot {
+            remaining: remaining.collect(),
+            ..Snapshot::from(self.checkpoint())
+        }
+    }
+}
+
+impl FileConcatReader<Vec<PathBuf>> {
+    /// Creates a `FileConcatReader` which continues where the reader of `snapshot` stopped.
+    ///
+    /// The current file is opened right away and seeked to the offset of the snapshot, the
+    /// remaining files follow. [`position`] and [`checkpoint`] continue from the values of the
+    /// snapshot. Options like [`follow`] aren't part of the snapshot and have to be set again.
+    ///
+    /// Fails if the current file can't be opened, or if it is shorter than the offset.
+    ///
+    /// ```no_run
+    /// use concat_reader::checkpoint::Snapshot;
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    ///
+    /// fn copy_some(files: Vec<&str>) -> io::Result<Snapshot> {
+    ///     let mut c = FileConcatReader::new(files);
+    ///     io::copy(&mut c.by_ref().take(1 << 20), &mut io::sink())?;
+    ///     Ok(c.snapshot())
+    /// }
+    ///
+    /// let snapshot = copy_some(vec!["big-1.bin", "big-2.bin"]).unwrap();
+    /// let mut c = FileConcatReader::resume(snapshot).unwrap();
+    /// io::copy(&mut c, &mut io::sink()).unwrap();
+    /// ```
+    ///
+    /// [`position`]:               #method.position
+    /// [`checkpoint`]:             #method.checkpoint
+    /// [`follow`]:                 #method.follow
+    pub fn resume(snapshot: Snapshot) -> Result<Self> {
+        let paths: Vec<PathBuf> = snapshot
+            .path
+            .into_iter()
+            .chain(snapshot.remaining)
+            .collect();
+        let mut reader = Self::new(paths);
+        let inner = &mut reader.inner;
+        inner.index = snapshot.source;
+        inner.taken += snapshot.source;
+        inner.total = snapshot.position;
+
+        if let ReaderState::Init(path) = &inner.curr {
+            if snapshot.offset > 0 {
+                let path = path.clone();
+                let file = open_at(&path, snapshot.offset)
+                    .map_err(|e| provenance::annotate(e, "file", Some(&path), 0))?;
+                inner.curr = ReaderState::Open(file, path);
+                inner.pos = snapshot.offset;
+            }
+        }
+        Ok(reader)
+    }
+}
+
+/// Opens the file at `path` and seeks to `offset`, which must not be past its end.
+fn open_at(path: &Path, offset: u64) -> Result<File> {
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() < offset {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("file is shorter than the offset {} to resume at", offset),
+        ));
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file)
+}
+
+/// Returns the length of the file at `path` if it is known before reading it.
+///
+/// Only regular files have a known length. FIFOs, sockets, devices and paths which can't be
+/// inspected return `None`, which puts features like [`progress`] into an unknown-length mode
+/// instead of failing. Note that pseudo files like the ones in `/proc` claim to be empty regular
+/// files.
+///
+/// [`progress`]:               struct.FileConcatReader.html#method.progress
+pub fn known_len<P: AsRef<Path>>(path: P) -> Option<u64> {
+    let meta = fs::metadata(path).ok()?;
+    if meta.is_file() {
+        Some(meta.len())
+    } else {
+        None
+    }
+}
+
+fn regular_len(path: &Path) -> Result<u64> {
+    let meta = fs::metadata(path)?;
+    if meta.is_file() {
+        Ok(meta.len())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a regular file, the length is unknown",
+        ))
+    }
+}
+
+impl<I> FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+    I::IntoIter: ExactSizeIterator,
+{
+    /// Returns the number of files left, including the current one.
+    ///
+    /// Unlike [`remaining_len`] this doesn't touch the file system. Paths which weren't checked
+    /// by a filter set with [`filter_sources`] yet are counted as well.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    ///
+    /// let mut c = FileConcatReader::new(vec!["foo.txt", "bar.txt", "baz.txt"]);
+    /// c.skip();
+    /// assert_eq!(c.remaining_sources(), 2);
+    /// ```
+    ///
+    /// [`remaining_len`]:          #method.remaining_len
+    /// [`filter_sources`]:         #method.filter_sources
+    pub fn remaining_sources(&self) -> usize {
+        self.inner.known_sources() + self.inner.rest.len()
+    }
+}
+
+impl<I> FileConcatReader<Restart<I>>
+where
+    I: Iterator + Clone,
+    I::Item: AsRef<Path>,
+{
+    /// Creates a new `FileConcatReader` which can [`reset`] to its first file.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    ///
+    /// let mut c = FileConcatReader::restartable(vec!["foo.txt", "bar.txt"]);
+    /// let len = io::copy(&mut c, &mut io::sink()).unwrap();
+    ///
+    /// // second pass over all files
+    /// c.reset();
+    /// let mut buffer = Vec::with_capacity(len as usize);
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    ///
+    /// [`reset`]:                  #method.reset
+    pub fn restartable<T: IntoIterator<IntoIter = I>>(paths: T) -> Self {
+        Self::new(Restart::new(paths))
+    }
+
+    /// Starts over with the first file, as if the `FileConcatReader` was just created.
+    ///
+    /// All files are opened again.
+    pub fn reset(&mut self) {
+        let inner = &mut self.inner;
+        inner.rest.restart();
+        inner.exhausted = false;
+        inner.poisoned = None;
+        inner.peeked = None;
+        inner.taken = 0;
+        inner.total = 0;
+        if let Some(checkpoints) = &mut inner.checkpoints {
+            checkpoints.schedule(0);
+        }
+        inner.seen.clear();
+        inner.skip();
+    }
+}
+
+impl<P: AsRef<Path>> FileConcatReader<SourceQueue<P>> {
+    /// Creates a new empty `FileConcatReader` backed by a [`SourceQueue`].
+    ///
+    /// New paths can be appended with [`push_path`] or through the [`sender`] from other
+    /// threads while the reader is in use.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// let mut c = FileConcatReader::with_queue();
+    /// c.push_path("foo.txt");
+    /// c.push_path("bar.txt");
+    /// ```
+    ///
+    /// [`SourceQueue`]:            ../queue/struct.SourceQueue.html
+    /// [`push_path`]:              #method.push_path
+    /// [`sender`]:                 #method.sender
+    pub fn with_queue() -> Self {
+        let mut reader = Self::new(SourceQueue::new());
+        reader.inner.opts.fuse = false;
+        reader.inner.exhausted = false;
+        reader
+    }
+
+    /// Appends a path to the end of the source list.
+    ///
+    /// If the reader already reached `EOF` it will continue with the appended file on the next
+    /// call to `read`.
+    pub fn push_path(&mut self, path: P) {
+        self.inner.rest.push(path);
+        if let ReaderState::Eof = self.inner.curr {
+            self.inner.skip();
+        }
+    }
+
+    /// Returns a [`Sender`] to append paths from another thread.
+    ///
+    /// [`Sender`]:                 https://doc.rust-lang.org/std/sync/mpsc/struct.Sender.html
+    pub fn sender(&self) -> Sender<P> {
+        self.inner.rest.sender()
+    }
+}
+
+impl<P: AsRef<Path>> FileConcatReader<Receiver<P>> {
+    /// Creates a new `FileConcatReader` which reads the files received from a channel.
+    ///
+    /// The channel is treated as the source list. When the current file reaches `EOF` the
+    /// `FileConcatReader` blocks until the next path is received. The `FileConcatReader` only
+    /// reaches `EOF` after all senders of the channel are dropped. Unlike [`new`] no path is
+    /// received before the first call to `read`.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    /// use std::path::PathBuf;
+    /// use std::sync::mpsc::channel;
+    /// use std::thread;
+    ///
+    /// let (tx, rx) = channel::<PathBuf>();
+    /// let mut c = FileConcatReader::from_channel(rx);
+    /// thread::spawn(move || {
+    ///     for entry in std::fs::read_dir("logs").unwrap() {
+    ///         tx.send(entry.unwrap().path()).unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut buffer = Vec::new();
+    /// c.read_to_end(&mut buffer).unwrap();
+    /// ```
+    ///
+    /// [`new`]:                    #method.new
+    pub fn from_channel(rx: Receiver<P>) -> Self {
+        FileConcatReader {
+            inner: InnerReader {
+                curr: ReaderState::Eof,
+                peeked: None,
+                rest: rx.into_iter(),
+                taken: 0,
+                index: 0,
+                filter: None,
+                cancel: None,
+                exhausted: false,
+                poisoned: None,
+                checkpoints: None,
+                holes: None,
+                stats: None,
+                span: SourceSpan::none(),
+                opened_at: SourceClock::default(),
+                data: 0..0,
+                lens: Vec::new(),
+                stamp: None,
+                seen: HashSet::new(),
+                pos: 0,
+                total: 0,
+                opts: Options {
+                    fuse: true,
+                    ..Options::default()
+                },
+            },
+        }
+    }
+}
+
+impl<I> ConcatRead for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    type Item = File;
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn skip(&mut self) -> bool {
+        #[cfg(feature = "tracing")]
+        if self.inner.curr.path().is_some() {
+            tracing::debug!(parent: &self.inner.span, bytes = self.inner.pos, "skipped source");
+        }
+        self.inner.skip()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        self.inner.take_current()
+    }
+}
+
+impl<I> FileConcatRead for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> Result<Metadata> {
+        match &self.inner.curr {
+            ReaderState::Open(file, _) => file.metadata(),
+            ReaderState::Init(path) | ReaderState::Err(_, path) => fs::metadata(path),
+            ReaderState::Eof => Err(io::Error::new(io::ErrorKind::NotFound, "no current file")),
+        }
+    }
+}
+
+impl<I> UnframedRead for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+}
+
+impl<I> RawRead for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+}
+
+impl<I> SourceRead for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.guarded(|inner| inner.read_source(buf))
+    }
+}
+
+impl<I> From<I> for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn from(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
 
 impl<I> Read for FileConcatReader<I>
 where
@@ -124,351 +2322,2211 @@ where
     I::Item: AsRef<Path>,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        self.guarded(|inner| inner.read(buf))
+    }
+}
+
+/// Returns the descriptor of the current file, or `-1` if no file is open.
+///
+/// Files are opened by the first read from them, and the descriptor changes when the reader moves
+/// on to the next file. An event loop waiting on the reader has to register the descriptor again
+/// after every read which returned data from a new file, see [`ConcatRead::current`].
+///
+/// [`ConcatRead::current`]:    ../trait.ConcatRead.html#tymethod.current
+#[cfg(unix)]
+impl<I> std::os::unix::io::AsRawFd for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.current().map_or(-1, |file| file.as_raw_fd())
+    }
+}
+
+impl<I> fmt::Debug for FileConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+enum ReaderState<R, E> {
+    Open(R, PathBuf),
+    Init(PathBuf),
+    Err(E, PathBuf),
+    Eof,
+}
+
+impl<R> ReaderState<R, io::Error>
+where
+    R: FileLike,
+{
+    fn open(&mut self) -> Result<()> {
+        self.open_with(|p| R::open(p))
+    }
+
+    fn open_with<F: FnOnce(&PathBuf) -> Result<R>>(&mut self, open: F) -> Result<()> {
+        let (s, err) = match self {
+            ReaderState::Init(p) => match open(p) {
+                Err(e) => (ReaderState::Err(copy_error(&e), p.clone()), Some(e)),
+                Ok(f) => (ReaderState::Open(f, p.clone()), None),
+            },
+            ReaderState::Eof => panic!("called `ReaderState::open()` on a `Eof` value"),
+            ReaderState::Open(_, _) => panic!("called `ReaderState::open()` on a `Open` value"),
+            ReaderState::Err(_, _) => panic!("called `ReaderState::open()` on a `Err` value"),
+        };
+
+        *self = s;
+        match err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn is_init(&self) -> bool {
+        matches!(*self, ReaderState::Init(_))
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ReaderState::Init(p) | ReaderState::Open(_, p) | ReaderState::Err(_, p) => Some(p),
+            ReaderState::Eof => None,
+        }
+    }
+
+    fn unwrap_err(&self) -> io::Error {
+        match self {
+            ReaderState::Err(e, _) => copy_error(e),
+            _ => panic!("no error to unwrap"),
+        }
+    }
+}
+
+/// Copies an error which is returned again on every read after the first.
+///
+/// OS errors are recreated from their code, so `raw_os_error()` still works on the copy.
+fn copy_error(e: &io::Error) -> io::Error {
+    match e.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(e.kind(), e.to_string()),
+    }
+}
+
+/// Calls `read` again as long as it is interrupted.
+fn retry_interrupted<F: FnMut() -> Result<usize>>(mut read: F) -> Result<usize> {
+    loop {
+        match read() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            res => return res,
+        }
+    }
+}
+
+impl<R> Read for ReaderState<R, io::Error>
+where
+    R: FileLike,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            ReaderState::Eof => Ok(0),
+            ReaderState::Init(_) => {
+                self.open()?;
+                self.read(buf)
+            }
+            ReaderState::Err(_, _) => Err(self.unwrap_err()),
+            ReaderState::Open(r, _) => r.read(buf),
+        }
+    }
+}
+
+impl<R, E, P> From<Option<P>> for ReaderState<R, E>
+where
+    P: AsRef<Path>,
+    R: FileLike,
+    E: Error,
+{
+    fn from(path: Option<P>) -> Self {
+        match path {
+            Some(p) => ReaderState::Init(p.as_ref().to_path_buf()),
+            None => ReaderState::Eof,
+        }
+    }
+}
+
+impl<R, E> fmt::Debug for ReaderState<R, E>
+where
+    R: fmt::Debug,
+    E: Error,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReaderState::Init(p) => write!(f, "ReaderState::Init({:?})", p),
+            ReaderState::Open(r, p) => write!(f, "ReaderState::Open({:?},{:?})", r, p),
+            ReaderState::Eof => write!(f, "ReaderState::Eof"),
+            ReaderState::Err(p, e) => write!(f, "ReaderState::Err({:?},{:?})", p, e),
+        }
+    }
+}
+
+struct InnerReader<R, I: IntoIterator> {
+    curr: ReaderState<R, io::Error>,
+    /// Path taken from `rest` by `peek_next_path`, with its index in the source list.
+    peeked: Option<(u64, I::Item)>,
+    rest: I::IntoIter,
+    /// Number of paths taken from `rest`, including filtered and peeked ones.
+    taken: u64,
+    /// Index of the current path in the source list.
+    index: u64,
+    /// Predicate set by `filter_sources`.
+    filter: Option<Filter>,
+    cancel: Option<CancelToken>,
+    /// Whether `rest` returned `None` and isn't polled again.
+    exhausted: bool,
+    /// Error all reads fail with, set by `AfterError::Poison`.
+    poisoned: Option<Arc<io::Error>>,
+    /// Callback set by `checkpoint_every`.
+    checkpoints: Option<Checkpoints>,
+    holes: Option<Holes>,
+    stats: Option<Stats>,
+    span: SourceSpan,
+    opened_at: SourceClock,
+    /// Range of the data in the current file found by `seek_data`, the bytes before it are a
+    /// hole.
+    data: Range<u64>,
+    /// Stamp of the current file when it was opened, recorded by `verify_unchanged`.
+    stamp: Option<Stamp>,
+    /// Files opened so far, recorded by `skip_duplicates`.
+    seen: HashSet<FileId>,
+    /// File lengths cached by `total_len`, by index of the path in the source list.
+    lens: Vec<Option<u64>>,
+    pos: u64,
+    total: u64,
+    opts: Options,
+}
+
+type Filter = Box<dyn Fn(&Path) -> bool + Send + Sync>;
+
+/// A callback receiving a [`Checkpoint`] every `every` bytes.
+struct Checkpoints {
+    every: u64,
+    /// Position of the next checkpoint.
+    next: u64,
+    callback: Box<dyn FnMut(&Checkpoint) + Send>,
+}
+
+impl Checkpoints {
+    /// Moves the next checkpoint to the first multiple of `every` after `total`.
+    fn schedule(&mut self, total: u64) {
+        self.next = (total / self.every + 1) * self.every;
+    }
+}
+
+/// Options shared by all files of an `InnerReader`.
+#[derive(Clone, Debug, Default)]
+struct Options {
+    follow: Option<Duration>,
+    limit_per_source: Option<u64>,
+    limit_total: Option<u64>,
+    verify: bool,
+    symlinks: Symlinks,
+    skip_duplicates: bool,
+    fifos: Fifos,
+    open_options: Option<fs::OpenOptions>,
+    sequential: bool,
+    readahead: bool,
+    /// Stops polling `rest` after it returned `None`, unset for queues.
+    fuse: bool,
+    after_error: AfterError,
+    return_interrupted: bool,
+    non_blocking: bool,
+}
+
+impl<R, I> InnerReader<R, I>
+where
+    R: FileLike,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn new(iter: I) -> InnerReader<R, I> {
+        let mut iter = iter.into_iter();
+        let curr: ReaderState<R, io::Error> = iter.next().into();
+        InnerReader {
+            taken: if curr.is_init() { 1 } else { 0 },
+            index: 0,
+            filter: None,
+            cancel: None,
+            exhausted: false,
+            poisoned: None,
+            checkpoints: None,
+            holes: None,
+            stats: None,
+            span: SourceSpan::none(),
+            opened_at: SourceClock::default(),
+            data: 0..0,
+            lens: Vec::new(),
+            stamp: None,
+            seen: HashSet::new(),
+            curr,
+            peeked: None,
+            rest: iter,
+            pos: 0,
+            total: 0,
+            opts: Options::default(),
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            source: self.source_index(),
+            path: self.file_path().map(Path::to_path_buf),
+            offset: self.pos,
+            position: self.total,
+        }
+    }
+
+    /// Returns the number of paths taken from `rest` which are left to read.
+    fn known_sources(&self) -> usize {
+        let curr = match self.curr {
+            ReaderState::Eof => 0,
+            _ => 1,
+        };
+        curr + self.peeked.is_some() as usize
+    }
+
+    /// Returns the index of the current path in the source list, or of the next one at `EOF`.
+    fn source_index(&self) -> u64 {
+        match (&self.curr, &self.peeked) {
+            (ReaderState::Eof, Some((index, _))) => *index,
+            (ReaderState::Eof, None) => self.taken,
+            _ => self.index,
+        }
+    }
+
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    fn materialize(
+        mut self,
+        limit: usize,
+    ) -> std::result::Result<
+        InnerReader<R, Vec<I::Item>>,
+        InnerReader<R, Chain<vec::IntoIter<I::Item>, I::IntoIter>>,
+    > {
+        let mut rest: Vec<_> = self.peeked.take().map(|(_, p)| p).into_iter().collect();
+        rest.reserve(cmp::min(limit, self.rest.size_hint().0));
+        let taken = self.taken - rest.len() as u64;
+        rest.extend(self.rest.by_ref().take(limit.saturating_sub(rest.len())));
+        let next = if rest.len() > limit {
+            rest.pop()
+        } else {
+            self.rest.next()
+        };
+        match next {
+            None => Ok(InnerReader {
+                curr: self.curr,
+                peeked: None,
+                rest: rest.into_iter(),
+                taken,
+                index: self.index,
+                filter: self.filter,
+                cancel: self.cancel,
+                exhausted: self.exhausted,
+                poisoned: self.poisoned,
+                checkpoints: self.checkpoints,
+                holes: self.holes,
+                stats: self.stats,
+                span: self.span,
+                opened_at: self.opened_at,
+                data: self.data,
+                lens: self.lens,
+                stamp: self.stamp,
+                seen: self.seen,
+                pos: self.pos,
+                total: self.total,
+                opts: self.opts,
+            }),
+            Some(next) => {
+                rest.push(next);
+                Err(InnerReader {
+                    curr: self.curr,
+                    peeked: None,
+                    rest: rest.into_iter().chain(self.rest),
+                    taken,
+                    index: self.index,
+                    filter: self.filter,
+                    cancel: self.cancel,
+                    exhausted: self.exhausted,
+                    poisoned: self.poisoned,
+                    checkpoints: self.checkpoints,
+                    holes: self.holes,
+                    stats: self.stats,
+                    span: self.span,
+                    opened_at: self.opened_at,
+                    data: self.data,
+                    lens: self.lens,
+                    stamp: self.stamp,
+                    seen: self.seen,
+                    pos: self.pos,
+                    total: self.total,
+                    opts: self.opts,
+                })
+            }
+        }
+    }
+}
+
+impl<R, I> ConcatRead for InnerReader<R, I>
+where
+    R: FileLike,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    type Item = R;
+
+    fn current(&self) -> Option<&Self::Item> {
+        match &self.curr {
+            ReaderState::Open(r, _) => Some(r),
+            _ => None,
+        }
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        match &mut self.curr {
+            ReaderState::Open(r, _) => Some(r),
+            _ => None,
+        }
+    }
+
+    fn skip(&mut self) -> bool {
+        if let (true, ReaderState::Open(file, _)) = (self.opts.readahead, &self.curr) {
+            file.advise_dont_need();
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.finish();
+        }
+        self.span = SourceSpan::none();
+        self.record_duration();
+        #[cfg(feature = "tracing")]
+        let had_path = self.curr.path().is_some();
+        self.curr = self.next_path().into();
+        #[cfg(feature = "tracing")]
+        if had_path && !self.curr.is_init() {
+            tracing::info!(
+                bytes = self.total,
+                paths = self.taken,
+                "concatenation ended"
+            );
+        }
+        self.pos = 0;
+        self.data = 0..0;
+        self.stamp = None;
+        self.curr.is_init()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        match mem::replace(&mut self.curr, ReaderState::Eof) {
+            ReaderState::Open(r, _) => {
+                self.skip();
+                Some(r)
+            }
+            curr => {
+                self.curr = curr;
+                None
+            }
+        }
+    }
+}
+
+impl<R, I> FileConcatRead for InnerReader<R, I>
+where
+    R: FileLike,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn file_path(&self) -> Option<&Path> {
+        match &self.curr {
+            ReaderState::Init(p) => Some(p.as_path()),
+            ReaderState::Open(_, p) => Some(p.as_path()),
+            ReaderState::Err(_, p) => Some(p.as_path()),
+            _ => None,
+        }
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        if self.peeked.is_none() {
+            self.peeked = self.pull();
+        }
+        self.peeked.as_ref().map(|(_, p)| p.as_ref())
+    }
+}
+
+impl<R, I> InnerReader<R, I>
+where
+    R: FileLike,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn read_next(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.check_cancel()?;
+
+        if self.total_left() == Some(0) {
+            return Ok(0);
+        }
+
+        if let ReaderState::Eof = self.curr {
+            self.skip();
+        }
+
+        loop {
+            match self.read_curr(buf) {
+                Ok(0) if self.opts.follow.is_some() => {
+                    if self.fifo_disconnected() && self.skip() {
+                        continue;
+                    }
+                    match self.read_follow(buf)? {
+                        Some(n) => return Ok(n),
+                        None => continue,
+                    }
+                }
+                Ok(0) => {
+                    #[cfg(feature = "tracing")]
+                    if self.curr.path().is_some() {
+                        tracing::debug!(parent: &self.span, bytes = self.pos, "end of source");
+                    }
+                    if !self.skip() {
+                        return Ok(0);
+                    }
+                }
+                val => return val,
+            }
+        }
+    }
+
+    /// Reads from the current file only. Returns `Ok(0)` at the end of the current file without
+    /// advancing to the next one.
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.total_left() == Some(0) {
+            return Ok(0);
+        }
+        self.check_cancel()?;
+
+        if let ReaderState::Eof = self.curr {
+            self.skip();
+        }
+
+        self.read_curr(buf)
+    }
+
+    fn total_left(&self) -> Option<u64> {
+        self.opts
+            .limit_total
+            .map(|limit| limit.saturating_sub(self.total))
+    }
+
+    /// Reads from the current file, respecting the byte limits.
+    fn read_curr(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut max = buf.len() as u64;
+        if let Some(limit) = self.opts.limit_per_source {
+            max = cmp::min(max, limit.saturating_sub(self.pos));
+        }
+        if let Some(left) = self.total_left() {
+            max = cmp::min(max, left);
+        }
+        if let Some(checkpoints) = &self.checkpoints {
+            max = cmp::min(max, checkpoints.next - self.total);
+        }
+        if max == 0 {
+            return Ok(0);
+        }
+
+        let check = self.opts.symlinks != Symlinks::Follow || self.opts.skip_duplicates;
+        if check && !self.check_path()? {
+            return Ok(0);
+        }
+
+        let opened = self.curr.is_init();
+        if opened {
+            self.open_curr()?;
+        }
+
+        let verify = self.opts.verify && self.opts.follow.is_none();
+        if verify && opened {
+            if let ReaderState::Open(file, _) = &self.curr {
+                self.stamp = Some(file.stamp()?);
+            }
+        }
+
+        if self.holes.is_some() {
+            if let Some(n) = self.read_hole(&mut buf[..max as usize])? {
+                return Ok(n);
+            }
+            if self.opts.limit_per_source == Some(self.pos) {
+                return Ok(0);
+            }
+            // stop at the next hole
+            max = cmp::min(max, self.data.end.saturating_sub(self.pos));
+            if let Some(limit) = self.opts.limit_per_source {
+                max = cmp::min(max, limit.saturating_sub(self.pos));
+            }
+        }
+
+        let n = match self.read_uninterrupted(&mut buf[..max as usize]) {
+            Ok(n) => n,
+            Err(e) => {
+                let would_block = self.opts.non_blocking && e.kind() == io::ErrorKind::WouldBlock;
+                if let (Some(stats), false) = (&mut self.stats, would_block) {
+                    stats.failed(&e);
+                }
+                return Err(e);
+            }
+        };
+        if let Some(stats) = &mut self.stats {
+            stats.read(n);
+        }
+        #[cfg(feature = "metrics")]
+        metrics::counter!("concat_reader_bytes_read_total").increment(n as u64);
+        self.pos += n as u64;
+        self.total += n as u64;
+        self.emit_checkpoint();
+        if n == 0 && verify {
+            self.verify_stamp()?;
+        }
+        Ok(n)
+    }
+
+    /// Records the time since the current file was opened in the duration histogram.
+    fn record_duration(&mut self) {
+        #[cfg(feature = "metrics")]
+        if let Some(opened_at) = self.opened_at.take() {
+            metrics::histogram!("concat_reader_file_duration_seconds")
+                .record(opened_at.elapsed().as_secs_f64());
+        }
+    }
+
+    /// Opens the current file with the options of the reader.
+    fn open_curr(&mut self) -> io::Result<()> {
+        #[cfg(feature = "tracing")]
+        if let Some(path) = self.curr.path() {
+            self.span = tracing::debug_span!("source", path = %path.display(), index = self.index);
+        }
+        let opts = &self.opts;
+        let start = self.stats.as_ref().map(|_| Instant::now());
+        let res = if opts.non_blocking {
+            self.curr.open_with(|p| R::open_non_blocking(p))
+        } else if opts.fifos == Fifos::NoWait {
+            self.curr.open_with(|p| R::open_no_wait(p))
+        } else {
+            let options = opts.open_options.as_ref();
+            self.curr
+                .open_with(|p| R::open_tuned(p, options, opts.sequential))
+        };
+        if let (Some(stats), Some(start), Some(path)) = (&mut self.stats, start, self.curr.path()) {
+            stats.opened(path, start, res.as_ref().err());
+        }
+        #[cfg(feature = "metrics")]
+        match res {
+            Ok(()) => {
+                metrics::counter!("concat_reader_files_opened_total").increment(1);
+                self.opened_at = Some(Instant::now());
+            }
+            Err(_) => metrics::counter!("concat_reader_open_errors_total").increment(1),
+        }
+        res?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, "opened source");
+        if self.opts.readahead {
+            if let Some(next) = self.peek_next_path() {
+                R::advise_will_need(next);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a hole at the current position. Returns the number of zeros written to `buf`, or
+    /// `None` if there is data to read after skipping a hole.
+    fn read_hole(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        if self.pos >= self.data.end {
+            if self.curr.is_init() {
+                self.open_curr()?;
+            }
+            match &mut self.curr {
+                ReaderState::Open(file, _) => self.data = file.seek_data(self.pos)?,
+                _ => return Ok(None),
+            }
+        }
+        if self.pos >= self.data.start {
+            return Ok(None);
+        }
+
+        let hole = self.data.start - self.pos;
+        match (&mut self.holes, &self.curr) {
+            (Some(Holes::Zeros), _) => {
+                let n = cmp::min(hole, buf.len() as u64) as usize;
+                buf[..n].fill(0);
+                if let Some(stats) = &mut self.stats {
+                    stats.read(n);
+                }
+                #[cfg(feature = "metrics")]
+                metrics::counter!("concat_reader_bytes_read_total").increment(n as u64);
+                self.pos += n as u64;
+                self.total += n as u64;
+                self.emit_checkpoint();
+                Ok(Some(n))
+            }
+            (Some(Holes::Skip(callback)), ReaderState::Open(_, path)) => {
+                let len = match self.opts.limit_per_source {
+                    Some(limit) => cmp::min(hole, limit.saturating_sub(self.pos)),
+                    None => hole,
+                };
+                callback(&Hole {
+                    path,
+                    offset: self.pos,
+                    len,
+                });
+                self.pos += len;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Applies the symlink policy and skips duplicates before the current path is opened.
+    /// Returns `false` if all remaining paths were skipped.
+    fn check_path(&mut self) -> io::Result<bool> {
+        while let ReaderState::Init(path) = &self.curr {
+            let is_link = self.opts.symlinks != Symlinks::Follow
+                && fs::symlink_metadata(path)
+                    .map(|meta| meta.file_type().is_symlink())
+                    .unwrap_or(false);
+            if is_link && self.opts.symlinks == Symlinks::Error {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is a symbolic link", path.display()),
+                ));
+            }
+            let duplicate = !is_link
+                && self.opts.skip_duplicates
+                && FileId::of(path).is_some_and(|id| !self.seen.insert(id));
+            if !is_link && !duplicate {
+                break;
+            }
+            if !self.skip() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns `true` if the current file is a FIFO whose writers disconnected and the policy
+    /// ends FIFOs at that point.
+    fn fifo_disconnected(&self) -> bool {
+        self.opts.fifos != Fifos::Block
+            && matches!(&self.curr, ReaderState::Open(file, _) if file.is_fifo())
+    }
+
+    /// Compares the current file at its end with its stamp from when it was opened.
+    fn verify_stamp(&self) -> io::Result<()> {
+        if let (Some(stamp), ReaderState::Open(file, path)) = (self.stamp, &self.curr) {
+            let now = file.stamp()?;
+            if now != stamp || self.pos != stamp.len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    FileChanged {
+                        path: path.clone(),
+                        len_at_open: stamp.len,
+                        len_at_eof: now.len,
+                    },
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the internal state after a `read`. Only active with `debug_assertions`.
+    fn debug_check_read(&self, len: usize, res: &io::Result<usize>) {
+        match *res {
+            Ok(n) => {
+                debug_assert!(
+                    n <= len,
+                    "FileConcatReader: read {} bytes into a buffer of {} bytes from {:?}",
+                    n,
+                    len,
+                    self.file_path()
+                );
+                debug_assert!(
+                    n == 0 || self.current().is_some(),
+                    "FileConcatReader: read {} bytes without an open file, state {:?}",
+                    n,
+                    self.curr
+                );
+                debug_assert!(
+                    n > 0 || len == 0 || self.file_path().is_none() || self.total_left() == Some(0),
+                    "FileConcatReader: reported EOF with state {:?}",
+                    self.curr
+                );
+            }
+            Err(ref e) => debug_assert!(
+                self.file_path().is_some()
+                    || Cancelled::of(e).is_some()
+                    || e.kind() == io::ErrorKind::WouldBlock,
+                "FileConcatReader: error {:?} is not attributed to a file",
+                e
+            ),
+        }
+    }
+
+    fn next_path(&mut self) -> Option<I::Item> {
+        let (index, path) = self.peeked.take().or_else(|| self.pull())?;
+        self.index = index;
+        Some(path)
+    }
+
+    /// Takes the next path from `rest` which passes the filter, with its index.
+    fn pull(&mut self) -> Option<(u64, I::Item)> {
+        if self.exhausted {
+            return None;
+        }
+        loop {
+            let path = match self.rest.next() {
+                Some(path) => path,
+                None => {
+                    // follow mode waits for new paths
+                    self.exhausted = self.opts.fuse && self.opts.follow.is_none();
+                    return None;
+                }
+            };
+            self.taken += 1;
+            if self.admits(path.as_ref()) {
+                return Some((self.taken - 1, path));
+            }
+        }
+    }
+
+    fn admits(&self, path: &Path) -> bool {
+        self.filter.as_ref().is_none_or(|f| f(path))
+    }
+
+    /// Calls the checkpoint callback if the next checkpoint was reached.
+    fn emit_checkpoint(&mut self) {
+        let reached = self
+            .checkpoints
+            .as_ref()
+            .is_some_and(|checkpoints| checkpoints.next == self.total);
+        if reached {
+            let checkpoint = self.checkpoint();
+            if let Some(checkpoints) = &mut self.checkpoints {
+                (checkpoints.callback)(&checkpoint);
+                checkpoints.schedule(self.total);
+            }
+        }
+    }
+
+    /// Reads from the current file, retrying interrupted reads unless `return_interrupted` is set.
+    fn read_uninterrupted(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.curr.read(buf) {
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::Interrupted && !self.opts.return_interrupted =>
+                {
+                    self.check_cancel()?
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn check_cancel(&self) -> io::Result<()> {
+        match &self.cancel {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for new data in the current file or a new path in the source list.
+    fn read_follow(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        let interval = self.opts.follow.unwrap_or_default();
+        loop {
+            if let Some(path) = self.next_path() {
+                if let Some(stats) = &mut self.stats {
+                    stats.finish();
+                }
+                self.span = SourceSpan::none();
+                self.record_duration();
+                self.curr = Some(path).into();
+                self.pos = 0;
+                self.data = 0..0;
+                return Ok(None);
+            }
+            if self.opts.non_blocking {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            match &self.cancel {
+                Some(token) => token.sleep(interval)?,
+                None => thread::sleep(interval),
+            }
+            match self.read_curr(buf) {
+                Ok(0) => continue,
+                val => return val.map(Some),
+            }
+        }
+    }
+}
+
+impl<R, I> Read for InnerReader<R, I>
+where
+    R: FileLike,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.read_next(buf);
+        self.debug_check_read(buf.len(), &res);
+        res
+    }
+}
+
+impl<R, I> InnerReader<R, I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+    I::IntoIter: Clone,
+{
+    /// Returns the current path and all paths after it which pass the filter, with their
+    /// indices in the source list.
+    fn remaining_paths(&self) -> Vec<(usize, PathBuf)> {
+        let curr = match &self.curr {
+            ReaderState::Init(p) | ReaderState::Open(_, p) | ReaderState::Err(_, p) => {
+                Some((self.index as usize, p.clone()))
+            }
+            ReaderState::Eof => None,
+        };
+        let peeked = self
+            .peeked
+            .iter()
+            .map(|(i, p)| (*i as usize, p.as_ref().to_path_buf()));
+        let rest = self
+            .rest
+            .clone()
+            .enumerate()
+            .filter(|(_, p)| self.filter.as_ref().is_none_or(|f| f(p.as_ref())))
+            .map(|(i, p)| (self.taken as usize + i, p.as_ref().to_path_buf()));
+        curr.into_iter().chain(peeked).chain(rest).collect()
+    }
+}
+
+impl<R, I> fmt::Debug for InnerReader<R, I>
+where
+    R: fmt::Debug,
+    I: IntoIterator,
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let peeked = self.peeked.as_ref().map(|(_, p)| p);
+        f.debug_struct("CatReader")
+            .field("curr", &self.curr)
+            .field("peeked", &peeked)
+            .field("remaining", &Remaining::of(&self.rest, peeked.is_some()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        known_len, Fifos, FileChanged, FileLike, Hole, InnerReader, Mismatch, PreflightFailed,
+        ReaderState, SourceClock, SourceSpan, Stamp, Symlinks,
+    };
+    use crate::layer::BoxedFileRead;
+    use crate::provenance::{self, Provenance};
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, FileConcatRead, FileConcatReader, SourceQueue};
+    use std::fs::OpenOptions;
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+    use std::path::Path;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+    use std::time::Duration;
+
+    impl FileLike for &'static [u8] {
+        fn open<P: AsRef<Path>>(p: P) -> io::Result<&'static [u8]> {
+            let string = p.as_ref().to_string_lossy().into_owned();
+            let reference: &str = &string;
+            match reference {
+                "test1.txt" => Ok(b"some\ntext\n"),
+                "empty" => Ok(b""),
+                "1byte" => Ok(b"1"),
+                "2byte" => Ok(b"22"),
+                "3byte" => Ok(b"333"),
+                "4byte" => Ok(b"4444"),
+                "dir/other.test.txt" => Ok(b"here's "),
+                _ => Err(io::Error::new(io::ErrorKind::NotFound, "file missing")),
+            }
+        }
+
+        fn stamp(&self) -> io::Result<Stamp> {
+            Ok(Stamp {
+                len: self.len() as u64,
+                modified: None,
+            })
+        }
+    }
+
+    #[test]
+    fn reads_from_multiple_files() {
+        let strs = &["1byte", "2byte", "3byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+
+        let mut buf = [0; 5];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"12233");
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Open([51],\"3byte\"), peeked: None, remaining: 0 }"
+        );
+    }
+
+    #[test]
+    fn init_next_reader_when_current_is_eof() {
+        let strs = &["1byte", "2byte", "3byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+
+        let mut buf = [0];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(&buf, b"1");
+
+        assert_eq!(reader.file_path(), Some(Path::new("1byte")));
+
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(&buf, b"2");
+
+        assert_eq!(reader.file_path(), Some(Path::new("2byte")));
+    }
+
+    #[test]
+    fn fails_on_file_error() {
+        let strs = &["1byte", "2byte", "404", "3byte", "4byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+
+        assert_eq!(buf, b"122");
+        assert_eq!(reader.file_path(), Some(Path::new("404")));
+    }
+
+    #[test]
+    fn can_skip_and_continue() {
+        let strs = &["404", "3byte", "4byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+
+        //skip to next reader
+        reader.skip();
+        assert!(reader.read_to_end(&mut buf).is_ok());
+        assert_eq!(buf, b"3334444");
     }
-}
 
-impl<I> fmt::Debug for FileConcatReader<I>
-where
-    I: IntoIterator,
-    I::Item: fmt::Debug,
-    I::IntoIter: Clone,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&self.inner, f)
+    #[test]
+    fn fails_on_file_error2() {
+        let strs = &["1byte", "2byte", "404", "3byte", "4byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+
+        let mut buf = [0; 5];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert!(reader.read(&mut buf).is_err());
     }
-}
 
-enum ReaderState<R, E> {
-    Open(R, PathBuf),
-    Init(PathBuf),
-    Err(E, PathBuf),
-    Eof,
-}
+    #[test]
+    fn can_debug_print() {
+        let strs = &["dir/other.test.txt", "404", "test1.txt"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
 
-impl<R> ReaderState<R, io::Error>
-where
-    R: FileLike,
-{
-    fn open(&mut self) -> Result<()> {
-        use std::mem;
-        let s = match self {
-            ReaderState::Init(p) => match FileLike::open(&p) {
-                Err(e) => ReaderState::Err(e, p.clone()),
-                Ok(f) => ReaderState::Open(f, p.clone()),
-            },
-            ReaderState::Eof => panic!("called `ReaderState::open()` on a `Eof` value"),
-            ReaderState::Open(_, _) => panic!("called `ReaderState::open()` on a `Open` value"),
-            ReaderState::Err(_, _) => panic!("called `ReaderState::open()` on a `Err` value"),
-        };
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Init(\"dir/other.test.txt\"), peeked: None, remaining: 2 }"
+        );
 
-        mem::replace(self, s);
-        if let ReaderState::Err(e, _) = &self {
-            return Err(io::Error::new(e.kind(), e.description()));
-        }
-        Ok(())
+        // read zero bytes no file has been opened
+        let mut buf = [];
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Init(\"dir/other.test.txt\"), peeked: None, remaining: 2 }"
+        );
+
+        // read one byte. File should be opened
+        let mut buf = [0];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf, [104]);
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Open([101, 114, 101, 39, 115, 32],\"dir/other.test.txt\"), peeked: None, remaining: 2 }"
+        );
+
+        // read rest of files and fail because of missing file
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Err(Custom { kind: NotFound, error: \"file missing\" },\"404\"), peeked: None, remaining: 1 }"
+        );
+
+        assert!(reader.read_to_end(&mut buf).is_err());
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Err(Custom { kind: NotFound, error: \"file missing\" },\"404\"), peeked: None, remaining: 1 }"
+        );
+        // we can skip the file if we want
+        reader.skip();
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Init(\"test1.txt\"), peeked: None, remaining: 0 }"
+        );
+
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 10);
+        assert_eq!(buf, b"ere's some\ntext\n");
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Eof, peeked: None, remaining: 0 }"
+        );
     }
 
-    fn is_init(&self) -> bool {
-        match *self {
-            ReaderState::Init(_) => true,
-            _ => false,
-        }
+    #[test]
+    fn stays_finished_after_eof() {
+        let tree = TempTree::new().unwrap();
+        let a = tree.file("a", b"1").unwrap();
+        let b = tree.file("b", b"2").unwrap();
+        let mut items = vec![Some(a), None, Some(b)].into_iter();
+        let mut reader = FileConcatReader::new(std::iter::from_fn(move || items.next().flatten()));
+        assert!(!reader.is_finished());
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"1");
+        assert!(reader.is_finished());
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+        assert_eq!(reader.file_path(), None);
+
+        let empty: Vec<&str> = Vec::new();
+        assert!(FileConcatReader::new(empty).is_finished());
+        let mut reader = FileConcatReader::with_queue();
+        assert!(reader.is_finished());
+        reader.push_path(tree.file("c", b"3").unwrap());
+        assert!(!reader.is_finished());
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"13");
+        assert!(reader.is_finished());
     }
 
-    fn unwrap_err(&self) -> io::Error {
-        match self {
-            ReaderState::Err(e, _) => io::Error::new(e.kind(), e.description()),
-            _ => panic!("no error to unwrap"),
-        }
+    #[test]
+    fn applies_after_error_policy() {
+        use crate::read::{AfterError, Poisoned};
+        use crate::records::Remainder;
+        use crate::SourceRead;
+
+        let tree = TempTree::new().unwrap();
+        let missing = tree.path().join("missing");
+        let b = tree.file("b", b"2").unwrap();
+        let paths = vec![missing.clone(), b];
+
+        let mut reader = FileConcatReader::new(paths.clone());
+        assert!(reader.read(&mut [0; 1]).is_err());
+        assert!(reader.read(&mut [0; 1]).is_err());
+
+        let mut reader = FileConcatReader::new(paths.clone()).after_error(AfterError::Skip);
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(Provenance::of(&err).unwrap().path(), Some(&*missing));
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"2");
+
+        let mut reader = FileConcatReader::new(paths.clone()).after_error(AfterError::Skip);
+        assert!(reader.read_source(&mut [0; 1]).is_err());
+        assert_eq!(reader.read_source(&mut [0; 1]).unwrap(), 1);
+
+        let mut reader = FileConcatReader::restartable(paths).after_error(AfterError::Poison);
+        assert!(reader.read(&mut [0; 1]).is_err());
+        reader.skip();
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("an earlier read failed: "));
+        let first = Poisoned::of(&err).unwrap().error();
+        assert_eq!(Provenance::of(first).unwrap().path(), Some(&*missing));
+        let err = reader.read_source(&mut [0; 1]).unwrap_err();
+        assert!(Poisoned::of(&err).is_some());
+        let records: Vec<_> = reader.fixed_records(1, Remainder::Error).collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_err());
+
+        let mut reader =
+            FileConcatReader::restartable(vec![missing]).after_error(AfterError::Poison);
+        assert!(reader.read(&mut [0; 1]).is_err());
+        reader.reset();
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert!(!err.to_string().starts_with("an earlier read failed: "));
     }
-}
 
-impl<R> Read for ReaderState<R, io::Error>
-where
-    R: FileLike,
-{
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        match self {
-            ReaderState::Eof => Ok(0),
-            ReaderState::Init(_) => {
-                self.open()?;
-                self.read(buf)
+    #[test]
+    fn retries_interrupted_reads() {
+        #[derive(Debug)]
+        struct Interrupting(u8, &'static [u8]);
+
+        impl Read for Interrupting {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0 > 0 {
+                    self.0 -= 1;
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                self.1.read(buf)
             }
-            ReaderState::Err(_, _) => Err(self.unwrap_err()),
-            ReaderState::Open(r, _) => r.read(buf),
         }
-    }
-}
 
-impl<R, E, P> From<Option<P>> for ReaderState<R, E>
-where
-    P: AsRef<Path>,
-    R: FileLike,
-    E: Error,
-{
-    fn from(path: Option<P>) -> Self {
-        match path {
-            Some(p) => ReaderState::Init(p.as_ref().to_path_buf()),
-            None => ReaderState::Eof,
+        impl FileLike for Interrupting {
+            fn open<P: AsRef<Path>>(_: P) -> io::Result<Interrupting> {
+                Ok(Interrupting(2, b"1"))
+            }
+
+            fn stamp(&self) -> io::Result<Stamp> {
+                Ok(Stamp {
+                    len: self.1.len() as u64,
+                    modified: None,
+                })
+            }
+        }
+
+        let mut reader: InnerReader<Interrupting, _> = InnerReader::new(&["a", "b"]);
+        assert_eq!(reader.read(&mut [0; 2]).unwrap(), 1);
+        assert_eq!(reader.read(&mut [0; 2]).unwrap(), 1);
+
+        let mut reader: InnerReader<Interrupting, _> = InnerReader::new(&["a"]);
+        reader.opts.return_interrupted = true;
+        for _ in 0..2 {
+            let err = reader.read(&mut [0; 1]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Interrupted);
         }
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
     }
-}
 
-impl<R, E> fmt::Debug for ReaderState<R, E>
-where
-    R: fmt::Debug,
-    E: Error,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ReaderState::Init(p) => write!(f, "ReaderState::Init({:?})", p),
-            ReaderState::Open(r, p) => write!(f, "ReaderState::Open({:?},{:?})", r, p),
-            ReaderState::Eof => write!(f, "ReaderState::Eof"),
-            ReaderState::Err(p, e) => write!(f, "ReaderState::Err({:?},{:?})", p, e),
+    #[test]
+    fn continues_with_paths_appended_after_eof() {
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(SourceQueue::new());
+
+        let mut buf = Vec::new();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 0);
+        assert_eq!(reader.file_path(), None);
+
+        reader.rest.push("1byte");
+        reader.rest.sender().send("2byte").unwrap();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 3);
+        assert_eq!(buf, b"122");
+        assert_eq!(reader.file_path(), None);
+
+        reader.rest.push("3byte");
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 3);
+        assert_eq!(buf, b"122333");
+    }
+
+    #[test]
+    fn reads_paths_until_channel_is_closed() {
+        let (tx, rx) = channel();
+        let mut reader: InnerReader<&'static [u8], Receiver<&str>> = InnerReader {
+            peeked: None,
+            curr: ReaderState::Eof,
+            rest: rx.into_iter(),
+            taken: 0,
+            index: 0,
+            filter: None,
+            cancel: None,
+            exhausted: false,
+            poisoned: None,
+            checkpoints: None,
+            holes: None,
+            stats: None,
+            span: SourceSpan::none(),
+            opened_at: SourceClock::default(),
+            data: 0..0,
+            lens: Vec::new(),
+            stamp: None,
+            seen: Default::default(),
+            pos: 0,
+            total: 0,
+            opts: Default::default(),
+        };
+        let sender = thread::spawn(move || {
+            for p in &["1byte", "2byte", "3byte"] {
+                tx.send(*p).unwrap();
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        sender.join().unwrap();
+        assert_eq!(buf, b"122333");
+    }
+
+    #[test]
+    fn limits_bytes_per_file() {
+        let strs = &["1byte", "3byte", "4byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+        reader.opts.limit_per_source = Some(2);
+
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"133");
+        assert_eq!(reader.file_path(), Some(Path::new("3byte")));
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"44");
+    }
+
+    #[test]
+    fn stops_at_total_limit() {
+        let strs = &["1byte", "3byte", "4byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+        reader.opts.limit_total = Some(3);
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"133");
+        assert_eq!(reader.file_path(), Some(Path::new("3byte")));
+        assert_eq!(reader.pos, 2);
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn stops_at_total_limit_set_after_read() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"34").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths);
+        reader.read_exact(&mut [0; 3]).unwrap();
+
+        let mut reader = reader.limit_total(1);
+        assert!(reader.is_finished());
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+        assert_eq!(reader.position(), 3);
+    }
+
+    #[test]
+    fn follows_last_file() {
+        let tree = TempTree::new().unwrap();
+        let first = tree.file("app.log.1", b"1").unwrap();
+        let last = tree.file("app.log", b"2").unwrap();
+
+        let mut reader =
+            FileConcatReader::new(vec![first, last.clone()]).follow(Duration::from_millis(1));
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            let mut f = OpenOptions::new().append(true).open(last).unwrap();
+            f.write_all(b"34").unwrap();
+        });
+
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+        writer.join().unwrap();
+        assert_eq!(&buf, b"1234");
+    }
+
+    #[test]
+    fn materialize_enables_debug_for_queues() {
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(SourceQueue::new());
+        for p in &["1byte", "2byte", "3byte"] {
+            reader.rest.push(p);
         }
+
+        let mut buf = [0];
+        reader.read_exact(&mut buf).unwrap();
+
+        let reader = match reader.materialize(2) {
+            Ok(r) => r,
+            Err(_) => panic!("expected all paths to be collected"),
+        };
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Open([],\"1byte\"), peeked: None, remaining: 2 }"
+        );
     }
-}
 
-struct InnerReader<R, I: IntoIterator> {
-    curr: ReaderState<R, io::Error>,
-    rest: I::IntoIter,
-}
+    #[test]
+    fn remaining_len_is_unknown_for_special_files() {
+        let tree = TempTree::new().unwrap();
+        let file = tree.file("a", b"aaaa").unwrap();
+        assert_eq!(known_len(&file), Some(4));
+        assert_eq!(known_len(tree.path()), None);
+        assert_eq!(known_len(tree.path().join("missing")), None);
 
-impl<R, I> InnerReader<R, I>
-where
-    R: FileLike,
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    fn new(iter: I) -> InnerReader<R, I> {
-        let mut iter = iter.into_iter();
-        let curr = iter.next().into();
-        InnerReader { curr, rest: iter }
+        let mut reader = FileConcatReader::new(vec![file.clone(), file.clone()]);
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.remaining_len(), Some(5));
+
+        let reader = FileConcatReader::new(vec![file, tree.path().join("missing")]);
+        assert_eq!(reader.remaining_len(), None);
+        assert_eq!(reader.progress(|_: &_| {}).progress().fraction(), None);
     }
-}
 
-impl<R, I> ConcatRead for InnerReader<R, I>
-where
-    R: FileLike,
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    type Item = R;
+    #[test]
+    fn resumes_from_snapshot() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"3456").unwrap(),
+            tree.file("c", b"78").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths.clone());
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf[..2]).unwrap();
+        reader.read_exact(&mut buf[..1]).unwrap();
+
+        let snapshot = reader.snapshot();
+        assert_eq!(snapshot.path.as_deref(), Some(paths[1].as_path()));
+        assert_eq!(snapshot.remaining, &paths[2..]);
+        assert_eq!((snapshot.source, snapshot.offset), (1, 1));
+
+        let mut resumed = FileConcatReader::resume(snapshot.clone()).unwrap();
+        assert_eq!(resumed.checkpoint(), snapshot.checkpoint());
+        let mut rest = String::new();
+        resumed.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "45678");
+        assert_eq!(resumed.checkpoint().source, 3);
+        assert_eq!(resumed.position(), 8);
+
+        std::fs::write(&paths[1], b"").unwrap();
+        let err = FileConcatReader::resume(snapshot).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            Provenance::of(&err).unwrap().path(),
+            Some(paths[1].as_path())
+        );
+    }
+
+    #[test]
+    fn resumes_past_limit_per_source() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"1234").unwrap(),
+            tree.file("b", b"56").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths);
+        reader.read_exact(&mut [0; 3]).unwrap();
+
+        let mut resumed = FileConcatReader::resume(reader.snapshot())
+            .unwrap()
+            .limit_per_source(1);
+        let mut rest = Vec::new();
+        resumed.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"5");
+    }
+
+    #[test]
+    fn emits_checkpoints_at_byte_intervals() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12345").unwrap(),
+            tree.file("b", b"6789").unwrap(),
+        ];
+        let (tx, rx) = channel();
+        let mut reader = FileConcatReader::new(paths.clone()).checkpoint_every(3, move |c| {
+            tx.send((c.source, c.offset, c.position)).unwrap();
+        });
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"123456789");
+        let checkpoints: Vec<_> = rx.try_iter().collect();
+        assert_eq!(checkpoints, vec![(0, 3, 3), (1, 1, 6), (1, 4, 9)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshots_are_serializable() {
+        fn assert_serde<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+        assert_serde::<crate::checkpoint::Snapshot>();
+        assert_serde::<crate::checkpoint::Checkpoint>();
+    }
+
+    #[test]
+    fn errors_carry_the_path_of_the_file() {
+        let tree = TempTree::new().unwrap();
+        let first = tree.file("a", b"a").unwrap();
+        let missing = tree.path().join("missing");
+
+        let mut reader = FileConcatReader::new(vec![first, missing.clone()]);
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let provenance = Provenance::of(&err).unwrap();
+        assert_eq!(provenance.path(), Some(missing.as_path()));
+        assert_eq!(provenance.error().kind(), io::ErrorKind::NotFound);
+        assert!(err.get_ref().unwrap().source().is_some());
+    }
+
+    #[test]
+    fn keeps_os_errors() {
+        let tree = TempTree::new().unwrap();
+        let missing = tree.path().join("missing");
 
-    fn current(&self) -> Option<&Self::Item> {
-        match &self.curr {
-            ReaderState::Open(r, _) => Some(&r),
-            _ => None,
+        let mut reader = FileConcatReader::new(vec![missing]);
+        let mut buf = [0; 4];
+        for _ in 0..2 {
+            let err = reader.read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            let code = provenance::raw_os_error(&err);
+            assert!(code.is_some());
+            assert_eq!(Provenance::of(&err).unwrap().error().raw_os_error(), code);
         }
     }
 
-    fn skip(&mut self) -> bool {
-        self.curr = self.rest.next().into();
-        self.curr.is_init()
-    }
-}
+    #[test]
+    fn reads_past_many_empty_files() {
+        let mut paths = vec!["empty"; 1_000_000];
+        paths.push("1byte");
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(paths);
 
-impl<R, I> FileConcatRead for InnerReader<R, I>
-where
-    R: FileLike,
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    fn file_path(&self) -> Option<&Path> {
-        match &self.curr {
-            ReaderState::Init(p) => Some(p.as_path()),
-            ReaderState::Open(_, p) => Some(p.as_path()),
-            ReaderState::Err(_, p) => Some(p.as_path()),
-            _ => None,
-        }
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"1");
     }
-}
 
-impl<R, I> Read for InnerReader<R, I>
-where
-    R: FileLike,
-    I: IntoIterator,
-    I::Item: AsRef<Path>,
-{
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if buf.is_empty() {
-            return Ok(0);
-        }
+    #[test]
+    fn peeks_next_path_without_opening() {
+        let strs = &["1byte", "404", "2byte"];
+        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
 
-        match self.curr.read(buf) {
-            Ok(0) => {
-                let has_items = self.skip();
-                if !has_items {
-                    Ok(0)
-                } else {
-                    self.read(buf)
-                }
-            }
-            val => val,
-        }
+        assert_eq!(reader.peek_next_path(), Some(Path::new("404")));
+        assert_eq!(
+            format!("{:?}", reader),
+            "CatReader { curr: ReaderState::Init(\"1byte\"), peeked: Some(\"404\"), remaining: 2 }"
+        );
+
+        let mut buf = [0];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.peek_next_path(), Some(Path::new("404")));
+        assert!(reader.read(&mut buf).is_err());
+        assert_eq!(reader.file_path(), Some(Path::new("404")));
+        assert_eq!(reader.peek_next_path(), Some(Path::new("2byte")));
     }
-}
 
-impl<R, I> fmt::Debug for InnerReader<R, I>
-where
-    R: fmt::Debug,
-    I: IntoIterator,
-    I::Item: fmt::Debug,
-    I::IntoIter: Clone,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let rest: Vec<_> = self.rest.clone().collect();
-        f.debug_struct("CatReader")
-            .field("curr", &self.curr)
-            .field("rest", &rest)
-            .finish()
+    #[test]
+    fn filters_paths_as_they_are_reached() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a.tmp", b"0").unwrap(),
+            tree.file("b", b"12").unwrap(),
+            tree.file("c", b"").unwrap(),
+            tree.file("d.tmp", b"3").unwrap(),
+            tree.file("e", b"45").unwrap(),
+        ];
+        let reader = FileConcatReader::new(paths.clone()).filter_sources(|path| {
+            path.extension().is_none() && std::fs::metadata(path).unwrap().len() > 0
+        });
+        assert_eq!(reader.file_path(), Some(paths[1].as_path()));
+        assert_eq!(reader.checkpoint().source, 1);
+        assert_eq!(reader.remaining_len(), Some(4));
+
+        let mut reader = reader;
+        assert_eq!(reader.peek_next_path(), Some(paths[4].as_path()));
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"124");
+        assert_eq!(reader.checkpoint().source, 4);
+        assert_eq!(reader.total_len().unwrap(), 2);
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"5");
+        assert_eq!(reader.checkpoint().source, 5);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{FileLike, InnerReader};
-    use crate::{ConcatRead, FileConcatRead};
-    use std::io::{self, Read};
-    use std::path::Path;
+    #[test]
+    fn resets_and_rewinds_files() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"345").unwrap(),
+        ];
+        let mut reader = FileConcatReader::restartable(paths).limit_total(8);
 
-    impl FileLike for &'static [u8] {
-        fn open<P: AsRef<Path>>(p: P) -> io::Result<&'static [u8]> {
-            let string = p.as_ref().to_string_lossy().into_owned();
-            let reference: &str = &string;
-            match reference {
-                "test1.txt" => Ok(b"some\ntext\n"),
-                "1byte" => Ok(b"1"),
-                "2byte" => Ok(b"22"),
-                "3byte" => Ok(b"333"),
-                "4byte" => Ok(b"4444"),
-                "dir/other.test.txt" => Ok(b"here's "),
-                _ => Err(io::Error::new(io::ErrorKind::NotFound, "file missing")),
-            }
-        }
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"1234");
+        reader.rewind_current().unwrap();
+        assert_eq!(reader.current_position(), 0);
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"345");
+        assert_eq!(reader.position(), 7);
+
+        reader.reset();
+        assert_eq!(reader.position(), 0);
+        let mut all = String::new();
+        reader.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "12345");
     }
 
     #[test]
-    fn reads_from_multiple_files() {
-        let strs = &["1byte", "2byte", "3byte"];
-        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+    fn caches_total_len() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"345").unwrap(),
+        ];
+        let mut reader = FileConcatReader::restartable(paths.clone());
+        assert_eq!(reader.total_len().unwrap(), 5);
 
-        let mut buf = [0; 5];
+        let mut buf = [0; 3];
         reader.read_exact(&mut buf).unwrap();
-        assert_eq!(&buf, b"12233");
+        assert_eq!(reader.total_len().unwrap(), 3);
+        assert_eq!(reader.remaining_len(), Some(2));
+
+        std::fs::write(&paths[0], b"123456").unwrap();
+        reader.reset();
+        assert_eq!(reader.total_len().unwrap(), 5);
+
+        let missing = tree.path().join("missing");
+        let mut reader = FileConcatReader::new(vec![paths[1].clone(), missing.clone()]);
+        let err = reader.total_len().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
         assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Open([51],\"3byte\"), rest: [] }"
+            Provenance::of(&err).unwrap().path(),
+            Some(missing.as_path())
         );
+        let err = FileConcatReader::new(vec![tree.path()])
+            .total_len()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
     }
 
     #[test]
-    fn init_next_reader_when_current_is_eof() {
-        let strs = &["1byte", "2byte", "3byte"];
-        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+    fn reports_metadata_of_current_file() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"345").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths.clone()).normalize_text();
+        assert_eq!(reader.current_len().unwrap(), 2);
 
-        let mut buf = [0];
-        assert_eq!(reader.read(&mut buf).unwrap(), 1);
-        assert_eq!(&buf, b"1");
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        std::fs::remove_file(&paths[1]).unwrap();
+        assert_eq!(reader.current_len().unwrap(), 3);
+        assert!(reader.current_metadata().unwrap().modified().is_ok());
 
-        assert_eq!(reader.file_path(), Some(Path::new("1byte")));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        let err = reader.current_metadata().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
 
-        assert_eq!(reader.read(&mut buf).unwrap(), 1);
-        assert_eq!(&buf, b"2");
+    #[test]
+    fn fails_on_files_changed_while_read() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"345").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths.clone()).verify_unchanged();
 
-        assert_eq!(reader.file_path(), Some(Path::new("2byte")));
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&paths[1])
+            .unwrap()
+            .write_all(b"6")
+            .unwrap();
+
+        let mut rest = Vec::new();
+        let err = reader.read_to_end(&mut rest).unwrap_err();
+        assert_eq!(rest, b"456");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let changed = FileChanged::of(&err).unwrap();
+        assert_eq!(changed.path(), paths[1].as_path());
+        assert_eq!((changed.len_at_open(), changed.len_at_eof()), (3, 4));
+
+        let mut reader = FileConcatReader::new(paths).verify_unchanged();
+        let mut all = String::new();
+        reader.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "123456");
     }
 
+    #[cfg(unix)]
     #[test]
-    fn fails_on_file_error() {
-        let strs = &["1byte", "2byte", "404", "3byte", "4byte"];
-        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+    fn applies_symlink_policy() {
+        let tree = TempTree::new().unwrap();
+        let file = tree.file("a", b"1").unwrap();
+        let link = tree.symlink("link", &file).unwrap();
+        let paths = vec![file.clone(), link.clone(), file];
 
-        let mut buf = Vec::new();
-        assert!(reader.read_to_end(&mut buf).is_err());
+        let read = |policy| {
+            let mut reader = FileConcatReader::new(paths.clone()).symlinks(policy);
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).map(|_| buf)
+        };
+        assert_eq!(read(Symlinks::Follow).unwrap(), b"111");
+        assert_eq!(read(Symlinks::Skip).unwrap(), b"11");
+        let err = read(Symlinks::Error).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(Provenance::of(&err).unwrap().path(), Some(link.as_path()));
 
-        assert_eq!(buf, b"122");
-        assert_eq!(reader.file_path(), Some(Path::new("404")));
+        let mut reader = FileConcatReader::new(vec![link]).symlinks(Symlinks::Skip);
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn can_skip_and_continue() {
-        let strs = &["404", "3byte", "4byte"];
-        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+    fn skips_duplicate_files() {
+        let tree = TempTree::new().unwrap();
+        let a = tree.file("a", b"1").unwrap();
+        let b = tree.file("b", b"2").unwrap();
+        let hard = tree.path().join("hard");
+        std::fs::hard_link(&a, &hard).unwrap();
+        let soft = tree.symlink("soft", &a).unwrap();
+        let paths = vec![a.clone(), hard, b, soft, tree.path().join(".").join("a")];
 
-        let mut buf = Vec::new();
-        assert!(reader.read_to_end(&mut buf).is_err());
+        let mut reader = FileConcatReader::restartable(paths).skip_duplicates();
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "12");
 
-        //skip to next reader
-        reader.skip();
-        assert!(reader.read_to_end(&mut buf).is_ok());
-        assert_eq!(buf, b"3334444");
+        reader.reset();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "1212");
     }
 
+    #[cfg(unix)]
     #[test]
-    fn fails_on_file_error2() {
-        let strs = &["1byte", "2byte", "404", "3byte", "4byte"];
-        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+    fn applies_fifo_policy() {
+        use crate::cancel::CancelToken;
+        use std::process::Command;
 
-        let mut buf = [0; 5];
+        let tree = TempTree::new().unwrap();
+        let fifo = tree.path().join("fifo");
+        assert!(Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap()
+            .success());
+        let paths = vec![
+            tree.file("a", b"1").unwrap(),
+            fifo.clone(),
+            tree.file("b", b"2").unwrap(),
+        ];
+
+        let mut reader = FileConcatReader::new(paths).fifos(Fifos::NoWait);
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "12");
+
+        let token = CancelToken::new();
+        let mut reader = FileConcatReader::with_queue()
+            .follow(Duration::from_millis(1))
+            .fifos(Fifos::SkipOnDisconnect)
+            .cancel_on(token.clone());
+        reader.push_path(fifo.clone());
+        let writer = thread::spawn(move || {
+            let mut f = OpenOptions::new().write(true).open(fifo).unwrap();
+            f.write_all(b"x").unwrap();
+            drop(f);
+            thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+
+        let mut buf = [0; 4];
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
-        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..1], b"x");
         assert!(reader.read(&mut buf).is_err());
+        writer.join().unwrap();
+        assert_eq!(reader.file_path(), None);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn can_debug_print() {
-        let strs = &["dir/other.test.txt", "404", "test1.txt"];
-        let mut reader: InnerReader<&'static [u8], _> = InnerReader::new(strs);
+    fn reads_fifo_in_non_blocking_mode() {
+        use crate::read::AfterError;
+        use std::process::Command;
 
-        assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Init(\"dir/other.test.txt\"), rest: [\"404\", \"test1.txt\"] }"
-        );
+        let tree = TempTree::new().unwrap();
+        let fifo = tree.path().join("fifo");
+        assert!(Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap()
+            .success());
+        let mut reader = FileConcatReader::with_queue()
+            .follow(Duration::from_secs(60))
+            .after_error(AfterError::Skip)
+            .collect_stats()
+            .non_blocking();
+        let mut buf = [0; 4];
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
 
-        // read zero bytes no file has been opened
-        let mut buf = [];
-        assert_eq!(reader.read(&mut buf).unwrap(), 0);
-        assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Init(\"dir/other.test.txt\"), rest: [\"404\", \"test1.txt\"] }"
-        );
+        reader.push_path(fifo.clone());
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        let mut writer = OpenOptions::new().write(true).open(&fifo).unwrap();
+        writer.write_all(b"x").unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(&buf[..1], b"x");
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(reader.file_path(), Some(&*fifo));
 
-        // read one byte. File should be opened
-        let mut buf = [0];
+        writer.write_all(b"y").unwrap();
+        drop(writer);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
-        assert_eq!(buf, [104]);
-        assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Open([101, 114, 101, 39, 115, 32],\"dir/other.test.txt\"), rest: [\"404\", \"test1.txt\"] }"
-        );
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(reader.stats().iter().all(|s| s.error.is_none()));
+    }
 
-        // read rest of files and fail because of missing file
-        let mut buf = Vec::new();
-        assert!(reader.read_to_end(&mut buf).is_err());
+    #[test]
+    fn reads_around_holes() {
+        use std::sync::{Arc, Mutex};
+
+        let tree = TempTree::new().unwrap();
+        let sparse = tree.path().join("sparse");
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&sparse)
+            .unwrap();
+        f.write_all(b"ab").unwrap();
+        f.seek(SeekFrom::Start(1 << 20)).unwrap();
+        f.write_all(b"cd").unwrap();
+        f.set_len((2 << 20) + 2).unwrap();
+        drop(f);
+        let paths = vec![tree.file("a", b"1").unwrap(), sparse.clone()];
+
+        let mut expected = Vec::new();
+        FileConcatReader::new(&paths)
+            .read_to_end(&mut expected)
+            .unwrap();
+        let mut zeroed = Vec::new();
+        FileConcatReader::new(&paths)
+            .zero_holes()
+            .read_to_end(&mut zeroed)
+            .unwrap();
+        assert!(zeroed == expected);
+
+        let holes = Arc::new(Mutex::new(Vec::new()));
+        let found = holes.clone();
+        let mut reader = FileConcatReader::new(&paths).skip_holes(move |hole: &Hole| {
+            found
+                .lock()
+                .unwrap()
+                .push((hole.path.to_path_buf(), hole.offset, hole.len))
+        });
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        let holes = holes.lock().unwrap();
+        let skipped: u64 = holes.iter().map(|(_, _, len)| len).sum();
+        assert_eq!(data.len() as u64 + skipped, expected.len() as u64);
+        assert!(holes.iter().all(|(path, _, _)| *path == sparse));
+        assert!(data.starts_with(b"1ab"));
+        assert!(data.windows(2).any(|w| w == b"cd"));
+        assert_eq!(reader.position(), data.len() as u64);
+    }
+
+    #[test]
+    fn opens_files_with_options() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"3").unwrap(),
+        ];
+
+        let mut options = OpenOptions::new();
+        options.read(true);
+        let mut reader = FileConcatReader::new(&paths)
+            .open_options(options)
+            .advise_sequential();
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "123");
+
+        // options without an access mode are invalid
+        let mut reader = FileConcatReader::new(&paths).open_options(OpenOptions::new());
+        let err = reader.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
         assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Err(Custom { kind: NotFound, error: \"file missing\" },\"404\"), rest: [\"test1.txt\"] }"
+            Provenance::of(&err).unwrap().path(),
+            Some(paths[0].as_path())
         );
+    }
 
-        assert!(reader.read_to_end(&mut buf).is_err());
+    #[test]
+    fn advises_readahead_of_next_file() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"3").unwrap(),
+        ];
+
+        let mut reader = FileConcatReader::new(&paths).advise_readahead();
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
+        // the next path was taken to advise it
+        assert!(reader.inner.peeked.is_some());
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "23");
+    }
+
+    #[test]
+    fn collects_stats_per_file() {
+        let tree = TempTree::new().unwrap();
+        let missing = tree.path().join("missing");
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            missing.clone(),
+            tree.file("b", b"345").unwrap(),
+        ];
+
+        let mut reader = FileConcatReader::new(&paths).collect_stats();
+        assert!(reader.stats().is_empty());
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
+        let stats = reader.stats();
+        assert_eq!((stats.len(), stats[0].bytes_read), (1, 1));
+
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(reader.skip());
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "345");
+
+        let stats = reader.stats();
+        let read: Vec<_> = stats.iter().map(|s| (&s.path, s.bytes_read)).collect();
+        assert_eq!(read, [(&paths[0], 2), (&missing, 0), (&paths[2], 3)]);
+        assert!(stats[0].error.is_none() && stats[2].error.is_none());
+        let err = stats[1].error.as_ref().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(stats.iter().all(|s| s.open_latency <= s.wall_time));
+
+        let mut reader = FileConcatReader::new(&paths[..1]);
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        assert!(reader.stats().is_empty());
+    }
+
+    #[test]
+    fn plans_without_reading() {
+        let tree = TempTree::new().unwrap();
+        let a = tree.file("a", b"12").unwrap();
+        let b = tree.file("b", b"345").unwrap();
+        let missing = tree.path().join("missing");
+        let paths = vec![
+            a.clone(),
+            tree.file("skipped.tmp", b"6").unwrap(),
+            a.clone(),
+            missing.clone(),
+            tree.path().to_path_buf(),
+            b.clone(),
+        ];
+
+        let mut reader = FileConcatReader::new(&paths)
+            .filter_sources(|p| p.extension().is_none_or(|ext| ext != "tmp"))
+            .skip_duplicates();
+        let plan = reader.plan();
+        let planned: Vec<_> = plan.files.iter().map(|f| (&f.path, f.len)).collect();
+        let dir = tree.path().to_path_buf();
         assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Err(Custom { kind: NotFound, error: \"file missing\" },\"404\"), rest: [\"test1.txt\"] }"
+            planned,
+            [(&a, Some(2)), (&missing, None), (&dir, None), (&b, Some(3))]
         );
-        // we can skip the file if we want
-        reader.skip();
+        assert_eq!(plan.total_len, 5);
+        assert!(!plan.is_ok());
+        let problems: Vec<_> = plan.problems().map(|f| &f.path).collect();
+        assert_eq!(problems, [&missing, &dir]);
         assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Init(\"test1.txt\"), rest: [] }"
+            plan.files[1].problem.as_ref().unwrap().kind(),
+            io::ErrorKind::NotFound
         );
 
-        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 10);
-        assert_eq!(buf, b"ere's some\ntext\n");
+        // the plan doesn't change the reader, and lists an opened file as is
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
+        assert_eq!(reader.plan().files.len(), 4);
+        assert_eq!(reader.current_position(), 1);
+    }
+
+    #[test]
+    fn fails_preflight_with_all_problems() {
+        let tree = TempTree::new().unwrap();
+        let a = tree.file("a", b"12").unwrap();
+        let missing = tree.path().join("missing");
+        let paths = vec![a.clone(), missing.clone(), tree.path().to_path_buf()];
+
+        let err = FileConcatReader::new(&paths).preflight().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let failed = PreflightFailed::of(&err).unwrap();
+        let problems: Vec<_> = failed.problems().iter().map(|f| &f.path).collect();
+        assert_eq!(problems, [&missing, &tree.path().to_path_buf()]);
+        assert!(err.to_string().starts_with("2 of 3 files can't be read: "));
+
+        let mut reader = FileConcatReader::new(&paths[..1]).preflight().unwrap();
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "12");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn traces_sources() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the messages of all events.
+        #[derive(Default)]
+        struct Messages(Arc<Mutex<Vec<String>>>);
+
+        impl Visit for &Messages {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0.lock().unwrap().push(format!("{:?}", value));
+                }
+            }
+        }
+
+        impl Subscriber for Messages {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event) {
+                event.record(&mut &*self);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"3").unwrap(),
+            tree.path().join("missing"),
+        ];
+        let subscriber = Messages::default();
+        let messages = subscriber.0.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            let mut reader = FileConcatReader::new(&paths);
+            reader.read_exact(&mut [0; 1]).unwrap();
+            reader.skip();
+            reader.read_to_end(&mut Vec::new()).unwrap_err();
+            reader.skip();
+            reader.read_to_end(&mut Vec::new()).unwrap();
+        });
         assert_eq!(
-            format!("{:?}", reader),
-            "CatReader { curr: ReaderState::Eof, rest: [] }"
+            *messages.lock().unwrap(),
+            [
+                "opened source",
+                "skipped source",
+                "opened source",
+                "end of source",
+                "read failed",
+                "skipped source",
+                "concatenation ended",
+            ]
         );
     }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn records_metrics() {
+        use metrics::{Counter, Gauge, Histogram, HistogramFn, Key, KeyName, SharedString, Unit};
+        use std::collections::HashMap;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Durations(Mutex<Vec<f64>>);
+
+        impl HistogramFn for Durations {
+            fn record(&self, value: f64) {
+                self.0.lock().unwrap().push(value);
+            }
+        }
+
+        /// Keeps the counters by name and the values of all histograms.
+        #[derive(Default)]
+        struct Recorder {
+            counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+            durations: Arc<Durations>,
+        }
+
+        impl Recorder {
+            fn counter(&self, name: &str) -> u64 {
+                let counters = self.counters.lock().unwrap();
+                counters.get(name).map_or(0, |c| c.load(Ordering::SeqCst))
+            }
+        }
+
+        impl metrics::Recorder for Recorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn register_counter(&self, key: &Key, _: &metrics::Metadata) -> Counter {
+                let mut counters = self.counters.lock().unwrap();
+                Counter::from_arc(counters.entry(key.name().to_owned()).or_default().clone())
+            }
+            fn register_gauge(&self, _: &Key, _: &metrics::Metadata) -> Gauge {
+                Gauge::noop()
+            }
+            fn register_histogram(&self, _: &Key, _: &metrics::Metadata) -> Histogram {
+                Histogram::from_arc(self.durations.clone())
+            }
+        }
+
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.path().join("missing"),
+            tree.file("b", b"3").unwrap(),
+        ];
+        let recorder = Recorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            let mut reader = FileConcatReader::new(&paths);
+            reader.read_to_end(&mut Vec::new()).unwrap_err();
+            reader.skip();
+            reader.read_to_end(&mut Vec::new()).unwrap();
+        });
+        assert_eq!(recorder.counter("concat_reader_bytes_read_total"), 3);
+        assert_eq!(recorder.counter("concat_reader_files_opened_total"), 2);
+        assert_eq!(recorder.counter("concat_reader_open_errors_total"), 1);
+        assert_eq!(recorder.durations.0.lock().unwrap().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exposes_fd_of_current_file() {
+        use std::os::unix::io::AsRawFd;
+
+        let tree = TempTree::new().unwrap();
+        let paths = vec![tree.file("a", b"1").unwrap(), tree.file("b", b"2").unwrap()];
+        let mut reader = FileConcatReader::new(paths);
+        assert_eq!(reader.as_raw_fd(), -1);
+
+        let mut buf = [0; 1];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.as_raw_fd(), reader.current().unwrap().as_raw_fd());
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.file_path().unwrap().file_name().unwrap(), "b");
+        assert_eq!(reader.as_raw_fd(), reader.current().unwrap().as_raw_fd());
+        assert!(!reader.skip());
+        assert_eq!(reader.as_raw_fd(), -1);
+    }
+
+    #[test]
+    fn verifies_against_reference() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"12").unwrap(),
+            tree.file("b", b"345").unwrap(),
+        ];
+        let verify = |reference: &[u8]| FileConcatReader::new(&paths).verify_against(reference);
+
+        assert_eq!(verify(b"12345").unwrap(), 5);
+
+        let err = verify(b"12x45").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mismatch = Mismatch::of(&err).unwrap();
+        assert_eq!(mismatch.offset(), 2);
+        assert_eq!(mismatch.path(), Some(paths[1].as_path()));
+        assert_eq!(mismatch.source_offset(), Some(0));
+
+        let err = verify(b"1234").unwrap_err();
+        let mismatch = Mismatch::of(&err).unwrap();
+        assert!(mismatch.reference_ended());
+        assert_eq!((mismatch.offset(), mismatch.source_offset()), (4, Some(2)));
+
+        let err = verify(b"123456").unwrap_err();
+        let mismatch = Mismatch::of(&err).unwrap();
+        assert_eq!((mismatch.offset(), mismatch.path()), (5, None));
+        assert!(!mismatch.reference_ended());
+
+        let reference = tree.file("reference", b"12345").unwrap();
+        let mut reader = FileConcatReader::new(&paths);
+        assert_eq!(reader.verify_against_file(reference).unwrap(), 5);
+    }
+
+    #[test]
+    fn takes_current_file() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"123").unwrap(),
+            tree.file("b", b"45").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths);
+        assert!(reader.take_current().is_none());
+
+        let mut buf = [0; 1];
+        reader.read_exact(&mut buf).unwrap();
+        let mut file = reader.take_current().unwrap();
+        let mut rest = String::new();
+        file.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "23");
+
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "2345");
+        assert!(reader.take_current().is_none());
+    }
+
+    #[test]
+    fn seeks_in_current_file() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"123").unwrap(),
+            tree.file("b", b"45").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths);
+        assert!(reader.current_mut().is_none());
+
+        let mut buf = [0; 1];
+        reader.read_exact(&mut buf).unwrap();
+        let file = reader.current_mut().unwrap();
+        file.seek(SeekFrom::End(-1)).unwrap();
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "345");
+    }
+
+    #[test]
+    fn boxes_file_reader() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![tree.file("a", b"1").unwrap(), tree.file("b", b"2").unwrap()];
+        let mut readers: Vec<BoxedFileRead> = vec![
+            FileConcatReader::new(paths.clone()).build_boxed_file(),
+            FileConcatReader::new(paths.clone())
+                .throttle(1 << 20)
+                .build_boxed_file(),
+        ];
+        for reader in &mut readers {
+            let mut buf = [0; 1];
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(reader.file_path(), Some(paths[0].as_path()));
+            assert!(reader.take_current().is_some());
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"2");
+        }
+    }
 }
@@ -0,0 +1,259 @@
+//! Checksums of each source and of the whole concatenation.
+//!
+//! This module is only available with the `hash` feature. [`HashReader`] feeds every byte it
+//! reads into a [`Hasher`] for the current source and one for the whole stream. The digest of a
+//! source is available as soon as the reader moves past its end, the digest of the whole stream
+//! at any time.
+//!
+//! [`Crc32`] is always available, [`Sha256`] requires the `sha256` feature.
+//!
+//! ```
+//! use concat_reader::hash::{Crc32, HashReader};
+//! use concat_reader::concat;
+//! use std::io::prelude::*;
+//!
+//! let mut reader = HashReader::<_, Crc32>::new(concat(vec!["1234".as_bytes(), "56789".as_bytes()]));
+//! let mut buffer = Vec::new();
+//! reader.read_to_end(&mut buffer).unwrap();
+//!
+//! assert_eq!(reader.source_digests().len(), 2);
+//! assert_eq!(reader.digest(), 0xCBF4_3926);
+//! ```
+//!
+//! [`HashReader`]:             struct.HashReader.html
+//! [`Hasher`]:                 trait.Hasher.html
+//! [`Crc32`]:                  struct.Crc32.html
+//! [`Sha256`]:                 struct.Sha256.html
+use crate::core::{ConcatRead, FileConcatRead, SourceRead};
+use crate::crc32;
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// An incremental checksum or digest algorithm.
+pub trait Hasher: Default {
+    /// The finished digest.
+    type Output: Clone;
+
+    /// Feeds `data` into the hasher.
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the digest of all data fed into the hasher so far.
+    fn finish(&self) -> Self::Output;
+}
+
+/// The CRC-32 checksum as used by gzip, zip and PNG.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Crc32(u32);
+
+impl Hasher for Crc32 {
+    type Output = u32;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0 = crc32::update(self.0, data);
+    }
+
+    fn finish(&self) -> u32 {
+        self.0
+    }
+}
+
+/// The SHA-256 digest.
+#[cfg(feature = "sha256")]
+#[derive(Clone, Debug, Default)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha256")]
+impl Hasher for Sha256 {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+
+    fn finish(&self) -> [u8; 32] {
+        sha2::Digest::finalize(self.0.clone()).into()
+    }
+}
+
+/// A reader adapter which computes a digest of each source and of the whole stream.
+///
+/// `HashReader` implements [`ConcatRead`] and [`FileConcatRead`] when the wrapped reader does.
+/// Skipping a source with [`skip`] discards the digest of the skipped source, the bytes read from
+/// it stay part of the stream digest.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+/// [`skip`]:                   ../trait.ConcatRead.html#tymethod.skip
+pub struct HashReader<R, H: Hasher> {
+    inner: R,
+    source: H,
+    stream: H,
+    digests: Vec<H::Output>,
+}
+
+impl<R: SourceRead, H: Hasher> HashReader<R, H> {
+    /// Creates a new `HashReader`.
+    pub fn new(inner: R) -> Self {
+        HashReader {
+            inner,
+            source: H::default(),
+            stream: H::default(),
+            digests: Vec::new(),
+        }
+    }
+
+    /// Returns the digests of all completely read sources in read order.
+    pub fn source_digests(&self) -> &[H::Output] {
+        &self.digests
+    }
+
+    /// Returns the digest of the bytes read from the current source so far.
+    pub fn current_digest(&self) -> H::Output {
+        self.source.finish()
+    }
+
+    /// Returns the digest of all bytes read so far.
+    pub fn digest(&self) -> H::Output {
+        self.stream.finish()
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `HashReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: SourceRead, H: Hasher> Read for HashReader<R, H> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.inner.read_source(buf)?;
+            if n > 0 {
+                self.source.update(&buf[..n]);
+                self.stream.update(&buf[..n]);
+                return Ok(n);
+            }
+
+            if self.inner.current().is_none() {
+                return Ok(0);
+            }
+            let source = std::mem::take(&mut self.source);
+            self.digests.push(source.finish());
+            if !self.inner.skip() {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+impl<R: SourceRead, H: Hasher> ConcatRead for HashReader<R, H> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.source = H::default();
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.take_current()?;
+        self.source = H::default();
+        Some(item)
+    }
+}
+
+impl<R: SourceRead + FileConcatRead, H: Hasher> FileConcatRead for HashReader<R, H> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Crc32, HashReader};
+    use crate::crc32;
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, ConcatReader, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn computes_digest_per_source() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"", b"22", b"333"];
+        let mut reader = HashReader::<_, Crc32>::new(ConcatReader::new(bytes));
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        reader.read_to_end(&mut buf).unwrap();
+
+        let expected: Vec<u32> = vec![
+            crc32::update(0, b"1"),
+            0,
+            crc32::update(0, b"22"),
+            crc32::update(0, b"333"),
+        ];
+        assert_eq!(reader.source_digests(), &expected[..]);
+        assert_eq!(reader.digest(), crc32::update(0, b"122333"));
+    }
+
+    #[test]
+    fn skipped_sources_have_no_digest() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"aaaa").unwrap(),
+            tree.file("b", b"bb").unwrap(),
+        ];
+        let mut reader = HashReader::<_, Crc32>::new(FileConcatReader::new(&paths));
+
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.current_digest(), crc32::update(0, b"aa"));
+        reader.skip();
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(reader.source_digests(), &[crc32::update(0, b"bb")]);
+        assert_eq!(reader.digest(), crc32::update(0, b"aabb"));
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn computes_sha256() {
+        use super::{Hasher, Sha256};
+
+        let mut hasher = Sha256::default();
+        hasher.update(b"abc");
+        assert_eq!(
+            hasher.finish()[..4],
+            [0xba, 0x78, 0x16, 0xbf],
+            "sha256 of abc starts with ba7816bf"
+        );
+    }
+}
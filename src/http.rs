@@ -0,0 +1,451 @@
+//! Serving content which is stored split over several files.
+//!
+//! A [`SplitFile`] indexes the lengths of its chunk files once and creates readers for any byte
+//! range of the whole content, opening and seeking only the files the range touches. Together
+//! with [`parse_range`] and [`content_range`] it answers HTTP `Range` requests.
+//!
+//! ```no_run
+//! use concat_reader::http::{content_range, parse_range, SplitFile};
+//! use std::io::{self, Read};
+//!
+//! fn main() -> io::Result<()> {
+//!     let file = SplitFile::open(vec!["video.000", "video.001", "video.002"])?;
+//!
+//!     match parse_range("bytes=1000-1999", file.len()) {
+//!         Some(range) => {
+//!             println!("206 Partial Content");
+//!             println!("Content-Range: {}", content_range(&range, file.len()));
+//!             let mut body = Vec::new();
+//!             file.body(range)?.read_to_end(&mut body)?;
+//!             assert_eq!(body.len(), 1000);
+//!         }
+//!         None => println!("416 Range Not Satisfiable"),
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! All ranges in this module are half open, `start..end`. [`parse_range`] and
+//! [`content_range`] convert from and to the inclusive ranges of HTTP.
+//!
+//! [`SplitFile`]:              struct.SplitFile.html
+//! [`parse_range`]:            fn.parse_range.html
+//! [`content_range`]:          fn.content_range.html
+use crate::checkpoint::invalid;
+use crate::file::known_len;
+use crate::indexed::{Entry, LengthIndex};
+use crate::provenance;
+use crate::ConcatReader;
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Take};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Content stored in a sequence of chunk files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitFile {
+    paths: Vec<PathBuf>,
+    /// Modification time of every chunk when it was indexed, if it is checked on open.
+    modified: Vec<Option<SystemTime>>,
+    /// Offset of every chunk in the content, followed by the total length.
+    offsets: Vec<u64>,
+}
+
+impl SplitFile {
+    /// Creates a `SplitFile` from chunk files, reading their lengths from the file system.
+    ///
+    /// Fails with `InvalidInput` if a path is not a regular file.
+    pub fn open<I>(paths: I) -> io::Result<SplitFile>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let chunks = paths
+            .into_iter()
+            .map(|p| {
+                let path = p.as_ref();
+                Ok((path.to_path_buf(), chunk_len(path)?))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(SplitFile::from_lengths(chunks))
+    }
+
+    /// Creates a `SplitFile` from chunk files with known lengths.
+    ///
+    /// To keep the lengths between runs store a [`LengthIndex`] and use [`with_length_index`].
+    ///
+    /// [`LengthIndex`]:            ../indexed/struct.LengthIndex.html
+    /// [`with_length_index`]:      #method.with_length_index
+    pub fn from_lengths<I>(chunks: I) -> SplitFile
+    where
+        I: IntoIterator<Item = (PathBuf, u64)>,
+    {
+        SplitFile::from_entries(chunks.into_iter().map(|(path, len)| {
+            (
+                path,
+                Entry {
+                    len,
+                    modified: None,
+                },
+            )
+        }))
+    }
+
+    /// Creates a `SplitFile` from chunk files with the lengths of a stored [`LengthIndex`].
+    ///
+    /// Files which are not in the index are queried as with [`open`]. An indexed chunk file whose
+    /// length or modification time changed since it was indexed fails the read with
+    /// `InvalidData` once the body reaches it.
+    ///
+    /// ```no_run
+    /// use concat_reader::http::SplitFile;
+    /// use concat_reader::indexed::LengthIndex;
+    /// use std::fs;
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let index: LengthIndex = fs::read_to_string("video.index")?.parse()?;
+    ///     let file = SplitFile::with_length_index(vec!["video.000", "video.001"], &index)?;
+    ///     println!("Content-Length: {}", file.len());
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`LengthIndex`]:            ../indexed/struct.LengthIndex.html
+    /// [`open`]:                   #method.open
+    pub fn with_length_index<I>(paths: I, index: &LengthIndex) -> io::Result<SplitFile>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let chunks = paths
+            .into_iter()
+            .map(|p| {
+                let path = p.as_ref();
+                let entry = match index.entry(path) {
+                    Some(entry) => entry,
+                    None => Entry {
+                        len: chunk_len(path)?,
+                        modified: None,
+                    },
+                };
+                Ok((path.to_path_buf(), entry))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(SplitFile::from_entries(chunks))
+    }
+
+    /// Creates a `SplitFile` from chunk files with the entries they were indexed with.
+    pub(crate) fn from_entries<I>(chunks: I) -> SplitFile
+    where
+        I: IntoIterator<Item = (PathBuf, Entry)>,
+    {
+        let mut paths = Vec::new();
+        let mut modified = Vec::new();
+        let mut offsets = vec![0];
+        for (path, entry) in chunks {
+            paths.push(path);
+            modified.push(entry.modified);
+            offsets.push(offsets[offsets.len() - 1] + entry.len);
+        }
+        SplitFile {
+            paths,
+            modified,
+            offsets,
+        }
+    }
+
+    /// Returns the length of the whole content.
+    pub fn len(&self) -> u64 {
+        self.offsets[self.offsets.len() - 1]
+    }
+
+    /// Returns `true` if the content is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reader over the bytes `range` of the whole content.
+    ///
+    /// Fails with `InvalidInput` if the range ends before it starts or after the content. Files
+    /// are opened when the reader reaches them, so a chunk file which is missing or shorter than
+    /// indexed fails the read, not this call. A shorter file fails with `UnexpectedEof`, unless
+    /// its modification time was indexed too, then any change fails with `InvalidData`.
+    pub fn body(&self, range: Range<u64>) -> io::Result<ConcatReader<Vec<Segment>>> {
+        if range.start > range.end || range.end > self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "range {}..{} is outside of the content of {} bytes",
+                    range.start,
+                    range.end,
+                    self.len()
+                ),
+            ));
+        }
+
+        let mut segments = Vec::new();
+        for (i, path) in self.paths.iter().enumerate() {
+            let (chunk_start, chunk_end) = (self.offsets[i], self.offsets[i + 1]);
+            let start = cmp::max(range.start, chunk_start);
+            let end = cmp::min(range.end, chunk_end);
+            if start < end {
+                segments.push(Segment {
+                    path: path.clone(),
+                    offset: start - chunk_start,
+                    len: end - start,
+                    indexed: self.modified[i].map(|modified| Entry {
+                        len: chunk_end - chunk_start,
+                        modified: Some(modified),
+                    }),
+                    reader: None,
+                });
+            }
+        }
+        Ok(ConcatReader::new(segments))
+    }
+}
+
+/// Returns the length of the chunk file at `path`, failing if it is not a regular file.
+fn chunk_len(path: &Path) -> io::Result<u64> {
+    known_len(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a regular file", path.display()),
+        )
+    })
+}
+
+/// The part of a single chunk file a range covers.
+///
+/// The file is opened on the first read. Errors are annotated with the path of the chunk file
+/// and the offset in it, see [`provenance`].
+///
+/// [`provenance`]:             ../provenance/index.html
+#[derive(Debug)]
+pub struct Segment {
+    path: PathBuf,
+    offset: u64,
+    len: u64,
+    /// The entry the chunk file was indexed with, if it is checked on open.
+    indexed: Option<Entry>,
+    reader: Option<Take<File>>,
+}
+
+impl Segment {
+    /// Returns the path of the chunk file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the range of the chunk file this segment covers.
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.len
+    }
+
+    /// Returns the offset in the chunk file the next read starts at.
+    fn file_offset(&self) -> u64 {
+        match &self.reader {
+            Some(reader) => self.offset + self.len - reader.limit(),
+            None => self.offset,
+        }
+    }
+
+    fn open(&self) -> io::Result<Take<File>> {
+        let mut file = File::open(&self.path)?;
+        if let Some(entry) = self.indexed {
+            if Entry::of(&file.metadata()?) != entry {
+                return Err(invalid(format!(
+                    "{} changed since its length was indexed",
+                    self.path.display()
+                )));
+            }
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        Ok(file.take(self.len))
+    }
+
+    fn read_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reader.is_none() {
+            self.reader = Some(self.open()?);
+        }
+        let reader = self.reader.as_mut().expect("opened above");
+        let n = reader.read(buf)?;
+        if n == 0 && !buf.is_empty() && reader.limit() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{} ends {} bytes before its indexed length",
+                    self.path.display(),
+                    reader.limit()
+                ),
+            ));
+        }
+        Ok(n)
+    }
+}
+
+impl Read for Segment {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let offset = self.file_offset();
+        self.read_chunk(buf)
+            .map_err(|e| provenance::annotate(e, "split", Some(&self.path), offset))
+    }
+}
+
+/// Parses the value of an HTTP `Range` header for content of `len` bytes.
+///
+/// Supports a single range in the forms `bytes=first-last`, `bytes=first-` and `bytes=-suffix`.
+/// A `last` beyond the content is clamped to its end. Returns `None` if the header is malformed,
+/// has multiple ranges or can't be satisfied, which a server answers with
+/// `416 Range Not Satisfiable`.
+///
+/// ```
+/// use concat_reader::http::parse_range;
+///
+/// assert_eq!(parse_range("bytes=0-499", 1000), Some(0..500));
+/// assert_eq!(parse_range("bytes=900-", 1000), Some(900..1000));
+/// assert_eq!(parse_range("bytes=-100", 1000), Some(900..1000));
+/// assert_eq!(parse_range("bytes=1000-", 1000), None);
+/// ```
+pub fn parse_range(header: &str, len: u64) -> Option<Range<u64>> {
+    let spec = header.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_at(spec.find('-')?);
+    let (first, last) = (first.trim(), last[1..].trim());
+
+    let range = if first.is_empty() {
+        let suffix: u64 = last.parse().ok()?;
+        len.saturating_sub(suffix)..len
+    } else {
+        let first: u64 = first.parse().ok()?;
+        let end = if last.is_empty() {
+            len
+        } else {
+            let last: u64 = last.parse().ok()?;
+            if last < first {
+                return None;
+            }
+            cmp::min(last.saturating_add(1), len)
+        };
+        first..end
+    };
+
+    if range.start < range.end {
+        Some(range)
+    } else {
+        None
+    }
+}
+
+/// Formats the value of an HTTP `Content-Range` header for `range` of content of `len` bytes.
+///
+/// ```
+/// use concat_reader::http::content_range;
+///
+/// assert_eq!(content_range(&(0..500), 1000), "bytes 0-499/1000");
+/// ```
+pub fn content_range(range: &Range<u64>, len: u64) -> String {
+    if range.start == range.end {
+        format!("bytes */{}", len)
+    } else {
+        format!("bytes {}-{}/{}", range.start, range.end - 1, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_range, SplitFile};
+    use crate::indexed::{IndexedConcatReader, LengthIndex};
+    use crate::test_util::TempTree;
+    use std::io::{self, Read};
+
+    #[test]
+    fn reads_ranges_across_chunk_boundaries() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("0", b"0123").unwrap(),
+            tree.file("1", b"").unwrap(),
+            tree.file("2", b"456").unwrap(),
+            tree.file("3", b"789").unwrap(),
+        ];
+        let file = SplitFile::open(&paths).unwrap();
+        assert_eq!(file.len(), 10);
+
+        let read = |start, end| {
+            let mut buf = String::new();
+            file.body(start..end)
+                .unwrap()
+                .read_to_string(&mut buf)
+                .unwrap();
+            buf
+        };
+        for start in 0..=10 {
+            for end in start..=10 {
+                assert_eq!(
+                    read(start, end),
+                    &"0123456789"[start as usize..end as usize]
+                );
+            }
+        }
+        let err = file.body(5..11).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn fails_on_chunks_shorter_than_indexed() {
+        let tree = TempTree::new().unwrap();
+        let path = tree.file("0", b"012").unwrap();
+        let file = SplitFile::from_lengths(vec![(path, 5)]);
+
+        let mut buf = Vec::new();
+        let err = file.body(1..5).unwrap().read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(buf, b"12");
+    }
+
+    #[test]
+    fn reads_ranges_with_length_index() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("0", b"0123").unwrap(),
+            tree.file("1", b"456").unwrap(),
+        ];
+        let index = IndexedConcatReader::new(paths[..1].to_vec())
+            .length_index()
+            .unwrap();
+        let index: LengthIndex = index.to_string().parse().unwrap();
+
+        // the indexed chunk is not queried, the other one is
+        std::fs::write(&paths[0], b"01234").unwrap();
+        let file = SplitFile::with_length_index(&paths, &index).unwrap();
+        assert_eq!(file.len(), 7);
+
+        let mut buf = String::new();
+        file.body(4..7).unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "456");
+        let err = file
+            .body(2..5)
+            .unwrap()
+            .read_to_string(&mut buf)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("changed since"), "{}", err);
+    }
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-0", 10), Some(0..1));
+        assert_eq!(parse_range("bytes=5-100", 10), Some(5..10));
+        assert_eq!(parse_range("bytes=-20", 10), Some(0..10));
+        assert_eq!(parse_range("bytes=-0", 10), None);
+        assert_eq!(parse_range("bytes=3-2", 10), None);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+    }
+}
@@ -0,0 +1,586 @@
+//! Random access to the files of a source list.
+//!
+//! An [`IndexedConcatReader`] reads a list of files in order like a [`FileConcatReader`], but
+//! keeps all paths. It can jump to any file with [`seek_to_source`], including files it already
+//! read, and open a byte range of a single file with [`open_at`] without touching its position.
+//! [`read_range`] reads a byte range of the whole concatenation and only opens the files which
+//! overlap the range, e.g. to serve partial content from chunked storage, like the body of a
+//! [`SplitFile`].
+//!
+//! The lengths of the files which `read_range` queries can be exported as a [`LengthIndex`] and
+//! imported on the next run with [`with_length_index`], so a directory of thousands of files
+//! doesn't have to be queried again before the first range can be read.
+//!
+//! ```no_run
+//! use concat_reader::indexed::IndexedConcatReader;
+//! use std::io::{self, Read};
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut reader = IndexedConcatReader::new(vec!["part.000", "part.001", "part.002"]);
+//!
+//!     // read the last two parts
+//!     reader.seek_to_source(1)?;
+//!     let mut buffer = Vec::new();
+//!     reader.read_to_end(&mut buffer)?;
+//!
+//!     // and the header of the first one
+//!     let mut header = [0; 16];
+//!     reader.open_at(0, 0..16)?.read_exact(&mut header)?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`IndexedConcatReader`]:    struct.IndexedConcatReader.html
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+//! [`seek_to_source`]:         struct.IndexedConcatReader.html#method.seek_to_source
+//! [`open_at`]:                struct.IndexedConcatReader.html#method.open_at
+//! [`read_range`]:             struct.IndexedConcatReader.html#method.read_range
+//! [`SplitFile`]:              ../http/struct.SplitFile.html
+//! [`LengthIndex`]:            struct.LengthIndex.html
+//! [`with_length_index`]:      struct.IndexedConcatReader.html#method.with_length_index
+use crate::checkpoint::{escape, invalid, unescape};
+use crate::http::{Segment, SplitFile};
+use crate::layer::{RawRead, UnframedRead};
+use crate::provenance;
+use crate::{ConcatRead, ConcatReader, FileConcatRead, SourceRead};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Take};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &str = "concat-reader length index";
+const VERSION: u32 = 1;
+
+/// The length and modification time of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct Entry {
+    pub(crate) len: u64,
+    pub(crate) modified: Option<SystemTime>,
+}
+
+impl Entry {
+    pub(crate) fn of(meta: &Metadata) -> Entry {
+        Entry {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        }
+    }
+}
+
+/// The lengths of the files of an [`IndexedConcatReader`], by path.
+///
+/// Created by [`IndexedConcatReader::length_index`]. The index is stored as text with
+/// `to_string` and read back with `parse`, or with the `serde` feature in any serde format.
+/// Every entry records the modification time of the file, and a file which is opened for a
+/// range is checked against its entry, see [`with_length_index`]. The same index serves the
+/// range requests of a [`SplitFile`], see [`SplitFile::with_length_index`].
+///
+/// ```
+/// use concat_reader::indexed::LengthIndex;
+///
+/// let index: LengthIndex = "concat-reader length index\nversion: 1\n3 - part.000\n"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(index.len_of("part.000"), Some(3));
+/// assert_eq!(index.to_string().parse::<LengthIndex>().unwrap(), index);
+/// ```
+///
+/// [`IndexedConcatReader`]:    struct.IndexedConcatReader.html
+/// [`IndexedConcatReader::length_index`]: struct.IndexedConcatReader.html#method.length_index
+/// [`with_length_index`]:      struct.IndexedConcatReader.html#method.with_length_index
+/// [`SplitFile`]:              ../http/struct.SplitFile.html
+/// [`SplitFile::with_length_index`]: ../http/struct.SplitFile.html#method.with_length_index
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LengthIndex {
+    entries: HashMap<PathBuf, Entry>,
+}
+
+impl LengthIndex {
+    /// Returns the number of files in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the index has no files.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the indexed length of the file at `path`.
+    pub fn len_of<P: AsRef<Path>>(&self, path: P) -> Option<u64> {
+        self.entries.get(path.as_ref()).map(|entry| entry.len)
+    }
+
+    /// Returns the entry of the file at `path`.
+    pub(crate) fn entry(&self, path: &Path) -> Option<Entry> {
+        self.entries.get(path).copied()
+    }
+}
+
+impl fmt::Display for LengthIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "version: {}", VERSION)?;
+        let mut paths: Vec<&PathBuf> = self.entries.keys().collect();
+        paths.sort();
+        for path in paths {
+            let entry = self.entries[path];
+            write!(f, "{} ", entry.len)?;
+            match entry
+                .modified
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            {
+                Some(since) => write!(f, "{}.{:09} ", since.as_secs(), since.subsec_nanos())?,
+                None => write!(f, "- ")?,
+            }
+            writeln!(f, "{}", escape(&path.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LengthIndex {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<LengthIndex> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim_end) != Some(MAGIC) {
+            return Err(invalid("not a concat-reader length index"));
+        }
+        match lines.next().map(str::trim_end) {
+            Some("version: 1") => {}
+            version => return Err(invalid(format!("unsupported index {:?}", version))),
+        }
+
+        let mut entries = HashMap::new();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let malformed = || invalid(format!("malformed index line {:?}", line));
+            let mut parts = line.splitn(3, ' ');
+            let (len, modified, path) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(len), Some(modified), Some(path)) => (len, modified, path),
+                _ => return Err(malformed()),
+            };
+            let modified = match modified {
+                "-" => None,
+                time => {
+                    let (secs, nanos) = time.split_once('.').ok_or_else(malformed)?;
+                    let secs = secs.parse().map_err(|_| malformed())?;
+                    let nanos = nanos.parse().map_err(|_| malformed())?;
+                    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+                }
+            };
+            let entry = Entry {
+                len: len.parse().map_err(|_| malformed())?,
+                modified,
+            };
+            entries.insert(PathBuf::from(unescape(path)), entry);
+        }
+        Ok(LengthIndex { entries })
+    }
+}
+
+/// A reader over a list of files which can move to any file by its index.
+#[derive(Debug)]
+pub struct IndexedConcatReader<P> {
+    paths: Vec<P>,
+    idx: usize,
+    file: Option<File>,
+    pos: u64,
+    /// Lengths of the files by index, filled by `read_range` or from a `LengthIndex`.
+    entries: Vec<Option<Entry>>,
+}
+
+impl<P: AsRef<Path>> IndexedConcatReader<P> {
+    /// Creates a new `IndexedConcatReader` starting with the first file.
+    pub fn new(paths: Vec<P>) -> Self {
+        IndexedConcatReader {
+            paths,
+            idx: 0,
+            file: None,
+            pos: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Uses the lengths of `index` instead of querying the files, see [`LengthIndex`].
+    ///
+    /// Files which are not in the index are queried as usual. The files [`read_range`] opens are
+    /// compared with their entry, and reading fails with `InvalidData` if the length or the
+    /// modification time of a file changed since it was indexed. The files before a range are
+    /// trusted without a check.
+    ///
+    /// ```no_run
+    /// use concat_reader::indexed::{IndexedConcatReader, LengthIndex};
+    /// use std::fs;
+    /// use std::io::{self, Read};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let paths: Vec<String> = (0..10_000).map(|i| format!("volume/{:05}", i)).collect();
+    ///     let mut volume = match fs::read_to_string("volume.index") {
+    ///         Ok(index) => IndexedConcatReader::new(paths).with_length_index(&index.parse()?),
+    ///         Err(_) => IndexedConcatReader::new(paths),
+    ///     };
+    ///     let mut block = vec![0; 4096];
+    ///     volume.read_range(1 << 30..(1 << 30) + 4096)?.read_exact(&mut block)?;
+    ///     fs::write("volume.index", volume.length_index()?.to_string())
+    /// }
+    /// ```
+    ///
+    /// [`LengthIndex`]:            struct.LengthIndex.html
+    /// [`read_range`]:             #method.read_range
+    pub fn with_length_index(mut self, index: &LengthIndex) -> Self {
+        self.entries = self
+            .paths
+            .iter()
+            .map(|path| index.entry(path.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Returns the lengths of all files as a [`LengthIndex`], querying the files which weren't
+    /// queried before.
+    ///
+    /// [`LengthIndex`]:            struct.LengthIndex.html
+    pub fn length_index(&mut self) -> io::Result<LengthIndex> {
+        let mut entries = HashMap::new();
+        for idx in 0..self.paths.len() {
+            let entry = self.entry_of(idx)?;
+            entries.insert(self.paths[idx].as_ref().to_path_buf(), entry);
+        }
+        Ok(LengthIndex { entries })
+    }
+
+    /// Returns the number of files.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns `true` if there are no files.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Returns the paths of all files.
+    pub fn paths(&self) -> &[P] {
+        &self.paths
+    }
+
+    /// Returns the index of the current file, which equals [`len`] after the last file.
+    ///
+    /// [`len`]:                    #method.len
+    pub fn source_index(&self) -> usize {
+        self.idx
+    }
+
+    /// Returns the number of bytes read from the current file.
+    pub fn current_position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Continues reading at the start of the file at `idx`.
+    ///
+    /// The file is opened on the next read. Fails with `InvalidInput` if `idx` is greater than
+    /// [`len`]. Seeking to `len` moves the reader to its `EOF`.
+    ///
+    /// [`len`]:                    #method.len
+    pub fn seek_to_source(&mut self, idx: usize) -> io::Result<()> {
+        if idx > self.paths.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("source {} is out of {} sources", idx, self.paths.len()),
+            ));
+        }
+        self.idx = idx;
+        self.file = None;
+        self.pos = 0;
+        Ok(())
+    }
+
+    /// Opens the bytes `range` of the file at `idx`, independent of the position of the reader.
+    ///
+    /// The returned reader ends early if the file is shorter than the range. Fails with
+    /// `InvalidInput` if there is no file at `idx` or the range ends before it starts.
+    pub fn open_at(&self, idx: usize, range: Range<u64>) -> io::Result<Take<File>> {
+        let path = self.paths.get(idx).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("source {} is out of {} sources", idx, self.paths.len()),
+            )
+        })?;
+        if range.start > range.end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("range {}..{} ends before it starts", range.start, range.end),
+            ));
+        }
+        let path = path.as_ref();
+        let annotate = |e| provenance::annotate(e, "indexed", Some(path), range.start);
+        let mut file = File::open(path).map_err(annotate)?;
+        file.seek(SeekFrom::Start(range.start)).map_err(annotate)?;
+        Ok(file.take(range.end - range.start))
+    }
+
+    /// Returns a reader over the bytes `range` of the concatenation of all files, independent of
+    /// the position of the reader.
+    ///
+    /// Only the files overlapping the range are opened, and each of them only when the returned
+    /// reader reaches it. The files before the range are only queried for their length, which is
+    /// kept for later calls. The range is cut at the end of the last file. Fails with
+    /// `InvalidInput` if the range ends before it starts.
+    ///
+    /// The reader reads the files like the body of a [`SplitFile`] with the queried lengths.
+    ///
+    /// ```no_run
+    /// use concat_reader::indexed::IndexedConcatReader;
+    /// use std::io::{self, Read};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut chunks = IndexedConcatReader::new(vec!["video.000", "video.001", "video.002"]);
+    ///     let mut part = Vec::new();
+    ///     chunks.read_range(10_000_000..12_000_000)?.read_to_end(&mut part)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`SplitFile`]:              ../http/struct.SplitFile.html
+    pub fn read_range(&mut self, range: Range<u64>) -> io::Result<ConcatReader<Vec<Segment>>> {
+        if range.start > range.end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("range {}..{} ends before it starts", range.start, range.end),
+            ));
+        }
+        // the files before the range are left out and the range moved by their length
+        let mut skipped = 0;
+        let mut end = 0;
+        let mut chunks = Vec::new();
+        for idx in 0..self.paths.len() {
+            if end >= range.end {
+                break;
+            }
+            let entry = self.entry_of(idx)?;
+            end += entry.len;
+            if end <= range.start {
+                skipped = end;
+            } else {
+                chunks.push((self.paths[idx].as_ref().to_path_buf(), entry));
+            }
+        }
+        let stop = cmp::min(range.end, end);
+        let start = cmp::min(range.start, stop);
+        SplitFile::from_entries(chunks).body(start - skipped..stop - skipped)
+    }
+
+    /// Returns the length of the file at `idx`, querying it once.
+    fn entry_of(&mut self, idx: usize) -> io::Result<Entry> {
+        if self.entries.len() <= idx {
+            self.entries.resize(idx + 1, None);
+        }
+        if let Some(entry) = self.entries[idx] {
+            return Ok(entry);
+        }
+        let path = self.paths[idx].as_ref();
+        let meta =
+            fs::metadata(path).map_err(|e| provenance::annotate(e, "indexed", Some(path), 0))?;
+        let entry = Entry::of(&meta);
+        self.entries[idx] = Some(entry);
+        Ok(entry)
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let path = match self.paths.get(self.idx) {
+            Some(path) => path.as_ref(),
+            None => return Ok(0),
+        };
+        if self.file.is_none() {
+            self.file = Some(File::open(path)?);
+        }
+        let n = self.file.as_mut().expect("opened above").read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<P: AsRef<Path>> ConcatRead for IndexedConcatReader<P> {
+    type Item = File;
+
+    fn skip(&mut self) -> bool {
+        let idx = self.idx.saturating_add(1).min(self.paths.len());
+        self.seek_to_source(idx).expect("index is in bounds");
+        self.idx < self.paths.len()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.file.as_ref()
+    }
+}
+
+impl<P: AsRef<Path>> SourceRead for IndexedConcatReader<P> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        self.read_inner(buf)
+            .map_err(|e| provenance::annotate(e, "indexed", self.file_path(), pos))
+    }
+}
+
+impl<P: AsRef<Path>> FileConcatRead for IndexedConcatReader<P> {
+    fn file_path(&self) -> Option<&Path> {
+        self.paths.get(self.idx).map(AsRef::as_ref)
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.paths.get(self.idx + 1).map(AsRef::as_ref)
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        match (&self.file, self.file_path()) {
+            (Some(file), _) => file.metadata(),
+            (None, Some(path)) => fs::metadata(path),
+            (None, None) => Err(io::Error::new(io::ErrorKind::NotFound, "no current file")),
+        }
+    }
+}
+
+impl<P: AsRef<Path>> UnframedRead for IndexedConcatReader<P> {}
+
+impl<P: AsRef<Path>> RawRead for IndexedConcatReader<P> {}
+
+impl<P: AsRef<Path>> Read for IndexedConcatReader<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || buf.is_empty() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexedConcatReader, LengthIndex};
+    use crate::http::Segment;
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, FileConcatRead};
+    use std::io::{self, Read};
+    use std::ops::Range;
+
+    #[test]
+    fn seeks_to_sources_and_opens_ranges() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("0", b"012").unwrap(),
+            tree.file("1", b"").unwrap(),
+            tree.file("2", b"345").unwrap(),
+        ];
+        let mut reader = IndexedConcatReader::new(paths.clone());
+
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "012345");
+        assert_eq!(reader.source_index(), 3);
+        assert_eq!(reader.file_path(), None);
+
+        reader.seek_to_source(2).unwrap();
+        assert_eq!(reader.file_path(), Some(paths[2].as_path()));
+        reader.seek_to_source(0).unwrap();
+        assert_eq!(reader.peek_next_path(), Some(paths[1].as_path()));
+        let mut first = [0; 2];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"01");
+        assert!(reader.skip());
+        assert_eq!(reader.source_index(), 1);
+
+        let mut range = String::new();
+        reader
+            .open_at(2, 1..10)
+            .unwrap()
+            .read_to_string(&mut range)
+            .unwrap();
+        assert_eq!(range, "45");
+        assert_eq!(reader.source_index(), 1);
+
+        let err = reader.seek_to_source(4).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(reader.open_at(3, 0..1).is_err());
+    }
+
+    #[test]
+    fn reads_ranges_across_files() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("0", b"012").unwrap(),
+            tree.file("1", b"").unwrap(),
+            tree.file("2", b"345").unwrap(),
+            tree.file("3", b"67").unwrap(),
+        ];
+        let mut reader = IndexedConcatReader::new(paths.clone());
+        let read = |reader: &mut IndexedConcatReader<_>, range| {
+            let mut buf = String::new();
+            reader
+                .read_range(range)
+                .unwrap()
+                .read_to_string(&mut buf)
+                .unwrap();
+            buf
+        };
+        assert_eq!(read(&mut reader, 2..7), "23456");
+        assert_eq!(read(&mut reader, 3..6), "345");
+        assert_eq!(read(&mut reader, 6..20), "67");
+        assert_eq!(read(&mut reader, 4..4), "");
+
+        // the files before the range are not opened
+        std::fs::remove_file(&paths[0]).unwrap();
+        let range = reader.read_range(4..5).unwrap();
+        assert_eq!(range.current().map(Segment::path), Some(paths[2].as_path()));
+        assert!(reader.read_range(Range { start: 5, end: 4 }).is_err());
+    }
+
+    #[test]
+    fn reuses_length_index() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("0", b"012").unwrap(),
+            tree.file("dir\nwith newline", b"345").unwrap(),
+            tree.file("2", b"67").unwrap(),
+        ];
+        let index = IndexedConcatReader::new(paths.clone())
+            .length_index()
+            .unwrap();
+        let index: LengthIndex = index.to_string().parse().unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.len_of(&paths[1]), Some(3));
+
+        // the indexed files before the range are neither opened nor queried
+        std::fs::remove_file(&paths[0]).unwrap();
+        let mut reader = IndexedConcatReader::new(paths.clone()).with_length_index(&index);
+        let mut buf = String::new();
+        reader
+            .read_range(4..8)
+            .unwrap()
+            .read_to_string(&mut buf)
+            .unwrap();
+        assert_eq!(buf, "4567");
+
+        std::fs::write(&paths[2], b"678").unwrap();
+        let err = reader
+            .read_range(6..7)
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("changed since"), "{}", err);
+    }
+}
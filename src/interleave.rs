@@ -0,0 +1,251 @@
+//! Reading several sources in turns.
+//!
+//! An [`InterleaveReader`] reads chunks of a fixed length from each of its sources in
+//! round-robin order, instead of reading one source to its end before the next one like a
+//! [`ConcatReader`]. A source which reaches `EOF` drops out, the others keep their turns. This
+//! multiplexes several live pipes fairly, so a busy pipe can't starve the others.
+//!
+//! ```
+//! use concat_reader::interleave::InterleaveReader;
+//! use std::io::prelude::*;
+//!
+//! let sources: Vec<&[u8]> = vec![b"aaaa", b"b", b"cccccc"];
+//! let mut reader = InterleaveReader::new(sources, 2);
+//!
+//! let mut buffer = String::new();
+//! reader.read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "aabccaacccc");
+//! ```
+//!
+//! [`InterleaveReader`]:       struct.InterleaveReader.html
+//! [`ConcatReader`]:           ../read/struct.ConcatReader.html
+use crate::provenance;
+use crate::ConcatRead;
+use std::cmp;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read};
+
+/// A reader which reads up to `chunk_len` bytes from each source in turn.
+///
+/// A turn ends after `chunk_len` bytes, or earlier if the source returns `WouldBlock`, so a
+/// non-blocking source without data doesn't hold up the others. `WouldBlock` is only returned
+/// when every source returned it in a row. After any other error the next `read` retries the
+/// same source.
+///
+/// [`current`] returns the source the next bytes are read from and [`skip`] drops it.
+///
+/// [`current`]:                ../trait.ConcatRead.html#tymethod.current
+/// [`skip`]:                   ../trait.ConcatRead.html#tymethod.skip
+pub struct InterleaveReader<R> {
+    /// Sources in the order of their next turn, the current one first.
+    sources: VecDeque<Slot<R>>,
+    chunk_len: usize,
+    /// Bytes left in the turn of the current source.
+    left: usize,
+}
+
+struct Slot<R> {
+    index: usize,
+    pos: u64,
+    reader: R,
+}
+
+impl<R: Read> InterleaveReader<R> {
+    /// Creates a new `InterleaveReader` reading `chunk_len` bytes from each of `sources` in turn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len` is `0`.
+    pub fn new<I: IntoIterator<Item = R>>(sources: I, chunk_len: usize) -> Self {
+        assert!(chunk_len > 0, "chunk length must be greater than 0");
+        let sources = sources
+            .into_iter()
+            .enumerate()
+            .map(|(index, reader)| Slot {
+                index,
+                pos: 0,
+                reader,
+            })
+            .collect();
+        InterleaveReader {
+            sources,
+            chunk_len,
+            left: chunk_len,
+        }
+    }
+
+    /// Returns the number of sources which haven't reached `EOF`.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns `true` if all sources reached `EOF`.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Returns the index of the current source in the list it was created from.
+    pub fn current_index(&self) -> Option<usize> {
+        self.sources.front().map(|slot| slot.index)
+    }
+
+    /// Returns the number of bytes read from the current source.
+    pub fn current_position(&self) -> u64 {
+        self.sources.front().map_or(0, |slot| slot.pos)
+    }
+
+    /// Ends the turn of the current source.
+    fn rotate(&mut self) {
+        self.sources.rotate_left(cmp::min(1, self.sources.len()));
+        self.left = self.chunk_len;
+    }
+}
+
+impl<R: Read> Read for InterleaveReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut blocked = 0;
+        loop {
+            let slot = match self.sources.front_mut() {
+                Some(slot) => slot,
+                None => return Ok(0),
+            };
+            let len = cmp::min(buf.len(), self.left);
+            match slot.reader.read(&mut buf[..len]) {
+                Ok(0) => {
+                    self.skip();
+                }
+                Ok(n) => {
+                    slot.pos += n as u64;
+                    self.left -= n;
+                    if self.left == 0 {
+                        self.rotate();
+                    }
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    blocked += 1;
+                    if blocked >= self.sources.len() {
+                        return Err(e);
+                    }
+                    self.rotate();
+                }
+                Err(e) => {
+                    return Err(provenance::annotate(e, "interleave", None, slot.pos));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> ConcatRead for InterleaveReader<R> {
+    type Item = R;
+
+    fn skip(&mut self) -> bool {
+        self.sources.pop_front();
+        self.left = self.chunk_len;
+        !self.sources.is_empty()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.sources.front().map(|slot| &slot.reader)
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.sources.front_mut().map(|slot| &mut slot.reader)
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.sources.get(1).map(|slot| &slot.reader)
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let slot = self.sources.pop_front()?;
+        self.left = self.chunk_len;
+        Some(slot.reader)
+    }
+}
+
+impl<R> fmt::Debug for InterleaveReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order: Vec<_> = self.sources.iter().map(|slot| slot.index).collect();
+        f.debug_struct("InterleaveReader")
+            .field("order", &order)
+            .field("chunk_len", &self.chunk_len)
+            .field("left", &self.left)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InterleaveReader;
+    use crate::ConcatRead;
+    use std::io::{self, Read};
+
+    /// Returns `WouldBlock` on every other read.
+    struct Stutter<'a> {
+        data: &'a [u8],
+        blocked: bool,
+    }
+
+    impl Read for Stutter<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.blocked = !self.blocked;
+            if self.blocked {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn takes_turns_and_tracks_sources() {
+        let sources: Vec<&[u8]> = vec![b"aaa", b"", b"bbbbb"];
+        let mut reader = InterleaveReader::new(sources, 2);
+
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"aa");
+        assert_eq!(reader.current_index(), Some(1));
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"bb");
+        assert_eq!(reader.len(), 2);
+        assert_eq!(reader.current_index(), Some(0));
+        assert_eq!(reader.peek_next(), Some(&&b"bbb"[..]));
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "abbb");
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn moves_on_when_a_source_would_block() {
+        let sources = vec![
+            Stutter {
+                data: b"aaaa",
+                blocked: false,
+            },
+            Stutter {
+                data: b"bb",
+                blocked: false,
+            },
+        ];
+        let mut reader = InterleaveReader::new(sources, 4);
+
+        let mut buf = [0; 4];
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"bb");
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"aaaa");
+    }
+}
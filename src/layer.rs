@@ -0,0 +1,135 @@
+//! The layering model of readers and decorators.
+//!
+//! Decorators can be stacked in any order the types allow, but not every order makes sense.
+//! Normalizing line endings of UTF-16 text before transcoding it, or transforming bytes below a
+//! [`PeekReader`] which already decoded headers from them, produces corrupt output. Instead of
+//! checking stacks at runtime, every reader declares what kind of stream it produces with the
+//! marker traits of this module and decorators require the kind of stream they work on:
+//!
+//! | Layer       | Produces         | Requires         | Examples                                        |
+//! |-------------|------------------|------------------|-------------------------------------------------|
+//! | source      | [`RawRead`]      |                  | [`ConcatReader`], [`FileConcatReader`]          |
+//! | observer    | same as inner    | any              | [`TeeReader`], [`ProgressReader`]               |
+//! | transcoder  | [`UnframedRead`] | [`RawRead`]      | [`Transcode`]                                   |
+//! | transform   | [`UnframedRead`] | [`UnframedRead`] | [`Normalize`], [`SkipPreamble`], [`MapSources`] |
+//! | framer      | `SourceRead`     | any              | [`PeekReader`]                                  |
+//! | terminal    | `Read`           | [`UnframedRead`] | [`DropHeaders`]                                 |
+//!
+//! A nonsensical stack fails to compile with an unsatisfied trait bound, which names the layer
+//! in the wrong place:
+//!
+//! ```compile_fail
+//! use concat_reader::peek::PeekReader;
+//! use concat_reader::text::Normalize;
+//! use concat_reader::concat;
+//!
+//! let sources: Vec<&[u8]> = vec![b"a\r\n"];
+//! // error: the trait `UnframedRead` is not implemented for `PeekReader<_>`
+//! let reader = Normalize::new(PeekReader::new(concat(sources)));
+//! ```
+//!
+//! # Static and boxed stacks
+//!
+//! Every decorator is generic over the reader it wraps, so a stack is a single statically
+//! dispatched type without any boxing, which can be named where needed:
+//!
+//! ```
+//! use concat_reader::peek::PeekReader;
+//! use concat_reader::preamble::SkipPreamble;
+//! use concat_reader::text::Normalize;
+//! use concat_reader::ConcatReader;
+//!
+//! type Stack<'a> = PeekReader<SkipPreamble<Normalize<ConcatReader<Vec<&'a [u8]>>>>>;
+//!
+//! fn stack(sources: Vec<&[u8]>) -> Stack {
+//!     let normalized = Normalize::new(ConcatReader::new(sources));
+//!     PeekReader::new(SkipPreamble::new(normalized).skip_lines_per_source(1))
+//! }
+//! ```
+//!
+//! Where the long types get in the way, [`build_boxed`] erases them into a [`BoxedRead`]. Boxed
+//! readers are plain `SourceRead`s, so only framers and observers can be stacked on them.
+//!
+//! ```
+//! use concat_reader::layer::BoxedRead;
+//! use concat_reader::{ConcatReader, SourceRead};
+//! use std::io::prelude::*;
+//!
+//! fn stack<'a>(sources: Vec<&'a [u8]>, strip: bool) -> BoxedRead<'a, &'a [u8]> {
+//!     let reader = ConcatReader::new(sources);
+//!     if strip {
+//!         reader.skip_lines_per_source(1).build_boxed()
+//!     } else {
+//!         reader.build_boxed()
+//!     }
+//! }
+//!
+//! let mut buffer = String::new();
+//! stack(vec![b"# a\n1\n", b"# b\n2\n"], true).read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "1\n2\n");
+//! ```
+//!
+//! File readers can be boxed with [`build_boxed_file`] into a [`BoxedFileRead`] instead, which
+//! keeps the file information:
+//!
+//! ```no_run
+//! use concat_reader::layer::BoxedFileRead;
+//! use concat_reader::{concat_path, FileConcatRead};
+//!
+//! struct Input {
+//!     reader: BoxedFileRead<'static>,
+//! }
+//!
+//! let input = Input {
+//!     reader: concat_path(vec!["a.log", "b.log"]).build_boxed_file(),
+//! };
+//! println!("{:?}", input.reader.file_path());
+//! ```
+//!
+//! [`PeekReader`]:             ../peek/struct.PeekReader.html
+//! [`build_boxed_file`]:       ../trait.FileConcatRead.html#method.build_boxed_file
+//! [`BoxedFileRead`]:          type.BoxedFileRead.html
+//! [`build_boxed`]:            ../trait.SourceRead.html#method.build_boxed
+//! [`BoxedRead`]:              type.BoxedRead.html
+//! [`RawRead`]:                trait.RawRead.html
+//! [`UnframedRead`]:           trait.UnframedRead.html
+//! [`ConcatReader`]:           ../read/struct.ConcatReader.html
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+//! [`TeeReader`]:              ../tee/struct.TeeReader.html
+//! [`ProgressReader`]:         ../progress/struct.ProgressReader.html
+//! [`Transcode`]:              ../encoding/struct.Transcode.html
+//! [`Normalize`]:              ../text/struct.Normalize.html
+//! [`SkipPreamble`]:           ../preamble/struct.SkipPreamble.html
+//! [`MapSources`]:             ../map/struct.MapSources.html
+//! [`DropHeaders`]:            ../text/struct.DropHeaders.html
+use crate::{FileConcatRead, SourceRead};
+use std::fs::File;
+
+/// A stream whose source boundaries haven't been consumed by a framing layer.
+///
+/// Byte transforms like [`Normalize`] require it, so they can't change bytes a framing layer
+/// already interpreted.
+///
+/// [`Normalize`]:              ../text/struct.Normalize.html
+pub trait UnframedRead: SourceRead {}
+
+/// A decorator stack with erased types, created by [`build_boxed`].
+///
+/// [`build_boxed`]:            ../trait.SourceRead.html#method.build_boxed
+pub type BoxedRead<'a, T> = Box<dyn SourceRead<Item = T> + 'a>;
+
+/// A file reader with erased types, created by [`build_boxed_file`].
+///
+/// Unlike a [`BoxedRead`] it keeps the file information of [`FileConcatRead`], so it can stand in
+/// for the `impl FileConcatRead` returned by [`concat_path`] in fields and signatures.
+///
+/// [`build_boxed_file`]:       ../trait.FileConcatRead.html#method.build_boxed_file
+/// [`BoxedRead`]:              type.BoxedRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+/// [`concat_path`]:            ../fn.concat_path.html
+pub type BoxedFileRead<'a, T = File> = Box<dyn FileConcatRead<Item = T> + 'a>;
+
+/// A stream of the bytes exactly as stored in the sources.
+///
+/// Layers which need the original encoding of the sources require it.
+pub trait RawRead: UnframedRead {}
@@ -22,17 +22,116 @@
 //!     }
 //! }
 //! ```
+//!
+//! # Features
+//!
+//! | Feature        | Enables                                                    |
+//! |----------------|------------------------------------------------------------|
+//! | `embedded-io`  | [`embedded`], concatenating `embedded-io` readers          |
+//! | `encoding`     | [`encoding`], transcoding sources to UTF-8                 |
+//! | `futures`      | [`async_read`] and [`body`], asynchronous concat readers   |
+//! | `hash`         | [`hash`], checksums per source                             |
+//! | `metrics`      | `metrics` counters of a [`FileConcatReader`]               |
+//! | `net`          | [`net`], reading from TCP and unix domain sockets          |
+//! | `object_store` | [`objects`], reading objects of S3 and other object stores |
+//! | `sha256`       | SHA-256 checksums in addition to `hash`                    |
+//! | `tar`          | [`tar`], reading the entries of tar archives               |
+//! | `test-util`    | [`test_util`], temporary file trees for tests              |
+//! | `tracing`      | `tracing` events of a [`FileConcatReader`]                 |
+//! | `bench-util`   | [`bench`], corpora for benchmarks                          |
+//! | `wasm`         | [`wasm`], reading JavaScript byte arrays in the browser    |
+//! | `zip`          | [`zip`], reading the entries of zip archives               |
+//!
+//! Every feature builds on the feature independent types in [`core`], so any subset of features
+//! can be enabled together.
+//!
+//! [`embedded`]:     embedded/index.html
+//! [`encoding`]:     encoding/index.html
+//! [`async_read`]:   async_read/index.html
+//! [`body`]:         body/index.html
+//! [`hash`]:         hash/index.html
+//! [`net`]:          net/index.html
+//! [`objects`]:      objects/index.html
+//! [`tar`]:          tar/index.html
+//! [`test_util`]:    test_util/index.html
+//! [`bench`]:        bench/index.html
+//! [`wasm`]:         wasm/index.html
+//! [`zip`]:          zip/index.html
+//! [`core`]:         core/index.html
 //! [`READ`]:         https://doc.rust-lang.org/std/io/trait.Read.html
 //! [`IntoIterator`]: https://doc.rust-lang.org/std/iter/trait.IntoIterator.html
 
-use std::io::Read;
+use self::layer::{BoxedFileRead, BoxedRead, RawRead};
+use self::records::Chunks;
+use self::turns::Turns;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
 use std::path::Path;
 
+pub mod adaptive;
+#[cfg(feature = "futures")]
+pub mod async_read;
+#[cfg(feature = "bench-util")]
+pub mod bench;
+#[cfg(feature = "futures")]
+pub mod body;
+pub mod buf;
+pub mod cancel;
+pub mod checkpoint;
+pub mod copy;
+pub mod core;
+#[cfg(any(test, feature = "test-util", feature = "hash", feature = "zip"))]
+mod crc32;
+#[cfg(feature = "embedded-io")]
+pub mod embedded;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+pub mod erased;
 pub mod file;
+#[cfg(feature = "hash")]
+pub mod hash;
+pub mod http;
+pub mod indexed;
+pub mod interleave;
+pub mod layer;
+pub mod map;
+pub mod merge;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "object_store")]
+pub mod objects;
+pub mod peek;
+pub mod pool;
+pub mod preamble;
+pub mod progress;
+pub mod provenance;
+pub mod queue;
 pub mod read;
+pub mod records;
+pub mod restart;
+pub mod sort;
+pub mod source;
+pub mod split;
+pub mod sync;
+#[cfg(feature = "tar")]
+pub mod tar;
+pub mod tee;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod text;
+pub mod throttle;
+pub mod timeout;
+pub mod turns;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "zip")]
+pub mod zip;
 
+pub use self::copy::{concat_path_to, concat_path_to_file};
 pub use self::file::FileConcatReader;
+pub use self::queue::SourceQueue;
 pub use self::read::ConcatReader;
+pub use self::restart::Restart;
 
 /// Concats multiple readers into a single reader.
 ///
@@ -47,7 +146,7 @@ pub use self::read::ConcatReader;
 /// let mut f = concat(vec![value1, value2]);
 /// f.read_to_string(&mut buffer).unwrap();
 /// ```
-pub fn concat<I: IntoIterator>(items: I) -> impl ConcatRead
+pub fn concat<I: IntoIterator>(items: I) -> impl RawRead<Item = I::Item>
 where
     I::Item: Read,
 {
@@ -77,7 +176,7 @@ where
 ///     }
 /// }
 /// ```
-pub fn concat_path<I: IntoIterator>(items: I) -> impl FileConcatRead
+pub fn concat_path<I: IntoIterator>(items: I) -> impl FileConcatRead<Item = File> + RawRead
 where
     I::Item: AsRef<Path>,
 {
@@ -120,6 +219,176 @@ pub trait ConcatRead: Read {
 
     /// Returns the current `Read` item in the internal iterator being read from.
     fn current(&self) -> Option<&Self::Item>;
+
+    /// Returns the current item mutably, e.g. to seek in the current file.
+    ///
+    /// Changing what the item reads next, e.g. by seeking, is not tracked by the reader: positions
+    /// and offsets it reports are then off by the difference. Readers which can't lend their
+    /// items mutably return `None`, like the default implementation.
+    ///
+    /// ```rust
+    /// use concat_reader::{concat, ConcatRead};
+    /// use std::io::{Cursor, Read, Seek, SeekFrom};
+    ///
+    /// let mut f = concat(vec![Cursor::new("some"), Cursor::new("another")]);
+    /// let mut buffer = [0; 2];
+    /// f.read_exact(&mut buffer).unwrap();
+    ///
+    /// f.current_mut().unwrap().seek(SeekFrom::Start(0)).unwrap();
+    /// let mut s = String::new();
+    /// f.read_to_string(&mut s).unwrap();
+    /// assert_eq!(s, "someanother");
+    /// ```
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        None
+    }
+
+    /// Returns the item after the current one without advancing to it.
+    ///
+    /// The item is taken from the internal iterator and kept until the reader reaches it, so a
+    /// reader backed by a channel blocks until the next item is received. Readers which can't
+    /// look ahead without reading the current item return `None`, like the default
+    /// implementation.
+    ///
+    /// ```rust
+    /// use concat_reader::{concat, ConcatRead};
+    /// use std::io::Read;
+    ///
+    /// let mut f = concat(vec!["some".as_bytes(), "another".as_bytes()]);
+    /// assert_eq!(f.peek_next(), Some(&"another".as_bytes()));
+    ///
+    /// let mut buffer = [0; 4];
+    /// f.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(f.current(), Some(&"".as_bytes()));
+    /// ```
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        None
+    }
+
+    /// Takes the current item out of the reader and continues with the next one.
+    ///
+    /// Returns `None` without advancing if there is no current item. Readers which can't give up
+    /// their items, because they only lend them or wrap them into something else, return `None`,
+    /// like the default implementation.
+    ///
+    /// ```rust
+    /// use concat_reader::{concat, ConcatRead};
+    /// use std::io::Read;
+    ///
+    /// let mut f = concat(vec!["some".as_bytes(), "another".as_bytes()]);
+    /// let mut buffer = [0; 2];
+    /// f.read_exact(&mut buffer).unwrap();
+    ///
+    /// assert_eq!(f.take_current(), Some("me".as_bytes()));
+    /// assert_eq!(f.current(), Some(&"another".as_bytes()));
+    /// ```
+    fn take_current(&mut self) -> Option<Self::Item> {
+        None
+    }
+
+    /// Returns an iterator over chunks of `size` bytes of the concatenated content, regardless of
+    /// where the items end. See [`Chunks`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is `0`.
+    ///
+    /// ```rust
+    /// use concat_reader::{concat, ConcatRead};
+    ///
+    /// let parts = concat(vec!["some".as_bytes(), "another".as_bytes()]).chunks(4);
+    /// let parts: Vec<Vec<u8>> = parts.map(Result::unwrap).collect();
+    /// assert_eq!(parts, [&b"some"[..], b"anot", b"her"]);
+    /// ```
+    ///
+    /// [`Chunks`]:                 records/struct.Chunks.html
+    fn chunks(self, size: usize) -> Chunks<Self>
+    where
+        Self: Sized,
+    {
+        Chunks::new(self, size)
+    }
+}
+
+/// `SourceRead` is a kind of `ConcatRead` which can read from the current item without
+/// continuing with the next one.
+///
+/// ```rust
+/// use concat_reader::{concat, ConcatRead, SourceRead};
+/// use std::io;
+///
+/// fn main() -> io::Result<()> {
+///     let mut f = concat(vec!["some".as_bytes(), "another".as_bytes()]);
+///
+///     let mut buffer = [0; 10];
+///     assert_eq!(f.read_source(&mut buffer)?, 4);
+///     assert_eq!(f.read_source(&mut buffer)?, 0);
+///
+///     //continue with the next Read object
+///     f.skip();
+///     assert_eq!(f.read_source(&mut buffer)?, 7);
+///     Ok(())
+/// }
+/// ```
+pub trait SourceRead: ConcatRead {
+    /// Reads from the current item only.
+    ///
+    /// Returns `Ok(0)` when the current item reached its `EOF`. The reader stays at the item until
+    /// [`skip`] is called.
+    ///
+    /// [`skip`]:                   trait.ConcatRead.html#tymethod.skip
+    fn read_source(&mut self, buf: &mut [u8]) -> std::io::Result<usize>;
+
+    /// Reads the rest of the current item into `buf` and continues with the next item.
+    ///
+    /// Returns the number of bytes appended to `buf`, `0` for an empty item or when there are no
+    /// items left. Reads never go past the end of the current item, so the bytes of one item
+    /// can be taken at a time without knowing its length. Interrupted reads are retried. On an
+    /// error the bytes read so far stay in `buf` and the reader stays at the item.
+    ///
+    /// ```rust
+    /// use concat_reader::{concat, SourceRead};
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut f = concat(vec!["some".as_bytes(), "another".as_bytes()]);
+    ///
+    ///     let mut buffer = Vec::new();
+    ///     assert_eq!(f.read_source_to_end(&mut buffer)?, 4);
+    ///     assert_eq!(buffer, b"some");
+    ///     assert_eq!(f.read_source_to_end(&mut buffer)?, 7);
+    ///     assert_eq!(buffer, b"someanother");
+    ///     assert_eq!(f.read_source_to_end(&mut buffer)?, 0);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn read_source_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        let start = buf.len();
+        let mut chunk = [0; 8 * 1024];
+        loop {
+            match self.read_source(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.skip();
+        Ok(buf.len() - start)
+    }
+
+    /// Boxes this reader, erasing the types of the decorators it is built of.
+    ///
+    /// Decorator stacks are statically dispatched, so their types grow with every layer. The
+    /// boxed reader has a short type at the cost of dynamic dispatch. See [`BoxedRead`].
+    ///
+    /// [`BoxedRead`]:              layer/type.BoxedRead.html
+    fn build_boxed<'a>(self) -> BoxedRead<'a, Self::Item>
+    where
+        Self: Sized + 'a,
+    {
+        Box::new(self)
+    }
 }
 
 /// `FileConcatRead` is a kind of `ConcatRead` which can provide information about the file currently read.
@@ -169,4 +438,120 @@ pub trait FileConcatRead: ConcatRead {
     ///
     /// [`File`]:                   https://doc.rust-lang.org/std/fs/struct.File.html
     fn file_path(&self) -> Option<&Path>;
+
+    /// Returns the path of the file after the current one without advancing to it.
+    ///
+    /// The file is not opened. Like [`peek_next`] this returns `None` for readers which can't
+    /// look ahead.
+    ///
+    /// [`peek_next`]:              trait.ConcatRead.html#method.peek_next
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        None
+    }
+
+    /// Returns the [`Metadata`] of the current file, e.g. its size, modification time and
+    /// permissions.
+    ///
+    /// The default implementation queries the file system for [`file_path`]. Readers holding an
+    /// open file query the file itself, which still works if the path was removed or replaced.
+    /// Fails with `NotFound` if there is no current file and with `Unsupported` for readers
+    /// whose files are not on the file system, like archive entries.
+    ///
+    /// [`Metadata`]:               https://doc.rust-lang.org/std/fs/struct.Metadata.html
+    /// [`file_path`]:              #tymethod.file_path
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        match self.file_path() {
+            Some(path) => fs::metadata(path),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no current file")),
+        }
+    }
+
+    /// Returns the length of the current file, see [`current_metadata`].
+    ///
+    /// [`current_metadata`]:       #method.current_metadata
+    fn current_len(&self) -> io::Result<u64> {
+        self.current_metadata().map(|meta| meta.len())
+    }
+
+    /// Boxes this reader, erasing its type but keeping the file information. See
+    /// [`BoxedFileRead`].
+    ///
+    /// [`BoxedFileRead`]:          layer/type.BoxedFileRead.html
+    fn build_boxed_file<'a>(self) -> BoxedFileRead<'a, Self::Item>
+    where
+        Self: Sized + 'a,
+    {
+        Box::new(self)
+    }
+
+    /// Returns a lending iterator handing out a reader for each source in turn, together with its
+    /// path. See [`Turns`].
+    ///
+    /// ```
+    /// use concat_reader::source::Source;
+    /// use concat_reader::{ConcatReader, FileConcatRead};
+    /// use std::io::Read;
+    ///
+    /// let sources = vec![Source::Bytes(b"12".to_vec()), Source::Bytes(b"345".to_vec())];
+    /// let mut reader = ConcatReader::from_sources(sources);
+    /// let mut sources = reader.sources();
+    ///
+    /// let mut lens = Vec::new();
+    /// while let Some(source) = sources.next() {
+    ///     lens.push(source.bytes().count());
+    /// }
+    /// assert_eq!(lens, [2, 3]);
+    /// ```
+    ///
+    /// [`Turns`]:                  turns/struct.Turns.html
+    fn sources(&mut self) -> Turns<'_, Self>
+    where
+        Self: SourceRead + Sized,
+    {
+        Turns::new(self)
+    }
+}
+
+impl<R: ConcatRead + ?Sized> ConcatRead for Box<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        (**self).skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        (**self).current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        (**self).current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        (**self).peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        (**self).take_current()
+    }
+}
+
+impl<R: SourceRead + ?Sized> SourceRead for Box<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        (**self).read_source(buf)
+    }
+}
+
+impl<R: FileConcatRead + ?Sized> FileConcatRead for Box<R> {
+    fn file_path(&self) -> Option<&Path> {
+        (**self).file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        (**self).peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        (**self).current_metadata()
+    }
 }
@@ -0,0 +1,276 @@
+//! Wrapping every source in a reader of its own.
+//!
+//! [`MapSources`] passes each source to a function which wraps it in another reader, e.g. a
+//! decoder, a [`Take`] or a throttle, and concatenates the wrapped readers. The sources still end
+//! where they ended before, and errors and [`file_path`] keep naming the file they came from.
+//!
+//! ```no_run
+//! use concat_reader::*;
+//! use std::io::prelude::*;
+//!
+//! // only the first kilobyte of every log
+//! let files = ["app-1.log", "app-2.log"];
+//! let mut reader = FileConcatReader::new(&files).map_sources(|source| source.take(1024));
+//! let mut buffer = Vec::new();
+//! reader.read_to_end(&mut buffer).unwrap();
+//! ```
+//!
+//! [`ConcatReader`] holds its sources as items, so [`ConcatReader::map_sources`] maps the items
+//! instead.
+//!
+//! [`MapSources`]:             struct.MapSources.html
+//! [`Take`]:                   https://doc.rust-lang.org/std/io/struct.Take.html
+//! [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+//! [`ConcatReader`]:           ../read/struct.ConcatReader.html
+//! [`ConcatReader::map_sources`]: ../read/struct.ConcatReader.html#method.map_sources
+use crate::layer::UnframedRead;
+use crate::provenance;
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The bytes of the current source of a [`MapSources`] reader.
+///
+/// A `CurrentSource` returns `EOF` at the end of its source. Reading it after [`MapSources`]
+/// moved to the next source reads from that source.
+///
+/// [`MapSources`]:             struct.MapSources.html
+pub struct CurrentSource<R> {
+    inner: Rc<RefCell<R>>,
+}
+
+impl<R: SourceRead> Read for CurrentSource<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.borrow_mut().read_source(buf)
+    }
+}
+
+impl<R> fmt::Debug for CurrentSource<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CurrentSource").finish()
+    }
+}
+
+/// A reader adapter which wraps every source of a [`FileConcatRead`] in a reader created by a
+/// function.
+///
+/// The function is called with a [`CurrentSource`] when a source is about to be read. A source
+/// ends when its wrapped reader returns `EOF`, even if the wrapped reader didn't consume all
+/// bytes of the source. [`current`] returns the wrapped reader of the current source, or `None`
+/// before it was created. The wrapped reader shares the underlying reader through an `Rc`, so
+/// `MapSources` isn't `Send`.
+///
+/// This struct is created by [`FileConcatReader::map_sources`].
+///
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+/// [`CurrentSource`]:          struct.CurrentSource.html
+/// [`current`]:                ../trait.ConcatRead.html#tymethod.current
+/// [`FileConcatReader::map_sources`]: ../file/struct.FileConcatReader.html#method.map_sources
+pub struct MapSources<R, F, W> {
+    inner: Rc<RefCell<R>>,
+    map: F,
+    curr: Option<W>,
+    /// Path of the current source, kept outside of `inner` to hand out references.
+    path: Option<PathBuf>,
+    position: u64,
+}
+
+impl<R, F, W> MapSources<R, F, W>
+where
+    R: UnframedRead + FileConcatRead,
+    F: FnMut(CurrentSource<R>) -> W,
+    W: Read,
+{
+    /// Creates a new `MapSources` wrapping every source of `inner` with `map`.
+    pub fn new(inner: R, map: F) -> Self {
+        let path = inner.file_path().map(Path::to_path_buf);
+        MapSources {
+            inner: Rc::new(RefCell::new(inner)),
+            map,
+            curr: None,
+            path,
+            position: 0,
+        }
+    }
+
+    /// Unwraps this `MapSources`, returning the underlying reader.
+    ///
+    /// The wrapped reader of the current source is dropped.
+    pub fn into_inner(self) -> R {
+        drop(self.curr);
+        match Rc::try_unwrap(self.inner) {
+            Ok(inner) => inner.into_inner(),
+            Err(_) => panic!("the underlying reader is shared outside of MapSources"),
+        }
+    }
+
+    fn refresh_path(&mut self) {
+        let inner = self.inner.borrow();
+        if inner.file_path() != self.path.as_deref() {
+            self.path = inner.file_path().map(Path::to_path_buf);
+        }
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.curr.is_none() {
+            if self.path.is_none() {
+                return Ok(0);
+            }
+            let source = CurrentSource {
+                inner: Rc::clone(&self.inner),
+            };
+            self.curr = Some((self.map)(source));
+        }
+        let res = self.curr.as_mut().expect("created above").read(buf);
+        self.refresh_path();
+        res
+    }
+}
+
+impl<R, F, W> Read for MapSources<R, F, W>
+where
+    R: UnframedRead + FileConcatRead,
+    F: FnMut(CurrentSource<R>) -> W,
+    W: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || self.path.is_none() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<R, F, W> ConcatRead for MapSources<R, F, W>
+where
+    R: UnframedRead + FileConcatRead,
+    F: FnMut(CurrentSource<R>) -> W,
+    W: Read,
+{
+    type Item = W;
+
+    fn skip(&mut self) -> bool {
+        self.curr = None;
+        self.position = 0;
+        let more = self.inner.borrow_mut().skip();
+        self.refresh_path();
+        more
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.curr.as_ref()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.curr.as_mut()
+    }
+}
+
+impl<R, F, W> SourceRead for MapSources<R, F, W>
+where
+    R: UnframedRead + FileConcatRead,
+    F: FnMut(CurrentSource<R>) -> W,
+    W: Read,
+{
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.read_inner(buf) {
+            Ok(n) => {
+                self.position += n as u64;
+                Ok(n)
+            }
+            Err(e) => Err(provenance::annotate(
+                e,
+                "map",
+                self.path.as_deref(),
+                self.position,
+            )),
+        }
+    }
+}
+
+impl<R, F, W> UnframedRead for MapSources<R, F, W>
+where
+    R: UnframedRead + FileConcatRead,
+    F: FnMut(CurrentSource<R>) -> W,
+    W: Read,
+{
+}
+
+impl<R, F, W> FileConcatRead for MapSources<R, F, W>
+where
+    R: UnframedRead + FileConcatRead,
+    F: FnMut(CurrentSource<R>) -> W,
+    W: Read,
+{
+    fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.borrow().current_metadata()
+    }
+}
+
+impl<R: fmt::Debug, F, W> fmt::Debug for MapSources<R, F, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapSources")
+            .field("inner", &self.inner)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::provenance::Provenance;
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, FileConcatRead, FileConcatReader};
+    use std::io::{self, Read};
+
+    #[test]
+    fn wraps_every_file_and_keeps_paths() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"1234").unwrap(),
+            tree.file("b", b"").unwrap(),
+            tree.file("c", b"5678").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(&paths).map_sources(|source| source.take(2));
+
+        let mut buf = [0; 3];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"12");
+        assert_eq!(reader.file_path(), Some(paths[0].as_path()));
+        assert_eq!(reader.current().map(|take| take.limit()), Some(0));
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"56");
+        assert_eq!(reader.file_path(), None);
+    }
+
+    #[test]
+    fn annotates_errors_with_the_path() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![tree.file("a", b"12").unwrap(), tree.path().join("missing")];
+        let mut reader = FileConcatReader::new(&paths).map_sources(|source| source);
+
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(buf, b"12");
+        let provenance = Provenance::of(&err).unwrap();
+        assert_eq!(provenance.crumbs().last().unwrap().layer, "map");
+        assert_eq!(provenance.path(), Some(paths[1].as_path()));
+    }
+}
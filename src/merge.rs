@@ -0,0 +1,308 @@
+//! Merging sorted sources line by line.
+//!
+//! A [`MergeReader`] reads several sources whose lines are sorted and returns all lines as one
+//! sorted stream, like `sort -m`. Only the current line of each source is kept in memory. Lines
+//! are compared as bytes without their line ending, or with a comparator set by
+//! [`compare_by`].
+//!
+//! ```
+//! use concat_reader::merge::MergeReader;
+//! use std::io::prelude::*;
+//!
+//! let sources: Vec<&[u8]> = vec![b"apple\ncherry\n", b"banana\ndate\n"];
+//! let mut reader = MergeReader::new(sources);
+//!
+//! let mut buffer = String::new();
+//! reader.read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "apple\nbanana\ncherry\ndate\n");
+//! ```
+//!
+//! [`MergeReader`]:            struct.MergeReader.html
+//! [`compare_by`]:             struct.MergeReader.html#method.compare_by
+use crate::provenance;
+use crate::FileConcatReader;
+use std::cmp::{self, Ordering};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::iter::{self, Once};
+use std::mem;
+use std::path::Path;
+
+/// The comparator of a [`MergeReader`] without [`compare_by`].
+///
+/// [`MergeReader`]:            struct.MergeReader.html
+/// [`compare_by`]:             struct.MergeReader.html#method.compare_by
+pub type ByteOrder = fn(&[u8], &[u8]) -> Ordering;
+
+/// A reader which merges the sorted lines of several sources into one sorted stream.
+///
+/// Lines end at `\n`, a last line without one gets one added. Equal lines are returned in the
+/// order of their sources. The sources are expected to be sorted by the same comparator, lines
+/// out of order are passed on as they are.
+pub struct MergeReader<R, C = ByteOrder> {
+    heads: Vec<Head<R>>,
+    cmp: C,
+    /// The line being returned.
+    line: Vec<u8>,
+    pos: usize,
+    /// Index of the source of `line`.
+    source: Option<usize>,
+    position: u64,
+}
+
+/// A source and its next line.
+struct Head<R> {
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Head<R> {
+    /// Reads the next line of the source if there is none.
+    fn fill(&mut self) -> io::Result<()> {
+        if self.line.is_empty() && !self.done {
+            self.done = self.reader.read_until(b'\n', &mut self.line)? == 0;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> MergeReader<R> {
+    /// Creates a new `MergeReader` over `sources`, comparing lines byte by byte.
+    pub fn new<I: IntoIterator<Item = R>>(sources: I) -> Self {
+        let heads = sources
+            .into_iter()
+            .map(|reader| Head {
+                reader: BufReader::new(reader),
+                line: Vec::new(),
+                done: false,
+            })
+            .collect();
+        MergeReader {
+            heads,
+            cmp: <[u8]>::cmp,
+            line: Vec::new(),
+            pos: 0,
+            source: None,
+            position: 0,
+        }
+    }
+}
+
+impl<P: AsRef<Path>> MergeReader<FileConcatReader<Once<P>>> {
+    /// Creates a new `MergeReader` over the files at `paths`.
+    ///
+    /// Each file is read by a [`FileConcatReader`] of its own, so the files are opened on the
+    /// first read and errors carry the path of their file.
+    ///
+    /// ```no_run
+    /// use concat_reader::merge::MergeReader;
+    /// use std::io;
+    ///
+    /// let mut reader = MergeReader::from_paths(vec!["sorted-1.txt", "sorted-2.txt"]);
+    /// io::copy(&mut reader, &mut io::stdout()).unwrap();
+    /// ```
+    ///
+    /// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+    pub fn from_paths<I: IntoIterator<Item = P>>(paths: I) -> Self {
+        Self::new(
+            paths
+                .into_iter()
+                .map(|path| FileConcatReader::new(iter::once(path))),
+        )
+    }
+}
+
+impl<R, C> MergeReader<R, C> {
+    /// Compares lines with `cmp` instead of byte by byte.
+    ///
+    /// `cmp` gets the lines without their line ending.
+    ///
+    /// ```
+    /// use concat_reader::merge::MergeReader;
+    /// use std::io::prelude::*;
+    ///
+    /// fn number(line: &[u8]) -> u64 {
+    ///     std::str::from_utf8(line).unwrap().parse().unwrap()
+    /// }
+    ///
+    /// let sources: Vec<&[u8]> = vec![b"2\n10\n", b"9\n11\n"];
+    /// let mut reader = MergeReader::new(sources).compare_by(|a, b| number(a).cmp(&number(b)));
+    ///
+    /// let mut buffer = String::new();
+    /// reader.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "2\n9\n10\n11\n");
+    /// ```
+    pub fn compare_by<D>(self, cmp: D) -> MergeReader<R, D>
+    where
+        D: FnMut(&[u8], &[u8]) -> Ordering,
+    {
+        MergeReader {
+            heads: self.heads,
+            cmp,
+            line: self.line,
+            pos: self.pos,
+            source: self.source,
+            position: self.position,
+        }
+    }
+
+    /// Returns the index of the source of the line being read, `None` before the first line.
+    pub fn line_source(&self) -> Option<usize> {
+        self.source
+    }
+
+    /// Returns the number of sources.
+    pub fn len(&self) -> usize {
+        self.heads.len()
+    }
+
+    /// Returns `true` if there are no sources.
+    pub fn is_empty(&self) -> bool {
+        self.heads.is_empty()
+    }
+
+    /// Returns references to the sources.
+    pub fn sources(&self) -> Vec<&R> {
+        self.heads
+            .iter()
+            .map(|head| head.reader.get_ref())
+            .collect()
+    }
+}
+
+impl<R, C> MergeReader<R, C>
+where
+    R: Read,
+    C: FnMut(&[u8], &[u8]) -> Ordering,
+{
+    /// Moves the smallest line of all sources into `line`. Returns `false` if all sources are
+    /// exhausted.
+    fn next_line(&mut self) -> io::Result<bool> {
+        for head in &mut self.heads {
+            head.fill()?;
+        }
+
+        let cmp = &mut self.cmp;
+        let mut min: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            if head.line.is_empty() {
+                continue;
+            }
+            let smaller = match min {
+                None => true,
+                Some(m) => cmp(trim(&head.line), trim(&self.heads[m].line)) == Ordering::Less,
+            };
+            if smaller {
+                min = Some(i);
+            }
+        }
+
+        let i = match min {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+        self.line.clear();
+        mem::swap(&mut self.line, &mut self.heads[i].line);
+        if self.line.last() != Some(&b'\n') {
+            self.line.push(b'\n');
+        }
+        self.pos = 0;
+        self.source = Some(i);
+        Ok(true)
+    }
+}
+
+/// Strips the line ending of `line`.
+fn trim(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\n").unwrap_or(line)
+}
+
+impl<R, C> Read for MergeReader<R, C>
+where
+    R: Read,
+    C: FnMut(&[u8], &[u8]) -> Ordering,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.pos == self.line.len() {
+            match self.next_line() {
+                Ok(true) => {}
+                Ok(false) => return Ok(0),
+                Err(e) => return Err(provenance::annotate(e, "merge", None, self.position)),
+            }
+        }
+
+        let n = cmp::min(buf.len(), self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: fmt::Debug, C> fmt::Debug for MergeReader<R, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MergeReader")
+            .field("sources", &self.sources())
+            .field("line_source", &self.source)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MergeReader;
+    use crate::provenance::Provenance;
+    use crate::test_util::TempTree;
+    use std::io::{self, BufRead, BufReader, Read};
+
+    #[test]
+    fn merges_sorted_lines_in_source_order() {
+        let sources: Vec<&[u8]> = vec![b"a\nc\nc\n", b"", b"b\nc\nd", b"a\n"];
+        let mut reader = BufReader::new(MergeReader::new(sources));
+
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 {
+            lines.push((line.clone(), reader.get_ref().line_source().unwrap()));
+            line.clear();
+        }
+        let expected = [
+            ("a\n", 0),
+            ("a\n", 3),
+            ("b\n", 2),
+            ("c\n", 0),
+            ("c\n", 0),
+            ("c\n", 2),
+            ("d\n", 2),
+        ];
+        let expected: Vec<_> = expected.iter().map(|&(l, s)| (l.to_string(), s)).collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn merges_files_and_reports_their_errors() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"1\n3\n").unwrap(),
+            tree.file("b", b"2\n").unwrap(),
+        ];
+        let mut buf = String::new();
+        MergeReader::from_paths(&paths)
+            .read_to_string(&mut buf)
+            .unwrap();
+        assert_eq!(buf, "1\n2\n3\n");
+
+        let missing = tree.path().join("missing");
+        let mut reader = MergeReader::from_paths(vec![paths[0].clone(), missing.clone()]);
+        let err = reader.read_to_string(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            Provenance::of(&err).unwrap().path(),
+            Some(missing.as_path())
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Reading from network endpoints one after another.
+//!
+//! This module is only available with the `net` feature. An [`Endpoint`] is the address of a
+//! TCP server or, on unix, the path of a unix domain socket. [`ConcatReader::from_endpoints`]
+//! concatenates the streams of a list of endpoints, connecting to each one only when the reader
+//! reaches it, e.g. to replay the data of a sequence of collectors.
+//!
+//! A [`Reconnect`] policy decides what happens when connecting fails or, optionally, when the
+//! connection breaks while it is read from: the reader tries again up to a number of times,
+//! waiting in between, before it returns the error.
+//!
+//! ```no_run
+//! use concat_reader::net::{Endpoint, Reconnect};
+//! use concat_reader::ConcatReader;
+//! use std::io::{self, Read};
+//! use std::time::Duration;
+//!
+//! fn main() -> io::Result<()> {
+//!     let endpoints = vec![
+//!         Endpoint::Tcp("collector-1:7000".into()),
+//!         Endpoint::Tcp("collector-2:7000".into()),
+//!     ];
+//!     let reconnect = Reconnect::attempts(3, Duration::from_secs(1));
+//!     let mut reader = ConcatReader::from_endpoints(endpoints, reconnect);
+//!     io::copy(&mut reader, &mut io::stdout())?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`Endpoint`]:               enum.Endpoint.html
+//! [`ConcatReader::from_endpoints`]: ../read/struct.ConcatReader.html#method.from_endpoints
+//! [`Reconnect`]:              struct.Reconnect.html
+use crate::provenance;
+use crate::ConcatReader;
+use std::fmt;
+use std::io::{self, Read};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// The address of a stream to read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// A TCP server, as `host:port`.
+    Tcp(String),
+    /// A unix domain socket.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Endpoint {
+    fn connect(&self) -> io::Result<Stream> {
+        match self {
+            Endpoint::Tcp(addr) => TcpStream::connect(addr.as_str()).map(Stream::Tcp),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "tcp://{}", addr),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// When and how often a [`Connection`] connects again after a failure.
+///
+/// [`Connection`]:             struct.Connection.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Reconnect {
+    attempts: usize,
+    delay: Duration,
+    on_read_errors: bool,
+}
+
+impl Reconnect {
+    /// Returns errors right away. This is the default.
+    pub fn never() -> Self {
+        Self::default()
+    }
+
+    /// Tries to connect up to `attempts` more times, waiting `delay` before each attempt.
+    pub fn attempts(attempts: usize, delay: Duration) -> Self {
+        Reconnect {
+            attempts,
+            delay,
+            on_read_errors: false,
+        }
+    }
+
+    /// Also connects again when reading from an established connection fails.
+    ///
+    /// The stream continues with whatever the endpoint sends on the new connection, so this only
+    /// suits endpoints which stream live data rather than a fixed content.
+    pub fn on_read_errors(mut self) -> Self {
+        self.on_read_errors = true;
+        self
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+/// The lazily established connection to an [`Endpoint`].
+///
+/// [`Endpoint`]:               enum.Endpoint.html
+pub struct Connection {
+    endpoint: Endpoint,
+    stream: Option<Stream>,
+    reconnect: Reconnect,
+    /// Failures since the last successful read.
+    failures: usize,
+    pos: u64,
+}
+
+impl Connection {
+    /// Creates a new `Connection` to `endpoint`, which connects on the first read.
+    pub fn new(endpoint: Endpoint, reconnect: Reconnect) -> Self {
+        Connection {
+            endpoint,
+            stream: None,
+            reconnect,
+            failures: 0,
+            pos: 0,
+        }
+    }
+
+    /// Returns the endpoint.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Returns `true` if the connection is established.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Counts a failure. Returns `false` if there are no attempts left.
+    fn retry(&mut self) -> bool {
+        if self.failures >= self.reconnect.attempts {
+            self.failures = 0;
+            return false;
+        }
+        self.failures += 1;
+        thread::sleep(self.reconnect.delay);
+        true
+    }
+
+    fn annotate(&self, err: io::Error) -> io::Error {
+        let err = io::Error::new(err.kind(), format!("{}: {}", self.endpoint, err));
+        provenance::annotate(err, "net", None, self.pos)
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => match self.endpoint.connect() {
+                    Ok(stream) => self.stream.insert(stream),
+                    Err(_) if self.retry() => continue,
+                    Err(e) => return Err(self.annotate(e)),
+                },
+            };
+            match stream.read(buf) {
+                Ok(n) => {
+                    if n > 0 {
+                        self.failures = 0;
+                    }
+                    self.pos += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+                Err(e) => {
+                    self.stream = None;
+                    if !(self.reconnect.on_read_errors && self.retry()) {
+                        return Err(self.annotate(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("endpoint", &self.endpoint)
+            .field("connected", &self.is_connected())
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+/// An iterator turning [`Endpoint`]s into [`Connection`]s.
+///
+/// [`Endpoint`]:               enum.Endpoint.html
+/// [`Connection`]:             struct.Connection.html
+#[derive(Debug)]
+pub struct Endpoints<I> {
+    iter: I,
+    reconnect: Reconnect,
+}
+
+impl<I: Iterator<Item = Endpoint>> Iterator for Endpoints<I> {
+    type Item = Connection;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reconnect = self.reconnect;
+        self.iter.next().map(|e| Connection::new(e, reconnect))
+    }
+}
+
+impl<I: Iterator<Item = Endpoint>> ConcatReader<Endpoints<I>> {
+    /// Creates a new `ConcatReader` reading from a list of [`Endpoint`]s, connecting to each one
+    /// when the reader reaches it.
+    ///
+    /// [`Endpoint`]:           ../net/enum.Endpoint.html
+    pub fn from_endpoints<S>(endpoints: S, reconnect: Reconnect) -> Self
+    where
+        S: IntoIterator<IntoIter = I, Item = Endpoint>,
+    {
+        ConcatReader::new(Endpoints {
+            iter: endpoints.into_iter(),
+            reconnect,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Endpoint, Reconnect};
+    use crate::provenance::Provenance;
+    use crate::ConcatReader;
+    use std::io::{self, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Serves `data` to the first client of a new listener.
+    fn serve(data: &'static [u8]) -> Endpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(data).unwrap();
+        });
+        Endpoint::Tcp(addr.to_string())
+    }
+
+    #[test]
+    fn reads_endpoints_in_order() {
+        let endpoints = vec![serve(b"12"), serve(b""), serve(b"345")];
+        let mut reader = ConcatReader::from_endpoints(endpoints, Reconnect::never());
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "12345");
+    }
+
+    #[test]
+    fn retries_connecting() {
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let endpoints = vec![Endpoint::Tcp(addr.to_string())];
+        let reconnect = Reconnect::attempts(2, Duration::from_millis(10));
+        let mut reader = ConcatReader::from_endpoints(endpoints, reconnect);
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(Provenance::of(&err).unwrap().crumbs()[0].layer, "net");
+        assert!(err.to_string().contains(&addr.to_string()), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_unix_sockets() {
+        use crate::test_util::TempTree;
+        use std::os::unix::net::UnixListener;
+
+        let tree = TempTree::new().unwrap();
+        let path = tree.path().join("socket");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"unix").unwrap();
+        });
+
+        let mut reader =
+            ConcatReader::from_endpoints(vec![Endpoint::Unix(path)], Reconnect::never());
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "unix");
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! Reading objects of an object store one after another.
+//!
+//! This module is only available with the `object_store` feature. An [`ObjectConcatReader`]
+//! reads the objects of any [`ObjectStore`], like S3, GCS, Azure or the local file system, as
+//! one asynchronous stream. Objects are fetched with ranged GETs of a fixed chunk size, so large
+//! objects are neither buffered whole nor read through a single long lived request, and a failed
+//! chunk is retried without starting the object over.
+//!
+//! Sources are either [`ObjectMeta`]s, e.g. from listing a prefix, or plain paths, whose size is
+//! requested with a HEAD request when the reader reaches them.
+//!
+//! ```no_run
+//! use concat_reader::objects::ObjectConcatReader;
+//! use object_store::memory::InMemory;
+//! use object_store::path::Path;
+//! use object_store::ObjectStore;
+//! use std::sync::Arc;
+//!
+//! let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+//! let parts = vec![Path::from("export/part-0"), Path::from("export/part-1")];
+//! let reader = ObjectConcatReader::new(store, parts).chunk_size(8 * 1024 * 1024);
+//! ```
+//!
+//! [`ObjectConcatReader`]:     struct.ObjectConcatReader.html
+//! [`ObjectStore`]:           https://docs.rs/object_store/0.12/object_store/trait.ObjectStore.html
+//! [`ObjectMeta`]:            https://docs.rs/object_store/0.12/object_store/struct.ObjectMeta.html
+use crate::provenance;
+use bytes::Bytes;
+use futures_io::AsyncRead;
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore};
+use std::cmp;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+pub use object_store;
+
+const DEFAULT_CHUNK_SIZE: u64 = 1024 * 1024;
+const DEFAULT_RETRIES: usize = 3;
+
+/// An object to read, with its size if it is already known.
+#[derive(Clone, Debug)]
+pub struct ObjectSource {
+    location: Path,
+    size: Option<u64>,
+}
+
+impl From<ObjectMeta> for ObjectSource {
+    fn from(meta: ObjectMeta) -> Self {
+        ObjectSource {
+            location: meta.location,
+            size: Some(meta.size),
+        }
+    }
+}
+
+impl From<Path> for ObjectSource {
+    fn from(location: Path) -> Self {
+        ObjectSource {
+            location,
+            size: None,
+        }
+    }
+}
+
+enum Fetched {
+    Size(u64),
+    Chunk(Bytes),
+}
+
+type Fetch = Pin<Box<dyn Future<Output = object_store::Result<Fetched>> + Send>>;
+
+/// Reads the objects of an [`ObjectStore`] one after another.
+///
+/// `ObjectConcatReader` implements `futures::io::AsyncRead`. [`object_key`] returns the path of
+/// the object currently read, like `file_path` of a [`FileConcatReader`]. Errors are annotated
+/// with the object key and the offset in the object, see [`provenance`].
+///
+/// [`ObjectStore`]:           https://docs.rs/object_store/0.12/object_store/trait.ObjectStore.html
+/// [`object_key`]:             #method.object_key
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`provenance`]:             ../provenance/index.html
+pub struct ObjectConcatReader<I: IntoIterator> {
+    store: Arc<dyn ObjectStore>,
+    sources: I::IntoIter,
+    curr: Option<ObjectSource>,
+    /// Offset of the next chunk to fetch in the current object.
+    pos: u64,
+    chunk: Bytes,
+    fetch: Option<Fetch>,
+    chunk_size: u64,
+    retries: usize,
+    failures: usize,
+}
+
+impl<I> ObjectConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Into<ObjectSource>,
+{
+    /// Creates a new `ObjectConcatReader` reading the objects of `sources` from `store`.
+    pub fn new(store: Arc<dyn ObjectStore>, sources: I) -> Self {
+        ObjectConcatReader {
+            store,
+            sources: sources.into_iter(),
+            curr: None,
+            pos: 0,
+            chunk: Bytes::new(),
+            fetch: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            retries: DEFAULT_RETRIES,
+            failures: 0,
+        }
+    }
+
+    /// Sets the number of bytes fetched with one ranged GET, 1 MiB by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        assert!(chunk_size > 0, "chunk size must not be 0");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets how often a failed request is retried before the read fails, 3 by default.
+    ///
+    /// Retries are made immediately. Stores talking HTTP already retry with a backoff on their
+    /// own, configured with their `RetryConfig`; these retries cover errors which are left, like
+    /// a connection dropped while a chunk is received.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Returns the key of the object currently read.
+    pub fn object_key(&self) -> Option<&Path> {
+        self.curr.as_ref().map(|curr| &curr.location)
+    }
+
+    /// Skips to the next object. Returns `false` if there is none.
+    pub fn skip(&mut self) -> bool {
+        self.chunk.clear();
+        self.fetch = None;
+        self.failures = 0;
+        self.pos = 0;
+        self.curr = self.sources.next().map(Into::into);
+        self.curr.is_some()
+    }
+
+    /// Starts the request for the next chunk or the size of the current object.
+    fn start_fetch(&self, curr: &ObjectSource, size: Option<u64>) -> Fetch {
+        let store = self.store.clone();
+        let location = curr.location.clone();
+        match size {
+            None => Box::pin(async move {
+                let meta = store.head(&location).await?;
+                Ok(Fetched::Size(meta.size))
+            }),
+            Some(size) => {
+                let range = self.pos..cmp::min(self.pos + self.chunk_size, size);
+                Box::pin(async move {
+                    let bytes = store.get_range(&location, range).await?;
+                    Ok(Fetched::Chunk(bytes))
+                })
+            }
+        }
+    }
+
+    fn annotate(&self, err: object_store::Error) -> io::Error {
+        let path = self
+            .object_key()
+            .map(|key| std::path::Path::new(key.as_ref()));
+        provenance::annotate(err.into(), "object", path, self.pos)
+    }
+}
+
+impl<I> AsyncRead for ObjectConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Into<ObjectSource>,
+    I::IntoIter: Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            if !this.chunk.is_empty() {
+                let n = cmp::min(buf.len(), this.chunk.len());
+                buf[..n].copy_from_slice(&this.chunk.split_to(n));
+                return Poll::Ready(Ok(n));
+            }
+
+            if let Some(fetch) = this.fetch.as_mut() {
+                let res = match fetch.as_mut().poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(res) => res,
+                };
+                this.fetch = None;
+                match res {
+                    Ok(Fetched::Size(size)) => {
+                        this.failures = 0;
+                        if let Some(curr) = this.curr.as_mut() {
+                            curr.size = Some(size);
+                        }
+                    }
+                    Ok(Fetched::Chunk(bytes)) => {
+                        this.failures = 0;
+                        if bytes.is_empty() {
+                            // the object shrank since its size was known
+                            this.skip();
+                            continue;
+                        }
+                        this.pos += bytes.len() as u64;
+                        this.chunk = bytes;
+                    }
+                    Err(_) if this.failures < this.retries => this.failures += 1,
+                    Err(e) => {
+                        this.failures = 0;
+                        return Poll::Ready(Err(this.annotate(e)));
+                    }
+                }
+                continue;
+            }
+
+            let curr = match &this.curr {
+                Some(curr) => curr,
+                None => {
+                    if !this.skip() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    continue;
+                }
+            };
+            match curr.size {
+                Some(size) if this.pos >= size => {
+                    this.skip();
+                }
+                size => this.fetch = Some(this.start_fetch(curr, size)),
+            }
+        }
+    }
+}
+
+impl<I: IntoIterator> fmt::Debug for ObjectConcatReader<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ObjectConcatReader")
+            .field("curr", &self.curr)
+            .field("pos", &self.pos)
+            .field("chunk_size", &self.chunk_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectConcatReader, ObjectSource};
+    use futures_io::AsyncRead;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Waker};
+
+    /// Polls `fut` until it is ready. The in-memory store never waits for anything.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = Box::pin(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    fn read_to_end<R: AsyncRead + Unpin>(reader: &mut R, buf_len: usize) -> io::Result<Vec<u8>> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut buf = vec![0; buf_len];
+        let mut out = Vec::new();
+        loop {
+            match Pin::new(&mut *reader).poll_read(&mut cx, &mut buf) {
+                Poll::Pending => continue,
+                Poll::Ready(Ok(0)) => return Ok(out),
+                Poll::Ready(Ok(n)) => out.extend_from_slice(&buf[..n]),
+                Poll::Ready(Err(e)) => return Err(e),
+            }
+        }
+    }
+
+    #[test]
+    fn reads_objects_in_chunks() {
+        let store = Arc::new(InMemory::new());
+        for (key, data) in [("a", &b"12345"[..]), ("b", b""), ("c", b"678")] {
+            block_on(store.put(&Path::from(key), data.to_vec().into())).unwrap();
+        }
+        let meta = block_on(store.head(&Path::from("c"))).unwrap();
+        let sources: Vec<ObjectSource> =
+            vec![Path::from("a").into(), Path::from("b").into(), meta.into()];
+
+        let mut reader = ObjectConcatReader::new(store.clone(), sources).chunk_size(2);
+        assert_eq!(read_to_end(&mut reader, 8).unwrap(), b"12345678");
+        assert!(reader.object_key().is_none());
+
+        let mut reader = ObjectConcatReader::new(store, vec![Path::from("a"), Path::from("x")]);
+        let err = read_to_end(&mut reader, 8).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(reader.object_key(), Some(&Path::from("x")));
+    }
+}
@@ -0,0 +1,261 @@
+//! Peeking at upcoming bytes, e.g. to decode record headers before reading the record.
+//!
+//! [`PeekReader`] keeps a small lookahead buffer which is only filled from the current source,
+//! so a peeked header always belongs to the source reported by [`current`] and [`file_path`].
+//!
+//! ```
+//! use concat_reader::peek::{Endian, PeekReader};
+//! use concat_reader::{concat, ConcatRead};
+//! use std::io::prelude::*;
+//!
+//! let records: Vec<&[u8]> = vec![b"\x00\x03abc", b"\x00\x01d"];
+//! let mut reader = PeekReader::new(concat(records));
+//!
+//! let mut bodies = Vec::new();
+//! loop {
+//!     match reader.peek_u16(Endian::Big).unwrap() {
+//!         Some(len) => {
+//!             let mut record = vec![0; 2 + len as usize];
+//!             reader.read_exact(&mut record).unwrap();
+//!             bodies.push(record.split_off(2));
+//!         }
+//!         None if reader.skip() => continue,
+//!         None => break,
+//!     }
+//! }
+//! assert_eq!(bodies, vec![b"abc".to_vec(), b"d".to_vec()]);
+//! ```
+//!
+//! [`PeekReader`]:             struct.PeekReader.html
+//! [`current`]:                ../trait.ConcatRead.html#tymethod.current
+//! [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+use crate::provenance;
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::convert::TryInto;
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Byte order of an encoded integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+impl Endian {
+    /// Decodes a `u16` from the first two bytes of `bytes`.
+    pub fn read_u16(self, bytes: &[u8]) -> Option<u16> {
+        let bytes = bytes.get(..2)?.try_into().ok()?;
+        Some(match self {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    /// Decodes a `u32` from the first four bytes of `bytes`.
+    pub fn read_u32(self, bytes: &[u8]) -> Option<u32> {
+        let bytes = bytes.get(..4)?.try_into().ok()?;
+        Some(match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Decodes a `u64` from the first eight bytes of `bytes`.
+    pub fn read_u64(self, bytes: &[u8]) -> Option<u64> {
+        let bytes = bytes.get(..8)?.try_into().ok()?;
+        Some(match self {
+            Endian::Little => u64::from_le_bytes(bytes),
+            Endian::Big => u64::from_be_bytes(bytes),
+        })
+    }
+}
+
+/// A reader adapter which can look at upcoming bytes of the current source without consuming
+/// them.
+///
+/// `PeekReader` implements [`ConcatRead`] and [`FileConcatRead`] when the wrapped reader does.
+/// Skipping to the next source discards the peeked bytes.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct PeekReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    position: u64,
+}
+
+impl<R: SourceRead> PeekReader<R> {
+    /// Creates a new `PeekReader`.
+    pub fn new(inner: R) -> Self {
+        PeekReader {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            position: 0,
+        }
+    }
+
+    /// Returns the next `n` bytes of the current source without consuming them.
+    ///
+    /// Returns less than `n` bytes if the current source ends earlier.
+    pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+
+        while self.buf.len() < n {
+            let len = self.buf.len();
+            self.buf.resize(n, 0);
+            match self.inner.read_source(&mut self.buf[len..]) {
+                Ok(read) => {
+                    self.buf.truncate(len + read);
+                    if read == 0 {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    self.buf.truncate(len);
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(provenance::annotate(e, "peek", None, self.position));
+                    }
+                }
+            }
+        }
+        Ok(&self.buf[..n.min(self.buf.len())])
+    }
+
+    /// Peeks a `u16`. Returns `None` if the current source has less than two bytes left.
+    pub fn peek_u16(&mut self, endian: Endian) -> io::Result<Option<u16>> {
+        self.peek(2).map(|b| endian.read_u16(b))
+    }
+
+    /// Peeks a `u32`. Returns `None` if the current source has less than four bytes left.
+    pub fn peek_u32(&mut self, endian: Endian) -> io::Result<Option<u32>> {
+        self.peek(4).map(|b| endian.read_u32(b))
+    }
+
+    /// Peeks a `u64`. Returns `None` if the current source has less than eight bytes left.
+    pub fn peek_u64(&mut self, endian: Endian) -> io::Result<Option<u64>> {
+        self.peek(8).map(|b| endian.read_u64(b))
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `PeekReader`, returning the underlying reader.
+    ///
+    /// Peeked bytes are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: SourceRead> Read for PeekReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.buf.len() {
+            let n = (&self.buf[self.pos..]).read(buf)?;
+            self.pos += n;
+            self.position += n as u64;
+            return Ok(n);
+        }
+        let res = self.inner.read(buf);
+        provenance::track(res, "peek", &mut self.position)
+    }
+}
+
+impl<R: SourceRead> ConcatRead for PeekReader<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.buf.clear();
+        self.pos = 0;
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.take_current()?;
+        self.buf.clear();
+        self.pos = 0;
+        Some(item)
+    }
+}
+
+impl<R: SourceRead> SourceRead for PeekReader<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.buf.len() {
+            return self.read(buf);
+        }
+        let res = self.inner.read_source(buf);
+        provenance::track(res, "peek", &mut self.position)
+    }
+}
+
+impl<R: SourceRead + FileConcatRead> FileConcatRead for PeekReader<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Endian, PeekReader};
+    use crate::ConcatReader;
+    use std::io::Read;
+
+    #[test]
+    fn peeks_without_consuming() {
+        let bytes: Vec<&[u8]> = vec![b"\x01\x02", b"\x03\x04\x05\x06"];
+        let mut reader = PeekReader::new(ConcatReader::new(bytes));
+
+        assert_eq!(reader.peek_u16(Endian::Little).unwrap(), Some(0x0201));
+        assert_eq!(reader.peek_u16(Endian::Big).unwrap(), Some(0x0102));
+        // a peek never crosses into the next source
+        assert_eq!(reader.peek_u32(Endian::Big).unwrap(), None);
+        assert_eq!(reader.peek(4).unwrap(), b"\x01\x02");
+
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"\x01\x02\x03");
+        assert_eq!(reader.peek_u16(Endian::Big).unwrap(), Some(0x0405));
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"\x04\x05\x06");
+    }
+
+    #[test]
+    fn decodes_integers() {
+        let bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(Endian::Big.read_u32(&bytes), Some(0x0102_0304));
+        assert_eq!(Endian::Little.read_u64(&bytes), Some(0x0807_0605_0403_0201));
+        assert_eq!(Endian::Little.read_u64(&bytes[1..]), None);
+    }
+}
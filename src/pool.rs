@@ -0,0 +1,224 @@
+//! Reusable buffers for readers which read in chunks.
+//!
+//! A [`BufferPool`] hands out buffers of a fixed chunk size and takes them back when they are
+//! dropped, keeping up to a fixed number of idle buffers for reuse. Readers which read ahead into
+//! buffers of their own, like [`Timeout`], take their buffers from a pool instead of allocating
+//! one per chunk, which matters when streaming many small files. A pool can be shared between
+//! readers by cloning it.
+//!
+//! The buffer type is pluggable through the [`Buffer`] trait. `Vec<u8>` is the default, with the
+//! `futures` feature `bytes::BytesMut` can be used as well.
+//!
+//! ```
+//! use concat_reader::pool::BufferPool;
+//!
+//! let pool: BufferPool = BufferPool::new(4096, 2);
+//! let buf = pool.get();
+//! assert_eq!(buf.len(), 4096);
+//! drop(buf);
+//! assert_eq!(pool.idle(), 1);
+//! ```
+//!
+//! [`BufferPool`]:             struct.BufferPool.html
+//! [`Buffer`]:                 trait.Buffer.html
+//! [`Timeout`]:                ../timeout/struct.Timeout.html
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A buffer type which can be kept in a [`BufferPool`].
+///
+/// [`BufferPool`]:             struct.BufferPool.html
+pub trait Buffer: AsRef<[u8]> + AsMut<[u8]> {
+    /// Creates a buffer of `len` zero bytes.
+    fn zeroed(len: usize) -> Self;
+}
+
+impl Buffer for Vec<u8> {
+    fn zeroed(len: usize) -> Self {
+        vec![0; len]
+    }
+}
+
+#[cfg(feature = "futures")]
+impl Buffer for bytes::BytesMut {
+    fn zeroed(len: usize) -> Self {
+        bytes::BytesMut::zeroed(len)
+    }
+}
+
+/// A pool of buffers of `chunk_size` bytes, keeping up to `count` idle buffers.
+///
+/// Buffers are allocated lazily. Getting a buffer from an empty pool allocates a new one, and
+/// returning a buffer to a full pool frees it, so a pool never blocks.
+pub struct BufferPool<B = Vec<u8>> {
+    shared: Arc<Shared<B>>,
+}
+
+struct Shared<B> {
+    chunk_size: usize,
+    count: usize,
+    idle: Mutex<Vec<B>>,
+}
+
+impl<B: Buffer> BufferPool<B> {
+    /// Creates a new empty `BufferPool`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn new(chunk_size: usize, count: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must not be 0");
+        BufferPool {
+            shared: Arc::new(Shared {
+                chunk_size,
+                count,
+                idle: Mutex::new(Vec::with_capacity(count)),
+            }),
+        }
+    }
+
+    /// Takes an idle buffer from the pool, or allocates a new one.
+    ///
+    /// The buffer returns to the pool when it is dropped. Reused buffers still hold the bytes of
+    /// their previous use.
+    pub fn get(&self) -> Pooled<B> {
+        let buf = self
+            .shared
+            .idle()
+            .pop()
+            .unwrap_or_else(|| B::zeroed(self.shared.chunk_size));
+        Pooled {
+            buf: Some(buf),
+            pool: self.shared.clone(),
+        }
+    }
+}
+
+impl<B> BufferPool<B> {
+    /// Returns the size of the buffers.
+    pub fn chunk_size(&self) -> usize {
+        self.shared.chunk_size
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    pub fn idle(&self) -> usize {
+        self.shared.idle().len()
+    }
+}
+
+impl<B> Shared<B> {
+    fn idle(&self) -> MutexGuard<'_, Vec<B>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<B> Clone for BufferPool<B> {
+    /// Returns a handle to the same pool.
+    fn clone(&self) -> Self {
+        BufferPool {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<B> fmt::Debug for BufferPool<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("chunk_size", &self.shared.chunk_size)
+            .field("count", &self.shared.count)
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+/// A buffer taken from a [`BufferPool`], which returns to the pool when it is dropped.
+///
+/// [`BufferPool`]:             struct.BufferPool.html
+pub struct Pooled<B> {
+    /// Always `Some` until the buffer is dropped or taken by `into_inner`.
+    buf: Option<B>,
+    pool: Arc<Shared<B>>,
+}
+
+impl<B> Pooled<B> {
+    /// Takes the buffer out of the pool for good.
+    pub fn into_inner(mut self) -> B {
+        self.buf.take().expect("buffer is present until dropped")
+    }
+}
+
+impl<B: Buffer> Deref for Pooled<B> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf
+            .as_ref()
+            .expect("buffer is present until dropped")
+            .as_ref()
+    }
+}
+
+impl<B: Buffer> DerefMut for Pooled<B> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buf
+            .as_mut()
+            .expect("buffer is present until dropped")
+            .as_mut()
+    }
+}
+
+impl<B> Drop for Pooled<B> {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            let mut idle = self.pool.idle();
+            if idle.len() < self.pool.count {
+                idle.push(buf);
+            }
+        }
+    }
+}
+
+impl<B> fmt::Debug for Pooled<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pooled")
+            .field("chunk_size", &self.pool.chunk_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferPool;
+
+    #[test]
+    fn reuses_up_to_count_buffers() {
+        let pool: BufferPool = BufferPool::new(8, 1);
+        let mut first = pool.get();
+        first[0] = 42;
+        let addr = first.as_ptr();
+        let second = pool.get();
+        drop(first);
+        drop(second);
+        assert_eq!(pool.idle(), 1);
+
+        let reused = pool.clone().get();
+        assert_eq!(reused.as_ptr(), addr);
+        assert_eq!(reused[0], 42);
+        assert_eq!(pool.idle(), 0);
+
+        let kept = reused.into_inner();
+        assert_eq!(kept.len(), 8);
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn pools_bytes_mut() {
+        let pool: BufferPool<bytes::BytesMut> = BufferPool::new(4, 2);
+        let buf = pool.get();
+        assert_eq!(&buf[..], &[0; 4]);
+        drop(buf);
+        assert_eq!(pool.idle(), 1);
+    }
+}
@@ -0,0 +1,252 @@
+//! Stripping fixed preambles from every source.
+//!
+//! Many exported data files start with a fixed number of bytes or lines which aren't part of the
+//! data. [`SkipPreamble`] drops them from every source, optionally keeping the preamble of the
+//! first source.
+//!
+//! ```
+//! use concat_reader::*;
+//! use std::io::prelude::*;
+//!
+//! let exports: Vec<&[u8]> = vec![b"MAGIC# export 1\ndata 1\n", b"MAGIC# export 2\ndata 2\n"];
+//! let mut reader = ConcatReader::new(exports)
+//!     .skip_bytes_per_source(5)
+//!     .skip_lines_per_source(1);
+//!
+//! let mut buffer = String::new();
+//! reader.read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "data 1\ndata 2\n");
+//! ```
+//!
+//! [`SkipPreamble`]:           struct.SkipPreamble.html
+use crate::layer::UnframedRead;
+use crate::provenance;
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::cmp;
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// A reader adapter which skips a number of bytes and lines at the start of every source.
+///
+/// The bytes are skipped first, the lines after them. Lines end at `\n`. A source which is
+/// shorter than its preamble produces no data.
+///
+/// This struct is created by the `skip_bytes_per_source` and `skip_lines_per_source` methods of
+/// [`ConcatReader`] and [`FileConcatReader`]. The wrapped reader is expected to be at the start
+/// of a source. `SkipPreamble` implements [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`]
+/// when the wrapped reader does.
+///
+/// [`ConcatReader`]:           ../read/struct.ConcatReader.html
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct SkipPreamble<R> {
+    inner: R,
+    bytes: u64,
+    lines: u64,
+    except_first: bool,
+    first: bool,
+    bytes_left: u64,
+    lines_left: u64,
+    position: u64,
+}
+
+impl<R: UnframedRead> SkipPreamble<R> {
+    /// Creates a new `SkipPreamble` which skips nothing.
+    pub fn new(inner: R) -> Self {
+        SkipPreamble {
+            inner,
+            bytes: 0,
+            lines: 0,
+            except_first: false,
+            first: true,
+            bytes_left: 0,
+            lines_left: 0,
+            position: 0,
+        }
+    }
+
+    /// Skips `n` bytes at the start of every source.
+    pub fn skip_bytes_per_source(mut self, n: u64) -> Self {
+        self.bytes = n;
+        self.reset();
+        self
+    }
+
+    /// Skips `n` lines at the start of every source, after the skipped bytes.
+    pub fn skip_lines_per_source(mut self, n: u64) -> Self {
+        self.lines = n;
+        self.reset();
+        self
+    }
+
+    /// Keeps the preamble of the first source.
+    pub fn except_first(mut self) -> Self {
+        self.except_first = true;
+        self.reset();
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `SkipPreamble`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn reset(&mut self) {
+        if self.first && self.except_first {
+            self.bytes_left = 0;
+            self.lines_left = 0;
+        } else {
+            self.bytes_left = self.bytes;
+            self.lines_left = self.lines;
+        }
+    }
+
+    /// Returns the offset of the first byte in `buf` after the preamble.
+    fn strip(&mut self, buf: &[u8]) -> usize {
+        let mut start = cmp::min(self.bytes_left, buf.len() as u64) as usize;
+        self.bytes_left -= start as u64;
+
+        while self.lines_left > 0 && start < buf.len() {
+            match buf[start..].iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    start += i + 1;
+                    self.lines_left -= 1;
+                }
+                None => start = buf.len(),
+            }
+        }
+        start
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.inner.read_source(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+
+            let start = self.strip(&buf[..n]);
+            if start < n {
+                buf.copy_within(start..n, 0);
+                return Ok(n - start);
+            }
+        }
+    }
+}
+
+impl<R: UnframedRead> Read for SkipPreamble<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || self.inner.current().is_none() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<R: UnframedRead> ConcatRead for SkipPreamble<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.first = false;
+        self.reset();
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.take_current()?;
+        self.first = false;
+        self.reset();
+        Some(item)
+    }
+}
+
+impl<R: UnframedRead> SourceRead for SkipPreamble<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.read_inner(buf);
+        provenance::track(res, "skip_preamble", &mut self.position)
+    }
+}
+
+impl<R: UnframedRead> UnframedRead for SkipPreamble<R> {}
+
+impl<R: UnframedRead + FileConcatRead> FileConcatRead for SkipPreamble<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkipPreamble;
+    use crate::test_util::TempTree;
+    use crate::{ConcatReader, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn skips_preamble_of_every_source_but_the_first() {
+        let bytes: Vec<&[u8]> = vec![b"#a\n#b\n1\n", b"#a\n", b"#a\n#b\n2\n"];
+        let mut reader = SkipPreamble::new(ConcatReader::new(bytes))
+            .skip_lines_per_source(2)
+            .except_first();
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "#a\n#b\n1\n2\n");
+    }
+
+    #[test]
+    fn skips_preamble_read_in_small_chunks() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"HDRline 1\nrest 1").unwrap(),
+            tree.file("b", b"HDRline 2\nrest 2").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(&paths)
+            .skip_bytes_per_source(3)
+            .skip_lines_per_source(1);
+
+        let mut out = Vec::new();
+        let mut buf = [0; 2];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out, b"rest 1rest 2");
+    }
+}
@@ -0,0 +1,505 @@
+//! Progress reporting for long running reads.
+//!
+//! [`ProgressReader`] counts the bytes read through it and calls a [`ProgressObserver`] every
+//! time a configurable amount of bytes was read and once at `EOF`. The total size is computed
+//! from file metadata when the reader is created from a [`FileConcatReader`]. If the length of a
+//! source can't be known up front, e.g. for FIFOs or network streams, the total is `None` and only
+//! the bytes read are reported.
+//!
+//! When the bytes produced by a source don't reflect the work it takes, e.g. for compressed
+//! files, a [`WeightedProgressReader`] measures the progress in completed sources with per-source
+//! weights instead.
+//!
+//! ```no_run
+//! use concat_reader::progress::Progress;
+//! use concat_reader::*;
+//! use std::io::prelude::*;
+//!
+//! let files = vec!["/path/to/file_1", "/path/to/file_2", "/path/to/file_3"];
+//! let mut c = FileConcatReader::new(files).progress(|p: &Progress| {
+//!     match p.total_bytes {
+//!         Some(total) => eprintln!("{} of {} bytes", p.bytes_read, total),
+//!         None => eprintln!("{} bytes", p.bytes_read),
+//!     }
+//! });
+//! let mut buffer = Vec::new();
+//! c.read_to_end(&mut buffer).unwrap();
+//! ```
+//!
+//! [`ProgressReader`]:         struct.ProgressReader.html
+//! [`ProgressObserver`]:       trait.ProgressObserver.html
+//! [`WeightedProgressReader`]: struct.WeightedProgressReader.html
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+use crate::layer::{RawRead, UnframedRead};
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const DEFAULT_INTERVAL: u64 = 64 * 1024;
+
+/// A snapshot of the progress of a `ProgressReader`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// Number of bytes read so far.
+    pub bytes_read: u64,
+    /// Expected number of bytes in total, `None` if the length of a source is unknown. This is a
+    /// best-effort estimate, which is dropped as soon as more bytes were read.
+    pub total_bytes: Option<u64>,
+    /// Time since the `ProgressReader` was created.
+    pub elapsed: Duration,
+    /// Number of completely read sources. Only counted by a `WeightedProgressReader`.
+    pub sources_done: usize,
+    /// Summed weight of the completely read sources.
+    pub weight_done: u64,
+    /// Summed weight of all sources, `0` if the progress is measured in bytes.
+    pub total_weight: u64,
+}
+
+impl Progress {
+    /// Returns the progress as a fraction between `0.0` and `1.0`.
+    ///
+    /// The fraction is computed from the source weights if there are any, otherwise from the
+    /// bytes read. Returns `None` if the total is unknown or if more bytes were read than expected,
+    /// as the estimate is wrong then.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.total_weight > 0 {
+            return Some((self.weight_done as f64 / self.total_weight as f64).min(1.0));
+        }
+        match self.total_bytes? {
+            total if self.bytes_read > total => None,
+            0 => Some(1.0),
+            total => Some(self.bytes_read as f64 / total as f64),
+        }
+    }
+
+    /// Estimates the remaining time from the throughput so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction()?;
+        if fraction == 0.0 {
+            return None;
+        }
+        let secs = self.elapsed.as_secs_f64();
+        Some(Duration::from_secs_f64(secs / fraction - secs))
+    }
+}
+
+/// Receives progress updates of a `ProgressReader`.
+///
+/// The trait is implemented for all `FnMut(&Progress)` closures.
+pub trait ProgressObserver {
+    /// Called periodically while reading and once when the reader reaches `EOF`.
+    fn on_progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressObserver for F {
+    fn on_progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// A reader adapter which reports its progress to a [`ProgressObserver`].
+///
+/// `ProgressReader` implements [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`] when the
+/// wrapped reader does.
+///
+/// [`ProgressObserver`]:       trait.ProgressObserver.html
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct ProgressReader<R, O> {
+    inner: R,
+    observer: O,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+    interval: u64,
+    next_report: u64,
+    start: Instant,
+    finished: bool,
+}
+
+impl<R: Read, O: ProgressObserver> ProgressReader<R, O> {
+    /// Creates a new `ProgressReader` expecting `total_bytes` to be read.
+    ///
+    /// Pass `None` if the total is unknown.
+    pub fn new(inner: R, total_bytes: Option<u64>, observer: O) -> Self {
+        ProgressReader {
+            inner,
+            observer,
+            bytes_read: 0,
+            total_bytes,
+            interval: DEFAULT_INTERVAL,
+            next_report: DEFAULT_INTERVAL,
+            start: Instant::now(),
+            finished: false,
+        }
+    }
+
+    /// Sets the number of bytes between two progress updates. Defaults to 64 KiB.
+    pub fn interval(mut self, bytes: u64) -> Self {
+        self.interval = bytes.max(1);
+        self.next_report = self.bytes_read + self.interval;
+        self
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the expected number of bytes in total, `None` if it is unknown.
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.total_bytes
+    }
+
+    /// Returns the current progress.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes_read: self.bytes_read,
+            // files in `/proc` report a length of 0 but produce data
+            total_bytes: self.total_bytes.filter(|&total| self.bytes_read <= total),
+            elapsed: self.start.elapsed(),
+            sources_done: 0,
+            weight_done: 0,
+            total_weight: 0,
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `ProgressReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn report(&mut self) {
+        let progress = self.progress();
+        self.observer.on_progress(&progress);
+    }
+
+    fn count(&mut self, n: usize, eof: bool) {
+        self.bytes_read += n as u64;
+
+        if eof {
+            if !self.finished {
+                self.finished = true;
+                self.report();
+            }
+        } else if self.bytes_read >= self.next_report {
+            self.next_report = self.bytes_read + self.interval;
+            self.report();
+        }
+    }
+}
+
+impl<R: Read, O: ProgressObserver> Read for ProgressReader<R, O> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count(n, n == 0 && !buf.is_empty());
+        Ok(n)
+    }
+}
+
+impl<R: SourceRead, O: ProgressObserver> SourceRead for ProgressReader<R, O> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read_source(buf)?;
+        self.count(
+            n,
+            n == 0 && !buf.is_empty() && self.inner.current().is_none(),
+        );
+        Ok(n)
+    }
+}
+
+impl<R: UnframedRead, O: ProgressObserver> UnframedRead for ProgressReader<R, O> {}
+
+impl<R: RawRead, O: ProgressObserver> RawRead for ProgressReader<R, O> {}
+
+impl<R: ConcatRead, O: ProgressObserver> ConcatRead for ProgressReader<R, O> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        self.inner.take_current()
+    }
+}
+
+impl<R: FileConcatRead, O: ProgressObserver> FileConcatRead for ProgressReader<R, O> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+/// A progress reader which measures progress in completed sources, each with its own weight.
+///
+/// The number of bytes produced by a source is not always a good measure of the work it takes,
+/// e.g. when sources are decompressed or filtered. `WeightedProgressReader` counts a source as
+/// done when the reader moves past its end and reports the summed weights of the done sources
+/// instead. Good weights are the on-disk sizes of compressed files or any user supplied estimate.
+///
+/// ```no_run
+/// use concat_reader::progress::{Progress, WeightedProgressReader};
+/// use concat_reader::*;
+/// use std::io::prelude::*;
+///
+/// let files = vec!["small.log.gz", "huge.log.gz"];
+/// let weights = vec![10, 9000];
+/// let mut c = WeightedProgressReader::new(concat_path(files), weights, |p: &Progress| {
+///     eprintln!("{:.1}% eta {:?}", p.fraction().unwrap_or(0.0) * 100.0, p.eta());
+/// });
+/// let mut buffer = Vec::new();
+/// c.read_to_end(&mut buffer).unwrap();
+/// ```
+pub struct WeightedProgressReader<R, O> {
+    inner: ProgressReader<R, O>,
+    weights: Vec<u64>,
+    sources_done: usize,
+    weight_done: u64,
+    total_weight: u64,
+}
+
+impl<R: SourceRead, O: ProgressObserver> WeightedProgressReader<R, O> {
+    /// Creates a new `WeightedProgressReader` with the weights of the sources in read order.
+    ///
+    /// Sources without a weight count as `0`.
+    pub fn new(inner: R, weights: Vec<u64>, observer: O) -> Self {
+        let total_weight = weights.iter().sum();
+        WeightedProgressReader {
+            inner: ProgressReader::new(inner, None, observer),
+            weights,
+            sources_done: 0,
+            weight_done: 0,
+            total_weight,
+        }
+    }
+
+    /// Sets the number of bytes between two progress updates. Defaults to 64 KiB.
+    pub fn interval(mut self, bytes: u64) -> Self {
+        self.inner = self.inner.interval(bytes);
+        self
+    }
+
+    /// Returns the current progress.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            sources_done: self.sources_done,
+            weight_done: self.weight_done,
+            total_weight: self.total_weight,
+            ..self.inner.progress()
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Unwraps this `WeightedProgressReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+
+    fn report(&mut self) {
+        let progress = self.progress();
+        self.inner.observer.on_progress(&progress);
+    }
+
+    fn source_done(&mut self) {
+        self.weight_done += self.weights.get(self.sources_done).copied().unwrap_or(0);
+        self.sources_done += 1;
+        self.report();
+    }
+}
+
+impl<R: SourceRead, O: ProgressObserver> Read for WeightedProgressReader<R, O> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.inner.inner.read_source(buf)?;
+            if n > 0 {
+                self.inner.bytes_read += n as u64;
+                if self.inner.bytes_read >= self.inner.next_report {
+                    self.inner.next_report = self.inner.bytes_read + self.inner.interval;
+                    self.report();
+                }
+                return Ok(n);
+            }
+
+            if self.inner.inner.current().is_none() {
+                return Ok(0);
+            }
+            self.source_done();
+            if !self.inner.inner.skip() {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+impl<R: SourceRead, O: ProgressObserver> ConcatRead for WeightedProgressReader<R, O> {
+    type Item = R::Item;
+
+    /// Skips to the next source. The skipped source counts as done.
+    fn skip(&mut self) -> bool {
+        if self.inner.inner.current().is_some() {
+            self.source_done();
+        }
+        self.inner.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.inner.peek_next()
+    }
+
+    /// Takes the current source, which counts as done.
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.inner.take_current()?;
+        self.source_done();
+        Some(item)
+    }
+}
+
+impl<R: SourceRead + FileConcatRead, O: ProgressObserver> FileConcatRead
+    for WeightedProgressReader<R, O>
+{
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Progress, ProgressReader, WeightedProgressReader};
+    use crate::test_util::TempTree;
+    use crate::{ConcatReader, FileConcatRead, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn reports_periodically_and_at_eof() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333", b"4444"];
+        let mut reports = Vec::new();
+        {
+            let mut reader =
+                ProgressReader::new(ConcatReader::new(bytes), Some(10), |p: &Progress| {
+                    reports.push(p.bytes_read)
+                })
+                .interval(3);
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).unwrap();
+            reader.read_to_end(&mut buf).unwrap();
+        }
+        assert_eq!(reports, vec![3, 6, 10, 10]);
+    }
+
+    #[test]
+    fn computes_total_from_metadata() {
+        let (_tree, paths) = TempTree::corpus().unwrap();
+        let mut last = None;
+        let mut reader = FileConcatReader::new(&paths).progress(|p: &Progress| last = Some(*p));
+        let total = reader.total_bytes();
+
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert!(reader.file_path().is_some());
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        drop(reader);
+
+        let last = last.unwrap();
+        assert_eq!(Some(last.bytes_read), total);
+        assert_eq!(last.fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn reports_weighted_progress_per_source() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333"];
+        let mut reports = Vec::new();
+        {
+            let observer = |p: &Progress| reports.push((p.sources_done, p.fraction()));
+            let mut reader =
+                WeightedProgressReader::new(ConcatReader::new(bytes), vec![1, 1, 2], observer)
+                    .interval(1000);
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).unwrap();
+            reader.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, b"122333");
+        }
+        assert_eq!(
+            reports,
+            vec![(1, Some(0.25)), (2, Some(0.5)), (3, Some(1.0))]
+        );
+    }
+
+    #[test]
+    fn weights_files_by_size_on_disk() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.sized_file("a", 300).unwrap(),
+            tree.sized_file("b", 100).unwrap(),
+        ];
+        let mut last = None;
+        let mut reader =
+            FileConcatReader::new(&paths).progress_weighted(|p: &Progress| last = Some(*p));
+        assert_eq!(reader.progress().total_weight, 400);
+
+        let mut buf = vec![0; 300];
+        reader.read_exact(&mut buf).unwrap();
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(reader.progress().sources_done, 2);
+        drop(reader);
+        assert_eq!(last.unwrap().fraction(), Some(1.0));
+    }
+}
@@ -0,0 +1,265 @@
+//! Tracing errors through stacks of decorators.
+//!
+//! An error raised deep inside a stack of readers, e.g. a missing file below a transcoder and a
+//! normalizer, only tells what went wrong, not where. The readers, transcoders, transforms and
+//! framers of this crate annotate errors passing through them with a [`Crumb`]: the name of the
+//! layer, the current file if known and its offset. Observers like `TeeReader` pass errors on
+//! unchanged. The crumbs are collected in a [`Provenance`], which wraps the original error while
+//! keeping its `ErrorKind` and exposing it as its [`source`].
+//!
+//! ```
+//! use concat_reader::provenance::Provenance;
+//! use concat_reader::*;
+//! use std::io::prelude::*;
+//!
+//! let files = ["does-not-exist.txt"];
+//! let mut reader = FileConcatReader::new(&files).normalize_text();
+//!
+//! let mut buffer = Vec::new();
+//! let err = reader.read_to_end(&mut buffer).unwrap_err();
+//! let provenance = Provenance::of(&err).unwrap();
+//! let layers: Vec<_> = provenance.crumbs().iter().map(|c| c.layer).collect();
+//! assert_eq!(layers, ["file", "normalize"]);
+//! println!("{}", err); // ... (at file does-not-exist.txt offset 0 → normalize offset 0)
+//! ```
+//!
+//! An annotated error is a custom error, so `raw_os_error()` returns `None` on it. The OS error
+//! code of the original error is returned by [`raw_os_error`].
+//!
+//! [`Crumb`]:                  struct.Crumb.html
+//! [`Provenance`]:             struct.Provenance.html
+//! [`raw_os_error`]:           fn.raw_os_error.html
+//! [`source`]:                 https://doc.rust-lang.org/std/error/trait.Error.html#method.source
+use crate::read::Poisoned;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One layer an error passed through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crumb {
+    /// Name of the layer, e.g. `"file"` or `"transcode"`.
+    pub layer: &'static str,
+    /// The file the layer was reading from, if it knows it.
+    pub path: Option<PathBuf>,
+    /// Number of bytes the layer returned before the error. Readers count from the start of the
+    /// current source, decorators from the start of their output.
+    pub offset: u64,
+}
+
+impl fmt::Display for Crumb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.layer)?;
+        if let Some(path) = &self.path {
+            write!(f, " {}", path.display())?;
+        }
+        write!(f, " offset {}", self.offset)
+    }
+}
+
+/// An error together with the layers it passed through, innermost first.
+///
+/// The original error, with its OS error code, is returned by [`error`], or directly from the
+/// annotated error by [`raw_os_error`].
+///
+/// [`error`]:                  #method.error
+/// [`raw_os_error`]:           fn.raw_os_error.html
+#[derive(Debug)]
+pub struct Provenance {
+    error: io::Error,
+    crumbs: Vec<Crumb>,
+}
+
+impl Provenance {
+    /// Returns the provenance of `err` if it passed through an annotating layer.
+    pub fn of(err: &io::Error) -> Option<&Provenance> {
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+
+    /// Returns the original error.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Returns the layers the error passed through, innermost first.
+    pub fn crumbs(&self) -> &[Crumb] {
+        &self.crumbs
+    }
+
+    /// Returns the path of the file the error occurred in, if a layer knew it.
+    pub fn path(&self) -> Option<&Path> {
+        self.crumbs.iter().find_map(|c| c.path.as_deref())
+    }
+
+    /// Consumes the provenance, returning the original error.
+    pub fn into_error(self) -> io::Error {
+        self.error
+    }
+}
+
+impl From<Provenance> for io::Error {
+    fn from(provenance: Provenance) -> io::Error {
+        io::Error::new(provenance.error.kind(), provenance)
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at ", self.error)?;
+        for (i, crumb) in self.crumbs.iter().enumerate() {
+            if i > 0 {
+                write!(f, " → ")?;
+            }
+            write!(f, "{}", crumb)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Error for Provenance {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Returns the OS error code of `err` or of the original error it wraps.
+///
+/// Looks through a [`Provenance`] and through the [`Poisoned`] error of a poisoned reader.
+///
+/// ```
+/// use concat_reader::provenance;
+/// use concat_reader::*;
+/// use std::io::prelude::*;
+///
+/// let mut reader = FileConcatReader::new(&["does-not-exist.txt"]);
+/// let err = reader.read(&mut [0; 4]).unwrap_err();
+/// assert_eq!(err.raw_os_error(), None);
+/// assert!(provenance::raw_os_error(&err).is_some());
+/// ```
+///
+/// [`Provenance`]:             struct.Provenance.html
+/// [`Poisoned`]:               ../read/struct.Poisoned.html
+pub fn raw_os_error(err: &io::Error) -> Option<i32> {
+    if let Some(provenance) = Provenance::of(err) {
+        return raw_os_error(provenance.error());
+    }
+    if let Some(poisoned) = Poisoned::of(err) {
+        return raw_os_error(poisoned.error());
+    }
+    err.raw_os_error()
+}
+
+/// Adds a crumb for `layer` to `err`.
+///
+/// `Interrupted` and `WouldBlock` errors are returned unchanged, as callers retry them.
+pub fn annotate(
+    err: io::Error,
+    layer: &'static str,
+    path: Option<&Path>,
+    offset: u64,
+) -> io::Error {
+    let kind = err.kind();
+    if kind == io::ErrorKind::Interrupted || kind == io::ErrorKind::WouldBlock {
+        return err;
+    }
+
+    let crumb = Crumb {
+        layer,
+        path: path.map(Path::to_path_buf),
+        offset,
+    };
+    let mut provenance = if Provenance::of(&err).is_some() {
+        let inner = err.into_inner().expect("provenance is a custom error");
+        *inner
+            .downcast::<Provenance>()
+            .expect("checked by Provenance::of")
+    } else {
+        Provenance {
+            error: err,
+            crumbs: Vec::new(),
+        }
+    };
+    provenance.crumbs.push(crumb);
+    provenance.into()
+}
+
+/// Counts the bytes of a successful read of `layer` in `position` or annotates its error.
+pub(crate) fn track(
+    res: io::Result<usize>,
+    layer: &'static str,
+    position: &mut u64,
+) -> io::Result<usize> {
+    match res {
+        Ok(n) => {
+            *position += n as u64;
+            Ok(n)
+        }
+        Err(e) => Err(annotate(e, layer, None, *position)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{annotate, Provenance};
+    use crate::peek::PeekReader;
+    use crate::preamble::SkipPreamble;
+    use crate::test_util::TempTree;
+    use crate::FileConcatReader;
+    use std::io::{self, Read};
+    use std::path::Path;
+
+    #[test]
+    fn collects_crumbs_and_keeps_kind() {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "bad data");
+        let err = annotate(err, "file", Some(Path::new("a.log")), 10);
+        let err = annotate(err, "transcode", None, 12);
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "bad data (at file a.log offset 10 → transcode offset 12)"
+        );
+        let provenance = Provenance::of(&err).unwrap();
+        assert_eq!(provenance.error().to_string(), "bad data");
+        assert_eq!(provenance.crumbs().len(), 2);
+        assert_eq!(provenance.path(), Some(Path::new("a.log")));
+        let source = err.get_ref().unwrap().source().unwrap();
+        assert_eq!(source.to_string(), "bad data");
+
+        let err = annotate(io::Error::from(io::ErrorKind::Interrupted), "file", None, 0);
+        assert!(Provenance::of(&err).is_none());
+    }
+
+    #[test]
+    fn traces_errors_through_decorator_stack() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"# header\r\nline\r\n").unwrap(),
+            tree.broken_symlink("b").unwrap(),
+        ];
+        let reader = FileConcatReader::new(&paths).normalize_text();
+        let mut reader = PeekReader::new(SkipPreamble::new(reader).skip_lines_per_source(1));
+
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(buf, b"line\n");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let crumbs = Provenance::of(&err).unwrap().crumbs();
+        let layers: Vec<_> = crumbs.iter().map(|c| (c.layer, c.offset)).collect();
+        assert_eq!(
+            layers,
+            [
+                ("file", 0),
+                ("normalize", 14),
+                ("skip_preamble", 5),
+                ("peek", 5)
+            ]
+        );
+        assert_eq!(crumbs[0].path.as_deref(), Some(paths[1].as_path()));
+        assert!(err
+            .to_string()
+            .ends_with("→ skip_preamble offset 5 → peek offset 5)"));
+    }
+}
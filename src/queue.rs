@@ -0,0 +1,113 @@
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// A growable source list which can be extended after a concat reader was created.
+///
+/// `SourceQueue` is an [`Iterator`] which yields the items pushed into it in FIFO order.
+/// An empty queue yields `None` but, unlike most iterators, it will yield items again once new
+/// items have been pushed. Readers backed by a `SourceQueue` therefore report `EOF` as long as
+/// the queue is drained and continue reading once new sources have been appended.
+///
+/// Items can be pushed from other threads through the [`Sender`] returned by
+/// [`SourceQueue::sender`].
+///
+/// # Examples
+/// ```
+/// use concat_reader::*;
+/// use std::io::prelude::*;
+///
+/// let mut c = ConcatReader::with_queue();
+/// c.push_source("some ".as_bytes());
+///
+/// let mut buffer = String::new();
+/// c.read_to_string(&mut buffer).unwrap();
+/// assert_eq!(buffer, "some ");
+///
+/// // the reader is at EOF now, but picks up new sources
+/// c.push_source("more".as_bytes());
+/// c.read_to_string(&mut buffer).unwrap();
+/// assert_eq!(buffer, "some more");
+/// ```
+///
+/// [`Iterator`]:               https://doc.rust-lang.org/std/iter/trait.Iterator.html
+/// [`Sender`]:                 https://doc.rust-lang.org/std/sync/mpsc/struct.Sender.html
+pub struct SourceQueue<T> {
+    tx: Sender<T>,
+    rx: Receiver<T>,
+}
+
+impl<T> SourceQueue<T> {
+    /// Creates a new empty `SourceQueue`.
+    pub fn new() -> Self {
+        let (tx, rx) = channel();
+        SourceQueue { tx, rx }
+    }
+
+    /// Appends an item to the back of the queue.
+    pub fn push(&mut self, item: T) {
+        // the queue owns the receiving end, sending can't fail
+        let _ = self.tx.send(item);
+    }
+
+    /// Returns a [`Sender`] which can be used to append items from another thread.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    /// use std::thread;
+    ///
+    /// let mut c = ConcatReader::with_queue();
+    /// let sender = c.sender();
+    /// thread::spawn(move || {
+    ///     sender.send("from another thread".as_bytes()).unwrap();
+    /// }).join().unwrap();
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "from another thread");
+    /// ```
+    ///
+    /// [`Sender`]:                 https://doc.rust-lang.org/std/sync/mpsc/struct.Sender.html
+    pub fn sender(&self) -> Sender<T> {
+        self.tx.clone()
+    }
+}
+
+impl<T> Default for SourceQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Iterator for SourceQueue<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> fmt::Debug for SourceQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SourceQueue").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceQueue;
+
+    #[test]
+    fn yields_items_pushed_after_drain() {
+        let mut queue = SourceQueue::new();
+        queue.push(1);
+        assert_eq!(queue.next(), Some(1));
+        assert_eq!(queue.next(), None);
+
+        queue.push(2);
+        queue.sender().send(3).unwrap();
+        assert_eq!(queue.next(), Some(2));
+        assert_eq!(queue.next(), Some(3));
+        assert_eq!(queue.next(), None);
+    }
+}
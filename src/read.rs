@@ -1,12 +1,33 @@
-use crate::ConcatRead;
+use crate::cancel::{CancelToken, Cancelled};
+use crate::layer::{RawRead, UnframedRead};
+use crate::preamble::SkipPreamble;
+use crate::provenance;
+use crate::queue::SourceQueue;
+use crate::restart::Restart;
+use crate::tee::TeeReader;
+use crate::throttle::Throttle;
+use crate::timeout::Timeout;
+use crate::{ConcatRead, SourceRead};
+use std::cmp;
+use std::error::Error;
 use std::fmt;
-use std::io::{Read, Result};
+use std::io::{self, Read, Result, Write};
+use std::iter::{self, Chain};
+use std::option;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use std::vec;
 
 /// The `ConcatReader` struct allows to read from multiple readers in a sequential order.
 ///
 /// If the current reader reaches its `EOF` the `ConcatReader` will start reading from the next
 /// reader in the iterator. If all readers reached `EOF` the `ConcatReader` will also be `EOF`.
 ///
+/// Once the iterator returned `None` it isn't polled again, so a finished `ConcatReader` keeps
+/// reporting `EOF` even if the iterator isn't fused. Only a reader created by [`with_queue`]
+/// continues with readers pushed after `EOF`.
+///
 /// # Examples
 /// ```no_run
 /// use concat_reader::*;
@@ -36,9 +57,140 @@ use std::io::{Read, Result};
 ///     Ok(())
 /// }
 /// ```
+///
+/// [`with_queue`]:             #method.with_queue
 pub struct ConcatReader<I: IntoIterator> {
     curr: Option<I::Item>,
+    /// Item taken from `iter` by `peek_next`.
+    peeked: Option<I::Item>,
     iter: I::IntoIter,
+    pos: u64,
+    total: u64,
+    /// Predicate set by `filter_sources`.
+    filter: Option<Filter<I::Item>>,
+    cancel: Option<CancelToken>,
+    /// Whether `iter` returned `None` and isn't polled again.
+    exhausted: bool,
+    /// Error all reads fail with, set by `AfterError::Poison`.
+    poisoned: Option<Arc<io::Error>>,
+    opts: Options,
+}
+
+type Filter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Options {
+    limit_per_source: Option<u64>,
+    limit_total: Option<u64>,
+    /// Stops polling `iter` after it returned `None`, unset for queues.
+    fuse: bool,
+    after_error: AfterError,
+    return_interrupted: bool,
+    non_blocking: bool,
+}
+
+/// What a concat reader does after a read failed, see [`ConcatReader::after_error`] and
+/// [`FileConcatReader::after_error`].
+///
+/// The policy applies to every way of reading, `read`, [`read_source`] and `copy_to`, and so to
+/// the iterators and copy helpers built on them. It doesn't apply to errors of the writer of
+/// `copy_to`.
+///
+/// Errors of kind `Interrupted` and cancellations leave the reader as it is under every policy,
+/// as the next read may succeed. So do `WouldBlock` errors of a reader in non-blocking mode, in
+/// blocking mode they are handled like any other error.
+///
+/// [`ConcatReader::after_error`]: struct.ConcatReader.html#method.after_error
+/// [`FileConcatReader::after_error`]: ../file/struct.FileConcatReader.html#method.after_error
+/// [`read_source`]:            ../trait.SourceRead.html#tymethod.read_source
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AfterError {
+    /// Stays on the failed source, the next read reads from it again. A file which failed to
+    /// open returns the same error until the reader is moved on with `skip`.
+    #[default]
+    Stay,
+    /// Moves on to the next source, so the error is returned once and the next read continues
+    /// with the next source.
+    Skip,
+    /// Fails every further read with a [`Poisoned`] error wrapping the first error.
+    ///
+    /// [`Poisoned`]:           struct.Poisoned.html
+    Poison,
+}
+
+impl AfterError {
+    /// Returns `true` if `err` changes the state of a reader under this policy.
+    pub(crate) fn applies_to(self, err: &io::Error, non_blocking: bool) -> bool {
+        let transient = match err.kind() {
+            io::ErrorKind::Interrupted => true,
+            io::ErrorKind::WouldBlock => non_blocking,
+            _ => false,
+        };
+        self != AfterError::Stay && !transient && Cancelled::of(err).is_none()
+    }
+}
+
+/// The error a reader fails with under [`AfterError::Poison`].
+///
+/// The failed read and every read after it return a `Poisoned` error. It keeps the `ErrorKind`
+/// of the first error and exposes it as its [`source`], together with its OS error code and
+/// [`Provenance`].
+///
+/// [`AfterError::Poison`]:     enum.AfterError.html#variant.Poison
+/// [`source`]:                 https://doc.rust-lang.org/std/error/trait.Error.html#method.source
+/// [`Provenance`]:             ../provenance/struct.Provenance.html
+#[derive(Clone, Debug)]
+pub struct Poisoned {
+    error: Arc<io::Error>,
+    /// Whether the error is returned by a read after the failed one.
+    earlier: bool,
+}
+
+impl Poisoned {
+    /// Returns the `Poisoned` error inside `err`, if it is one.
+    pub fn of(err: &io::Error) -> Option<&Poisoned> {
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+
+    /// Returns the error of the read which poisoned the reader.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Poisons a reader with `err`, returning the error to keep and the error of the failed read.
+    pub(crate) fn first(err: io::Error) -> (Arc<io::Error>, io::Error) {
+        let kind = err.kind();
+        let error = Arc::new(err);
+        let poisoned = Poisoned {
+            error: error.clone(),
+            earlier: false,
+        };
+        (error, io::Error::new(kind, poisoned))
+    }
+
+    /// Returns the error of a read from a reader poisoned with `error`.
+    pub(crate) fn repeat(error: &Arc<io::Error>) -> io::Error {
+        let poisoned = Poisoned {
+            error: error.clone(),
+            earlier: true,
+        };
+        io::Error::new(error.kind(), poisoned)
+    }
+}
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.earlier {
+            write!(f, "an earlier read failed: ")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for Poisoned {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
 }
 
 impl<I> ConcatReader<I>
@@ -57,7 +209,705 @@ where
     pub fn new(iter: I) -> Self {
         let mut iter = iter.into_iter();
         let curr = iter.next();
-        Self { iter, curr }
+        Self {
+            iter,
+            exhausted: curr.is_none(),
+            poisoned: None,
+            curr,
+            peeked: None,
+            pos: 0,
+            total: 0,
+            filter: None,
+            cancel: None,
+            opts: Options {
+                fuse: true,
+                ..Options::default()
+            },
+        }
+    }
+
+    /// Limits the number of bytes read from each reader.
+    ///
+    /// After `limit` bytes have been read from the current reader, the `ConcatReader` continues
+    /// with the next reader, as if [`Read::take`] was applied to each item. [`current`] still
+    /// returns the unwrapped item.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["first".as_bytes(), "second".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes).limit_per_source(3);
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "firsec");
+    /// ```
+    ///
+    /// [`Read::take`]:             https://doc.rust-lang.org/std/io/trait.Read.html#method.take
+    /// [`current`]:                ../trait.ConcatRead.html#tymethod.current
+    pub fn limit_per_source(mut self, limit: u64) -> Self {
+        self.opts.limit_per_source = Some(limit);
+        self
+    }
+
+    /// Limits the number of bytes read from all readers together.
+    ///
+    /// After `limit` bytes have been read the `ConcatReader` reports `EOF`, even if there are
+    /// readers left. The reader it stopped at is still available through [`current`] and
+    /// [`current_position`].
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["first".as_bytes(), "second".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes).limit_total(7);
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "firstse");
+    /// assert_eq!(c.current_position(), 2);
+    /// ```
+    ///
+    /// [`current`]:                ../trait.ConcatRead.html#tymethod.current
+    /// [`current_position`]:       #method.current_position
+    pub fn limit_total(mut self, limit: u64) -> Self {
+        self.opts.limit_total = Some(limit);
+        self
+    }
+
+    /// Checks `token` before every read, see [`CancelToken`].
+    ///
+    /// [`CancelToken`]:            ../cancel/struct.CancelToken.html
+    pub fn cancel_on(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets what the reader does after a read failed, see [`AfterError`].
+    ///
+    /// ```
+    /// use concat_reader::read::AfterError;
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    ///
+    /// struct Broken;
+    ///
+    /// impl Read for Broken {
+    ///     fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+    ///         Err(io::Error::new(io::ErrorKind::Other, "broken"))
+    ///     }
+    /// }
+    ///
+    /// let sources: Vec<Box<dyn Read>> = vec![Box::new(Broken), Box::new(&b"ok"[..])];
+    /// let mut c = ConcatReader::new(sources).after_error(AfterError::Skip);
+    /// let mut buffer = String::new();
+    /// assert!(c.read_to_string(&mut buffer).is_err());
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "ok");
+    /// ```
+    ///
+    /// [`AfterError`]:             enum.AfterError.html
+    pub fn after_error(mut self, policy: AfterError) -> Self {
+        self.opts.after_error = policy;
+        self
+    }
+
+    /// Returns `Interrupted` errors of the current reader instead of retrying the read.
+    ///
+    /// By default a read interrupted by a signal is retried until it succeeds, fails otherwise
+    /// or the token set with [`cancel_on`] is cancelled, like `read_exact` does. Callers which
+    /// act on signals in their own read loop can have the errors returned to them instead.
+    ///
+    /// [`cancel_on`]:              #method.cancel_on
+    pub fn return_interrupted(mut self) -> Self {
+        self.opts.return_interrupted = true;
+        self
+    }
+
+    /// Turns on non-blocking mode, for sources like non-blocking sockets which return
+    /// `WouldBlock` while no data is available.
+    ///
+    /// A `WouldBlock` error of the current reader is returned as it is and the reader stays on
+    /// that source, whatever [`after_error`] policy is set, so the read can be repeated once the
+    /// source is ready. In blocking mode `WouldBlock` is handled like any other error, and
+    /// `AfterError::Skip` moves on to the next source.
+    ///
+    /// ```
+    /// use concat_reader::read::AfterError;
+    /// use concat_reader::*;
+    /// use std::io::{self, Read};
+    ///
+    /// struct NotReady;
+    ///
+    /// impl Read for NotReady {
+    ///     fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+    ///         Err(io::ErrorKind::WouldBlock.into())
+    ///     }
+    /// }
+    ///
+    /// let sources: Vec<Box<dyn Read>> = vec![Box::new(NotReady), Box::new(&b"ok"[..])];
+    /// let mut c = ConcatReader::new(sources)
+    ///     .after_error(AfterError::Skip)
+    ///     .non_blocking();
+    /// let err = c.read(&mut [0; 2]).unwrap_err();
+    /// assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    /// assert!(c.read(&mut [0; 2]).is_err());
+    /// ```
+    ///
+    /// [`after_error`]:            #method.after_error
+    pub fn non_blocking(mut self) -> Self {
+        self.opts.non_blocking = true;
+        self
+    }
+
+    /// Reads only the readers which pass `filter`.
+    ///
+    /// The predicate is called once per item when the `ConcatReader` reaches it, before anything
+    /// is read from it. Setting a filter replaces a previous one.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes: Vec<&[u8]> = vec![b"first", b"", b"# note", b"second"];
+    /// let mut c = ConcatReader::new(bytes).filter_sources(|item| !item.starts_with(b"#"));
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "firstsecond");
+    /// ```
+    pub fn filter_sources<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&I::Item) -> bool + Send + Sync + 'static,
+    {
+        let rejected = self.pos == 0 && self.curr.as_ref().is_some_and(|curr| !filter(curr));
+        self.filter = Some(Box::new(filter));
+        if rejected {
+            self.next_source();
+        }
+        self
+    }
+
+    /// Wraps the reader in a [`SkipPreamble`] reader which skips `n` bytes at the start of every
+    /// reader.
+    ///
+    /// Chain [`skip_lines_per_source`] to skip lines after the bytes and [`except_first`] to keep
+    /// the preamble of the first reader.
+    ///
+    /// [`SkipPreamble`]:           ../preamble/struct.SkipPreamble.html
+    /// [`skip_lines_per_source`]:  ../preamble/struct.SkipPreamble.html#method.skip_lines_per_source
+    /// [`except_first`]:           ../preamble/struct.SkipPreamble.html#method.except_first
+    pub fn skip_bytes_per_source(self, n: u64) -> SkipPreamble<Self> {
+        SkipPreamble::new(self).skip_bytes_per_source(n)
+    }
+
+    /// Wraps the reader in a [`SkipPreamble`] reader which skips `n` lines at the start of every
+    /// reader.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["# 1\nfirst".as_bytes(), "# 2\nsecond".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes).skip_lines_per_source(1);
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "firstsecond");
+    /// ```
+    ///
+    /// [`SkipPreamble`]:           ../preamble/struct.SkipPreamble.html
+    pub fn skip_lines_per_source(self, n: u64) -> SkipPreamble<Self> {
+        SkipPreamble::new(self).skip_lines_per_source(n)
+    }
+
+    /// Wraps the reader in a [`TeeReader`] which writes every byte read into `writer`.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["first".as_bytes(), "second".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes).tee(Vec::new());
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(c.into_inner().1, buffer.as_bytes());
+    /// ```
+    ///
+    /// [`TeeReader`]:              ../tee/struct.TeeReader.html
+    pub fn tee<W: Write>(self, writer: W) -> TeeReader<Self, W> {
+        TeeReader::new(self, writer)
+    }
+
+    /// Wraps the reader in a [`Throttle`] reader, which reads at most `bytes_per_sec` bytes per
+    /// second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sec` is `0`.
+    ///
+    /// [`Throttle`]:               ../throttle/struct.Throttle.html
+    pub fn throttle(self, bytes_per_sec: u64) -> Throttle<Self> {
+        Throttle::new(self, bytes_per_sec)
+    }
+
+    /// Wraps the reader in a [`Timeout`] reader, which fails reads taking longer than `timeout`
+    /// with an error of kind `TimedOut`.
+    ///
+    /// The reader is moved to a thread of its own, so it has to be `Send`.
+    ///
+    /// [`Timeout`]:                ../timeout/struct.Timeout.html
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self>
+    where
+        Self: Send + 'static,
+    {
+        Timeout::new(self, timeout)
+    }
+
+    /// Wraps every reader in the reader returned by `map`.
+    ///
+    /// `map` is called once per item when the `ConcatReader` reaches it. A filter set with
+    /// [`filter_sources`] still sees the unwrapped items.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["first".as_bytes(), "second".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes).map_sources(|item| item.take(3));
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "firsec");
+    /// ```
+    ///
+    /// [`filter_sources`]:         #method.filter_sources
+    #[allow(clippy::type_complexity)]
+    pub fn map_sources<F, W>(
+        self,
+        mut map: F,
+    ) -> ConcatReader<iter::Map<iter::Filter<I::IntoIter, Filter<I::Item>>, F>>
+    where
+        F: FnMut(I::Item) -> W,
+        W: Read,
+    {
+        let filter = self.filter.unwrap_or_else(|| Box::new(|_| true));
+        ConcatReader {
+            curr: self.curr.map(&mut map),
+            peeked: self.peeked.map(&mut map),
+            iter: self.iter.filter(filter).map(map),
+            pos: self.pos,
+            total: self.total,
+            filter: None,
+            cancel: self.cancel,
+            exhausted: self.exhausted,
+            poisoned: self.poisoned,
+            opts: self.opts,
+        }
+    }
+
+    /// Returns the number of bytes read from all readers.
+    pub fn position(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns `true` if the reader reached its end, so every `read` returns `Ok(0)`.
+    ///
+    /// Tells a true end apart from an `Ok(0)` for an empty buffer. The reader is finished when
+    /// the last reader reached `EOF` and the iterator returned `None`, or when [`limit_total`]
+    /// was reached. A reader created by [`with_queue`] is finished while its queue is empty.
+    ///
+    /// [`limit_total`]:            #method.limit_total
+    /// [`with_queue`]:             #method.with_queue
+    pub fn is_finished(&self) -> bool {
+        let ended =
+            self.curr.is_none() && self.peeked.is_none() && (self.exhausted || !self.opts.fuse);
+        ended || self.total_left() == Some(0)
+    }
+
+    /// Returns the number of bytes read from the current reader.
+    pub fn current_position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Returns the bounds on the number of readers left, including the current one, like
+    /// `Iterator::size_hint`.
+    ///
+    /// The bounds are taken from the `size_hint` of the iterator. With a filter set by
+    /// [`filter_sources`] the lower bound only counts the readers which already passed it.
+    ///
+    /// [`filter_sources`]:         #method.filter_sources
+    pub fn sources_hint(&self) -> (usize, Option<usize>) {
+        let known = self.curr.is_some() as usize + self.peeked.is_some() as usize;
+        let (lower, upper) = self.iter.size_hint();
+        let lower = if self.filter.is_some() { 0 } else { lower };
+        (
+            lower.saturating_add(known),
+            upper.and_then(|upper| upper.checked_add(known)),
+        )
+    }
+
+    /// Copies all remaining bytes to `writer`, returning the number of bytes copied.
+    ///
+    /// Unlike `io::copy(&mut reader, writer)`, which copies through the generic `read` of the
+    /// `ConcatReader`, every reader is copied with an `io::copy` of its own. This keeps the fast
+    /// paths of the standard library, like `copy_file_range`, `sendfile` and `splice` on Linux,
+    /// for items which are files, pipes or sockets. The byte limits apply as for `read`, and
+    /// [`position`] counts the bytes taken from a reader even if copying them failed.
+    ///
+    /// The [`after_error`] policy only applies to errors of the readers, which are annotated like
+    /// those of `read`. To tell them from errors of `writer`, which are returned unchanged, the
+    /// policies `Skip` and `Poison` copy through a wrapper of `writer`, which gives up the fast
+    /// paths. With the default `Stay` the errors of `io::copy` are returned unchanged.
+    ///
+    /// The bytes taken from the failed reader but not written are lost, a further `copy_to`
+    /// continues after them.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let files = vec![File::open("part-1.bin")?, File::open("part-2.bin")?];
+    ///     let mut out = File::create("joined.bin")?;
+    ///     ConcatReader::new(files).copy_to(&mut out)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`position`]:               #method.position
+    /// [`after_error`]:            #method.after_error
+    pub fn copy_to<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64> {
+        if let Some(err) = &self.poisoned {
+            return Err(Poisoned::repeat(err));
+        }
+        let mut copied = 0;
+        loop {
+            if let Some(token) = &self.cancel {
+                token
+                    .check()
+                    .map_err(|e| provenance::annotate(e, "reader", None, self.pos))?;
+            }
+            let mut max = match self.total_left() {
+                Some(0) => return Ok(copied),
+                left => left.unwrap_or(u64::MAX),
+            };
+            if let Some(limit) = self.opts.limit_per_source {
+                max = cmp::min(max, limit.saturating_sub(self.pos));
+            }
+            if self.curr.is_none() {
+                self.next_source();
+            }
+            let reader = match &mut self.curr {
+                Some(reader) => reader,
+                None => return Ok(copied),
+            };
+
+            let mut source = reader.take(max);
+            let (res, read_failed) = if self.opts.after_error == AfterError::Stay {
+                (io::copy(&mut source, writer), false)
+            } else {
+                let mut sink = WriteSide {
+                    inner: &mut *writer,
+                    failed: false,
+                };
+                let res = io::copy(&mut source, &mut sink);
+                (res, !sink.failed)
+            };
+            let n = max - source.limit();
+            self.pos += n;
+            self.total += n;
+            copied += n;
+            match res {
+                Err(e) if read_failed => {
+                    let e = provenance::annotate(e, "reader", None, self.pos);
+                    return Err(self.apply_after_error(e));
+                }
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+            if self.total_left() != Some(0) {
+                self.next_source();
+            }
+        }
+    }
+
+    /// Splits the `ConcatReader` into the current reader and an iterator over the readers after
+    /// it.
+    ///
+    /// The current reader keeps its read position, [`current_position`] tells how much of it was
+    /// read. The iterator yields the readers exactly as the source list does, a filter set with
+    /// [`filter_sources`] isn't applied to them.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["first".as_bytes(), "second".as_bytes(), "third".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes);
+    /// let mut buffer = [0; 2];
+    /// c.read_exact(&mut buffer).unwrap();
+    ///
+    /// let (current, rest) = c.into_parts();
+    /// assert_eq!(current, Some("rst".as_bytes()));
+    /// assert_eq!(rest.collect::<Vec<_>>(), ["second".as_bytes(), "third".as_bytes()]);
+    /// ```
+    ///
+    /// [`current_position`]:       #method.current_position
+    /// [`filter_sources`]:         #method.filter_sources
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(
+        self,
+    ) -> (
+        Option<I::Item>,
+        Chain<option::IntoIter<I::Item>, I::IntoIter>,
+    ) {
+        (self.curr, self.peeked.into_iter().chain(self.iter))
+    }
+
+    /// Converts the `ConcatReader` into an iterator over the current reader, if any, and all
+    /// readers after it.
+    ///
+    /// See [`into_parts`].
+    ///
+    /// [`into_parts`]:             #method.into_parts
+    #[allow(clippy::type_complexity)]
+    pub fn into_remaining(
+        self,
+    ) -> Chain<option::IntoIter<I::Item>, Chain<option::IntoIter<I::Item>, I::IntoIter>> {
+        let (curr, rest) = self.into_parts();
+        curr.into_iter().chain(rest)
+    }
+
+    /// Collects the remaining readers of the internal iterator into a `Vec`.
+    ///
+    /// One-shot iterators like channels or queues can't be inspected without consuming them, so
+    /// `Debug` only shows a size hint of the remaining readers. `materialize` converts the reader
+    /// into one backed by a `Vec`, keeping the current reader and its read position. At most
+    /// `limit` readers will be collected. If the iterator yields more readers, the reader is
+    /// returned as `Err` without losing any items.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let mut c = ConcatReader::with_queue();
+    /// c.push_source("first".as_bytes());
+    /// c.push_source("second".as_bytes());
+    ///
+    /// let c = match c.materialize(10) {
+    ///     Ok(c) => c,
+    ///     Err(_) => panic!("more than 10 sources left"),
+    /// };
+    /// assert_eq!(format!("{:?}", c), "Concat { curr: Some([102, 105, 114, 115, 116]), peeked: None, remaining: 1 }");
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn materialize(
+        mut self,
+        limit: usize,
+    ) -> std::result::Result<
+        ConcatReader<Vec<I::Item>>,
+        ConcatReader<Chain<vec::IntoIter<I::Item>, I::IntoIter>>,
+    > {
+        let mut rest: Vec<_> = self.peeked.take().into_iter().collect();
+        rest.reserve(cmp::min(limit, self.iter.size_hint().0));
+        rest.extend(self.iter.by_ref().take(limit.saturating_sub(rest.len())));
+        let next = if rest.len() > limit {
+            rest.pop()
+        } else {
+            self.iter.next()
+        };
+        match next {
+            None => Ok(ConcatReader {
+                curr: self.curr,
+                peeked: None,
+                iter: rest.into_iter(),
+                pos: self.pos,
+                total: self.total,
+                filter: self.filter,
+                cancel: self.cancel,
+                exhausted: self.exhausted,
+                poisoned: self.poisoned,
+                opts: self.opts,
+            }),
+            Some(next) => {
+                rest.push(next);
+                Err(ConcatReader {
+                    curr: self.curr,
+                    peeked: None,
+                    iter: rest.into_iter().chain(self.iter),
+                    pos: self.pos,
+                    total: self.total,
+                    filter: self.filter,
+                    cancel: self.cancel,
+                    exhausted: self.exhausted,
+                    poisoned: self.poisoned,
+                    opts: self.opts,
+                })
+            }
+        }
+    }
+}
+
+impl<I> ConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+    I::IntoIter: ExactSizeIterator,
+{
+    /// Returns the number of readers left, including the current one.
+    ///
+    /// Readers which weren't checked by a filter set with [`filter_sources`] yet are counted as
+    /// well.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let bytes = vec!["first".as_bytes(), "second".as_bytes()];
+    /// let mut c = ConcatReader::new(bytes);
+    /// assert_eq!(c.remaining_sources(), 2);
+    ///
+    /// c.read_to_end(&mut Vec::new()).unwrap();
+    /// assert_eq!(c.remaining_sources(), 0);
+    /// ```
+    ///
+    /// [`filter_sources`]:         #method.filter_sources
+    pub fn remaining_sources(&self) -> usize {
+        self.curr.is_some() as usize + self.peeked.is_some() as usize + self.iter.len()
+    }
+}
+
+impl<I> ConcatReader<Restart<I>>
+where
+    I: Iterator + Clone,
+    I::Item: Read,
+{
+    /// Creates a new `ConcatReader` which can [`reset`] to its first reader.
+    ///
+    /// The readers are kept in a [`Restart`], which clones the iterator over `items`. A reset
+    /// starts over with clones of the original items, so items like `&[u8]` are read again
+    /// from their start, while items sharing a cursor like `&File` continue where they are.
+    ///
+    /// [`reset`]:                  #method.reset
+    /// [`Restart`]:                ../restart/struct.Restart.html
+    pub fn restartable<T: IntoIterator<IntoIter = I>>(items: T) -> Self {
+        Self::new(Restart::new(items))
+    }
+
+    /// Starts over with the first reader, as if the `ConcatReader` was just created.
+    pub fn reset(&mut self) {
+        self.iter.restart();
+        self.exhausted = false;
+        self.poisoned = None;
+        self.peeked = None;
+        self.total = 0;
+        self.next_source();
+    }
+}
+
+impl<R: Read, const N: usize> ConcatReader<[R; N]> {
+    /// Creates a new `ConcatReader` from a fixed number of readers.
+    ///
+    /// The readers are kept inline in the `ConcatReader`, so creating and reading it doesn't
+    /// allocate. This suits latency sensitive code which concatenates a few known readers, like a
+    /// header, a body and a trailer.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    ///
+    /// let mut c = ConcatReader::from_array([&b"head "[..], b"body ", b"tail"]);
+    /// let mut buffer = [0; 14];
+    /// c.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(&buffer, b"head body tail");
+    /// ```
+    pub fn from_array(readers: [R; N]) -> Self {
+        Self::new(readers)
+    }
+}
+
+impl<R: Read> ConcatReader<SourceQueue<R>> {
+    /// Creates a new empty `ConcatReader` backed by a [`SourceQueue`].
+    ///
+    /// New readers can be appended with [`push_source`] or through the [`sender`] from other
+    /// threads while the reader is in use.
+    ///
+    /// [`SourceQueue`]:            ../queue/struct.SourceQueue.html
+    /// [`push_source`]:            #method.push_source
+    /// [`sender`]:                 #method.sender
+    pub fn with_queue() -> Self {
+        let mut reader = Self::new(SourceQueue::new());
+        reader.opts.fuse = false;
+        reader.exhausted = false;
+        reader
+    }
+
+    /// Appends a reader to the end of the source list.
+    ///
+    /// If the reader already reached `EOF` it will continue with the appended reader on the next
+    /// call to `read`.
+    pub fn push_source(&mut self, reader: R) {
+        self.iter.push(reader);
+        if self.curr.is_none() {
+            self.next_source();
+        }
+    }
+
+    /// Returns a [`Sender`] to append readers from another thread.
+    ///
+    /// [`Sender`]:                 https://doc.rust-lang.org/std/sync/mpsc/struct.Sender.html
+    pub fn sender(&self) -> Sender<R> {
+        self.iter.sender()
+    }
+}
+
+impl<R: Read> ConcatReader<Receiver<R>> {
+    /// Creates a new `ConcatReader` which reads the readers received from a channel.
+    ///
+    /// The channel is treated as the source list. When the current reader reaches `EOF` the
+    /// `ConcatReader` blocks until the next reader is received. The `ConcatReader` only reaches
+    /// `EOF` after all senders of the channel are dropped. Unlike [`new`] no reader is received
+    /// before the first call to `read`.
+    ///
+    /// ```
+    /// use concat_reader::*;
+    /// use std::io::prelude::*;
+    /// use std::sync::mpsc::channel;
+    /// use std::thread;
+    ///
+    /// let (tx, rx) = channel();
+    /// let mut c = ConcatReader::from_channel(rx);
+    /// thread::spawn(move || {
+    ///     tx.send("first ".as_bytes()).unwrap();
+    ///     tx.send("second".as_bytes()).unwrap();
+    /// });
+    ///
+    /// let mut buffer = String::new();
+    /// c.read_to_string(&mut buffer).unwrap();
+    /// assert_eq!(buffer, "first second");
+    /// ```
+    ///
+    /// [`new`]:                    #method.new
+    pub fn from_channel(rx: Receiver<R>) -> Self {
+        ConcatReader {
+            curr: None,
+            peeked: None,
+            iter: rx.into_iter(),
+            pos: 0,
+            total: 0,
+            filter: None,
+            cancel: None,
+            exhausted: false,
+            poisoned: None,
+            opts: Options {
+                fuse: true,
+                ..Options::default()
+            },
+        }
     }
 }
 
@@ -72,10 +922,27 @@ where
         self.curr.as_ref()
     }
 
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.curr.as_mut()
+    }
+
     fn skip(&mut self) -> bool {
-        self.curr = self.iter.next();
+        self.next_source();
         self.curr.is_some()
     }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        if self.peeked.is_none() {
+            self.peeked = self.pull();
+        }
+        self.peeked.as_ref()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.curr.take()?;
+        self.next_source();
+        Some(item)
+    }
 }
 
 impl<I> From<I> for ConcatReader<I>
@@ -88,23 +955,181 @@ where
     }
 }
 
-impl<I> Read for ConcatReader<I>
+impl<I> ConcatReader<I>
 where
     I: IntoIterator,
     I::Item: Read,
 {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    fn next_source(&mut self) {
+        self.curr = self.peeked.take().or_else(|| self.pull());
+        self.pos = 0;
+    }
+
+    /// Takes the next item from `iter` which passes the filter.
+    fn pull(&mut self) -> Option<I::Item> {
+        if self.exhausted {
+            return None;
+        }
+        let item = match &self.filter {
+            Some(filter) => self.iter.by_ref().find(|item| filter(item)),
+            None => self.iter.next(),
+        };
+        self.exhausted = item.is_none() && self.opts.fuse;
+        item
+    }
+
+    fn total_left(&self) -> Option<u64> {
+        self.opts
+            .limit_total
+            .map(|limit| limit.saturating_sub(self.total))
+    }
+
+    /// Runs `read` unless the reader is poisoned and applies the `AfterError` policy to its error.
+    fn guarded<F>(&mut self, read: F) -> Result<usize>
+    where
+        F: FnOnce(&mut Self) -> Result<usize>,
+    {
+        if let Some(err) = &self.poisoned {
+            return Err(Poisoned::repeat(err));
+        }
+        read(self).map_err(|e| self.apply_after_error(e))
+    }
+
+    /// Applies the `AfterError` policy to the error `err` of a reader.
+    fn apply_after_error(&mut self, err: io::Error) -> io::Error {
+        if !self
+            .opts
+            .after_error
+            .applies_to(&err, self.opts.non_blocking)
+        {
+            return err;
+        }
+        if self.opts.after_error == AfterError::Skip {
+            self.next_source();
+            return err;
+        }
+        let (poisoned, err) = Poisoned::first(err);
+        self.poisoned = Some(poisoned);
+        err
+    }
+
+    fn read_next(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let n = self.read_current(buf)?;
+            if n > 0 || buf.is_empty() || self.curr.is_none() || self.total_left() == Some(0) {
+                return Ok(n);
+            }
+            self.next_source();
+        }
+    }
+
+    /// Checks the internal state after a `read`. Only active with `debug_assertions`.
+    fn debug_check_read(&self, len: usize, res: &Result<usize>) {
+        if let Ok(n) = *res {
+            debug_assert!(
+                n <= len,
+                "ConcatReader: inner reader returned {} bytes for a buffer of {} bytes",
+                n,
+                len
+            );
+            debug_assert!(
+                n == 0 || self.curr.is_some(),
+                "ConcatReader: read {} bytes without a current reader",
+                n
+            );
+        }
+    }
+}
+
+impl<I> UnframedRead for ConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+}
+
+impl<I> RawRead for ConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+}
+
+impl<I> SourceRead for ConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+    fn read_source(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.guarded(|this| this.read_current(buf))
+    }
+}
+
+impl<I> ConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+    /// Reads from the current reader only, without the `AfterError` policy.
+    fn read_current(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(token) = &self.cancel {
+            token
+                .check()
+                .map_err(|e| provenance::annotate(e, "reader", None, self.pos))?;
+        }
+
+        let total_left = self.total_left();
+        if total_left == Some(0) {
+            return Ok(0);
+        }
+
+        if self.curr.is_none() {
+            self.next_source();
+        }
+
+        let mut max = buf.len() as u64;
+        if let Some(limit) = self.opts.limit_per_source {
+            max = cmp::min(max, limit.saturating_sub(self.pos));
+        }
+        if let Some(left) = total_left {
+            max = cmp::min(max, left);
+        }
+        let pos = self.pos;
         let n = match self.curr {
             None => 0,
-            Some(ref mut r) => r.read(buf)?,
+            Some(ref mut r) => loop {
+                match r.read(&mut buf[..max as usize]) {
+                    Err(ref e)
+                        if e.kind() == io::ErrorKind::Interrupted
+                            && !self.opts.return_interrupted =>
+                    {
+                        if let Some(token) = &self.cancel {
+                            token
+                                .check()
+                                .map_err(|e| provenance::annotate(e, "reader", None, pos))?;
+                        }
+                    }
+                    res => break res.map_err(|e| provenance::annotate(e, "reader", None, pos))?,
+                }
+            },
         };
+        self.pos += n as u64;
+        self.total += n as u64;
+        Ok(n)
+    }
+}
 
-        if n > 0 || buf.is_empty() || self.curr.is_none() {
-            Ok(n)
-        } else {
-            self.curr = self.iter.next();
-            self.read(buf)
-        }
+impl<I> Read for ConcatReader<I>
+where
+    I: IntoIterator,
+    I::Item: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.guarded(|this| {
+            let res = this.read_next(buf);
+            this.debug_check_read(buf.len(), &res);
+            res
+        })
     }
 }
 
@@ -112,20 +1137,79 @@ impl<I> fmt::Debug for ConcatReader<I>
 where
     I: IntoIterator,
     I::Item: fmt::Debug,
-    I::IntoIter: Clone,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let rest: Vec<_> = self.iter.clone().collect();
         f.debug_struct("Concat")
             .field("curr", &self.curr)
-            .field("rest", &rest)
+            .field("peeked", &self.peeked)
+            .field(
+                "remaining",
+                &Remaining::of(&self.iter, self.peeked.is_some()),
+            )
             .finish()
     }
 }
 
+/// The number of items left in a source list as reported by the `size_hint` of its iterator.
+///
+/// `Debug` prints an exact number as `3`, a range as `3..=5` and an open range as `3..`.
+pub(crate) struct Remaining(usize, Option<usize>);
+
+impl Remaining {
+    /// Returns the items left in `iter`, plus a peeked item.
+    pub(crate) fn of<T: Iterator>(iter: &T, peeked: bool) -> Self {
+        let (lower, upper) = iter.size_hint();
+        let peeked = peeked as usize;
+        Remaining(
+            lower.saturating_add(peeked),
+            upper.and_then(|upper| upper.checked_add(peeked)),
+        )
+    }
+}
+
+impl fmt::Debug for Remaining {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Remaining(lower, Some(upper)) if lower == upper => write!(f, "{}", lower),
+            Remaining(lower, Some(upper)) => write!(f, "{}..={}", lower, upper),
+            Remaining(lower, None) => write!(f, "{}..", lower),
+        }
+    }
+}
+
+/// A writer which remembers if a write failed, to tell its errors from those of the reader.
+struct WriteSide<'a, W: ?Sized> {
+    inner: &'a mut W,
+    failed: bool,
+}
+
+impl<W: Write + ?Sized> WriteSide<'_, W> {
+    fn track<T>(&mut self, res: Result<T>) -> Result<T> {
+        match &res {
+            Err(e) if e.kind() != io::ErrorKind::Interrupted => self.failed = true,
+            _ => {}
+        }
+        res
+    }
+}
+
+impl<W: Write + ?Sized> Write for WriteSide<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let res = self.inner.write(buf);
+        self.track(res)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let res = self.inner.flush();
+        self.track(res)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::ConcatReader;
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, ConcatReader};
+    use std::fs::File;
     use std::io::prelude::*;
 
     #[test]
@@ -137,4 +1221,508 @@ mod tests {
         reader.read_exact(&mut buf).unwrap();
         assert_eq!(&buf, b"12233");
     }
+
+    #[test]
+    fn reads_past_many_empty_readers() {
+        let mut bytes: Vec<&[u8]> = vec![b""; 1_000_000];
+        bytes.push(b"1");
+        let mut reader = ConcatReader::new(bytes);
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"1");
+    }
+
+    #[test]
+    fn filters_readers_as_they_are_reached() {
+        let bytes: Vec<&[u8]> = vec![b"", b"1", b"", b"22", b"333"];
+        let mut reader = ConcatReader::new(bytes).filter_sources(|item| item.len() % 2 == 1);
+
+        assert_eq!(reader.current(), Some(&&b"1"[..]));
+        assert_eq!(reader.peek_next(), Some(&&b"333"[..]));
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "1333");
+    }
+
+    #[test]
+    fn reads_source_to_end() {
+        use crate::SourceRead;
+
+        let bytes: Vec<&[u8]> = vec![b"12", b"", b"3456"];
+        let mut reader = ConcatReader::new(bytes).limit_per_source(3);
+        let mut buf = Vec::new();
+        reader.read_exact(&mut [0; 1]).unwrap();
+        assert_eq!(reader.read_source_to_end(&mut buf).unwrap(), 1);
+        assert_eq!(buf, b"2");
+        assert_eq!(reader.read_source_to_end(&mut buf).unwrap(), 0);
+        assert_eq!(reader.current(), Some(&&b"3456"[..]));
+        assert_eq!(reader.read_source_to_end(&mut buf).unwrap(), 3);
+        assert_eq!(buf, b"2345");
+        assert!(reader.is_finished());
+        assert_eq!(reader.read_source_to_end(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn stays_finished_after_eof() {
+        let mut items = vec![Some(&b"1"[..]), None, Some(&b"2"[..])].into_iter();
+        let mut reader = ConcatReader::new(std::iter::from_fn(move || items.next().flatten()));
+        assert!(!reader.is_finished());
+        assert_eq!(reader.read(&mut []).unwrap(), 0);
+        assert!(!reader.is_finished());
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"1");
+        assert!(reader.is_finished());
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+        assert!(!reader.skip());
+
+        let mut reader = ConcatReader::with_queue();
+        assert!(reader.is_finished());
+        reader.push_source(&b"3"[..]);
+        assert!(!reader.is_finished());
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"13");
+        assert!(reader.is_finished());
+
+        let bytes: Vec<&[u8]> = vec![b"45"];
+        let mut reader = ConcatReader::new(bytes).limit_total(1);
+        reader.read_exact(&mut [0; 1]).unwrap();
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn applies_after_error_policy() {
+        use super::{AfterError, Poisoned};
+        use crate::provenance::Provenance;
+        use crate::SourceRead;
+        use std::io::ErrorKind;
+
+        struct Failing(ErrorKind);
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(self.0, "failing"))
+            }
+        }
+
+        let sources = || -> Vec<Box<dyn Read>> {
+            vec![Box::new(Failing(ErrorKind::Other)), Box::new(&b"1"[..])]
+        };
+        let mut reader = ConcatReader::new(sources());
+        assert!(reader.read(&mut [0; 1]).is_err());
+        assert!(reader.read(&mut [0; 1]).is_err());
+
+        let mut reader = ConcatReader::new(sources()).after_error(AfterError::Skip);
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"1");
+
+        let mut reader = ConcatReader::new(sources()).after_error(AfterError::Skip);
+        assert!(reader.read_source(&mut [0; 1]).is_err());
+        assert_eq!(reader.read_source(&mut [0; 1]).unwrap(), 1);
+
+        let mut reader = ConcatReader::new(sources()).after_error(AfterError::Poison);
+        assert!(reader.read(&mut [0; 1]).is_err());
+        reader.skip();
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert!(err
+            .to_string()
+            .starts_with("an earlier read failed: failing"));
+        let first = Poisoned::of(&err).unwrap().error();
+        assert_eq!(Provenance::of(first).unwrap().crumbs()[0].layer, "reader");
+        assert!(Poisoned::of(&reader.read_source(&mut [0; 1]).unwrap_err()).is_some());
+        let mut out = Vec::new();
+        assert!(Poisoned::of(&reader.copy_to(&mut out).unwrap_err()).is_some());
+        assert!(out.is_empty());
+
+        let sources: Vec<&[u8]> = vec![b"1", b"2"];
+        let mut reader = ConcatReader::restartable(sources).after_error(AfterError::Poison);
+        reader.poisoned = Some(Poisoned::first(ErrorKind::Other.into()).0);
+        assert!(reader.read(&mut [0; 1]).is_err());
+        reader.reset();
+        let mut buf = Vec::new();
+        reader.copy_to(&mut buf).unwrap();
+        assert_eq!(buf, b"12");
+
+        let sources: Vec<Box<dyn Read>> = vec![
+            Box::new(Failing(ErrorKind::WouldBlock)),
+            Box::new(&b"1"[..]),
+        ];
+        let mut reader = ConcatReader::new(sources)
+            .after_error(AfterError::Poison)
+            .non_blocking();
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        reader.skip();
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
+    }
+
+    #[test]
+    fn keeps_source_on_would_block_in_non_blocking_mode() {
+        use super::AfterError;
+        use std::io::ErrorKind;
+
+        struct NotReady(u8, &'static [u8]);
+
+        impl Read for NotReady {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0 > 0 {
+                    self.0 -= 1;
+                    return Err(ErrorKind::WouldBlock.into());
+                }
+                self.1.read(buf)
+            }
+        }
+
+        let sources = || vec![NotReady(1, b"1"), NotReady(0, b"2")];
+        let mut reader = ConcatReader::new(sources()).after_error(AfterError::Skip);
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"2");
+
+        let mut reader = ConcatReader::new(sources())
+            .after_error(AfterError::Skip)
+            .non_blocking();
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"12");
+    }
+
+    #[test]
+    fn retries_interrupted_reads() {
+        use std::io::ErrorKind;
+
+        struct Interrupting(u8, &'static [u8]);
+
+        impl Read for Interrupting {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0 > 0 {
+                    self.0 -= 1;
+                    return Err(ErrorKind::Interrupted.into());
+                }
+                self.1.read(buf)
+            }
+        }
+
+        let mut reader = ConcatReader::new(vec![Interrupting(2, b"1"), Interrupting(1, b"2")]);
+        let mut buf = [0; 2];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(reader.read(&mut buf[1..]).unwrap(), 1);
+        assert_eq!(&buf, b"12");
+
+        let mut reader = ConcatReader::new(vec![Interrupting(1, b"1")]).return_interrupted();
+        let err = reader.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 1);
+    }
+
+    #[test]
+    fn peeks_next_reader_without_advancing() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333"];
+        let mut reader = ConcatReader::new(bytes);
+
+        assert_eq!(reader.peek_next(), Some(&&b"22"[..]));
+        assert_eq!(reader.current(), Some(&&b"1"[..]));
+        assert_eq!(
+            format!("{:?}", reader),
+            "Concat { curr: Some([49]), peeked: Some([50, 50]), remaining: 2 }"
+        );
+
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"12");
+        assert_eq!(reader.peek_next(), Some(&&b"333"[..]));
+
+        let mut reader = match reader.materialize(0) {
+            Ok(_) => panic!("expected a reader left"),
+            Err(r) => r,
+        };
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"2333");
+    }
+
+    #[test]
+    fn resets_to_first_reader() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22"];
+        let mut reader = ConcatReader::restartable(bytes).limit_total(2);
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(reader.peek_next(), None);
+
+        reader.reset();
+        assert_eq!(reader.current(), Some(&&b"1"[..]));
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"1212");
+    }
+
+    #[test]
+    fn continues_with_readers_appended_after_eof() {
+        let mut reader = ConcatReader::with_queue();
+        reader.push_source(&b"1"[..]);
+
+        let mut buf = Vec::new();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 1);
+        assert!(reader.current().is_none());
+
+        reader.push_source(&b"22"[..]);
+        reader.sender().send(&b"333"[..]).unwrap();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 5);
+        assert_eq!(buf, b"122333");
+    }
+
+    struct Overflowing;
+
+    impl Read for Overflowing {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(buf.len() + 1)
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "inner reader returned 2 bytes for a buffer of 1 bytes")]
+    fn checks_inner_reader_result() {
+        let mut reader = ConcatReader::new(vec![Overflowing]);
+        let mut buf = [0];
+        let _ = reader.read(&mut buf);
+    }
+
+    #[test]
+    fn limits_bytes_per_reader() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333", b"4444"];
+        let mut reader = ConcatReader::new(bytes).limit_per_source(2);
+
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"122");
+        assert_eq!(reader.current(), Some(&&b""[..]));
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"3344");
+    }
+
+    #[test]
+    fn limits_per_reader_after_read() {
+        let bytes: Vec<&[u8]> = vec![b"333", b"4444"];
+        let mut reader = ConcatReader::new(bytes);
+        reader.read_exact(&mut [0; 2]).unwrap();
+
+        let mut reader = reader.limit_per_source(1);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"4");
+    }
+
+    #[test]
+    fn debug_prints_one_shot_iterators() {
+        let (tx, rx) = std::sync::mpsc::channel::<&[u8]>();
+        tx.send(b"1").unwrap();
+        let mut reader = ConcatReader::from_channel(rx);
+        assert_eq!(
+            format!("{:?}", reader),
+            "Concat { curr: None, peeked: None, remaining: 0.. }"
+        );
+
+        reader.peek_next();
+        assert_eq!(
+            format!("{:?}", reader),
+            "Concat { curr: None, peeked: Some([49]), remaining: 1.. }"
+        );
+    }
+
+    #[test]
+    fn counts_remaining_sources() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333"];
+        let mut reader = ConcatReader::new(bytes.clone());
+        assert_eq!(reader.sources_hint(), (3, Some(3)));
+        reader.peek_next();
+        assert_eq!(reader.remaining_sources(), 3);
+        reader.skip();
+        assert_eq!(reader.remaining_sources(), 2);
+
+        let reader = ConcatReader::new(bytes).filter_sources(|b| b.len() > 1);
+        assert_eq!(reader.sources_hint(), (1, Some(2)));
+        assert_eq!(reader.remaining_sources(), 2);
+
+        let (_tx, rx) = std::sync::mpsc::channel::<&[u8]>();
+        assert_eq!(ConcatReader::from_channel(rx).sources_hint(), (0, None));
+    }
+
+    #[test]
+    fn splits_into_current_and_rest() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333"];
+        let mut reader = ConcatReader::new(bytes.clone());
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        reader.peek_next();
+        assert_eq!(
+            reader.into_remaining().collect::<Vec<_>>(),
+            [&b"2"[..], b"333"]
+        );
+
+        let mut reader = ConcatReader::new(bytes);
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        let (current, mut rest) = reader.into_parts();
+        assert!(current.is_none());
+        assert!(rest.next().is_none());
+    }
+
+    #[test]
+    fn reads_from_array() {
+        let mut reader = ConcatReader::from_array([&b"1"[..], b"", b"22"]);
+        assert_eq!(reader.peek_next(), Some(&&b""[..]));
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"122");
+        assert!(!reader.skip());
+    }
+
+    #[test]
+    fn copies_reader_by_reader() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333", b"4444"];
+        let mut reader = ConcatReader::new(bytes).limit_per_source(3).limit_total(8);
+        let mut out = Vec::new();
+        assert_eq!(reader.copy_to(&mut out).unwrap(), 8);
+        assert_eq!(out, b"12233344");
+        assert_eq!((reader.position(), reader.current_position()), (8, 2));
+
+        let tree = TempTree::new().unwrap();
+        let open = || {
+            vec![
+                File::open(tree.file("a", b"first ").unwrap()).unwrap(),
+                File::open(tree.file("b", b"second").unwrap()).unwrap(),
+            ]
+        };
+        let out = tree.path().join("out");
+        ConcatReader::new(open())
+            .copy_to(&mut File::create(&out).unwrap())
+            .unwrap();
+        assert_eq!(std::fs::read(&out).unwrap(), b"first second");
+
+        let mut reader = ConcatReader::new(open()).limit_per_source(5).limit_total(8);
+        let copied = reader.copy_to(&mut File::create(&out).unwrap()).unwrap();
+        assert_eq!(copied, 8);
+        assert_eq!(std::fs::read(&out).unwrap(), b"firstsec");
+        assert_eq!((reader.position(), reader.current_position()), (8, 3));
+    }
+
+    #[test]
+    fn passes_writer_errors_through() {
+        use super::AfterError;
+        use crate::provenance::Provenance;
+        use std::io;
+
+        /// Accepts one write, then fails.
+        struct Full(Option<usize>);
+
+        impl Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0.is_some() {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                self.0 = Some(buf.len());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("failing"))
+            }
+        }
+
+        let big = vec![7; 100_000];
+        for policy in [AfterError::Stay, AfterError::Skip, AfterError::Poison] {
+            let bytes: Vec<&[u8]> = vec![b"1", &big, b"2"];
+            let mut reader = ConcatReader::new(bytes).after_error(policy);
+            let mut full = Full(None);
+            let err = reader.copy_to(&mut full).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+            assert!(Provenance::of(&err).is_none());
+            // the bytes of the failed write were taken from the reader and are lost
+            let written = full.0.unwrap() as u64;
+            assert_eq!(written, 1);
+            assert!(reader.position() > written);
+            assert_eq!(reader.current_position(), reader.position() - written);
+
+            let taken = reader.position();
+            let mut out = Vec::new();
+            let rest = reader.copy_to(&mut out).unwrap();
+            assert_eq!(taken + rest, 100_002);
+            assert_eq!(out.len() as u64, rest);
+            assert_eq!(out.last(), Some(&b'2'));
+        }
+
+        let sources: Vec<Box<dyn Read>> = vec![Box::new(Failing), Box::new(&b"2"[..])];
+        let mut reader = ConcatReader::new(sources).after_error(AfterError::Skip);
+        let mut out = Vec::new();
+        let err = reader.copy_to(&mut out).unwrap_err();
+        assert!(Provenance::of(&err).is_some());
+        assert_eq!(reader.copy_to(&mut out).unwrap(), 1);
+        assert_eq!(out, b"2");
+    }
+
+    #[test]
+    fn stops_at_total_limit() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333", b"4444"];
+        let mut reader = ConcatReader::new(bytes).limit_total(5);
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"12233");
+        assert_eq!(reader.position(), 5);
+        assert_eq!(reader.current_position(), 2);
+        assert_eq!(reader.current(), Some(&&b"3"[..]));
+    }
+
+    #[test]
+    fn stops_at_total_limit_set_after_read() {
+        let bytes: Vec<&[u8]> = vec![b"1", b"22", b"333"];
+        let mut reader = ConcatReader::new(bytes);
+        reader.read_exact(&mut [0; 4]).unwrap();
+
+        let mut reader = reader.limit_total(2);
+        assert!(reader.is_finished());
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+        let mut out = Vec::new();
+        assert_eq!(reader.copy_to(&mut out).unwrap(), 0);
+        assert_eq!(reader.position(), 4);
+    }
+
+    #[test]
+    fn materialize_keeps_all_readers_when_over_limit() {
+        let mut reader = ConcatReader::with_queue();
+        for r in [&b"1"[..], b"22", b"333"] {
+            reader.push_source(r);
+        }
+
+        let mut buf = [0];
+        reader.read_exact(&mut buf).unwrap();
+
+        let mut reader = match reader.materialize(1) {
+            Ok(_) => panic!("expected more readers than the limit"),
+            Err(r) => r,
+        };
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"22333");
+    }
 }
@@ -0,0 +1,601 @@
+//! Iterators over records of concatenated files.
+//!
+//! The byte stream of a concat reader loses the boundaries of its sources. The iterators in this
+//! module split the content into records which never span two files and tag every record with
+//! the path of the file it was read from.
+//!
+//! [`Chunks`] does the opposite and splits the concatenated content into blocks of one size,
+//! regardless of where the sources end, e.g. to upload many small files as uniform parts.
+//!
+//! [`Chunks`]:                 struct.Chunks.html
+use crate::{ConcatRead, FileConcatRead, FileConcatReader, SourceRead};
+use std::io::{self, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+/// A record read from a file together with its origin.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Path of the file the record was read from.
+    pub path: PathBuf,
+    /// Index of the record in its file, starting at `0`.
+    pub index: u64,
+    /// The content of the record.
+    pub data: Vec<u8>,
+}
+
+/// Defines how [`FixedRecords`] handles files with a length which is not a multiple of the
+/// record length.
+///
+/// [`FixedRecords`]:           struct.FixedRecords.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Remainder {
+    /// Yield an `InvalidData` error for the incomplete record. The next call continues with the
+    /// next file.
+    #[default]
+    Error,
+    /// Fill the incomplete record with the given byte.
+    Pad(u8),
+    /// Drop the incomplete record.
+    Truncate,
+}
+
+/// An iterator over fixed-length records of a [`FileConcatReader`].
+///
+/// A read error ends the current file, the next call continues with the next file.
+///
+/// This struct is created by [`FileConcatReader::fixed_records`].
+///
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`FileConcatReader::fixed_records`]: ../file/struct.FileConcatReader.html#method.fixed_records
+pub struct FixedRecords<I: IntoIterator> {
+    reader: FileConcatReader<I>,
+    record_len: usize,
+    remainder: Remainder,
+    index: u64,
+    done: bool,
+}
+
+impl<I> FixedRecords<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    pub(crate) fn new(
+        reader: FileConcatReader<I>,
+        record_len: usize,
+        remainder: Remainder,
+    ) -> Self {
+        assert!(record_len > 0, "record length must be greater than 0");
+        FixedRecords {
+            reader,
+            record_len,
+            remainder,
+            index: 0,
+            done: false,
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &FileConcatReader<I> {
+        &self.reader
+    }
+
+    /// Unwraps this `FixedRecords`, returning the underlying reader.
+    pub fn into_inner(self) -> FileConcatReader<I> {
+        self.reader
+    }
+
+    fn next_file(&mut self) {
+        self.index = 0;
+        if !self.reader.skip() {
+            self.done = true;
+        }
+    }
+
+    /// Moves past the file a read failed on, as far as the `AfterError` policy didn't already.
+    fn skip_failed(&mut self, err: &io::Error) {
+        self.index = 0;
+        if !self.reader.skip_after_error(err) {
+            self.done = true;
+        }
+    }
+
+    fn record(&mut self, data: Vec<u8>) -> Record {
+        let path = self
+            .reader
+            .file_path()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let record = Record {
+            path,
+            index: self.index,
+            data,
+        };
+        self.index += 1;
+        record
+    }
+}
+
+impl<I> Iterator for FixedRecords<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let mut data = vec![0; self.record_len];
+            let mut filled = 0;
+            while filled < data.len() {
+                match self.reader.read_source(&mut data[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        self.skip_failed(&e);
+                        return Some(Err(e));
+                    }
+                }
+            }
+
+            if filled == data.len() {
+                return Some(Ok(self.record(data)));
+            }
+
+            if self.reader.file_path().is_none() {
+                self.done = true;
+                return None;
+            }
+
+            if filled == 0 {
+                self.next_file();
+                continue;
+            }
+
+            data.truncate(filled);
+            let item = match self.remainder {
+                Remainder::Error => Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} ends with an incomplete record of {} bytes, expected {}",
+                        self.reader.file_path().unwrap_or(Path::new("")).display(),
+                        filled,
+                        self.record_len
+                    ),
+                ))),
+                Remainder::Pad(byte) => {
+                    data.resize(self.record_len, byte);
+                    Some(Ok(self.record(data)))
+                }
+                Remainder::Truncate => None,
+            };
+            self.next_file();
+            if item.is_some() {
+                return item;
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over delimited records of a [`FileConcatReader`].
+///
+/// Records end at a delimiter or at the end of a file, so they never span two files. The
+/// delimiter is not part of the record and a file ending with a delimiter has no empty record at
+/// its end, as with [`BufRead::split`].
+///
+/// A read error ends the current file, the next call continues with the next file.
+///
+/// This struct is created by [`FileConcatReader::records`] and
+/// [`FileConcatReader::records_by`].
+///
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`BufRead::split`]:         https://doc.rust-lang.org/std/io/trait.BufRead.html#method.split
+/// [`FileConcatReader::records`]: ../file/struct.FileConcatReader.html#method.records
+/// [`FileConcatReader::records_by`]: ../file/struct.FileConcatReader.html#method.records_by
+pub struct DelimitedRecords<I: IntoIterator> {
+    reader: FileConcatReader<I>,
+    delim: Vec<u8>,
+    buf: Vec<u8>,
+    index: u64,
+    done: bool,
+}
+
+impl<I> DelimitedRecords<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    pub(crate) fn new(reader: FileConcatReader<I>, delim: &[u8]) -> Self {
+        assert!(!delim.is_empty(), "delimiter must not be empty");
+        DelimitedRecords {
+            reader,
+            delim: delim.to_vec(),
+            buf: Vec::new(),
+            index: 0,
+            done: false,
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &FileConcatReader<I> {
+        &self.reader
+    }
+
+    /// Unwraps this `DelimitedRecords`, returning the underlying reader.
+    ///
+    /// Bytes which were read but not yet returned as a record are lost.
+    pub fn into_inner(self) -> FileConcatReader<I> {
+        self.reader
+    }
+
+    fn next_file(&mut self) {
+        self.buf.clear();
+        self.index = 0;
+        if !self.reader.skip() {
+            self.done = true;
+        }
+    }
+
+    /// Moves past the file a read failed on, as far as the `AfterError` policy didn't already.
+    fn skip_failed(&mut self, err: &io::Error) {
+        self.buf.clear();
+        self.index = 0;
+        if !self.reader.skip_after_error(err) {
+            self.done = true;
+        }
+    }
+
+    fn record(&mut self, data: Vec<u8>) -> Record {
+        let path = self
+            .reader
+            .file_path()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let record = Record {
+            path,
+            index: self.index,
+            data,
+        };
+        self.index += 1;
+        record
+    }
+}
+
+impl<I> Iterator for DelimitedRecords<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut scanned = 0;
+        while !self.done {
+            if let Some(i) = self.buf[scanned..]
+                .windows(self.delim.len())
+                .position(|w| w == &self.delim[..])
+            {
+                let end = scanned + i;
+                let rest = self.buf.split_off(end + self.delim.len());
+                let mut data = mem::replace(&mut self.buf, rest);
+                data.truncate(end);
+                return Some(Ok(self.record(data)));
+            }
+            // a delimiter may start in the bytes already scanned
+            scanned = (self.buf.len() + 1).saturating_sub(self.delim.len());
+
+            let mut chunk = [0; 8 * 1024];
+            match self.reader.read_source(&mut chunk) {
+                Ok(0) => {}
+                Ok(n) => {
+                    self.buf.extend_from_slice(&chunk[..n]);
+                    continue;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.skip_failed(&e);
+                    return Some(Err(e));
+                }
+            }
+
+            if !self.buf.is_empty() {
+                let data = mem::take(&mut self.buf);
+                return Some(Ok(self.record(data)));
+            }
+
+            if self.reader.file_path().is_none() {
+                self.done = true;
+                return None;
+            }
+            self.next_file();
+            scanned = 0;
+        }
+        None
+    }
+}
+
+/// An iterator over the lines of a [`FileConcatReader`] together with the file they were read
+/// from.
+///
+/// Each item is the path of the file, the line number in that file starting at `1` and the line
+/// without its trailing `\n` or `\r\n`. Lines never span two files, a file which doesn't end with
+/// a newline still ends its last line.
+///
+/// A line which is not valid UTF-8 yields an `InvalidData` error. A read error ends the current
+/// file, the next call continues with the next file.
+///
+/// This struct is created by [`FileConcatReader::lines_with_source`].
+///
+/// [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+/// [`FileConcatReader::lines_with_source`]: ../file/struct.FileConcatReader.html#method.lines_with_source
+pub struct LinesWithSource<I: IntoIterator> {
+    records: DelimitedRecords<I>,
+}
+
+impl<I> LinesWithSource<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    pub(crate) fn new(reader: FileConcatReader<I>) -> Self {
+        LinesWithSource {
+            records: DelimitedRecords::new(reader, b"\n"),
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &FileConcatReader<I> {
+        self.records.get_ref()
+    }
+
+    /// Unwraps this `LinesWithSource`, returning the underlying reader.
+    ///
+    /// Bytes which were read but not yet returned as a line are lost.
+    pub fn into_inner(self) -> FileConcatReader<I> {
+        self.records.into_inner()
+    }
+}
+
+impl<I> Iterator for LinesWithSource<I>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    type Item = io::Result<(PathBuf, u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Record {
+            path,
+            index,
+            mut data,
+        } = match self.records.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        if data.last() == Some(&b'\r') {
+            data.pop();
+        }
+        let line_no = index + 1;
+        Some(match String::from_utf8(data) {
+            Ok(line) => Ok((path, line_no, line)),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {} of {} is not valid UTF-8", line_no, path.display()),
+            )),
+        })
+    }
+}
+
+/// An iterator over chunks of a fixed size of the concatenated content of a reader.
+///
+/// Chunks span sources, every chunk has `size` bytes except the last one, which has what is
+/// left. A read error is yielded as is and the bytes read before it are kept, the next call
+/// continues the chunk.
+///
+/// This struct is created by [`ConcatRead::chunks`].
+///
+/// [`ConcatRead::chunks`]:     ../trait.ConcatRead.html#method.chunks
+#[derive(Debug)]
+pub struct Chunks<R> {
+    reader: R,
+    size: usize,
+    chunk: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Chunks<R> {
+    pub(crate) fn new(reader: R, size: usize) -> Self {
+        assert!(size > 0, "chunk size must not be 0");
+        Chunks {
+            reader,
+            size,
+            chunk: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Unwraps this `Chunks`, returning the underlying reader.
+    ///
+    /// Bytes which were read but not yet returned as a chunk are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for Chunks<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut filled = self.chunk.len();
+        self.chunk.resize(self.size, 0);
+        while filled < self.size {
+            match self.reader.read(&mut self.chunk[filled..]) {
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.chunk.truncate(filled);
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.chunk.truncate(filled);
+        if filled == 0 {
+            return None;
+        }
+        Some(Ok(mem::take(&mut self.chunk)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunks, Record, Remainder};
+    use crate::test_util::TempTree;
+    use crate::{concat, ConcatRead, FileConcatReader};
+    use std::io::{self, Read};
+
+    #[test]
+    fn yields_records_per_file() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"aabb").unwrap(),
+            tree.file("empty", b"").unwrap(),
+            tree.file("b", b"ccd").unwrap(),
+            tree.file("c", b"ee").unwrap(),
+        ];
+
+        let records = FileConcatReader::new(&paths).fixed_records(2, Remainder::Pad(b'-'));
+        let records: Vec<Record> = records.collect::<io::Result<_>>().unwrap();
+        let data: Vec<&[u8]> = records.iter().map(|r| &r.data[..]).collect();
+        assert_eq!(data, vec![&b"aa"[..], b"bb", b"cc", b"d-", b"ee"]);
+        assert_eq!(records[3].path, paths[2]);
+        assert_eq!(records[3].index, 1);
+        assert_eq!(records[4].index, 0);
+
+        let records = FileConcatReader::new(&paths).fixed_records(2, Remainder::Truncate);
+        assert_eq!(records.count(), 4);
+    }
+
+    #[test]
+    fn fails_on_incomplete_record() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"aab").unwrap(),
+            tree.file("b", b"cc").unwrap(),
+        ];
+
+        let mut records = FileConcatReader::new(&paths).fixed_records(2, Remainder::Error);
+        assert_eq!(records.next().unwrap().unwrap().data, b"aa");
+        let err = records.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(records.next().unwrap().unwrap().data, b"cc");
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn attributes_lines_to_files() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"one\r\ntwo").unwrap(),
+            tree.file("empty", b"").unwrap(),
+            tree.file("b", b"three\n\nfour\n").unwrap(),
+        ];
+
+        let lines = FileConcatReader::new(&paths).lines_with_source();
+        let lines: Vec<_> = lines.collect::<io::Result<_>>().unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                (paths[0].clone(), 1, "one".to_string()),
+                (paths[0].clone(), 2, "two".to_string()),
+                (paths[2].clone(), 1, "three".to_string()),
+                (paths[2].clone(), 2, "".to_string()),
+                (paths[2].clone(), 3, "four".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn continues_after_error() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"\xff\n").unwrap(),
+            tree.path().join("missing"),
+            tree.file("b", b"b").unwrap(),
+        ];
+
+        let mut lines = FileConcatReader::new(&paths).lines_with_source();
+        let err = lines.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = lines.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(lines.next().unwrap().unwrap().2, "b");
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn splits_records_by_delimiter() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"a1\0a2\0").unwrap(),
+            tree.file("b", b"b1\0\0b2").unwrap(),
+        ];
+
+        let records = FileConcatReader::new(&paths).records(0);
+        let records: Vec<Record> = records.collect::<io::Result<_>>().unwrap();
+        let data: Vec<&[u8]> = records.iter().map(|r| &r.data[..]).collect();
+        assert_eq!(data, vec![&b"a1"[..], b"a2", b"b1", b"", b"b2"]);
+        assert_eq!(records[2].path, paths[1]);
+        assert_eq!(records[4].index, 2);
+
+        let records = FileConcatReader::new(&paths).records_by(b"\0b");
+        let data: Vec<Vec<u8>> = records.map(|r| r.unwrap().data).collect();
+        assert_eq!(
+            data,
+            vec![b"a1\0a2\0".to_vec(), b"b1\0".to_vec(), b"2".to_vec()]
+        );
+    }
+
+    struct FailOnce(bool);
+
+    impl Read for FailOnce {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.0 {
+                self.0 = true;
+                return Err(io::Error::other("flaky"));
+            }
+            buf[0] = b'x';
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn splits_into_chunks_across_sources() {
+        let bytes: Vec<&[u8]> = vec![b"abc", b"", b"d", b"efgh"];
+        let chunks: Vec<Vec<u8>> = concat(bytes).chunks(3).collect::<io::Result<_>>().unwrap();
+        assert_eq!(
+            chunks,
+            vec![b"abc".to_vec(), b"def".to_vec(), b"gh".to_vec()]
+        );
+
+        let mut chunks = Chunks::new((&b"ab"[..]).chain(FailOnce(false)).take(4), 3);
+        assert!(chunks.next().unwrap().is_err());
+        assert_eq!(chunks.next().unwrap().unwrap(), b"abx");
+        assert_eq!(chunks.next().unwrap().unwrap(), b"x");
+        assert!(chunks.next().is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use std::iter::FusedIterator;
+
+/// A source list which can be restarted from its first item.
+///
+/// `Restart` is an [`Iterator`] which keeps a copy of the iterator it was created from. Readers
+/// backed by a `Restart`, created with [`ConcatReader::restartable`] or
+/// [`FileConcatReader::restartable`], can [`reset`] to read all sources again, e.g. for parsers
+/// which scan their input twice.
+///
+/// # Examples
+/// ```
+/// use concat_reader::*;
+/// use std::io::prelude::*;
+///
+/// let mut c = ConcatReader::restartable(vec!["first ".as_bytes(), "second".as_bytes()]);
+///
+/// let mut buffer = String::new();
+/// c.read_to_string(&mut buffer).unwrap();
+/// c.reset();
+/// c.read_to_string(&mut buffer).unwrap();
+/// assert_eq!(buffer, "first secondfirst second");
+/// ```
+///
+/// [`Iterator`]:               https://doc.rust-lang.org/std/iter/trait.Iterator.html
+/// [`ConcatReader::restartable`]: ../read/struct.ConcatReader.html#method.restartable
+/// [`FileConcatReader::restartable`]: ../file/struct.FileConcatReader.html#method.restartable
+/// [`reset`]:                  ../read/struct.ConcatReader.html#method.reset
+#[derive(Clone, Debug)]
+pub struct Restart<I> {
+    origin: I,
+    iter: I,
+}
+
+impl<I: Iterator + Clone> Restart<I> {
+    /// Creates a new `Restart` over `items`.
+    pub fn new<T: IntoIterator<IntoIter = I>>(items: T) -> Self {
+        let origin = items.into_iter();
+        Restart {
+            iter: origin.clone(),
+            origin,
+        }
+    }
+
+    /// Starts over with the first item.
+    pub fn restart(&mut self) {
+        self.iter = self.origin.clone();
+    }
+}
+
+impl<I: Iterator> Iterator for Restart<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for Restart<I> {}
+
+impl<I: FusedIterator> FusedIterator for Restart<I> {}
@@ -0,0 +1,362 @@
+//! Ordering path lists before reading them.
+//!
+//! Paths collected from a directory listing come in no useful order. [`sort_paths`] orders them
+//! by one of the [`SortBy`] policies, and [`FileConcatReader::sorted`] creates a reader over the
+//! sorted paths. [`natural_cmp`] compares names with numbers the way people read them, so
+//! `app.log.2` comes before `app.log.10`.
+//!
+//! Files whose order is only known from their contents, like log segments starting with a
+//! sequence number, are ordered by a key of their first bytes with [`sort_by_header`] or read in
+//! that order with [`FileConcatReader::ordered_by_header`].
+//!
+//! ```no_run
+//! use concat_reader::sort::SortBy;
+//! use concat_reader::*;
+//! use std::io::prelude::*;
+//!
+//! let paths: Vec<_> = std::fs::read_dir("logs")
+//!     .unwrap()
+//!     .map(|entry| entry.unwrap().path())
+//!     .collect();
+//! let mut c = FileConcatReader::sorted(paths, SortBy::Natural);
+//! let mut buffer = String::new();
+//! c.read_to_string(&mut buffer).unwrap();
+//! ```
+//!
+//! [`sort_paths`]:             fn.sort_paths.html
+//! [`SortBy`]:                 enum.SortBy.html
+//! [`FileConcatReader::sorted`]: ../file/struct.FileConcatReader.html#method.sorted
+//! [`natural_cmp`]:            fn.natural_cmp.html
+//! [`sort_by_header`]:         fn.sort_by_header.html
+//! [`FileConcatReader::ordered_by_header`]: ../file/struct.FileConcatReader.html#method.ordered_by_header
+use crate::FileConcatReader;
+use std::cmp::{Ordering, Reverse};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::time::SystemTime;
+use std::vec;
+
+/// The order of a path list.
+///
+/// Sorting is stable. Orders by file metadata read it once per path, paths which can't be
+/// inspected are put last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Lexicographic by path, component by component.
+    Name,
+    /// By path, comparing runs of digits by their numeric value, see [`natural_cmp`].
+    ///
+    /// [`natural_cmp`]:        fn.natural_cmp.html
+    Natural,
+    /// Oldest modification time first.
+    ModifiedAscending,
+    /// Newest modification time first.
+    ModifiedDescending,
+    /// Smallest file first.
+    SizeAscending,
+    /// Largest file first.
+    SizeDescending,
+}
+
+/// Sorts `paths` in place by `by`.
+///
+/// ```
+/// use concat_reader::sort::{sort_paths, SortBy};
+///
+/// let mut paths = vec!["app.log.10", "app.log.2", "app.log"];
+/// sort_paths(&mut paths, SortBy::Natural);
+/// assert_eq!(paths, ["app.log", "app.log.2", "app.log.10"]);
+/// ```
+pub fn sort_paths<P: AsRef<Path>>(paths: &mut [P], by: SortBy) {
+    match by {
+        SortBy::Name => paths.sort_by(|a, b| a.as_ref().cmp(b.as_ref())),
+        SortBy::Natural => paths.sort_by(|a, b| {
+            natural_cmp(&a.as_ref().to_string_lossy(), &b.as_ref().to_string_lossy())
+        }),
+        SortBy::ModifiedAscending => paths.sort_by_cached_key(|p| last(modified(p.as_ref()))),
+        SortBy::ModifiedDescending => {
+            paths.sort_by_cached_key(|p| last(modified(p.as_ref()).map(Reverse)))
+        }
+        SortBy::SizeAscending => paths.sort_by_cached_key(|p| last(size(p.as_ref()))),
+        SortBy::SizeDescending => paths.sort_by_cached_key(|p| last(size(p.as_ref()).map(Reverse))),
+    }
+}
+
+/// Sorts `paths` in place by a key of the first `header_len` bytes of each file.
+///
+/// `key` gets fewer bytes for files shorter than `header_len`. Sorting is stable, files which
+/// can't be read are put last.
+///
+/// ```no_run
+/// use concat_reader::sort::sort_by_header;
+/// use std::convert::TryInto;
+///
+/// // every segment starts with its sequence number
+/// let mut paths = vec!["wal/a.seg", "wal/b.seg", "wal/c.seg"];
+/// sort_by_header(&mut paths, 8, |header| header.try_into().map(u64::from_be_bytes).ok());
+/// ```
+pub fn sort_by_header<P, F, K>(paths: &mut [P], header_len: usize, mut key: F)
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8]) -> K,
+    K: Ord,
+{
+    paths.sort_by_cached_key(|p| last(header(p.as_ref(), header_len).ok().map(|h| key(&h))));
+}
+
+/// Reads up to `len` bytes from the start of the file at `path`.
+fn header(path: &Path, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(len);
+    File::open(path)?.take(len as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// An iterator over paths in the order of a key of their first bytes.
+///
+/// The keys are resolved when the first path is taken, see [`sort_by_header`]. This struct is
+/// created by [`FileConcatReader::ordered_by_header`].
+///
+/// [`sort_by_header`]:         fn.sort_by_header.html
+/// [`FileConcatReader::ordered_by_header`]: ../file/struct.FileConcatReader.html#method.ordered_by_header
+pub struct ByHeader<P, F, K> {
+    paths: Option<Vec<P>>,
+    sorted: vec::IntoIter<P>,
+    header_len: usize,
+    key: F,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<P, F, K> ByHeader<P, F, K>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8]) -> K,
+    K: Ord,
+{
+    /// Creates a new `ByHeader` over `paths`, without reading any file yet.
+    pub fn new<I: IntoIterator<Item = P>>(paths: I, header_len: usize, key: F) -> Self {
+        ByHeader {
+            paths: Some(paths.into_iter().collect()),
+            sorted: Vec::new().into_iter(),
+            header_len,
+            key,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<P, F, K> Iterator for ByHeader<P, F, K>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8]) -> K,
+    K: Ord,
+{
+    type Item = P;
+
+    fn next(&mut self) -> Option<P> {
+        if let Some(mut paths) = self.paths.take() {
+            sort_by_header(&mut paths, self.header_len, &mut self.key);
+            self.sorted = paths.into_iter();
+        }
+        self.sorted.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.sorted.len() + self.paths.as_ref().map_or(0, Vec::len);
+        (len, Some(len))
+    }
+}
+
+impl<P, F, K> FusedIterator for ByHeader<P, F, K>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8]) -> K,
+    K: Ord,
+{
+}
+
+impl<P: fmt::Debug, F, K> fmt::Debug for ByHeader<P, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ByHeader")
+            .field("paths", &self.paths)
+            .field("sorted", &self.sorted.as_slice())
+            .field("header_len", &self.header_len)
+            .finish()
+    }
+}
+
+/// Compares two strings, treating runs of ASCII digits as numbers.
+///
+/// Numbers compare by value, everything else byte by byte. Strings which only differ in leading
+/// zeros are ordered byte by byte to keep the order total.
+///
+/// ```
+/// use concat_reader::sort::natural_cmp;
+/// use std::cmp::Ordering;
+///
+/// assert_eq!(natural_cmp("part-9.bin", "part-10.bin"), Ordering::Less);
+/// assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+/// ```
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (x.first(), y.first()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let (m, rest_x) = split_digits(x);
+                let (n, rest_y) = split_digits(y);
+                let ord = m.len().cmp(&n.len()).then_with(|| m.cmp(n));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                x = rest_x;
+                y = rest_y;
+            }
+            (Some(c), Some(d)) => {
+                if c != d {
+                    return c.cmp(d);
+                }
+                x = &x[1..];
+                y = &y[1..];
+            }
+        }
+    }
+}
+
+/// Splits a leading run of digits off `s`, returning it without leading zeros and the rest.
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    let end = s
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(s.len());
+    let zeros = s[..end].iter().take_while(|&&c| c == b'0').count();
+    (&s[zeros..end], &s[end..])
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn size(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|meta| meta.len())
+}
+
+/// Sort key putting `None` after all values.
+fn last<T: Ord>(key: Option<T>) -> (bool, Option<T>) {
+    (key.is_none(), key)
+}
+
+impl<P: AsRef<Path>> FileConcatReader<Vec<P>> {
+    /// Creates a new `FileConcatReader` over `paths` sorted by `by`.
+    ///
+    /// See [`sort_paths`].
+    ///
+    /// [`sort_paths`]:         ../sort/fn.sort_paths.html
+    pub fn sorted<I: IntoIterator<Item = P>>(paths: I, by: SortBy) -> Self {
+        let mut paths: Vec<P> = paths.into_iter().collect();
+        sort_paths(&mut paths, by);
+        FileConcatReader::new(paths)
+    }
+}
+
+impl<P, F, K> FileConcatReader<ByHeader<P, F, K>>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8]) -> K,
+    K: Ord,
+{
+    /// Creates a new `FileConcatReader` which reads `paths` in the order of a key of their first
+    /// `header_len` bytes.
+    ///
+    /// The headers are read once, when the reader takes its first path, see
+    /// [`sort_by_header`]. Files which can't be read are put last, where the reader reports
+    /// their error.
+    ///
+    /// ```no_run
+    /// use concat_reader::*;
+    /// use std::convert::TryInto;
+    /// use std::io::prelude::*;
+    ///
+    /// let segments = ["wal/a.seg", "wal/b.seg", "wal/c.seg"];
+    /// let mut c = FileConcatReader::ordered_by_header(&segments, 8, |header| {
+    ///     header.try_into().map(u64::from_be_bytes).ok()
+    /// });
+    /// let mut log = Vec::new();
+    /// c.read_to_end(&mut log).unwrap();
+    /// ```
+    ///
+    /// [`sort_by_header`]:         ../sort/fn.sort_by_header.html
+    pub fn ordered_by_header<I: IntoIterator<Item = P>>(
+        paths: I,
+        header_len: usize,
+        key: F,
+    ) -> Self {
+        FileConcatReader::new(ByHeader::new(paths, header_len, key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{natural_cmp, sort_by_header, sort_paths, SortBy};
+    use crate::test_util::TempTree;
+    use crate::FileConcatReader;
+    use std::cmp::Ordering;
+    use std::io::Read;
+
+    #[test]
+    fn compares_numbers_by_value() {
+        let mut names = vec!["a10", "a2", "a02", "b", "a", "a1b", "a1a", "10", "9z"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["9z", "10", "a", "a1a", "a1b", "a02", "a2", "a10", "b"]
+        );
+        assert_eq!(natural_cmp("x007", "x7"), Ordering::Less);
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+    }
+
+    #[test]
+    fn orders_files_by_their_header() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"3third").unwrap(),
+            tree.file("b", b"1first").unwrap(),
+            tree.file("c", b"").unwrap(),
+            tree.file("d", b"2second").unwrap(),
+        ];
+        let missing = tree.path().join("missing");
+
+        let mut sorted = vec![missing.clone(), paths[0].clone(), paths[2].clone()];
+        sort_by_header(&mut sorted, 1, |header| header.first().copied());
+        assert_eq!(sorted, [paths[2].clone(), paths[0].clone(), missing]);
+
+        let mut reader = FileConcatReader::ordered_by_header(&paths, 1, |header| header.to_vec());
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "1first2second3third");
+    }
+
+    #[test]
+    fn sorts_by_metadata() {
+        let tree = TempTree::new().unwrap();
+        let big = tree.file("big", b"333").unwrap();
+        let small = tree.file("small", b"1").unwrap();
+        let missing = tree.path().join("missing");
+
+        let mut paths = vec![missing.clone(), big.clone(), small.clone()];
+        sort_paths(&mut paths, SortBy::SizeAscending);
+        assert_eq!(paths, [small.clone(), big.clone(), missing.clone()]);
+        sort_paths(&mut paths, SortBy::SizeDescending);
+        assert_eq!(paths, [big.clone(), small.clone(), missing]);
+
+        let mut reader = FileConcatReader::sorted(vec![small, big], SortBy::Name);
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "3331");
+    }
+}
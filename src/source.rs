@@ -0,0 +1,363 @@
+//! Mixing different kinds of sources in one reader.
+//!
+//! A [`Source`] is a file path, a reader, bytes in memory, stdin or the output of a command. [`ConcatReader::from_sources`]
+//! concatenates a list of them, opening files and spawning commands only when they are reached, and still reports the
+//! path of file sources through [`file_path`].
+//!
+//! ```
+//! use concat_reader::source::Source;
+//! use concat_reader::ConcatReader;
+//! use std::io::prelude::*;
+//!
+//! let sources = vec![
+//!     Source::Bytes(b"id,name\n".to_vec()),
+//!     Source::Reader(Box::new(std::io::Cursor::new("1,foo\n"))),
+//! ];
+//! let mut reader = ConcatReader::from_sources(sources);
+//!
+//! let mut buffer = String::new();
+//! reader.read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "id,name\n1,foo\n");
+//! ```
+//!
+//! On unix a [`Source::Raw`] passes through any reader backed by a file descriptor, like a socket
+//! or a pipe, and the reader implements `AsRawFd` returning the descriptor of the current source.
+//! An event loop, e.g. one built on mio, can then wait until the current source is readable.
+//!
+//! [`Source`]:                 enum.Source.html
+//! [`Source::Raw`]:            enum.Source.html#variant.Raw
+//! [`ConcatReader::from_sources`]: ../read/struct.ConcatReader.html#method.from_sources
+//! [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+use crate::provenance;
+use crate::{ConcatRead, ConcatReader, FileConcatRead};
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+/// A reader backed by a file descriptor, like a `TcpStream`, a `UnixStream` or a `ChildStdout`.
+#[cfg(unix)]
+pub trait FdRead: Read + AsRawFd {}
+
+#[cfg(unix)]
+impl<R: Read + AsRawFd> FdRead for R {}
+
+/// A source of a [`ConcatReader`] created with [`from_sources`].
+///
+/// [`ConcatReader`]:           ../read/struct.ConcatReader.html
+/// [`from_sources`]:           ../read/struct.ConcatReader.html#method.from_sources
+pub enum Source {
+    /// A file, which is opened when the reader reaches it.
+    Path(PathBuf),
+    /// Any reader.
+    Reader(Box<dyn Read>),
+    /// Bytes in memory.
+    Bytes(Vec<u8>),
+    /// The standard input of the process.
+    Stdin,
+    /// The stdout of a child process, which is spawned when the reader reaches it.
+    ///
+    /// A non-zero exit status fails the read at the end of the output.
+    Command(Command),
+    /// A reader backed by a file descriptor, which the reader exposes through `AsRawFd`.
+    #[cfg(unix)]
+    Raw(Box<dyn FdRead>),
+}
+
+impl Source {
+    /// Returns the path of a `Path` source.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Source::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            Source::Reader(_) => f.write_str("Reader"),
+            Source::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Source::Stdin => f.write_str("Stdin"),
+            Source::Command(command) => f.debug_tuple("Command").field(command).finish(),
+            #[cfg(unix)]
+            Source::Raw(reader) => f.debug_tuple("Raw").field(&reader.as_raw_fd()).finish(),
+        }
+    }
+}
+
+impl From<PathBuf> for Source {
+    fn from(path: PathBuf) -> Self {
+        Source::Path(path)
+    }
+}
+
+impl From<Command> for Source {
+    fn from(command: Command) -> Self {
+        Source::Command(command)
+    }
+}
+
+impl From<Vec<u8>> for Source {
+    fn from(bytes: Vec<u8>) -> Self {
+        Source::Bytes(bytes)
+    }
+}
+
+impl From<Box<dyn Read>> for Source {
+    fn from(reader: Box<dyn Read>) -> Self {
+        Source::Reader(reader)
+    }
+}
+
+/// A [`Source`] being read.
+///
+/// [`Source`]:                 enum.Source.html
+#[derive(Debug)]
+pub struct SourceReader {
+    source: Source,
+    file: Option<File>,
+    child: Option<Child>,
+    pos: usize,
+}
+
+impl SourceReader {
+    /// Returns the source.
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// Returns the path of a `Path` source.
+    pub fn path(&self) -> Option<&Path> {
+        self.source.path()
+    }
+
+    /// Returns the file descriptor of the source, if it has one.
+    ///
+    /// Files are only opened and commands spawned by the first read, until then they have no
+    /// descriptor.
+    #[cfg(unix)]
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        match &self.source {
+            Source::Path(_) => self.file.as_ref().map(File::as_raw_fd),
+            Source::Stdin => Some(io::stdin().as_raw_fd()),
+            Source::Command(_) => self
+                .child
+                .as_ref()
+                .and_then(|child| child.stdout.as_ref())
+                .map(AsRawFd::as_raw_fd),
+            Source::Raw(reader) => Some(reader.as_raw_fd()),
+            Source::Reader(_) | Source::Bytes(_) => None,
+        }
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            Source::Path(path) => {
+                if self.file.is_none() {
+                    let file = File::open(&path)
+                        .map_err(|e| provenance::annotate(e, "source", Some(path), 0))?;
+                    self.file = Some(file);
+                }
+                self.file.as_mut().expect("opened above").read(buf)
+            }
+            Source::Reader(reader) => reader.read(buf),
+            Source::Bytes(bytes) => {
+                let n = (&bytes[self.pos..]).read(buf)?;
+                self.pos += n;
+                Ok(n)
+            }
+            Source::Stdin => io::stdin().read(buf),
+            Source::Command(command) => {
+                if self.child.is_none() {
+                    let child = command
+                        .stdout(Stdio::piped())
+                        .spawn()
+                        .map_err(|e| provenance::annotate(e, "source", None, 0))?;
+                    self.child = Some(child);
+                }
+                let child = self.child.as_mut().expect("spawned above");
+                let n = match child.stdout.as_mut() {
+                    Some(stdout) => stdout.read(buf)?,
+                    None => 0,
+                };
+                if n == 0 && !buf.is_empty() {
+                    let status = child.wait()?;
+                    if !status.success() {
+                        return Err(io::Error::other(format!(
+                            "{:?} exited with {}",
+                            command, status
+                        )));
+                    }
+                }
+                Ok(n)
+            }
+            #[cfg(unix)]
+            Source::Raw(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// An iterator turning [`Source`]s into [`SourceReader`]s.
+///
+/// [`Source`]:                 enum.Source.html
+/// [`SourceReader`]:           struct.SourceReader.html
+#[derive(Debug)]
+pub struct Sources<I> {
+    iter: I,
+}
+
+impl<I: Iterator<Item = Source>> Iterator for Sources<I> {
+    type Item = SourceReader;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|source| SourceReader {
+            source,
+            file: None,
+            child: None,
+            pos: 0,
+        })
+    }
+}
+
+impl<I: Iterator<Item = Source>> ConcatReader<Sources<I>> {
+    /// Creates a new `ConcatReader` from a list of [`Source`]s of different kinds.
+    ///
+    /// [`Source`]:             ../source/enum.Source.html
+    pub fn from_sources<S>(sources: S) -> Self
+    where
+        S: IntoIterator<IntoIter = I, Item = Source>,
+    {
+        ConcatReader::new(Sources {
+            iter: sources.into_iter(),
+        })
+    }
+}
+
+impl<I: Iterator<Item = Source>> FileConcatRead for ConcatReader<Sources<I>> {
+    fn file_path(&self) -> Option<&Path> {
+        self.current().and_then(SourceReader::path)
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.peek_next().and_then(SourceReader::path)
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        match self.current() {
+            Some(SourceReader {
+                file: Some(file), ..
+            }) => file.metadata(),
+            Some(SourceReader {
+                source: Source::Path(path),
+                ..
+            }) => fs::metadata(path),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "no current file")),
+        }
+    }
+}
+
+/// Returns the descriptor of the current source, or `-1` if it has none, see
+/// [`SourceReader::raw_fd`].
+///
+/// The descriptor changes when the reader moves on to the next source, so an event loop has to
+/// register it again after every read which returned data from a new source.
+///
+/// [`SourceReader::raw_fd`]:   struct.SourceReader.html#method.raw_fd
+#[cfg(unix)]
+impl<I: Iterator<Item = Source>> AsRawFd for ConcatReader<Sources<I>> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.current().and_then(SourceReader::raw_fd).unwrap_or(-1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Source;
+    use crate::provenance::Provenance;
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, ConcatReader, FileConcatRead};
+    use std::io::{self, Read};
+    use std::process::Command;
+
+    #[test]
+    fn mixes_sources_and_reports_paths() {
+        let tree = TempTree::new().unwrap();
+        let path = tree.file("data.csv", b"1,foo\n").unwrap();
+        let missing = tree.path().join("missing.csv");
+        let sources = vec![
+            Source::Bytes(b"id,name\n".to_vec()),
+            Source::Path(path.clone()),
+            Source::Reader(Box::new(io::Cursor::new("2,bar\n"))),
+            Source::Path(missing.clone()),
+        ];
+        let mut reader = ConcatReader::from_sources(sources);
+
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.file_path(), None);
+        assert!(reader.skip());
+        reader.read_exact(&mut buf[..6]).unwrap();
+        assert_eq!(&buf[..6], b"1,foo\n");
+        assert_eq!(reader.file_path(), Some(path.as_path()));
+
+        let mut rest = Vec::new();
+        let err = reader.read_to_end(&mut rest).unwrap_err();
+        assert_eq!(rest, b"2,bar\n");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let crumbs = Provenance::of(&err).unwrap().crumbs();
+        assert_eq!(crumbs[0].path.as_deref(), Some(missing.as_path()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawns_commands_and_fails_on_exit_status() {
+        let command = |script: &str| {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            Source::Command(command)
+        };
+        let sources = vec![
+            command("printf a"),
+            Source::Bytes(b"b".to_vec()),
+            command("printf c; exit 3"),
+        ];
+        let mut reader = ConcatReader::from_sources(sources);
+
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(buf, b"abc");
+        assert!(err.to_string().contains("exit status: 3"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exposes_fd_of_current_source() {
+        use std::io::Write;
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let (mut tx, rx) = UnixStream::pair().unwrap();
+        let fd = rx.as_raw_fd();
+        tx.write_all(b"raw").unwrap();
+        drop(tx);
+        let sources = vec![Source::Bytes(b"1".to_vec()), Source::Raw(Box::new(rx))];
+        let mut reader = ConcatReader::from_sources(sources);
+        assert_eq!(reader.as_raw_fd(), -1);
+
+        let mut buf = [0; 1];
+        reader.read_exact(&mut buf).unwrap();
+        assert!(reader.skip());
+        assert_eq!(reader.as_raw_fd(), fd);
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "raw");
+    }
+}
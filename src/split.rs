@@ -0,0 +1,290 @@
+//! Splitting a stream into numbered files.
+//!
+//! A [`SplitFileWriter`] is the write side of a [`FileConcatReader`]: it writes a stream into
+//! files of a maximum size, named after a template, which a `FileConcatReader` joins again. The
+//! files are created when the first byte for them is written, so there is never an empty part
+//! at the end.
+//!
+//! With [`split_at`] parts only end after a delimiter, so no line or record is cut in two, e.g.
+//! to shard a log file for parallel processing.
+//!
+//! ```no_run
+//! use concat_reader::split::SplitFileWriter;
+//! use std::fs::File;
+//! use std::io;
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut parts = SplitFileWriter::new("backup/part-{:04}.bin", 64 * 1024 * 1024);
+//!     io::copy(&mut File::open("backup.tar")?, &mut parts)?;
+//!     let paths = parts.finish()?;
+//!     println!("wrote {} parts", paths.len());
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`SplitFileWriter`]:        struct.SplitFileWriter.html
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+//! [`split_at`]:               struct.SplitFileWriter.html#method.split_at
+use std::cmp;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+type Wrap = Box<dyn FnMut(File) -> io::Result<Box<dyn Write + Send>> + Send>;
+
+/// A file name with a placeholder for the number of the part.
+#[derive(Clone, Debug)]
+struct Template {
+    prefix: String,
+    width: usize,
+    suffix: String,
+}
+
+impl Template {
+    fn parse(template: &str) -> Option<Self> {
+        let start = template.find('{')?;
+        let end = start + template[start..].find('}')?;
+        let width = match &template[start + 1..end] {
+            "" => 0,
+            spec => spec.strip_prefix(":0")?.parse().ok()?,
+        };
+        Some(Template {
+            prefix: template[..start].to_owned(),
+            width,
+            suffix: template[end + 1..].to_owned(),
+        })
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        let name = format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            index,
+            self.suffix,
+            width = self.width
+        );
+        PathBuf::from(name)
+    }
+}
+
+/// A writer which splits its input into numbered files of at most `max_bytes` bytes.
+///
+/// Parts are numbered from `0`. The size limit counts the bytes written to the
+/// `SplitFileWriter`, so parts which are compressed by [`wrap_parts`] end up smaller on disk.
+///
+/// [`wrap_parts`]:             #method.wrap_parts
+pub struct SplitFileWriter {
+    template: Template,
+    max_bytes: u64,
+    wrap: Option<Wrap>,
+    delim: Option<u8>,
+    part: Option<Box<dyn Write + Send>>,
+    /// Bytes written to the current part.
+    written: u64,
+    /// Whether the part may end after the bytes written so far.
+    boundary: bool,
+    paths: Vec<PathBuf>,
+}
+
+impl SplitFileWriter {
+    /// Creates a new `SplitFileWriter` writing parts of at most `max_bytes` bytes.
+    ///
+    /// `template` is the path of the parts with a placeholder for their number, either `{}` or a
+    /// zero padded width like `{:04}`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bytes` is `0` or `template` has no placeholder.
+    pub fn new<T: AsRef<str>>(template: T, max_bytes: u64) -> Self {
+        assert!(max_bytes > 0, "part size must not be 0");
+        let template = Template::parse(template.as_ref())
+            .expect("template must contain a `{}` or `{:0N}` placeholder");
+        SplitFileWriter {
+            template,
+            max_bytes,
+            wrap: None,
+            delim: None,
+            part: None,
+            written: 0,
+            boundary: true,
+            paths: Vec::new(),
+        }
+    }
+
+    /// Writes every part through the writer `wrap` returns for its file, e.g. to compress it.
+    ///
+    /// The wrapping writer is dropped when the part is full, so it has to finish its output on
+    /// drop, or in `flush`, which is called before.
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "zip")]
+    /// # {
+    /// use concat_reader::split::SplitFileWriter;
+    /// use flate2::write::GzEncoder;
+    /// use flate2::Compression;
+    ///
+    /// let parts = SplitFileWriter::new("logs/part-{:03}.gz", 1 << 30)
+    ///     .wrap_parts(|file| Ok(Box::new(GzEncoder::new(file, Compression::default()))));
+    /// # }
+    /// ```
+    pub fn wrap_parts<F>(mut self, wrap: F) -> Self
+    where
+        F: FnMut(File) -> io::Result<Box<dyn Write + Send>> + Send + 'static,
+    {
+        self.wrap = Some(Box::new(wrap));
+        self
+    }
+
+    /// Only ends parts after `delim`, e.g. `b'\n'` to keep lines whole.
+    ///
+    /// A part which reached `max_bytes` is continued up to the next `delim`, so parts are larger
+    /// than `max_bytes` by the rest of the record which crossed the limit.
+    ///
+    /// ```no_run
+    /// use concat_reader::split::SplitFileWriter;
+    /// use concat_reader::FileConcatReader;
+    /// use std::io;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut logs = FileConcatReader::new(vec!["app.log.2", "app.log.1", "app.log"]);
+    ///     let mut shards = SplitFileWriter::new("shards/{:03}.log", 256 << 20).split_at(b'\n');
+    ///     io::copy(&mut logs, &mut shards)?;
+    ///     shards.finish()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn split_at(mut self, delim: u8) -> Self {
+        self.delim = Some(delim);
+        self
+    }
+
+    /// Returns the paths of the parts created so far.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Closes the current part and returns the paths of all parts.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.close_part()?;
+        Ok(self.paths)
+    }
+
+    fn open_part(&mut self) -> io::Result<&mut Box<dyn Write + Send>> {
+        if self.part.is_none() {
+            let path = self.template.path(self.paths.len());
+            let file = File::create(&path)?;
+            let part = match &mut self.wrap {
+                Some(wrap) => wrap(file)?,
+                None => Box::new(file),
+            };
+            self.paths.push(path);
+            self.part = Some(part);
+        }
+        Ok(self.part.as_mut().expect("opened above"))
+    }
+
+    fn close_part(&mut self) -> io::Result<()> {
+        self.written = 0;
+        if let Some(mut part) = self.part.take() {
+            part.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for SplitFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.written >= self.max_bytes && self.boundary {
+            self.close_part()?;
+        }
+        let len = match self.delim {
+            Some(delim) if self.written >= self.max_bytes => buf
+                .iter()
+                .position(|&b| b == delim)
+                .map_or(buf.len(), |i| i + 1),
+            _ => cmp::min(buf.len() as u64, self.max_bytes - self.written) as usize,
+        };
+        let n = self.open_part()?.write(&buf[..len])?;
+        self.written += n as u64;
+        if n > 0 {
+            self.boundary = self.delim.is_none_or(|delim| buf[n - 1] == delim);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.part {
+            Some(part) => part.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for SplitFileWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SplitFileWriter")
+            .field("template", &self.template)
+            .field("max_bytes", &self.max_bytes)
+            .field("written", &self.written)
+            .field("paths", &self.paths)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SplitFileWriter;
+    use crate::test_util::TempTree;
+    use crate::FileConcatReader;
+    use std::fs;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn splits_into_parts_and_joins_them() {
+        let tree = TempTree::new().unwrap();
+        let template = tree.path().join("part-{:02}.bin");
+        let mut parts = SplitFileWriter::new(template.to_str().unwrap(), 4);
+        parts.write_all(b"0123456789").unwrap();
+        let paths = parts.finish().unwrap();
+
+        let names: Vec<_> = paths.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(names, ["part-00.bin", "part-01.bin", "part-02.bin"]);
+        assert_eq!(fs::read(&paths[2]).unwrap(), b"89");
+        let mut joined = String::new();
+        FileConcatReader::new(&paths)
+            .read_to_string(&mut joined)
+            .unwrap();
+        assert_eq!(joined, "0123456789");
+
+        let template = tree.path().join("framed-{}");
+        let mut parts = SplitFileWriter::new(template.to_str().unwrap(), 2).wrap_parts(|mut f| {
+            f.write_all(b">")?;
+            Ok(Box::new(f))
+        });
+        parts.write_all(b"abc").unwrap();
+        let paths = parts.finish().unwrap();
+        assert_eq!(fs::read(&paths[0]).unwrap(), b">ab");
+        assert_eq!(fs::read(&paths[1]).unwrap(), b">c");
+    }
+
+    #[test]
+    fn splits_only_after_delimiters() {
+        let tree = TempTree::new().unwrap();
+        let template = tree.path().join("shard-{}.log");
+        let mut shards = SplitFileWriter::new(template.to_str().unwrap(), 5).split_at(b'\n');
+        for byte in b"ab\ncdefg\nhijk\nl\nmnopqrstu\nv" {
+            shards.write_all(&[*byte]).unwrap();
+        }
+        let paths = shards.finish().unwrap();
+
+        let shards: Vec<String> = paths
+            .iter()
+            .map(|p| fs::read_to_string(p).unwrap())
+            .collect();
+        assert_eq!(shards, ["ab\ncdefg\n", "hijk\n", "l\nmnopqrstu\n", "v"]);
+    }
+}
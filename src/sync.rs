@@ -0,0 +1,180 @@
+//! Sharing readers between threads.
+//!
+//! # Thread safety
+//!
+//! Readers own their sources and iterators and don't share state behind the scenes, so a reader
+//! is `Send` whenever the iterator and items it is built from are `Send`. Closures passed to
+//! builders like `filter_sources`, `checkpoint_every` or [`Transcode::declare`] are required to
+//! be `Send` for this reason. [`MapSources`] shares its inner reader with the mapped sources and
+//! is never `Send`.
+//!
+//! Readers are not `Sync`: reading needs `&mut self`, so sharing a reader needs a lock anyway.
+//! [`SyncConcatReader`] puts a reader behind a `Mutex` and implements `Read` for shared
+//! references, like `&File` does, so several threads can read from the same reader. Every
+//! read takes the lock, so the bytes of a single read come from one reader without being
+//! interleaved with other threads.
+//!
+//! ```
+//! use concat_reader::sync::SyncConcatReader;
+//! use concat_reader::ConcatReader;
+//! use std::io::Read;
+//! use std::sync::Arc;
+//! use std::thread;
+//!
+//! let bytes: Vec<&'static [u8]> = vec![b"12", b"34"];
+//! let reader = Arc::new(SyncConcatReader::new(ConcatReader::new(bytes)));
+//! let workers: Vec<_> = (0..2)
+//!     .map(|_| {
+//!         let reader = reader.clone();
+//!         thread::spawn(move || {
+//!             let mut buf = [0; 2];
+//!             (&*reader).read_exact(&mut buf).unwrap();
+//!             buf
+//!         })
+//!     })
+//!     .collect();
+//!
+//! let mut read: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+//! read.sort();
+//! assert_eq!(read, [*b"12", *b"34"]);
+//! ```
+//!
+//! [`Transcode::declare`]:     ../encoding/struct.Transcode.html#method.declare
+//! [`MapSources`]:             ../map/struct.MapSources.html
+//! [`SyncConcatReader`]:       struct.SyncConcatReader.html
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Mutex, MutexGuard};
+
+/// A reader behind a `Mutex` which can be read from multiple threads.
+///
+/// See the [module documentation](index.html) for an example.
+pub struct SyncConcatReader<R> {
+    inner: Mutex<R>,
+}
+
+impl<R> SyncConcatReader<R> {
+    /// Creates a new `SyncConcatReader` wrapping `inner`.
+    pub fn new(inner: R) -> Self {
+        SyncConcatReader {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Locks the reader for exclusive access, e.g. to call `skip` or `file_path` on it.
+    ///
+    /// Reads from other threads block until the guard is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, R> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Gets a mutable reference to the underlying reader without locking.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Unwraps this `SyncConcatReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R: Read> Read for SyncConcatReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.get_mut().read(buf)
+    }
+}
+
+impl<R: Read> Read for &SyncConcatReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for SyncConcatReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.inner.try_lock() {
+            Ok(inner) => f.debug_tuple("SyncConcatReader").field(&*inner).finish(),
+            Err(_) => f.write_str("SyncConcatReader(<locked>)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyncConcatReader;
+    use crate::checkpoint::Checkpoint;
+    use crate::interleave::InterleaveReader;
+    use crate::peek::PeekReader;
+    use crate::preamble::SkipPreamble;
+    use crate::progress::{Progress, ProgressReader};
+    use crate::tee::TeeReader;
+    use crate::text::{DropHeaders, Normalize};
+    use crate::throttle::Throttle;
+    use crate::{ConcatRead, ConcatReader, FileConcatReader, SourceQueue};
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn readers_are_send() {
+        type Files = FileConcatReader<Vec<PathBuf>>;
+        assert_send::<ConcatReader<Vec<File>>>();
+        assert_send::<ConcatReader<SourceQueue<File>>>();
+        assert_send::<Files>();
+        assert_send::<FileConcatReader<SourceQueue<PathBuf>>>();
+        assert_send::<FileConcatReader<std::vec::IntoIter<&'static str>>>();
+        assert_send::<InterleaveReader<File>>();
+        assert_send::<PeekReader<Files>>();
+        assert_send::<SkipPreamble<Files>>();
+        assert_send::<Normalize<Files>>();
+        assert_send::<DropHeaders<Files>>();
+        assert_send::<Throttle<Files>>();
+        assert_send::<TeeReader<Files, Vec<u8>>>();
+        assert_send::<ProgressReader<Files, fn(&Progress)>>();
+        #[cfg(feature = "encoding")]
+        assert_send::<crate::encoding::Transcode<Files>>();
+        #[cfg(feature = "hash")]
+        assert_send::<crate::hash::HashReader<Files, crate::hash::Crc32>>();
+
+        let files = FileConcatReader::new(Vec::<PathBuf>::new())
+            .filter_sources(|path| path.extension().is_some())
+            .checkpoint_every(1, |_: &Checkpoint| ());
+        thread::spawn(move || drop(files)).join().unwrap();
+
+        assert_sync::<SyncConcatReader<Files>>();
+    }
+
+    #[test]
+    fn reads_from_multiple_threads() {
+        let bytes: Vec<&'static [u8]> = vec![b"1234", b"5678"];
+        let reader = Arc::new(SyncConcatReader::new(ConcatReader::new(bytes)));
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let reader = reader.clone();
+                thread::spawn(move || {
+                    let mut buf = [0; 2];
+                    (&*reader).read_exact(&mut buf)?;
+                    Ok(buf)
+                })
+            })
+            .collect();
+
+        let mut read = workers
+            .into_iter()
+            .map(|w| w.join().unwrap())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        read.sort();
+        assert_eq!(read, [*b"12", *b"34", *b"56", *b"78"]);
+
+        let mut reader = Arc::try_unwrap(reader).unwrap();
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+        assert!(reader.into_inner().current().is_none());
+    }
+}
@@ -0,0 +1,474 @@
+//! Concatenating the entries of a tar archive.
+//!
+//! This module is only available with the `tar` feature. [`TarConcatReader`] reads the regular
+//! file entries of an archive which match a filter one after another, in archive order, and
+//! reports the name of the current entry through [`file_path`].
+//!
+//! ```no_run
+//! use concat_reader::tar::TarConcatReader;
+//! use concat_reader::FileConcatRead;
+//! use std::io::{self, BufRead, BufReader};
+//!
+//! fn main() -> io::Result<()> {
+//!     let reader = TarConcatReader::open("dump.tar", |path| {
+//!         path.extension().is_some_and(|ext| ext == "jsonl")
+//!     })?;
+//!     let mut reader = BufReader::new(reader);
+//!     let mut line = String::new();
+//!     while reader.read_line(&mut line)? > 0 {
+//!         print!("{}: {}", reader.get_ref().file_path().unwrap().display(), line);
+//!         line.clear();
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The ustar, GNU long name and pax path formats are supported. Directories, links and other
+//! special entries are skipped. The archive is read as a stream, so compressed archives can be
+//! read by wrapping the file in a decompressor.
+//!
+//! [`TarConcatReader`]:        struct.TarConcatReader.html
+//! [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+use crate::core::{ConcatRead, FileConcatRead, RawRead, SourceRead, UnframedRead};
+use std::cmp;
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+const BLOCK: u64 = 512;
+
+/// A regular file entry of a tar archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TarEntry {
+    path: PathBuf,
+    size: u64,
+}
+
+impl TarEntry {
+    /// Returns the path of the entry in the archive.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size of the entry.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// A reader over the contents of the entries of a tar archive which match a filter.
+///
+/// `TarConcatReader` implements [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`] with one
+/// source per entry. A malformed archive fails with an `InvalidData` error, a truncated one with
+/// `UnexpectedEof`.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct TarConcatReader<R, F> {
+    inner: R,
+    filter: F,
+    curr: Option<TarEntry>,
+    /// Data bytes left in the current entry.
+    left: u64,
+    /// Padding bytes after the data of the current entry.
+    padding: u64,
+    started: bool,
+    done: bool,
+    err: Option<io::Error>,
+}
+
+impl<F> TarConcatReader<File, F>
+where
+    F: FnMut(&Path) -> bool,
+{
+    /// Opens the tar archive at `path` and reads the entries for which `filter` returns `true`.
+    pub fn open<P: AsRef<Path>>(path: P, filter: F) -> io::Result<Self> {
+        Ok(TarConcatReader::new(File::open(path)?, filter))
+    }
+}
+
+impl<R, F> TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+    /// Creates a new `TarConcatReader` reading the entries of the archive `inner` for which
+    /// `filter` returns `true`.
+    pub fn new(inner: R, filter: F) -> Self {
+        TarConcatReader {
+            inner,
+            filter,
+            curr: None,
+            left: 0,
+            padding: 0,
+            started: false,
+            done: false,
+            err: None,
+        }
+    }
+
+    /// Unwraps this `TarConcatReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Moves to the next matching entry. Returns `false` at the end of the archive.
+    fn next_entry(&mut self) -> io::Result<bool> {
+        self.curr = None;
+        self.discard(self.left + self.padding)?;
+        self.left = 0;
+        self.padding = 0;
+
+        let mut long_path = None;
+        while !self.done {
+            let mut header = [0; BLOCK as usize];
+            if !self.read_block(&mut header)? || header.iter().all(|&b| b == 0) {
+                self.done = true;
+                break;
+            }
+            let header = Header(&header);
+            header.check()?;
+
+            let size = header.size()?;
+            let padding = (BLOCK - size % BLOCK) % BLOCK;
+            match header.kind() {
+                b'L' => long_path = Some(self.read_path(size, padding)?),
+                b'x' => long_path = pax_path(&self.read_data(size, padding)?).or(long_path),
+                b'0' | b'\0' | b'7' => {
+                    let path = long_path.take().unwrap_or_else(|| header.path());
+                    if (self.filter)(&path) {
+                        self.curr = Some(TarEntry { path, size });
+                        self.left = size;
+                        self.padding = padding;
+                        return Ok(true);
+                    }
+                    self.discard(size + padding)?;
+                }
+                _ => {
+                    long_path = None;
+                    self.discard(size + padding)?;
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reads a header block. Returns `false` if the archive ends before it.
+    fn read_block(&mut self, block: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < block.len() {
+            match self.inner.read(&mut block[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(truncated()),
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_data(&mut self, size: u64, padding: u64) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        (&mut self.inner).take(size).read_to_end(&mut data)?;
+        if (data.len() as u64) < size {
+            return Err(truncated());
+        }
+        self.discard(padding)?;
+        Ok(data)
+    }
+
+    fn read_path(&mut self, size: u64, padding: u64) -> io::Result<PathBuf> {
+        let data = self.read_data(size, padding)?;
+        Ok(path_from(until_nul(&data)))
+    }
+
+    fn discard(&mut self, n: u64) -> io::Result<()> {
+        if io::copy(&mut (&mut self.inner).take(n), &mut io::sink())? < n {
+            return Err(truncated());
+        }
+        Ok(())
+    }
+}
+
+impl<R, F> Read for TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || buf.is_empty() || self.curr.is_none() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<R, F> ConcatRead for TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+    type Item = TarEntry;
+
+    fn skip(&mut self) -> bool {
+        self.started = true;
+        match self.next_entry() {
+            Ok(found) => found,
+            Err(e) => {
+                self.err = Some(e);
+                self.done = true;
+                true
+            }
+        }
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.curr.as_ref()
+    }
+}
+
+impl<R, F> SourceRead for TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.next_entry() {
+                self.done = true;
+                return Err(e);
+            }
+        }
+        if self.left == 0 {
+            return Ok(0);
+        }
+
+        let max = cmp::min(buf.len() as u64, self.left) as usize;
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 && max > 0 {
+            return Err(truncated());
+        }
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+impl<R, F> FileConcatRead for TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+    fn file_path(&self) -> Option<&Path> {
+        self.curr.as_ref().map(TarEntry::path)
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "entries of a tar archive have no file system metadata",
+        ))
+    }
+}
+
+impl<R, F> UnframedRead for TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+}
+
+impl<R, F> RawRead for TarConcatReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Path) -> bool,
+{
+}
+
+impl<R, F> fmt::Debug for TarConcatReader<R, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TarConcatReader")
+            .field("curr", &self.curr)
+            .field("left", &self.left)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+struct Header<'a>(&'a [u8; BLOCK as usize]);
+
+impl Header<'_> {
+    fn kind(&self) -> u8 {
+        self.0[156]
+    }
+
+    fn path(&self) -> PathBuf {
+        let name = until_nul(&self.0[..100]);
+        let prefix = until_nul(&self.0[345..500]);
+        if &self.0[257..262] == b"ustar" && !prefix.is_empty() {
+            let mut path = prefix.to_vec();
+            path.push(b'/');
+            path.extend_from_slice(name);
+            path_from(&path)
+        } else {
+            path_from(name)
+        }
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        let field = &self.0[124..136];
+        if field[0] & 0x80 != 0 {
+            // GNU base-256 encoding for sizes of 8 GiB and more
+            return field[1..]
+                .iter()
+                .try_fold(0u64, |acc, &b| acc.checked_mul(256)?.checked_add(b as u64))
+                .ok_or_else(|| invalid("tar entry size out of range"));
+        }
+        octal(field).ok_or_else(|| invalid("invalid tar entry size"))
+    }
+
+    fn check(&self) -> io::Result<()> {
+        let expected = octal(&self.0[148..156]).ok_or_else(|| invalid("invalid tar checksum"))?;
+        let sum: u64 = self
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| u64::from(if (148..156).contains(&i) { b' ' } else { b }))
+            .sum();
+        if sum != expected {
+            return Err(invalid("tar header checksum mismatch"));
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the `path` of a pax extended header.
+fn pax_path(data: &[u8]) -> Option<PathBuf> {
+    let mut rest = data;
+    let mut path = None;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(value) = record.strip_prefix(b"path=") {
+            path = Some(path_from(value));
+        }
+        rest = &rest[len..];
+    }
+    path
+}
+
+fn octal(field: &[u8]) -> Option<u64> {
+    let digits = std::str::from_utf8(until_nul(field)).ok()?.trim();
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+fn until_nul(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    &bytes[..end]
+}
+
+fn path_from(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "tar archive is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TarConcatReader;
+    use crate::{ConcatRead, FileConcatRead};
+    use std::io::{self, Read};
+    use std::path::Path;
+
+    fn header(name: &str, kind: u8, size: usize) -> Vec<u8> {
+        let mut block = vec![0; 512];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        block[156] = kind;
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[148..156].copy_from_slice(b"        ");
+        let sum: u32 = block.iter().map(|&b| b as u32).sum();
+        block[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        block
+    }
+
+    fn entry(archive: &mut Vec<u8>, name: &str, kind: u8, data: &[u8]) {
+        archive.extend(header(name, kind, data.len()));
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().div_ceil(512) * 512, 0);
+    }
+
+    fn archive() -> Vec<u8> {
+        let gnu = format!("{}/b.jsonl", "d".repeat(120));
+        let pax = format!("{}/c.jsonl", "e".repeat(120));
+        let pax = format!("{} path={}\n", 10 + pax.len(), pax);
+        let mut archive = Vec::new();
+        entry(&mut archive, "a.jsonl", b'0', b"{\"a\":1}\n");
+        entry(&mut archive, "dir/", b'5', b"");
+        entry(&mut archive, "skipped.txt", b'0', b"skipped");
+        entry(&mut archive, "././@LongLink", b'L', gnu.as_bytes());
+        entry(&mut archive, "cut", b'0', b"{\"b\":2}\n");
+        entry(&mut archive, "PaxHeaders/cut", b'x', pax.as_bytes());
+        entry(&mut archive, "cut", b'0', b"{\"c\":3}\n");
+        archive.extend(vec![0; 1024]);
+        archive
+    }
+
+    #[test]
+    fn reads_matching_entries_in_order() {
+        let archive = archive();
+        let jsonl = |p: &Path| p.extension().is_some_and(|e| e == "jsonl");
+        let mut reader = TarConcatReader::new(&archive[..], jsonl);
+
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "{\"a\":1}\n{\"b\":2}\n{\"c\":3}\n");
+
+        let mut reader = TarConcatReader::new(&archive[..], jsonl);
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(reader.file_path(), Some(Path::new("a.jsonl")));
+        assert!(reader.skip());
+        let path = reader.file_path().unwrap();
+        assert!(path.ends_with("b.jsonl") && path.starts_with("d".repeat(120)));
+        assert!(reader.skip());
+        let path = reader.file_path().unwrap();
+        assert!(path.ends_with("c.jsonl") && path.starts_with("e".repeat(120)));
+        assert_eq!(reader.current().unwrap().size(), 8);
+        assert!(!reader.skip());
+    }
+
+    #[test]
+    fn fails_on_corrupt_archives() {
+        let mut corrupt = archive();
+        corrupt[1030] ^= 1;
+        let mut reader = TarConcatReader::new(&corrupt[..], |_: &Path| true);
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let archive = &archive()[..700];
+        let mut reader = TarConcatReader::new(archive, |_: &Path| true);
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
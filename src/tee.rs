@@ -0,0 +1,172 @@
+//! Copying the concatenated stream into a writer while reading it.
+//!
+//! [`TeeReader`] writes every byte returned by `read` into a [`Write`] sink, e.g. to archive the
+//! raw stream while it is parsed. It is created by [`ConcatReader::tee`] and
+//! [`FileConcatReader::tee`].
+//!
+//! ```
+//! use concat_reader::*;
+//! use std::io::prelude::*;
+//!
+//! let bytes = vec!["first".as_bytes(), "second".as_bytes()];
+//! let mut c = ConcatReader::new(bytes).tee(Vec::new());
+//!
+//! let mut buffer = [0; 7];
+//! c.read_exact(&mut buffer).unwrap();
+//! assert_eq!(c.get_ref().1, b"firstse");
+//! ```
+//!
+//! [`TeeReader`]:              struct.TeeReader.html
+//! [`Write`]:                  https://doc.rust-lang.org/std/io/trait.Write.html
+//! [`ConcatReader::tee`]:      ../read/struct.ConcatReader.html#method.tee
+//! [`FileConcatReader::tee`]:  ../file/struct.FileConcatReader.html#method.tee
+use crate::layer::{RawRead, UnframedRead};
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::fs::Metadata;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// A reader adapter which writes all bytes it reads into a writer.
+///
+/// The bytes are written before `read` returns. If writing fails the error is returned from
+/// `read` and the bytes which were read are lost for the caller but may have been written
+/// partially.
+///
+/// `TeeReader` implements [`ConcatRead`] and [`FileConcatRead`] when the wrapped reader does.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct TeeReader<R, W> {
+    inner: R,
+    writer: W,
+}
+
+impl<R: Read, W: Write> TeeReader<R, W> {
+    /// Creates a new `TeeReader` copying all bytes read from `inner` into `writer`.
+    pub fn new(inner: R, writer: W) -> Self {
+        TeeReader { inner, writer }
+    }
+
+    /// Gets references to the underlying reader and writer.
+    pub fn get_ref(&self) -> (&R, &W) {
+        (&self.inner, &self.writer)
+    }
+
+    /// Gets mutable references to the underlying reader and writer.
+    pub fn get_mut(&mut self) -> (&mut R, &mut W) {
+        (&mut self.inner, &mut self.writer)
+    }
+
+    /// Unwraps this `TeeReader`, returning the underlying reader and writer.
+    pub fn into_inner(self) -> (R, W) {
+        (self.inner, self.writer)
+    }
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.writer.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl<R: ConcatRead, W: Write> ConcatRead for TeeReader<R, W> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        self.inner.take_current()
+    }
+}
+
+impl<R: SourceRead, W: Write> SourceRead for TeeReader<R, W> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read_source(buf)?;
+        self.writer.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl<R: UnframedRead, W: Write> UnframedRead for TeeReader<R, W> {}
+
+impl<R: RawRead, W: Write> RawRead for TeeReader<R, W> {}
+
+impl<R: FileConcatRead, W: Write> FileConcatRead for TeeReader<R, W> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TeeReader;
+    use crate::test_util::TempTree;
+    use crate::{ConcatRead, FileConcatRead, FileConcatReader};
+    use std::io::{self, Read, Write};
+
+    #[test]
+    fn copies_bytes_and_keeps_file_path() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"aaaa").unwrap(),
+            tree.file("b", b"bb").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(&paths).tee(Vec::new());
+
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.file_path(), Some(paths[0].as_path()));
+        reader.skip();
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"bb");
+        assert_eq!(reader.into_inner().1, b"aabb");
+    }
+
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken sink"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fails_if_writer_fails() {
+        let mut reader = TeeReader::new(&b"data"[..], Broken);
+        let mut buf = [0; 4];
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().to_string(),
+            "broken sink"
+        );
+    }
+}
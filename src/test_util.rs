@@ -0,0 +1,237 @@
+//! Fixtures for testing readers against real files.
+//!
+//! This module is only available with the `test-util` feature. It creates temporary file trees
+//! with files of different sizes, text encodings, compressed files and broken symlinks, which
+//! can be used to check the behavior of [`FileConcatReader`] configurations end to end.
+//!
+//! ```
+//! use concat_reader::test_util::{assert_concat_eq, TempTree};
+//! use concat_reader::FileConcatReader;
+//!
+//! let tree = TempTree::new().unwrap();
+//! let a = tree.file("a.txt", b"first\n").unwrap();
+//! let b = tree.sized_file("b.bin", 4096).unwrap();
+//!
+//! let paths = vec![a, b];
+//! assert_concat_eq(FileConcatReader::new(&paths), &paths);
+//! ```
+//!
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+use crate::crc32;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TREE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory which is removed with all its content when dropped.
+#[derive(Debug)]
+pub struct TempTree {
+    root: PathBuf,
+}
+
+impl TempTree {
+    /// Creates a new empty directory below [`env::temp_dir`].
+    ///
+    /// [`env::temp_dir`]:          https://doc.rust-lang.org/std/env/fn.temp_dir.html
+    pub fn new() -> io::Result<TempTree> {
+        let n = TREE_COUNT.fetch_add(1, Ordering::SeqCst);
+        let root = env::temp_dir().join(format!("concat-reader-{}-{}", process::id(), n));
+        fs::create_dir_all(&root)?;
+        Ok(TempTree { root })
+    }
+
+    /// Creates a tree with a mix of file kinds and returns it with the paths of all readable files
+    /// in the created order.
+    ///
+    /// The corpus contains empty files, files of different sizes, UTF-8 and UTF-16 text with
+    /// byte order marks, CRLF line endings, a gzip file and files in nested directories.
+    pub fn corpus() -> io::Result<(TempTree, Vec<PathBuf>)> {
+        let tree = TempTree::new()?;
+        let paths = vec![
+            tree.file("empty.txt", b"")?,
+            tree.file("one.txt", b"1")?,
+            tree.sized_file("4k.bin", 4096)?,
+            tree.file("utf8-bom.txt", b"\xEF\xBB\xBFbom\n")?,
+            tree.utf16le_file("utf16le.txt", "utf-16\n")?,
+            tree.file("crlf.txt", b"windows\r\nline\r\n")?,
+            tree.gzip_file("text.gz", b"compressed\n")?,
+            tree.file("nested/dir/deep.txt", b"deep\n")?,
+            tree.sized_file("nested/100k.bin", 100 * 1024)?,
+        ];
+        Ok((tree, paths))
+    }
+
+    /// Returns the root directory of the tree.
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Creates a file with the given content. Missing parent directories are created.
+    pub fn file<P: AsRef<Path>>(&self, name: P, content: &[u8]) -> io::Result<PathBuf> {
+        let path = self.root.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    /// Creates a file of `len` bytes filled with a repeating, position dependent pattern.
+    pub fn sized_file<P: AsRef<Path>>(&self, name: P, len: usize) -> io::Result<PathBuf> {
+        let content: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        self.file(name, &content)
+    }
+
+    /// Creates a UTF-16LE text file starting with a byte order mark.
+    pub fn utf16le_file<P: AsRef<Path>>(&self, name: P, text: &str) -> io::Result<PathBuf> {
+        let mut content = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            content.extend_from_slice(&unit.to_le_bytes());
+        }
+        self.file(name, &content)
+    }
+
+    /// Creates a gzip compressed file with the given uncompressed content.
+    ///
+    /// The content is stored in uncompressed deflate blocks, which every gzip decoder accepts.
+    pub fn gzip_file<P: AsRef<Path>>(&self, name: P, content: &[u8]) -> io::Result<PathBuf> {
+        let mut gz = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        let mut blocks = content.chunks(0xffff).peekable();
+        if blocks.peek().is_none() {
+            gz.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            let last = if blocks.peek().is_none() { 1 } else { 0 };
+            let len = block.len() as u16;
+            gz.push(last);
+            gz.extend_from_slice(&len.to_le_bytes());
+            gz.extend_from_slice(&(!len).to_le_bytes());
+            gz.extend_from_slice(block);
+        }
+        gz.extend_from_slice(&crc32::update(0, content).to_le_bytes());
+        gz.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        self.file(name, &gz)
+    }
+
+    /// Creates a symlink pointing to a file which does not exist.
+    #[cfg(unix)]
+    pub fn broken_symlink<P: AsRef<Path>>(&self, name: P) -> io::Result<PathBuf> {
+        let path = self.root.join(name);
+        let target = self.root.join("does-not-exist");
+        std::os::unix::fs::symlink(target, &path)?;
+        Ok(path)
+    }
+
+    /// Creates a symlink pointing to `target`.
+    #[cfg(unix)]
+    pub fn symlink<P: AsRef<Path>, T: AsRef<Path>>(
+        &self,
+        name: P,
+        target: T,
+    ) -> io::Result<PathBuf> {
+        let path = self.root.join(name);
+        std::os::unix::fs::symlink(target, &path)?;
+        Ok(path)
+    }
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Returns existing pseudo files, which claim to be empty regular files but produce data when
+/// read, like the files in `/proc` on Linux.
+///
+/// Use them to check that size based shortcuts don't skip or truncate such files. The list only
+/// contains files with stable content and is empty on systems without such files.
+pub fn pseudo_files() -> Vec<PathBuf> {
+    ["/proc/version", "/proc/filesystems", "/proc/self/cmdline"]
+        .iter()
+        .map(PathBuf::from)
+        .filter(|p| match fs::metadata(p) {
+            Ok(meta) => meta.is_file() && meta.len() == 0,
+            Err(_) => false,
+        })
+        .collect()
+}
+
+/// Returns the content of all files in `paths` concatenated, as `cat` would produce it.
+pub fn expected_concat<I: IntoIterator>(paths: I) -> io::Result<Vec<u8>>
+where
+    I::Item: AsRef<Path>,
+{
+    let mut expected = Vec::new();
+    for path in paths {
+        expected.extend(fs::read(path)?);
+    }
+    Ok(expected)
+}
+
+/// Asserts that `reader` produces exactly the concatenated content of `paths`.
+///
+/// # Panics
+///
+/// Panics with the first differing offset if the content differs or reading fails.
+pub fn assert_concat_eq<R: Read, I: IntoIterator>(mut reader: R, paths: I)
+where
+    I::Item: AsRef<Path>,
+{
+    let expected = expected_concat(paths).expect("failed to read expected files");
+    let mut actual = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut actual) {
+        panic!("read failed after {} bytes: {}", actual.len(), e);
+    }
+
+    if let Some(offset) = actual.iter().zip(&expected).position(|(a, e)| a != e) {
+        panic!("content differs at offset {}", offset);
+    }
+    assert_eq!(actual.len(), expected.len(), "content length differs");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_concat_eq, TempTree};
+    use crate::{ConcatRead, FileConcatRead, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn concats_corpus() {
+        let (_tree, paths) = TempTree::corpus().unwrap();
+        assert_concat_eq(FileConcatReader::new(&paths), &paths);
+    }
+
+    #[test]
+    fn tree_is_removed_on_drop() {
+        let tree = TempTree::new().unwrap();
+        let root = tree.path().to_path_buf();
+        tree.file("a/b.txt", b"b").unwrap();
+        drop(tree);
+        assert!(!root.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fails_on_broken_symlink_and_continues_after_skip() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a.txt", b"a").unwrap(),
+            tree.broken_symlink("broken").unwrap(),
+            tree.file("b.txt", b"b").unwrap(),
+        ];
+
+        let mut reader = FileConcatReader::new(&paths);
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+        assert_eq!(reader.file_path(), Some(paths[1].as_path()));
+
+        reader.skip();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"ab");
+    }
+}
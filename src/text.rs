@@ -0,0 +1,466 @@
+//! Helpers for concatenating line based text files.
+//!
+//! [`DropHeaders`] turns N CSV (or TSV, ...) files with a header line each into a single valid
+//! stream: the header of the first source is kept, the first line of every following source is
+//! dropped and a missing newline at the end of a source is added.
+//!
+//! ```
+//! use concat_reader::text::DropHeaders;
+//! use concat_reader::concat;
+//! use std::io::prelude::*;
+//!
+//! let csv: Vec<&[u8]> = vec![b"id,name\n1,foo\n", b"id,name\n2,bar"];
+//! let mut reader = DropHeaders::new(concat(csv));
+//!
+//! let mut buffer = String::new();
+//! reader.read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "id,name\n1,foo\n2,bar\n");
+//! ```
+//!
+//! [`Normalize`] strips byte order marks at the start of every source and converts `\r\n` line
+//! endings to `\n`, so files exported on different systems can be mixed.
+//!
+//! [`DropHeaders`]:            struct.DropHeaders.html
+//! [`Normalize`]:              struct.Normalize.html
+use crate::layer::UnframedRead;
+use crate::provenance;
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::mem;
+use std::path::Path;
+
+const BOMS: [&[u8]; 3] = [b"\xEF\xBB\xBF", b"\xFF\xFE", b"\xFE\xFF"];
+
+/// A reader adapter which keeps only the header line of the first source.
+///
+/// The header is the first line of the first source which isn't empty. Lines end at `\n`, so
+/// `\r\n` line endings are handled as well, but quoted CSV fields spanning multiple lines in a
+/// header are not supported.
+///
+/// `DropHeaders` implements [`ConcatRead`] and [`FileConcatRead`] when the wrapped reader does.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct DropHeaders<R> {
+    inner: R,
+    header_seen: bool,
+    skipping: bool,
+    last: Option<u8>,
+    pending_newline: bool,
+    position: u64,
+}
+
+impl<R: UnframedRead> DropHeaders<R> {
+    /// Creates a new `DropHeaders` reader.
+    pub fn new(inner: R) -> Self {
+        DropHeaders {
+            inner,
+            header_seen: false,
+            skipping: false,
+            last: None,
+            pending_newline: false,
+            position: 0,
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `DropHeaders`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn next_source(&mut self) -> bool {
+        self.end_source();
+        self.inner.skip()
+    }
+
+    /// Prepares for the next source after the current one ended or was taken.
+    fn end_source(&mut self) {
+        if let Some(last) = self.last.take() {
+            self.header_seen = true;
+            self.pending_newline = last != b'\n';
+        }
+        self.skipping = self.header_seen;
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.pending_newline {
+                self.pending_newline = false;
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+
+            let mut n = self.inner.read_source(buf)?;
+            if n == 0 {
+                if self.inner.current().is_none() {
+                    return Ok(0);
+                }
+                if !self.next_source() && !self.pending_newline {
+                    return Ok(0);
+                }
+                continue;
+            }
+
+            if self.skipping {
+                match buf[..n].iter().position(|&b| b == b'\n') {
+                    Some(i) => {
+                        self.skipping = false;
+                        buf.copy_within(i + 1..n, 0);
+                        n -= i + 1;
+                    }
+                    None => continue,
+                }
+                if n == 0 {
+                    continue;
+                }
+            }
+
+            self.last = Some(buf[n - 1]);
+            return Ok(n);
+        }
+    }
+}
+
+impl<R: UnframedRead> Read for DropHeaders<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.read_inner(buf);
+        provenance::track(res, "drop_headers", &mut self.position)
+    }
+}
+
+impl<R: UnframedRead> ConcatRead for DropHeaders<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.next_source()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.take_current()?;
+        self.end_source();
+        Some(item)
+    }
+}
+
+impl<R: UnframedRead + FileConcatRead> FileConcatRead for DropHeaders<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+/// A reader adapter which normalizes text from different systems.
+///
+/// By default `Normalize` strips a UTF-8 or UTF-16 byte order mark at the start of every source
+/// and converts `\r\n` to `\n`. A `\r` at the end of a source is kept, as the `\n` of the next
+/// source doesn't belong to the same line. Note that stripping a UTF-16 byte order mark doesn't
+/// transcode the text.
+///
+/// `Normalize` implements [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`] when the wrapped
+/// reader does.
+///
+/// ```
+/// use concat_reader::text::Normalize;
+/// use concat_reader::concat;
+/// use std::io::prelude::*;
+///
+/// let text: Vec<&[u8]> = vec![b"\xEF\xBB\xBFwindows\r\n", b"\xEF\xBB\xBFlinux\n"];
+/// let mut reader = Normalize::new(concat(text));
+///
+/// let mut buffer = String::new();
+/// reader.read_to_string(&mut buffer).unwrap();
+/// assert_eq!(buffer, "windows\nlinux\n");
+/// ```
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct Normalize<R> {
+    inner: R,
+    strip_bom: bool,
+    crlf_to_lf: bool,
+    at_start: bool,
+    head: Vec<u8>,
+    cr_held: bool,
+    out: Vec<u8>,
+    pos: usize,
+    position: u64,
+}
+
+impl<R: UnframedRead> Normalize<R> {
+    /// Creates a new `Normalize` reader with all normalizations enabled.
+    pub fn new(inner: R) -> Self {
+        Normalize {
+            inner,
+            strip_bom: true,
+            crlf_to_lf: true,
+            at_start: true,
+            head: Vec::new(),
+            cr_held: false,
+            out: Vec::new(),
+            pos: 0,
+            position: 0,
+        }
+    }
+
+    /// Enables or disables stripping byte order marks.
+    pub fn strip_bom(mut self, enabled: bool) -> Self {
+        self.strip_bom = enabled;
+        self
+    }
+
+    /// Enables or disables converting `\r\n` to `\n`.
+    pub fn crlf_to_lf(mut self, enabled: bool) -> Self {
+        self.crlf_to_lf = enabled;
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `Normalize`, returning the underlying reader.
+    ///
+    /// Bytes which were read but not yet returned are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Drops the buffered bytes of the current source.
+    fn reset_source(&mut self) {
+        self.at_start = true;
+        self.head.clear();
+        self.cr_held = false;
+        self.out.clear();
+        self.pos = 0;
+    }
+
+    /// Reads the next chunk of the current source into `out`. Returns `false` at the end of the
+    /// source.
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; 8 * 1024];
+        let n = self.inner.read_source(&mut chunk)?;
+
+        if self.at_start && self.strip_bom {
+            self.head.extend_from_slice(&chunk[..n]);
+            if n > 0 && self.head.len() < 3 {
+                return Ok(true);
+            }
+            self.at_start = false;
+            let head = mem::take(&mut self.head);
+            let bom = BOMS.iter().find(|bom| head.starts_with(bom));
+            self.process(&head[bom.map_or(0, |bom| bom.len())..]);
+        } else {
+            self.process(&chunk[..n]);
+        }
+
+        if n == 0 {
+            if mem::take(&mut self.cr_held) {
+                self.out.push(b'\r');
+            }
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.pos < self.out.len() {
+                let n = (&self.out[self.pos..]).read(buf)?;
+                self.pos += n;
+                return Ok(n);
+            }
+
+            self.out.clear();
+            self.pos = 0;
+            if !self.fill()? && self.out.is_empty() {
+                return Ok(0);
+            }
+        }
+    }
+
+    fn process(&mut self, data: &[u8]) {
+        for &b in data {
+            if mem::take(&mut self.cr_held) && b != b'\n' {
+                self.out.push(b'\r');
+            }
+            if self.crlf_to_lf && b == b'\r' {
+                self.cr_held = true;
+            } else {
+                self.out.push(b);
+            }
+        }
+    }
+}
+
+impl<R: UnframedRead> Read for Normalize<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || self.inner.current().is_none() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<R: UnframedRead> ConcatRead for Normalize<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.reset_source();
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        let item = self.inner.take_current()?;
+        self.reset_source();
+        Some(item)
+    }
+}
+
+impl<R: UnframedRead> SourceRead for Normalize<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.read_inner(buf);
+        provenance::track(res, "normalize", &mut self.position)
+    }
+}
+
+impl<R: UnframedRead> UnframedRead for Normalize<R> {}
+
+impl<R: UnframedRead + FileConcatRead> FileConcatRead for Normalize<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DropHeaders, Normalize};
+    use crate::test_util::TempTree;
+    use crate::{ConcatReader, FileConcatReader};
+    use std::io::Read;
+
+    #[test]
+    fn keeps_first_header_only() {
+        let csv: Vec<&[u8]> = vec![
+            b"",
+            b"a,b\r\n1,2\r\n",
+            b"a,b\r\n",
+            b"a,b",
+            b"a,b\r\n3,4",
+            b"a,b\r\n5,6\r\n",
+        ];
+        let mut reader = DropHeaders::new(ConcatReader::new(csv));
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "a,b\r\n1,2\r\n3,4\n5,6\r\n");
+    }
+
+    #[test]
+    fn drops_headers_read_in_small_chunks() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a.csv", b"head\nline 1\n").unwrap(),
+            tree.file("b.csv", b"head\nline 2\n").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(&paths).drop_repeated_headers();
+
+        let mut out = Vec::new();
+        let mut buf = [0; 3];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out, b"head\nline 1\nline 2\n");
+    }
+
+    #[test]
+    fn normalizes_boms_and_line_endings() {
+        let text: Vec<&[u8]> = vec![
+            b"\xEF\xBB",
+            b"\xFF\xFEa\r\nb\r",
+            b"\nc\r\rd\r\n",
+            b"\xEF\xBB\xBF",
+            b"e\xEF\xBB\xBF",
+        ];
+        let mut reader = Normalize::new(ConcatReader::new(text));
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"\xEF\xBBa\nb\r\nc\r\rd\ne\xEF\xBB\xBF");
+    }
+
+    #[test]
+    fn normalizes_files_read_in_small_chunks() {
+        let (_tree, paths) = TempTree::corpus().unwrap();
+        let mut reader = FileConcatReader::new(&paths[3..6]).normalize_text();
+
+        let mut out = Vec::new();
+        let mut buf = [0; 1];
+        while reader.read(&mut buf).unwrap() == 1 {
+            out.push(buf[0]);
+        }
+        assert_eq!(out, b"bom\nu\0t\0f\0-\x001\x006\0\n\0windows\nline\n");
+    }
+}
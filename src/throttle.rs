@@ -0,0 +1,392 @@
+//! Limiting the rate of reading.
+//!
+//! [`Throttle`] limits the number of bytes per second read from the reader it wraps, e.g. to
+//! replay large log archives into a system which can't take them at disk speed. It is created by
+//! [`ConcatReader::throttle`] and [`FileConcatReader::throttle`]. With the `futures` feature
+//! [`AsyncThrottle`] does the same for `AsyncRead` readers.
+//!
+//! The rate is enforced with a token bucket: reading takes tokens, which refill at the rate up to
+//! the burst size. A read waits until enough tokens are available, and a full bucket allows a
+//! burst of reads without waiting.
+//!
+//! ```no_run
+//! use concat_reader::*;
+//! use std::io;
+//!
+//! let files = ["archive/app-1.log", "archive/app-2.log"];
+//! // 1 MiB per second
+//! let mut reader = FileConcatReader::new(&files).throttle(1024 * 1024);
+//! io::copy(&mut reader, &mut io::stdout()).unwrap();
+//! ```
+//!
+//! [`Throttle`]:               struct.Throttle.html
+//! [`AsyncThrottle`]:          struct.AsyncThrottle.html
+//! [`ConcatReader::throttle`]: ../read/struct.ConcatReader.html#method.throttle
+//! [`FileConcatReader::throttle`]: ../file/struct.FileConcatReader.html#method.throttle
+use crate::layer::{RawRead, UnframedRead};
+use crate::{ConcatRead, FileConcatRead, SourceRead};
+use std::fs::Metadata;
+use std::io::{self, Read};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A token bucket refilling at `rate` bytes per second up to `capacity` bytes.
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "rate must be greater than 0");
+        let rate = bytes_per_sec as f64;
+        Bucket {
+            rate,
+            capacity: rate,
+            tokens: rate,
+            last: Instant::now(),
+        }
+    }
+
+    fn set_capacity(&mut self, bytes: u64) {
+        assert!(bytes > 0, "burst size must be greater than 0");
+        self.capacity = bytes as f64;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    /// Returns how many of `want` bytes may be read now, or how long to wait until they may.
+    ///
+    /// Reads larger than the bucket wait for a full bucket, smaller ones for all their bytes, so
+    /// the reader isn't woken up for every single byte.
+    fn grant(&mut self, want: usize) -> Result<usize, Duration> {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+
+        let need = (want as f64).min(self.capacity);
+        if self.tokens >= need {
+            Ok(want.min(self.tokens as usize).max(1))
+        } else {
+            Err(Duration::from_secs_f64((need - self.tokens) / self.rate))
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.tokens -= n as f64;
+    }
+}
+
+/// A reader adapter which limits the number of bytes read per second.
+///
+/// The burst size defaults to the number of bytes of one second. `read` blocks the thread while
+/// waiting for tokens. `Throttle` implements [`ConcatRead`], [`SourceRead`] and
+/// [`FileConcatRead`] when the wrapped reader does.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+#[derive(Debug)]
+pub struct Throttle<R> {
+    inner: R,
+    bucket: Bucket,
+}
+
+impl<R: Read> Throttle<R> {
+    /// Creates a new `Throttle` reading at most `bytes_per_sec` bytes per second from `inner`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sec` is `0`.
+    pub fn new(inner: R, bytes_per_sec: u64) -> Self {
+        Throttle {
+            inner,
+            bucket: Bucket::new(bytes_per_sec),
+        }
+    }
+
+    /// Sets the number of bytes which can be read at once after the reader was idle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is `0`.
+    pub fn burst(mut self, bytes: u64) -> Self {
+        self.bucket.set_capacity(bytes);
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `Throttle`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Blocks until bytes may be read and returns how many of `want`.
+    fn wait(&mut self, want: usize) -> usize {
+        loop {
+            match self.bucket.grant(want) {
+                Ok(n) => return n,
+                Err(delay) => thread::sleep(delay),
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Throttle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.read(buf);
+        }
+        let len = self.wait(buf.len());
+        let n = self.inner.read(&mut buf[..len])?;
+        self.bucket.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: ConcatRead> ConcatRead for Throttle<R> {
+    type Item = R::Item;
+
+    fn skip(&mut self) -> bool {
+        self.inner.skip()
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.inner.current()
+    }
+
+    fn current_mut(&mut self) -> Option<&mut Self::Item> {
+        self.inner.current_mut()
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.inner.peek_next()
+    }
+
+    fn take_current(&mut self) -> Option<Self::Item> {
+        self.inner.take_current()
+    }
+}
+
+impl<R: SourceRead> SourceRead for Throttle<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.read_source(buf);
+        }
+        let len = self.wait(buf.len());
+        let n = self.inner.read_source(&mut buf[..len])?;
+        self.bucket.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: UnframedRead> UnframedRead for Throttle<R> {}
+
+impl<R: RawRead> RawRead for Throttle<R> {}
+
+impl<R: FileConcatRead> FileConcatRead for Throttle<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path()
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.inner.peek_next_path()
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        self.inner.current_metadata()
+    }
+}
+
+#[cfg(feature = "futures")]
+pub use self::with_futures::AsyncThrottle;
+#[cfg(feature = "futures")]
+pub(crate) use self::with_futures::Timer;
+
+#[cfg(feature = "futures")]
+mod with_futures {
+    use super::Bucket;
+    use futures_io::AsyncRead;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::mpsc::{self, Sender};
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+    use std::time::Instant;
+
+    /// Wakes tasks at a deadline from a thread of its own, which ends with the `Timer`.
+    #[derive(Debug)]
+    pub(crate) struct Timer {
+        tx: Sender<(Instant, Waker)>,
+    }
+
+    impl Timer {
+        pub(crate) fn new() -> io::Result<Self> {
+            let (tx, rx) = mpsc::channel::<(Instant, Waker)>();
+            thread::Builder::new()
+                .name("concat-reader-timer".into())
+                .spawn(move || {
+                    for (deadline, waker) in rx {
+                        let now = Instant::now();
+                        if deadline > now {
+                            thread::sleep(deadline - now);
+                        }
+                        waker.wake();
+                    }
+                })?;
+            Ok(Timer { tx })
+        }
+
+        pub(crate) fn wake_at(&self, deadline: Instant, waker: Waker) {
+            // the thread only ends when `tx` is dropped
+            let _ = self.tx.send((deadline, waker));
+        }
+    }
+
+    /// The asynchronous counterpart of [`Throttle`].
+    ///
+    /// `AsyncThrottle` doesn't depend on a runtime: while the reader waits for tokens, a timer
+    /// thread started on the first wait wakes the task.
+    ///
+    /// This struct is created by [`AsyncConcatReader::throttle`].
+    ///
+    /// [`Throttle`]:               struct.Throttle.html
+    /// [`AsyncConcatReader::throttle`]: ../async_read/struct.AsyncConcatReader.html#method.throttle
+    #[derive(Debug)]
+    pub struct AsyncThrottle<R> {
+        inner: R,
+        bucket: Bucket,
+        timer: Option<Timer>,
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncThrottle<R> {
+        /// Creates a new `AsyncThrottle` reading at most `bytes_per_sec` bytes per second from
+        /// `inner`.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `bytes_per_sec` is `0`.
+        pub fn new(inner: R, bytes_per_sec: u64) -> Self {
+            AsyncThrottle {
+                inner,
+                bucket: Bucket::new(bytes_per_sec),
+                timer: None,
+            }
+        }
+
+        /// Sets the number of bytes which can be read at once after the reader was idle.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `bytes` is `0`.
+        pub fn burst(mut self, bytes: u64) -> Self {
+            self.bucket.set_capacity(bytes);
+            self
+        }
+
+        /// Gets a reference to the underlying reader.
+        pub fn get_ref(&self) -> &R {
+            &self.inner
+        }
+
+        /// Unwraps this `AsyncThrottle`, returning the underlying reader.
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncThrottle<R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if buf.is_empty() {
+                return Pin::new(&mut this.inner).poll_read(cx, buf);
+            }
+            match this.bucket.grant(buf.len()) {
+                Ok(len) => {
+                    let res = Pin::new(&mut this.inner).poll_read(cx, &mut buf[..len]);
+                    if let Poll::Ready(Ok(n)) = res {
+                        this.bucket.consume(n);
+                    }
+                    res
+                }
+                Err(delay) => {
+                    if this.timer.is_none() {
+                        this.timer = Some(Timer::new()?);
+                    }
+                    let timer = this.timer.as_ref().expect("started above");
+                    timer.wake_at(Instant::now() + delay, cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Throttle;
+    use crate::test_util::TempTree;
+    use crate::{ConcatReader, FileConcatRead, FileConcatReader};
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn limits_the_rate_after_a_burst() {
+        let bytes: Vec<&[u8]> = vec![&[1; 1500], &[2; 1500]];
+        let mut reader = ConcatReader::new(bytes).throttle(10_000).burst(1000);
+
+        let start = Instant::now();
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.len(), 3000);
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+
+    #[test]
+    fn keeps_file_path() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![tree.file("a", b"1234").unwrap()];
+        let mut reader = Throttle::new(FileConcatReader::new(&paths), 2);
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.file_path(), Some(paths[0].as_path()));
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn waits_without_blocking_the_task() {
+        use crate::async_read::futures_io::AsyncRead;
+        use crate::async_read::AsyncConcatReader;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        let bytes: Vec<&[u8]> = vec![b"1234"];
+        let mut reader = AsyncConcatReader::new(bytes).throttle(1000).burst(2);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut buf = [0; 4];
+        let res = Pin::new(&mut reader).poll_read(&mut cx, &mut buf);
+        assert!(matches!(res, Poll::Ready(Ok(2))));
+        assert!(Pin::new(&mut reader)
+            .poll_read(&mut cx, &mut buf)
+            .is_pending());
+    }
+}
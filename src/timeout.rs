@@ -0,0 +1,378 @@
+//! Failing reads which block for too long.
+//!
+//! Reads from pipes, sockets or network mounts can block indefinitely. [`Timeout`] returns a
+//! `TimedOut` error instead if a single read takes longer than a given duration, attributed to
+//! the file being read. It is created by [`ConcatReader::timeout`] and
+//! [`FileConcatReader::timeout`]. With the `futures` feature [`AsyncTimeout`] does the same for
+//! `AsyncRead` readers.
+//!
+//! ```no_run
+//! use concat_reader::*;
+//! use std::io::{self, Read};
+//! use std::time::Duration;
+//!
+//! let files = vec!["/mnt/nfs/export-1.csv", "/mnt/nfs/export-2.csv"];
+//! let mut reader = FileConcatReader::new(files).timeout(Duration::from_secs(30));
+//! let mut buffer = Vec::new();
+//! match reader.read_to_end(&mut buffer) {
+//!     Err(e) if e.kind() == io::ErrorKind::TimedOut => eprintln!("mount is hanging: {}", e),
+//!     res => {
+//!         res.unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! [`Timeout`]:                struct.Timeout.html
+//! [`AsyncTimeout`]:           struct.AsyncTimeout.html
+//! [`ConcatReader::timeout`]:  ../read/struct.ConcatReader.html#method.timeout
+//! [`FileConcatReader::timeout`]: ../file/struct.FileConcatReader.html#method.timeout
+use crate::pool::{BufferPool, Pooled};
+use crate::provenance;
+use crate::FileConcatRead;
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// A buffer for the worker thread to read into and the number of bytes to read.
+type Request = (Pooled<Vec<u8>>, usize);
+
+/// The result of a read of the worker thread and the path it read from.
+type Response = (io::Result<(Pooled<Vec<u8>>, usize)>, Option<PathBuf>);
+
+/// Size of the buffers of the default pool, which is also the maximum size of a read.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A reader adapter which fails reads that take longer than a timeout.
+///
+/// The wrapped reader is moved to a thread of its own, which performs the reads. When a read
+/// times out it keeps running in the background: the next `read` waits for it again and returns
+/// its bytes, so no data is lost or reordered. The thread ends when the `Timeout` is dropped and
+/// the running read returned.
+///
+/// The thread reads into buffers of a [`BufferPool`] of 64 KiB chunks, which can be replaced
+/// with [`buffer_pool`]. A single read returns at most one chunk.
+///
+/// [`BufferPool`]:             ../pool/struct.BufferPool.html
+/// [`buffer_pool`]:            #method.buffer_pool
+pub struct Timeout<R> {
+    tx: Sender<Request>,
+    rx: Receiver<Response>,
+    timeout: Duration,
+    pool: BufferPool,
+    /// A read was requested from the thread and not answered yet.
+    pending: bool,
+    /// Bytes received which didn't fit into the buffer of `read`, and their length.
+    chunk: Option<(Pooled<Vec<u8>>, usize)>,
+    pos: usize,
+    path: Option<PathBuf>,
+    position: u64,
+    _inner: PhantomData<fn(R)>,
+}
+
+impl<R: Read + Send + 'static> Timeout<R> {
+    /// Creates a new `Timeout` which fails reads from `inner` taking longer than `timeout`.
+    pub fn new(inner: R, timeout: Duration) -> Self {
+        Self::spawn(inner, timeout, |_| None)
+    }
+
+    fn spawn<F>(mut inner: R, timeout: Duration, path_of: F) -> Self
+    where
+        F: Fn(&R) -> Option<PathBuf> + Send + 'static,
+    {
+        let path = path_of(&inner);
+        let (tx, requests) = mpsc::channel::<Request>();
+        let (responses, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("concat-reader-timeout".into())
+            .spawn(move || {
+                for (mut buf, len) in requests {
+                    let res = inner.read(&mut buf[..len]).map(|n| (buf, n));
+                    if responses.send((res, path_of(&inner))).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn reader thread");
+        Timeout {
+            tx,
+            rx,
+            timeout,
+            pool: BufferPool::new(CHUNK_SIZE, 2),
+            pending: false,
+            chunk: None,
+            pos: 0,
+            path,
+            position: 0,
+            _inner: PhantomData,
+        }
+    }
+}
+
+impl<R: FileConcatRead + Send + 'static> Timeout<R> {
+    /// Creates a new `Timeout` which attributes timeouts to the file `inner` is reading.
+    ///
+    /// A timed out read is attributed to the file of the last read which returned, which is the
+    /// file the read started in.
+    pub fn with_paths(inner: R, timeout: Duration) -> Self {
+        Self::spawn(inner, timeout, |inner| {
+            inner.file_path().map(Path::to_path_buf)
+        })
+    }
+}
+
+impl<R> Timeout<R> {
+    /// Takes the buffers of the thread from `pool`.
+    ///
+    /// A pool shared by several readers keeps the number of allocated buffers low when many
+    /// sources are read one after another.
+    pub fn buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Returns the path of the file of the last read, if the `Timeout` was created with
+    /// [`with_paths`].
+    ///
+    /// [`with_paths`]:             #method.with_paths
+    pub fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Copies buffered bytes into `buf`. The chunk returns to the pool once it is consumed.
+    fn take_buffered(&mut self, buf: &mut [u8]) -> usize {
+        let (chunk, len) = match &self.chunk {
+            Some((chunk, len)) => (chunk, *len),
+            None => return 0,
+        };
+        let n = cmp::min(buf.len(), len - self.pos);
+        buf[..n].copy_from_slice(&chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        self.position += n as u64;
+        if self.pos == len {
+            self.chunk = None;
+        }
+        n
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.chunk.is_some() {
+            return Ok(self.take_buffered(buf));
+        }
+
+        if !self.pending {
+            let chunk = self.pool.get();
+            let len = cmp::min(buf.len(), chunk.len());
+            self.tx.send((chunk, len)).map_err(|_| thread_gone())?;
+            self.pending = true;
+        }
+        match self.rx.recv_timeout(self.timeout) {
+            Ok((res, path)) => {
+                self.pending = false;
+                self.path = path;
+                self.chunk = Some(res?);
+                self.pos = 0;
+                Ok(self.take_buffered(buf))
+            }
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("read timed out after {:?}", self.timeout),
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(thread_gone()),
+        }
+    }
+}
+
+fn thread_gone() -> io::Error {
+    io::Error::other("reader thread panicked")
+}
+
+impl<R> Read for Timeout<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.read_inner(buf)
+            .map_err(|e| provenance::annotate(e, "timeout", self.path.as_deref(), self.position))
+    }
+}
+
+impl<R> fmt::Debug for Timeout<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("timeout", &self.timeout)
+            .field("pending", &self.pending)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[cfg(feature = "futures")]
+pub use self::with_futures::AsyncTimeout;
+
+#[cfg(feature = "futures")]
+mod with_futures {
+    use crate::throttle::Timer;
+    use futures_io::AsyncRead;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::{Duration, Instant};
+
+    /// The asynchronous counterpart of [`Timeout`].
+    ///
+    /// A read times out if the wrapped reader stays pending for longer than the timeout. A timer
+    /// thread started on the first pending read wakes the task at the deadline, so
+    /// `AsyncTimeout` doesn't depend on a runtime.
+    ///
+    /// This struct is created by [`AsyncConcatReader::timeout`].
+    ///
+    /// [`Timeout`]:                struct.Timeout.html
+    /// [`AsyncConcatReader::timeout`]: ../async_read/struct.AsyncConcatReader.html#method.timeout
+    #[derive(Debug)]
+    pub struct AsyncTimeout<R> {
+        inner: R,
+        timeout: Duration,
+        deadline: Option<Instant>,
+        timer: Option<Timer>,
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncTimeout<R> {
+        /// Creates a new `AsyncTimeout` which fails reads from `inner` pending longer than
+        /// `timeout`.
+        pub fn new(inner: R, timeout: Duration) -> Self {
+            AsyncTimeout {
+                inner,
+                timeout,
+                deadline: None,
+                timer: None,
+            }
+        }
+
+        /// Gets a reference to the underlying reader.
+        pub fn get_ref(&self) -> &R {
+            &self.inner
+        }
+
+        /// Unwraps this `AsyncTimeout`, returning the underlying reader.
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncTimeout<R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if let Poll::Ready(res) = Pin::new(&mut this.inner).poll_read(cx, buf) {
+                this.deadline = None;
+                return Poll::Ready(res);
+            }
+
+            let now = Instant::now();
+            match this.deadline {
+                Some(deadline) if now >= deadline => {
+                    this.deadline = None;
+                    Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("read timed out after {:?}", this.timeout),
+                    )))
+                }
+                Some(_) => Poll::Pending,
+                None => {
+                    let deadline = now + this.timeout;
+                    this.deadline = Some(deadline);
+                    if this.timer.is_none() {
+                        this.timer = Some(Timer::new()?);
+                    }
+                    let timer = this.timer.as_ref().expect("started above");
+                    timer.wake_at(deadline, cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timeout;
+    use crate::pool::BufferPool;
+    use crate::provenance::Provenance;
+    use crate::test_util::TempTree;
+    use crate::{ConcatReader, FileConcatReader};
+    use std::io::{self, Read};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Sleeps before the first read.
+    struct Slow<'a>(&'a [u8], Option<Duration>);
+
+    impl Read for Slow<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if let Some(delay) = self.1.take() {
+                thread::sleep(delay);
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn fails_slow_reads_without_losing_data() {
+        let items = vec![Slow(b"1234", Some(Duration::from_millis(100)))];
+        let mut reader = ConcatReader::new(items).timeout(Duration::from_millis(10));
+
+        let mut buf = [0; 2];
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(Provenance::of(&err).unwrap().crumbs()[0].layer, "timeout");
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf, b"12");
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf, b"34");
+    }
+
+    #[test]
+    fn reads_into_pooled_buffers() {
+        let pool = BufferPool::new(2, 1);
+        let mut reader =
+            Timeout::new(&b"12345"[..], Duration::from_secs(5)).buffer_pool(pool.clone());
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(pool.idle(), 1);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"345");
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn keeps_paths_of_files() {
+        let tree = TempTree::new().unwrap();
+        let missing = tree.path().join("missing");
+        let paths = vec![tree.file("a", b"12").unwrap(), missing.clone()];
+        let mut reader = FileConcatReader::new(paths.clone()).timeout(Duration::from_secs(5));
+        assert_eq!(reader.file_path(), Some(paths[0].as_path()));
+
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(buf, b"12");
+        assert_eq!(reader.file_path(), Some(missing.as_path()));
+        let provenance = Provenance::of(&err).unwrap();
+        assert_eq!(provenance.path(), Some(missing.as_path()));
+        assert_eq!(provenance.crumbs().last().unwrap().layer, "timeout");
+
+        let reader = Timeout::new(&b""[..], Duration::from_secs(1));
+        assert_eq!(reader.file_path(), None);
+    }
+}
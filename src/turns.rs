@@ -0,0 +1,158 @@
+//! Reading one source at a time.
+//!
+//! [`FileConcatRead::sources`] hands out a reader for each source in turn, for code which wants
+//! "for each file, give me a `Read`" but still uses the lazy opening, error handling and
+//! decoding layers of a concat reader. [`Turns`] is a lending iterator: every [`Turn`] borrows
+//! the reader and ends when the next one is requested, so it can't outlive its turn.
+//!
+//! ```no_run
+//! use concat_reader::{concat_path, FileConcatRead};
+//! use std::io::{self, Read};
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut reader = concat_path(vec!["a.json", "b.json"]);
+//!     let mut sources = reader.sources();
+//!     while let Some(mut source) = sources.next() {
+//!         let mut text = String::new();
+//!         source.read_to_string(&mut text)?;
+//!         println!("{:?}: {} bytes", source.path(), text.len());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`FileConcatRead::sources`]: ../trait.FileConcatRead.html#method.sources
+//! [`Turns`]:                  struct.Turns.html
+//! [`Turn`]:                   struct.Turn.html
+use crate::{FileConcatRead, SourceRead};
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// A lending iterator over the sources of a reader, created by [`FileConcatRead::sources`].
+///
+/// `Turns` can't implement `Iterator`, because every [`Turn`] borrows it. Use
+/// `while let Some(turn) = turns.next()` instead of a `for` loop.
+///
+/// [`FileConcatRead::sources`]: ../trait.FileConcatRead.html#method.sources
+/// [`Turn`]:                   struct.Turn.html
+pub struct Turns<'a, R: ?Sized> {
+    reader: &'a mut R,
+    started: bool,
+}
+
+impl<'a, R: FileConcatRead + SourceRead + ?Sized> Turns<'a, R> {
+    pub(crate) fn new(reader: &'a mut R) -> Self {
+        Turns {
+            reader,
+            started: false,
+        }
+    }
+
+    /// Returns a reader for the next source, or `None` if there are no sources left.
+    ///
+    /// The first turn is the source the reader is at, including what is left of it if it was
+    /// read from before. Every later turn skips to the next source, dropping what the previous
+    /// turn didn't read.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Turn<'_, R>> {
+        if self.started {
+            if !self.reader.skip() {
+                return None;
+            }
+        } else {
+            self.started = true;
+            if self.reader.file_path().is_none() && self.reader.current().is_none() {
+                return None;
+            }
+        }
+        Some(Turn {
+            reader: &mut *self.reader,
+        })
+    }
+}
+
+impl<R: ?Sized> fmt::Debug for Turns<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Turns")
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+/// The reader of a single source, handed out by [`Turns::next`].
+///
+/// Reading returns `Ok(0)` at the end of the source. Errors are returned like by the reader
+/// itself, and the next turn continues with the next source.
+///
+/// [`Turns::next`]:            struct.Turns.html#method.next
+pub struct Turn<'t, R: ?Sized> {
+    reader: &'t mut R,
+}
+
+impl<R: FileConcatRead + SourceRead + ?Sized> Turn<'_, R> {
+    /// Returns the path of the source.
+    pub fn path(&self) -> Option<&Path> {
+        self.reader.file_path()
+    }
+
+    /// Returns the item the source is read from, see [`ConcatRead::current`].
+    ///
+    /// [`ConcatRead::current`]:    ../trait.ConcatRead.html#tymethod.current
+    pub fn current(&self) -> Option<&R::Item> {
+        self.reader.current()
+    }
+}
+
+impl<R: SourceRead + ?Sized> Read for Turn<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read_source(buf)
+    }
+}
+
+impl<R: FileConcatRead + SourceRead + ?Sized> fmt::Debug for Turn<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Turn").field("path", &self.path()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TempTree;
+    use crate::{FileConcatRead, FileConcatReader};
+    use std::io::{self, Read};
+
+    #[test]
+    fn hands_out_one_reader_per_source() {
+        let tree = TempTree::new().unwrap();
+        let paths = vec![
+            tree.file("a", b"123").unwrap(),
+            tree.path().join("missing"),
+            tree.file("b", b"").unwrap(),
+            tree.file("c", b"45").unwrap(),
+        ];
+        let mut reader = FileConcatReader::new(paths.clone());
+        let mut sources = reader.sources();
+
+        let mut read = Vec::new();
+        while let Some(mut source) = sources.next() {
+            let path = source.path().unwrap().to_path_buf();
+            let mut first = [0; 1];
+            match source.read(&mut first) {
+                Ok(n) => read.push((path, first[..n].to_vec())),
+                Err(e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            }
+        }
+        assert_eq!(
+            read,
+            vec![
+                (paths[0].clone(), b"1".to_vec()),
+                (paths[2].clone(), Vec::new()),
+                (paths[3].clone(), b"4".to_vec()),
+            ]
+        );
+
+        let mut empty = FileConcatReader::new(Vec::<&str>::new());
+        assert!(empty.sources().next().is_none());
+    }
+}
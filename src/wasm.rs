@@ -0,0 +1,94 @@
+//! Reading JavaScript byte arrays in the browser.
+//!
+//! The crate builds for `wasm32-unknown-unknown`. There is no file system on that target, so
+//! file readers like [`FileConcatReader`] fail with `Unsupported` when they open a file, and
+//! readers which need threads, like [`Timeout`], fail to start. Readers over sources in memory
+//! work as usual.
+//!
+//! A [`Uint8ArrayReader`] reads a `Uint8Array` without copying it into wasm memory first, so the
+//! chunks of an uploaded file can be concatenated with [`concat_chunks`]. `Blob`s are read
+//! asynchronously in JavaScript, pass them as the `Uint8Array` of their `arrayBuffer()`.
+//!
+//! ```no_run
+//! use concat_reader::wasm::concat_chunks;
+//! use js_sys::{Array, Uint8Array};
+//! use std::io::Read;
+//!
+//! let chunks = Array::of2(
+//!     &Uint8Array::from(&b"some "[..]),
+//!     &Uint8Array::from(&b"chunks"[..]),
+//! );
+//! let mut buffer = String::new();
+//! concat_chunks(&chunks).read_to_string(&mut buffer).unwrap();
+//! assert_eq!(buffer, "some chunks");
+//! ```
+//!
+//! [`FileConcatReader`]:       ../file/struct.FileConcatReader.html
+//! [`Timeout`]:                ../timeout/struct.Timeout.html
+//! [`Uint8ArrayReader`]:       struct.Uint8ArrayReader.html
+//! [`concat_chunks`]:          fn.concat_chunks.html
+use crate::ConcatReader;
+use js_sys::{Array, Uint8Array};
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read};
+
+/// A reader over the bytes of a JavaScript `Uint8Array`.
+pub struct Uint8ArrayReader {
+    array: Uint8Array,
+    pos: u32,
+}
+
+impl Uint8ArrayReader {
+    /// Creates a new `Uint8ArrayReader` reading `array` from its start.
+    pub fn new(array: Uint8Array) -> Self {
+        Uint8ArrayReader { array, pos: 0 }
+    }
+
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> u32 {
+        self.array.length() - self.pos
+    }
+
+    /// Unwraps this `Uint8ArrayReader`, returning the underlying array.
+    pub fn into_inner(self) -> Uint8Array {
+        self.array
+    }
+}
+
+impl From<Uint8Array> for Uint8ArrayReader {
+    fn from(array: Uint8Array) -> Self {
+        Uint8ArrayReader::new(array)
+    }
+}
+
+impl Read for Uint8ArrayReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = cmp::min(self.remaining() as usize, buf.len());
+        let end = self.pos + len as u32;
+        self.array.subarray(self.pos, end).copy_to(&mut buf[..len]);
+        self.pos = end;
+        Ok(len)
+    }
+}
+
+impl fmt::Debug for Uint8ArrayReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Uint8ArrayReader")
+            .field("pos", &self.pos)
+            .field("len", &self.array.length())
+            .finish()
+    }
+}
+
+/// Concats a JavaScript array of `Uint8Array` chunks into a single reader.
+///
+/// Elements of `chunks` which are not `Uint8Array`s are read like `new Uint8Array(element)`,
+/// which accepts `ArrayBuffer`s and arrays of numbers.
+pub fn concat_chunks(chunks: &Array) -> ConcatReader<Vec<Uint8ArrayReader>> {
+    let readers = chunks
+        .iter()
+        .map(|chunk| Uint8ArrayReader::new(Uint8Array::new(&chunk)))
+        .collect();
+    ConcatReader::new(readers)
+}
@@ -0,0 +1,516 @@
+//! Concatenating the entries of a zip archive.
+//!
+//! This module is only available with the `zip` feature. [`ZipConcatReader`] decompresses the
+//! entries of an archive which match a filter one after another and reports the name of the
+//! current entry through [`file_path`]. Entries are read in the order of the archive's central
+//! directory or sorted by name, see [`Order`].
+//!
+//! ```no_run
+//! use concat_reader::zip::{Order, ZipConcatReader};
+//! use std::io::{self, Read};
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut reader = ZipConcatReader::open("dump.zip", |path| {
+//!         path.extension().is_some_and(|ext| ext == "csv")
+//!     })?
+//!     .order(Order::Name)
+//!     .drop_repeated_headers();
+//!
+//!     let mut csv = String::new();
+//!     reader.read_to_string(&mut csv)?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Stored and deflated entries are supported, encrypted entries and zip64 archives are not. The
+//! CRC-32 of every entry is verified at its end.
+//!
+//! [`ZipConcatReader`]:        struct.ZipConcatReader.html
+//! [`file_path`]:              ../trait.FileConcatRead.html#tymethod.file_path
+//! [`Order`]:                  enum.Order.html
+use crate::core::{ConcatRead, FileConcatRead, RawRead, SourceRead, UnframedRead};
+use crate::crc32;
+use crate::text::DropHeaders;
+use flate2::read::DeflateDecoder;
+use std::cmp;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Take};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+
+/// The order in which entries are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Order {
+    /// The order the entries are stored in the archive.
+    #[default]
+    Archive,
+    /// Sorted by the names of the entries, byte by byte.
+    Name,
+}
+
+/// A file entry of a zip archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZipEntry {
+    path: PathBuf,
+    name: Vec<u8>,
+    size: u64,
+    compressed_size: u64,
+    method: u16,
+    crc: u32,
+    encrypted: bool,
+    offset: u64,
+}
+
+impl ZipEntry {
+    /// Returns the path of the entry in the archive.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the decompressed size of the entry.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+enum State<R> {
+    Idle(R),
+    Stored(Take<R>),
+    Deflated(DeflateDecoder<Take<R>>),
+    Closed,
+}
+
+impl<R: Read> State<R> {
+    fn into_inner(self) -> Option<R> {
+        match self {
+            State::Idle(r) => Some(r),
+            State::Stored(r) => Some(r.into_inner()),
+            State::Deflated(r) => Some(r.into_inner().into_inner()),
+            State::Closed => None,
+        }
+    }
+}
+
+/// A reader over the decompressed contents of the entries of a zip archive which match a filter.
+///
+/// `ZipConcatReader` implements [`ConcatRead`], [`SourceRead`] and [`FileConcatRead`] with one
+/// source per entry. Directory entries are skipped. A malformed archive or an entry with a wrong
+/// CRC-32 fails with an `InvalidData` error.
+///
+/// [`ConcatRead`]:             ../trait.ConcatRead.html
+/// [`SourceRead`]:             ../trait.SourceRead.html
+/// [`FileConcatRead`]:         ../trait.FileConcatRead.html
+pub struct ZipConcatReader<R> {
+    state: State<R>,
+    entries: Vec<ZipEntry>,
+    next: usize,
+    curr: Option<usize>,
+    crc: u32,
+    read: u64,
+    started: bool,
+    err: Option<io::Error>,
+}
+
+impl ZipConcatReader<File> {
+    /// Opens the zip archive at `path` and reads the entries for which `filter` returns `true`.
+    pub fn open<P, F>(path: P, filter: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> bool,
+    {
+        ZipConcatReader::new(File::open(path)?, filter)
+    }
+}
+
+impl<R: Read + Seek> ZipConcatReader<R> {
+    /// Creates a new `ZipConcatReader` reading the entries of the archive `inner` for which
+    /// `filter` returns `true`.
+    ///
+    /// Reads the central directory of the archive and fails if it is malformed.
+    pub fn new<F>(mut inner: R, mut filter: F) -> io::Result<Self>
+    where
+        F: FnMut(&Path) -> bool,
+    {
+        let mut entries: Vec<_> = read_directory(&mut inner)?
+            .into_iter()
+            .filter(|e| !e.name.ends_with(b"/") && filter(&e.path))
+            .collect();
+        entries.sort_by_key(|e| e.offset);
+        Ok(ZipConcatReader {
+            state: State::Idle(inner),
+            entries,
+            next: 0,
+            curr: None,
+            crc: 0,
+            read: 0,
+            started: false,
+            err: None,
+        })
+    }
+
+    /// Sets the order in which entries are read. Only has an effect before the first read.
+    pub fn order(mut self, order: Order) -> Self {
+        if !self.started {
+            match order {
+                Order::Archive => self.entries.sort_by_key(|e| e.offset),
+                Order::Name => self.entries.sort_by(|a, b| a.name.cmp(&b.name)),
+            }
+        }
+        self
+    }
+
+    /// Returns the entries which will be read, in reading order.
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Keeps only the header line of the first entry, see [`DropHeaders`].
+    ///
+    /// [`DropHeaders`]:        ../text/struct.DropHeaders.html
+    pub fn drop_repeated_headers(self) -> DropHeaders<Self> {
+        DropHeaders::new(self)
+    }
+
+    /// Unwraps this `ZipConcatReader`, returning the underlying reader.
+    ///
+    /// Returns `None` if an error left the archive in an unknown state.
+    pub fn into_inner(self) -> Option<R> {
+        self.state.into_inner()
+    }
+
+    /// Moves to the next entry. Returns `false` if there is none.
+    fn next_entry(&mut self) -> io::Result<bool> {
+        self.curr = None;
+        let mut inner = match mem::replace(&mut self.state, State::Closed).into_inner() {
+            Some(inner) => inner,
+            None => return Ok(false),
+        };
+        let entry = match self.entries.get(self.next) {
+            Some(entry) => entry,
+            None => {
+                self.state = State::Idle(inner);
+                return Ok(false);
+            }
+        };
+
+        if entry.encrypted {
+            return Err(invalid(format!("{} is encrypted", entry.path.display())));
+        }
+        inner.seek(SeekFrom::Start(entry.offset))?;
+        let mut header = [0; 30];
+        inner.read_exact(&mut header)?;
+        if u32_at(&header, 0) != LOCAL_HEADER {
+            return Err(invalid("invalid zip local file header"));
+        }
+        let skip = u64::from(u16_at(&header, 26)) + u64::from(u16_at(&header, 28));
+        inner.seek(SeekFrom::Current(skip as i64))?;
+
+        let data = inner.take(entry.compressed_size);
+        self.state = match entry.method {
+            0 => State::Stored(data),
+            8 => State::Deflated(DeflateDecoder::new(data)),
+            method => {
+                return Err(invalid(format!(
+                    "{} uses the unsupported compression method {}",
+                    entry.path.display(),
+                    method
+                )))
+            }
+        };
+        self.curr = Some(self.next);
+        self.next += 1;
+        self.crc = 0;
+        self.read = 0;
+        Ok(true)
+    }
+
+    fn read_entry(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let entry = match self.curr {
+            Some(i) => &self.entries[i],
+            None => return Ok(0),
+        };
+        let max = cmp::min(buf.len() as u64, entry.size - self.read) as usize;
+        let n = match &mut self.state {
+            State::Stored(r) => r.read(&mut buf[..max])?,
+            State::Deflated(r) => r.read(&mut buf[..max])?,
+            _ => 0,
+        };
+        self.crc = crc32::update(self.crc, &buf[..n]);
+        self.read += n as u64;
+
+        if n == 0 && !buf.is_empty() {
+            if self.read < entry.size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} is truncated", entry.path.display()),
+                ));
+            }
+            if self.crc != entry.crc {
+                return Err(invalid(format!(
+                    "CRC-32 mismatch in {}",
+                    entry.path.display()
+                )));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Read for ZipConcatReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.read_source(buf)?;
+            if n > 0 || buf.is_empty() || self.curr.is_none() || !self.skip() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> ConcatRead for ZipConcatReader<R> {
+    type Item = ZipEntry;
+
+    fn skip(&mut self) -> bool {
+        self.started = true;
+        match self.next_entry() {
+            Ok(found) => found,
+            Err(e) => {
+                self.err = Some(e);
+                true
+            }
+        }
+    }
+
+    fn current(&self) -> Option<&Self::Item> {
+        self.curr.map(|i| &self.entries[i])
+    }
+
+    fn peek_next(&mut self) -> Option<&Self::Item> {
+        self.entries.get(self.next)
+    }
+}
+
+impl<R: Read + Seek> SourceRead for ZipConcatReader<R> {
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+        if !self.started {
+            self.started = true;
+            self.next_entry()?;
+        }
+        self.read_entry(buf)
+    }
+}
+
+impl<R: Read + Seek> FileConcatRead for ZipConcatReader<R> {
+    fn file_path(&self) -> Option<&Path> {
+        self.current().map(ZipEntry::path)
+    }
+
+    fn peek_next_path(&mut self) -> Option<&Path> {
+        self.peek_next().map(ZipEntry::path)
+    }
+
+    fn current_metadata(&self) -> io::Result<Metadata> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "entries of a zip archive have no file system metadata",
+        ))
+    }
+}
+
+impl<R: Read + Seek> UnframedRead for ZipConcatReader<R> {}
+
+impl<R: Read + Seek> RawRead for ZipConcatReader<R> {}
+
+impl<R> fmt::Debug for ZipConcatReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ZipConcatReader")
+            .field("entries", &self.entries.len())
+            .field("curr", &self.curr.map(|i| &self.entries[i].path))
+            .finish()
+    }
+}
+
+/// Reads the entries of the central directory.
+fn read_directory<R: Read + Seek>(inner: &mut R) -> io::Result<Vec<ZipEntry>> {
+    // the end of central directory record is 22 bytes plus a comment of up to 64 KiB
+    let len = inner.seek(SeekFrom::End(0))?;
+    let tail_len = cmp::min(len, 22 + 0xFFFF);
+    inner.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = Vec::with_capacity(tail_len as usize);
+    inner.read_to_end(&mut tail)?;
+
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == END_OF_DIRECTORY)
+        .ok_or_else(|| invalid("not a zip archive"))?;
+    let count = u16_at(&tail, end + 10);
+    let size = u32_at(&tail, end + 12);
+    let offset = u32_at(&tail, end + 16);
+    if count == 0xFFFF || size == 0xFFFF_FFFF || offset == 0xFFFF_FFFF {
+        return Err(invalid("zip64 archives are not supported"));
+    }
+
+    inner.seek(SeekFrom::Start(u64::from(offset)))?;
+    let mut directory = vec![0; size as usize];
+    inner.read_exact(&mut directory)?;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut pos = 0;
+    for _ in 0..count {
+        let header = directory
+            .get(pos..pos + 46)
+            .filter(|h| u32_at(h, 0) == CENTRAL_HEADER)
+            .ok_or_else(|| invalid("invalid zip central directory"))?;
+        let name_len = u16_at(header, 28) as usize;
+        let extra_len = u16_at(header, 30) as usize;
+        let comment_len = u16_at(header, 32) as usize;
+        let name = directory
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or_else(|| invalid("invalid zip central directory"))?
+            .to_vec();
+        entries.push(ZipEntry {
+            path: PathBuf::from(String::from_utf8_lossy(&name).into_owned()),
+            name,
+            size: u64::from(u32_at(header, 24)),
+            compressed_size: u64::from(u32_at(header, 20)),
+            method: u16_at(header, 10),
+            crc: u32_at(header, 16),
+            encrypted: u16_at(header, 8) & 1 != 0,
+            offset: u64::from(u32_at(header, 42)),
+        });
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().expect("two bytes"))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().expect("four bytes"))
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Order, ZipConcatReader};
+    use crate::crc32;
+    use crate::{ConcatRead, FileConcatRead};
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::{self, Cursor, Read, Write};
+    use std::path::Path;
+
+    /// Builds an archive, deflating entries whose name ends with `.gz.csv`.
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut directory = Vec::new();
+        for (name, data) in entries {
+            let (method, stored) = if name.ends_with(".gz.csv") {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).unwrap();
+                (8u16, encoder.finish().unwrap())
+            } else {
+                (0u16, data.to_vec())
+            };
+            let mut fields = Vec::new();
+            fields.extend(&[20, 0, 0, 0]);
+            fields.extend(&method.to_le_bytes());
+            fields.extend(&[0, 0, 0, 0]);
+            fields.extend(&crc32::update(0, data).to_le_bytes());
+            fields.extend(&(stored.len() as u32).to_le_bytes());
+            fields.extend(&(data.len() as u32).to_le_bytes());
+            fields.extend(&(name.len() as u16).to_le_bytes());
+            fields.extend(&[0, 0]);
+
+            directory.extend(&super::CENTRAL_HEADER.to_le_bytes());
+            directory.extend(&[20, 0]);
+            directory.extend(&fields);
+            // comment length, disk, internal and external attributes
+            directory.extend(&[0; 10]);
+            directory.extend(&(zip.len() as u32).to_le_bytes());
+            directory.extend(name.as_bytes());
+
+            zip.extend(&super::LOCAL_HEADER.to_le_bytes());
+            zip.extend(&fields);
+            zip.extend(name.as_bytes());
+            zip.extend(&stored);
+        }
+        let offset = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(&super::END_OF_DIRECTORY.to_le_bytes());
+        zip.extend(&[0; 4]);
+        zip.extend(&(entries.len() as u16).to_le_bytes());
+        zip.extend(&(entries.len() as u16).to_le_bytes());
+        zip.extend(&(directory.len() as u32).to_le_bytes());
+        zip.extend(&offset.to_le_bytes());
+        zip.extend(&[0, 0]);
+        zip
+    }
+
+    fn csv(path: &Path) -> bool {
+        path.extension().is_some_and(|e| e == "csv")
+    }
+
+    #[test]
+    fn reads_matching_entries_in_order() {
+        let zip = archive(&[
+            ("b.gz.csv", b"id\n2\n"),
+            ("dir/", b""),
+            ("readme.txt", b"skipped"),
+            ("a.csv", b"id\n1\n"),
+        ]);
+
+        let mut reader = ZipConcatReader::new(Cursor::new(&zip), csv).unwrap();
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "id\n2\nid\n1\n");
+
+        let mut reader = ZipConcatReader::new(Cursor::new(&zip), csv)
+            .unwrap()
+            .order(Order::Name);
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(reader.file_path(), Some(Path::new("a.csv")));
+        assert!(reader.skip());
+        assert_eq!(reader.current().unwrap().size(), 5);
+        assert!(!reader.skip());
+
+        let mut reader = ZipConcatReader::new(Cursor::new(&zip), csv)
+            .unwrap()
+            .order(Order::Name)
+            .drop_repeated_headers();
+        buf.clear();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "id\n1\n2\n");
+    }
+
+    #[test]
+    fn fails_on_crc_mismatch() {
+        let mut zip = archive(&[("a.csv", b"data")]);
+        // the data follows the 30 bytes of the local header and the name
+        zip[35] = b'D';
+
+        let mut reader = ZipConcatReader::new(Cursor::new(zip), csv).unwrap();
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(buf, b"Data");
+
+        let err = ZipConcatReader::new(Cursor::new(b"no zip"), csv).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
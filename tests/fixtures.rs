@@ -0,0 +1,81 @@
+use concat_reader::file::known_len;
+use concat_reader::progress::Progress;
+use concat_reader::test_util::{assert_concat_eq, expected_concat, pseudo_files, TempTree};
+use concat_reader::{concat_path, ConcatRead, FileConcatRead, FileConcatReader};
+use std::io::Read;
+
+#[test]
+fn reads_corpus_in_order() {
+    let (_tree, paths) = TempTree::corpus().unwrap();
+    assert_concat_eq(concat_path(&paths), &paths);
+}
+
+#[test]
+fn reports_path_of_each_file() {
+    let (_tree, paths) = TempTree::corpus().unwrap();
+    let mut reader = FileConcatReader::new(&paths);
+    let mut seen = Vec::new();
+    let mut buf = [0; 1];
+    while reader.read(&mut buf).unwrap() == 1 {
+        let path = reader.file_path().unwrap().to_path_buf();
+        if seen.last() != Some(&path) {
+            seen.push(path);
+        }
+    }
+
+    // the empty file never produces a byte
+    assert_eq!(seen, paths[1..].to_vec());
+}
+
+#[test]
+fn skips_rest_of_file() {
+    let (_tree, paths) = TempTree::corpus().unwrap();
+    let mut reader = FileConcatReader::new(&paths[2..4]);
+    let mut buf = [0; 10];
+    reader.read_exact(&mut buf).unwrap();
+    reader.skip();
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, expected_concat(&paths[3..4]).unwrap());
+}
+
+#[test]
+fn reads_pseudo_files_reporting_zero_length() {
+    let (_tree, mut paths) = TempTree::corpus().unwrap();
+    let pseudo = pseudo_files();
+    for path in &pseudo {
+        assert_eq!(known_len(path), Some(0));
+    }
+    paths.extend(pseudo.iter().cloned());
+    assert_concat_eq(concat_path(&paths), &paths);
+
+    let lines = FileConcatReader::new(&pseudo).lines_with_source();
+    let first_lines: Vec<_> = lines
+        .map(|l| l.unwrap())
+        .filter(|(_, line_no, _)| *line_no == 1)
+        .map(|(path, _, _)| path)
+        .collect();
+    assert_eq!(first_lines, pseudo);
+}
+
+#[test]
+fn progress_drops_total_of_pseudo_files() {
+    let pseudo = pseudo_files();
+    if pseudo.is_empty() {
+        return;
+    }
+
+    let mut last = None;
+    let mut reader = FileConcatReader::new(&pseudo).progress(|p: &Progress| last = Some(*p));
+    assert_eq!(reader.total_bytes(), Some(0));
+
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).unwrap();
+    drop(reader);
+
+    let last = last.unwrap();
+    assert_eq!(last.bytes_read, buf.len() as u64);
+    assert_eq!(last.total_bytes, None);
+    assert_eq!(last.fraction(), None);
+}